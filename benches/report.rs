@@ -0,0 +1,152 @@
+//! Times `report` against a generated database, to catch report generation
+//! slowing down as databases grow.
+//!
+//! Run with `cargo bench`. The size of the database and the number of runs can
+//! be set with `CTT_BENCH_SESSIONS`, `CTT_BENCH_HEARTBEATS` (per session) and
+//! `CTT_BENCH_RUNS`; with `CTT_BENCH_BUDGET_MS` set, the bench fails when the
+//! median run of any case takes longer.
+
+use chrono::{Duration, TimeZone, Utc};
+use rusqlite::{params, Connection};
+use std::path::Path;
+use std::process::Command;
+use std::time::Instant;
+
+const BIN: &str = env!("CARGO_BIN_EXE_claude-time-tracker");
+const PROJECTS: usize = 20;
+const MONTH: &str = "2025-01";
+
+fn env_or(name: &str, default: usize) -> usize {
+    std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+}
+
+fn run(home: &Path, db: &Path, args: &[&str]) {
+    let output = Command::new(BIN)
+        .env("HOME", home)
+        .env_remove("CTT_CONFIG")
+        .arg("--db")
+        .arg(db)
+        .args(args)
+        .output()
+        .expect("Failed to run claude-time-tracker");
+    assert!(
+        output.status.success(),
+        "{} failed: {}",
+        args.join(" "),
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+/// Fill the database with finished sessions spread over the month
+fn populate(db: &Path, sessions: usize, heartbeats: usize) -> rusqlite::Result<()> {
+    let mut conn = Connection::open(db)?;
+    let tx = conn.transaction()?;
+    {
+        let mut project = tx.prepare("INSERT INTO projects (path) VALUES (?)")?;
+        for i in 0..PROJECTS {
+            project.execute(params![format!("/bench/project-{}", i)])?;
+        }
+
+        let mut session = tx.prepare(
+            "INSERT INTO sessions (project_id, branch, work_item, started_at, ended_at, active_seconds, status)
+             VALUES (?, ?, ?, ?, ?, ?, 'completed')",
+        )?;
+        let mut heartbeat = tx.prepare(
+            "INSERT INTO heartbeats (session_id, timestamp, file_path, event_type) VALUES (?, ?, ?, ?)",
+        )?;
+        let mut commit = tx.prepare("INSERT INTO commits (session_id, hash, message, committed_at) VALUES (?, ?, ?, ?)")?;
+        let mut branch = tx.prepare(
+            "INSERT INTO session_branches (session_id, branch, work_item, active_seconds) VALUES (?, ?, ?, ?)",
+        )?;
+
+        let month_start = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+        let spacing = Duration::seconds(30 * 24 * 3600 / sessions.max(1) as i64);
+        for i in 0..sessions {
+            let started = month_start + spacing * i as i32;
+            let ended = started + Duration::seconds(15 * heartbeats as i64);
+            let work_item = format!("PROJ-{}", i % 50);
+            session.execute(params![
+                (i % PROJECTS) as i64 + 1,
+                format!("feature/{}", work_item),
+                work_item,
+                started.to_rfc3339(),
+                ended.to_rfc3339(),
+                15 * heartbeats as i64,
+            ])?;
+            let session_id = tx.last_insert_rowid();
+
+            for h in 0..heartbeats {
+                let event = if h % 20 == 0 { "prompt-submit" } else { "post-tool-use" };
+                heartbeat.execute(params![
+                    session_id,
+                    (started + Duration::seconds(15 * h as i64)).to_rfc3339(),
+                    format!("/bench/project-{}/src/module-{}/file.rs", i % PROJECTS, h % 8),
+                    event,
+                ])?;
+            }
+            commit.execute(params![session_id, format!("{:040x}", i), "Bench commit", ended.to_rfc3339()])?;
+            if i % 4 == 0 {
+                branch.execute(params![session_id, "main", None::<String>, 15 * heartbeats as i64 / 2])?;
+            }
+        }
+    }
+    tx.commit()
+}
+
+fn main() {
+    let sessions = env_or("CTT_BENCH_SESSIONS", 2_000);
+    let heartbeats = env_or("CTT_BENCH_HEARTBEATS", 200);
+    let runs = env_or("CTT_BENCH_RUNS", 5).max(1);
+    let budget = std::env::var("CTT_BENCH_BUDGET_MS").ok().and_then(|v| v.parse::<u128>().ok());
+
+    let dir = tempfile::tempdir().expect("Failed to create temp dir");
+    let db = dir.path().join("bench.db");
+
+    // The tracker creates its own schema; the rows go in directly
+    run(dir.path(), &db, &["report", "--month", MONTH, "--format", "json"]);
+    let started = Instant::now();
+    populate(&db, sessions, heartbeats).expect("Failed to populate database");
+    println!(
+        "{} sessions, {} heartbeats (populated in {:.1?})",
+        sessions,
+        sessions * heartbeats,
+        started.elapsed()
+    );
+
+    // One untimed run, so no case pays for checkpointing the inserted rows
+    run(dir.path(), &db, &["report", "--month", MONTH, "--format", "json"]);
+
+    let cases: [(&str, &[&str]); 3] = [
+        ("report", &["report", "--month", MONTH, "--format", "json"]),
+        ("report --modules", &["report", "--month", MONTH, "--format", "json", "--modules"]),
+        ("report --metric wall-clock", &["report", "--month", MONTH, "--format", "json", "--metric", "wall-clock"]),
+    ];
+
+    let mut over_budget = Vec::new();
+    for (name, args) in cases {
+        let mut times: Vec<u128> = (0..runs)
+            .map(|_| {
+                let started = Instant::now();
+                run(dir.path(), &db, args);
+                started.elapsed().as_millis()
+            })
+            .collect();
+        times.sort_unstable();
+        let median = times[times.len() / 2];
+        println!(
+            "{:<28} median {:>6} ms  min {:>6} ms  max {:>6} ms",
+            name,
+            median,
+            times[0],
+            times[times.len() - 1]
+        );
+        if budget.is_some_and(|budget| median > budget) {
+            over_budget.push(name);
+        }
+    }
+
+    if !over_budget.is_empty() {
+        eprintln!("Over the {} ms budget: {}", budget.unwrap_or_default(), over_budget.join(", "));
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,249 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::config::WorkCalendar;
+use crate::models::{Session, WindowEvent};
+
+/// Bucket type of exported sessions
+const BUCKET_TYPE: &str = "app.claude-time-tracker";
+
+/// Bucket type of aw-watcher-window
+const WINDOW_BUCKET_TYPE: &str = "currentwindow";
+
+/// ActivityWatch export: buckets by id, as `aw-server` exports and imports them
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Export {
+    pub buckets: BTreeMap<String, Bucket>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Bucket {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(default)]
+    pub client: String,
+    #[serde(default)]
+    pub hostname: String,
+    #[serde(default)]
+    pub events: Vec<Event>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Event {
+    pub timestamp: DateTime<Utc>,
+    /// Seconds
+    pub duration: f64,
+    #[serde(default)]
+    pub data: serde_json::Value,
+}
+
+/// A session, its project name and its active spans as (start, seconds)
+pub type SessionSpans = (Session, String, Vec<(DateTime<Utc>, i64)>);
+
+/// Claude time and computer activity on one day
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DayComparison {
+    pub day: NaiveDate,
+    pub claude_seconds: i64,
+    pub window_seconds: i64,
+    /// Claude time during which a window was focused
+    pub overlap_seconds: i64,
+}
+
+/// Sessions' active spans as ActivityWatch events, one bucket per machine
+pub fn export(sessions: &[SessionSpans], default_machine: &str) -> Export {
+    let mut buckets: BTreeMap<String, Bucket> = BTreeMap::new();
+    for (session, project, spans) in sessions {
+        let hostname = session.machine.as_deref().unwrap_or(default_machine);
+        let id = format!("claude-time-tracker_{}", hostname);
+        let bucket = buckets.entry(id.clone()).or_insert_with(|| Bucket {
+            id,
+            kind: BUCKET_TYPE.to_string(),
+            client: "claude-time-tracker".to_string(),
+            hostname: hostname.to_string(),
+            events: Vec::new(),
+        });
+        for &(start, seconds) in spans {
+            bucket.events.push(Event {
+                timestamp: start,
+                duration: seconds as f64,
+                data: serde_json::json!({
+                    "project": project,
+                    "branch": session.branch,
+                    "work_item": session.work_item,
+                    "session": session.uuid,
+                }),
+            });
+        }
+    }
+    Export { buckets }
+}
+
+/// Window events from an ActivityWatch export
+///
+/// Takes a full export (only its `currentwindow` buckets are read) or the
+/// event list of a single bucket, as returned by `/api/0/buckets/<id>/events`.
+pub fn parse_window_events(content: &str, file_name: &str) -> Result<Vec<WindowEvent>> {
+    let value: serde_json::Value = serde_json::from_str(content).context("Not a JSON file")?;
+    let buckets: Vec<(String, Vec<Event>)> = if value.is_array() {
+        let events = serde_json::from_value(value).context("Unexpected ActivityWatch event list")?;
+        vec![(file_name.to_string(), events)]
+    } else {
+        let export: Export = serde_json::from_value(value).context("Unexpected ActivityWatch export")?;
+        export
+            .buckets
+            .into_values()
+            .filter(|b| b.kind == WINDOW_BUCKET_TYPE)
+            .map(|b| (b.id, b.events))
+            .collect()
+    };
+
+    let mut events = Vec::new();
+    for (bucket, bucket_events) in buckets {
+        for event in bucket_events.into_iter().filter(|e| e.duration > 0.0) {
+            let text = |key: &str| event.data.get(key).and_then(|v| v.as_str()).map(String::from);
+            events.push(WindowEvent {
+                bucket: bucket.clone(),
+                timestamp: event.timestamp,
+                duration_seconds: event.duration,
+                app: text("app"),
+                title: text("title"),
+            });
+        }
+    }
+    events.sort_by_key(|e| e.timestamp);
+    Ok(events)
+}
+
+/// A period from start to end
+type Interval = (DateTime<Utc>, DateTime<Utc>);
+
+/// Claude time next to window activity per working day
+pub fn compare(claude: &[(DateTime<Utc>, i64)], windows: &[WindowEvent], calendar: WorkCalendar) -> Vec<DayComparison> {
+    let mut days: BTreeMap<NaiveDate, (Vec<Interval>, Vec<Interval>)> = BTreeMap::new();
+    for &(start, seconds) in claude {
+        days.entry(calendar.day_of(start))
+            .or_default()
+            .0
+            .push((start, start + Duration::seconds(seconds)));
+    }
+    for window in windows {
+        let end = window.timestamp + Duration::milliseconds((window.duration_seconds * 1000.0) as i64);
+        days.entry(calendar.day_of(window.timestamp))
+            .or_default()
+            .1
+            .push((window.timestamp, end));
+    }
+
+    days.into_iter()
+        .map(|(day, (claude, windows))| {
+            let claude = merge(claude);
+            let windows = merge(windows);
+            DayComparison {
+                day,
+                claude_seconds: total(&claude),
+                window_seconds: total(&windows),
+                overlap_seconds: overlap(&claude, &windows),
+            }
+        })
+        .collect()
+}
+
+/// Sort intervals and join the overlapping ones
+fn merge(mut intervals: Vec<Interval>) -> Vec<Interval> {
+    intervals.sort();
+    let mut merged: Vec<Interval> = Vec::new();
+    for (start, end) in intervals {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+fn total(intervals: &[Interval]) -> i64 {
+    intervals.iter().map(|(start, end)| (*end - *start).num_seconds()).sum()
+}
+
+/// Seconds covered by both lists of merged intervals
+fn overlap(a: &[Interval], b: &[Interval]) -> i64 {
+    let (mut i, mut j, mut seconds) = (0, 0, 0);
+    while i < a.len() && j < b.len() {
+        let start = a[i].0.max(b[j].0);
+        let end = a[i].1.min(b[j].1);
+        if end > start {
+            seconds += (end - start).num_seconds();
+        }
+        if a[i].1 < b[j].1 {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    seconds
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Timezone;
+
+    #[test]
+    fn test_parse_window_events() {
+        let export = r#"{"buckets": {
+            "aw-watcher-window_laptop": {"id": "aw-watcher-window_laptop", "type": "currentwindow", "hostname": "laptop",
+                "events": [
+                    {"timestamp": "2025-01-15T09:00:00+00:00", "duration": 600.5, "data": {"app": "Terminal", "title": "claude"}},
+                    {"timestamp": "2025-01-15T08:00:00Z", "duration": 0, "data": {"app": "Finder", "title": ""}}
+                ]},
+            "aw-watcher-afk_laptop": {"id": "aw-watcher-afk_laptop", "type": "afkstatus",
+                "events": [{"timestamp": "2025-01-15T09:00:00Z", "duration": 900, "data": {"status": "not-afk"}}]}
+        }}"#;
+        let events = parse_window_events(export, "export.json").unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].bucket, "aw-watcher-window_laptop");
+        assert_eq!(events[0].app.as_deref(), Some("Terminal"));
+        assert_eq!(events[0].duration_seconds, 600.5);
+
+        let list = r#"[{"timestamp": "2025-01-15T09:00:00Z", "duration": 60, "data": {"app": "Code"}}]"#;
+        let events = parse_window_events(list, "events.json").unwrap();
+        assert_eq!(events[0].bucket, "events.json");
+        assert_eq!(events[0].title, None);
+    }
+
+    #[test]
+    fn test_compare() {
+        let at = |time: &str| format!("2025-01-15T{}Z", time).parse::<DateTime<Utc>>().unwrap();
+        let window = |time: &str, seconds: f64| WindowEvent {
+            bucket: "aw-watcher-window_laptop".to_string(),
+            timestamp: at(time),
+            duration_seconds: seconds,
+            app: None,
+            title: None,
+        };
+        let calendar = WorkCalendar {
+            timezone: Timezone::parse("UTC").unwrap(),
+            day_start_hour: 0,
+        };
+
+        // Claude 09:00-10:00; windows 09:30-09:50 and 09:45-10:30
+        let days = compare(
+            &[(at("09:00:00"), 3600)],
+            &[window("09:30:00", 1200.0), window("09:45:00", 2700.0)],
+            calendar,
+        );
+        assert_eq!(
+            days,
+            vec![DayComparison {
+                day: NaiveDate::from_ymd_opt(2025, 1, 15).unwrap(),
+                claude_seconds: 3600,
+                window_seconds: 3600,
+                overlap_seconds: 1800,
+            }]
+        );
+    }
+}
@@ -0,0 +1,312 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+
+/// Name used to recognize hook entries that belong to this tool
+const BINARY_NAME: &str = "claude-time-tracker";
+
+/// Matcher for tool calls that touch files (used for module attribution)
+const TOOL_USE_MATCHER: &str = "Edit|MultiEdit|Write|NotebookEdit";
+
+/// Which Claude Code settings file to modify
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum HookScope {
+    /// ~/.claude/settings.json
+    User,
+    /// <project>/.claude/settings.json
+    Project,
+}
+
+/// A hook entry this tool installs
+#[derive(Debug, Clone)]
+pub struct HookSpec {
+    pub event: &'static str,
+    pub matcher: Option<&'static str>,
+    pub command: String,
+}
+
+/// Hooks needed for tracking, pointing at the given binary
+pub fn hook_specs(binary: &str, tool_use: bool) -> Vec<HookSpec> {
+    let command = |action: &str| format!("\"{}\" {} --path \"$CLAUDE_PROJECT_DIR\"", binary, action);
+
+    let mut specs = vec![
+        HookSpec {
+            event: "SessionStart",
+            matcher: None,
+            command: command("start"),
+        },
+        HookSpec {
+            event: "UserPromptSubmit",
+            matcher: None,
+            command: command("heartbeat"),
+        },
+        HookSpec {
+            event: "Stop",
+            matcher: None,
+            command: command("stop"),
+        },
+    ];
+
+    if tool_use {
+        specs.push(HookSpec {
+            event: "PostToolUse",
+            matcher: Some(TOOL_USE_MATCHER),
+            command: command("heartbeat"),
+        });
+    }
+
+    specs
+}
+
+/// State of one expected hook in a settings file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HookState {
+    /// Installed and pointing at the expected binary
+    Ok,
+    /// Installed, but with a different command (e.g. an old binary path)
+    Mismatch(String),
+    Missing,
+}
+
+/// Compare installed hooks against the expected ones
+pub fn check(settings: &Value, specs: &[HookSpec]) -> Vec<(&'static str, HookState)> {
+    specs
+        .iter()
+        .map(|spec| {
+            let installed: Vec<&str> = settings["hooks"][spec.event]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|entry| entry["hooks"].as_array())
+                .flatten()
+                .filter(|command| is_own_command(command))
+                .filter_map(|command| command["command"].as_str())
+                .collect();
+
+            let state = if installed.contains(&spec.command.as_str()) {
+                HookState::Ok
+            } else if let Some(other) = installed.first() {
+                HookState::Mismatch(other.to_string())
+            } else {
+                HookState::Missing
+            };
+
+            (spec.event, state)
+        })
+        .collect()
+}
+
+/// Whether the optional tool-use heartbeat hook is installed
+pub fn has_tool_use_hook(settings: &Value) -> bool {
+    settings["hooks"]["PostToolUse"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry["hooks"].as_array())
+        .flatten()
+        .any(is_own_command)
+}
+
+/// Path of the settings file for a scope
+pub fn settings_path(scope: HookScope, project_dir: &Path) -> Result<PathBuf> {
+    let base = match scope {
+        HookScope::User => dirs::home_dir().context("Could not determine home directory")?,
+        HookScope::Project => project_dir.to_path_buf(),
+    };
+    Ok(base.join(".claude").join("settings.json"))
+}
+
+/// Load a settings file, treating a missing file as empty settings
+pub fn load(path: &Path) -> Result<Value> {
+    if !path.exists() {
+        return Ok(json!({}));
+    }
+
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read settings file: {}", path.display()))?;
+
+    if content.trim().is_empty() {
+        return Ok(json!({}));
+    }
+
+    serde_json::from_str(&content)
+        .with_context(|| format!("Failed to parse settings file: {}", path.display()))
+}
+
+/// Write a settings file, creating its directory if needed
+pub fn save(path: &Path, settings: &Value) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+    }
+
+    let content = serde_json::to_string_pretty(settings)?;
+    std::fs::write(path, content + "\n")
+        .with_context(|| format!("Failed to write settings file: {}", path.display()))
+}
+
+/// Add the given hooks, replacing any previously installed entries of this tool
+///
+/// Returns a description of each change made.
+pub fn install(settings: &mut Value, specs: &[HookSpec]) -> Result<Vec<String>> {
+    let mut changes = remove_hooks(settings)?;
+
+    let hooks = hooks_object(settings)?;
+    for spec in specs {
+        let mut entry = json!({
+            "hooks": [{ "type": "command", "command": spec.command }]
+        });
+        if let Some(matcher) = spec.matcher {
+            entry["matcher"] = json!(matcher);
+        }
+
+        hooks
+            .entry(spec.event)
+            .or_insert_with(|| json!([]))
+            .as_array_mut()
+            .with_context(|| format!("hooks.{} is not an array", spec.event))?
+            .push(entry);
+
+        changes.push(format!("Added {} hook: {}", spec.event, spec.command));
+    }
+
+    Ok(changes)
+}
+
+/// Remove every hook command that invokes this tool, dropping entries left empty
+///
+/// Returns a description of each removed command.
+pub fn remove_hooks(settings: &mut Value) -> Result<Vec<String>> {
+    let mut changes = Vec::new();
+    let Some(hooks) = settings.get_mut("hooks").and_then(|h| h.as_object_mut()) else {
+        return Ok(changes);
+    };
+
+    for (event, entries) in hooks.iter_mut() {
+        let Some(entries) = entries.as_array_mut() else {
+            continue;
+        };
+
+        for entry in entries.iter_mut() {
+            if let Some(commands) = entry.get_mut("hooks").and_then(|h| h.as_array_mut()) {
+                commands.retain(|command| {
+                    let ours = is_own_command(command);
+                    if ours {
+                        changes.push(format!(
+                            "Removed {} hook: {}",
+                            event,
+                            command["command"].as_str().unwrap_or_default()
+                        ));
+                    }
+                    !ours
+                });
+            }
+        }
+
+        entries.retain(|entry| {
+            entry
+                .get("hooks")
+                .and_then(|h| h.as_array())
+                .is_none_or(|h| !h.is_empty())
+        });
+    }
+
+    hooks.retain(|_, entries| entries.as_array().is_none_or(|e| !e.is_empty()));
+
+    if hooks.is_empty() {
+        if let Some(root) = settings.as_object_mut() {
+            root.remove("hooks");
+        }
+    }
+
+    Ok(changes)
+}
+
+fn hooks_object(settings: &mut Value) -> Result<&mut serde_json::Map<String, Value>> {
+    let root = settings
+        .as_object_mut()
+        .context("Settings file is not a JSON object")?;
+
+    root.entry("hooks")
+        .or_insert_with(|| json!({}))
+        .as_object_mut()
+        .context("\"hooks\" in settings is not an object")
+}
+
+fn is_own_command(command: &Value) -> bool {
+    command
+        .get("command")
+        .and_then(|c| c.as_str())
+        .is_some_and(|c| c.contains(BINARY_NAME))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_install_preserves_other_hooks() {
+        let mut settings = json!({
+            "model": "opus",
+            "hooks": {
+                "Stop": [
+                    { "hooks": [{ "type": "command", "command": "say done" }] }
+                ]
+            }
+        });
+
+        let specs = hook_specs("/usr/local/bin/claude-time-tracker", false);
+        let changes = install(&mut settings, &specs).unwrap();
+        assert_eq!(changes.len(), 3);
+
+        assert_eq!(settings["model"], "opus");
+        let stop = settings["hooks"]["Stop"].as_array().unwrap();
+        assert_eq!(stop.len(), 2);
+        assert_eq!(stop[0]["hooks"][0]["command"], "say done");
+        assert_eq!(
+            settings["hooks"]["SessionStart"][0]["hooks"][0]["command"],
+            "\"/usr/local/bin/claude-time-tracker\" start --path \"$CLAUDE_PROJECT_DIR\""
+        );
+    }
+
+    #[test]
+    fn test_check_and_remove() {
+        let mut settings = json!({
+            "hooks": {
+                "Stop": [
+                    { "hooks": [{ "type": "command", "command": "say done" }] }
+                ]
+            }
+        });
+
+        install(&mut settings, &hook_specs("/old/claude-time-tracker", false)).unwrap();
+        // Simulate a manually removed hook
+        settings["hooks"].as_object_mut().unwrap().remove("UserPromptSubmit");
+
+        let states = check(&settings, &hook_specs("/new/claude-time-tracker", false));
+        assert!(matches!(states[0], ("SessionStart", HookState::Mismatch(_))));
+        assert_eq!(states[1], ("UserPromptSubmit", HookState::Missing));
+        assert!(!has_tool_use_hook(&settings));
+
+        let removed = remove_hooks(&mut settings).unwrap();
+        assert_eq!(removed.len(), 2);
+        assert!(settings["hooks"].get("SessionStart").is_none());
+        assert_eq!(settings["hooks"]["Stop"][0]["hooks"][0]["command"], "say done");
+    }
+
+    #[test]
+    fn test_install_replaces_stale_entries() {
+        let mut settings = json!({});
+
+        install(&mut settings, &hook_specs("/old/claude-time-tracker", true)).unwrap();
+        let changes = install(&mut settings, &hook_specs("/new/claude-time-tracker", true)).unwrap();
+
+        // 4 removals of the old entries, 4 additions
+        assert_eq!(changes.len(), 8);
+        let heartbeat = settings["hooks"]["UserPromptSubmit"].as_array().unwrap();
+        assert_eq!(heartbeat.len(), 1);
+        assert!(heartbeat[0]["hooks"][0]["command"].as_str().unwrap().contains("/new/"));
+        assert_eq!(settings["hooks"]["PostToolUse"][0]["matcher"], TOOL_USE_MATCHER);
+    }
+}
@@ -1,9 +1,33 @@
-use clap::{Parser, Subcommand};
+use chrono::{DateTime, NaiveDate, Utc};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::claude_settings::HookScope;
+use crate::config::Attribution;
+use crate::report::redact::RedactProfile;
+use crate::report::TimeMetric;
 
 #[derive(Parser)]
 #[command(name = "claude-time-tracker")]
 #[command(about = "Track Claude Code usage time per project", long_about = None)]
 pub struct Cli {
+    /// Database file to use instead of settings.database_path
+    #[arg(long, global = true, env = "CTT_DB_PATH")]
+    pub db: Option<PathBuf>,
+
+    /// Global config file to use instead of ~/.config/claude-time-tracker/config.toml
+    #[arg(long, global = true, env = "CTT_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    /// Increase log verbosity (-v: debug, -vv: trace)
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+
+    /// Only log errors
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -15,9 +39,12 @@ pub enum Commands {
         /// Project path
         #[arg(short, long)]
         path: String,
+        /// Work item to track, regardless of the branch name
+        #[arg(short, long, env = "CTT_WORK_ITEM")]
+        work_item: Option<String>,
     },
 
-    /// Record activity heartbeat (called by UserPromptSubmit hook)
+    /// Record activity heartbeat (called by UserPromptSubmit and PostToolUse hooks)
     Heartbeat {
         /// Project path
         #[arg(short, long)]
@@ -32,30 +59,160 @@ pub enum Commands {
     },
 
     /// Generate time tracking report
-    Report {
-        /// Month to report (YYYY-MM format), defaults to current month
-        #[arg(short, long)]
-        month: Option<String>,
-
-        /// Filter by project name or path
-        #[arg(short = 'P', long)]
-        project: Option<String>,
+    Report(ReportArgs),
 
-        /// Output format: md, csv, json (can specify multiple, comma-separated)
-        #[arg(short, long, default_value = "md")]
-        format: String,
+    /// Search sessions by work item, branch, commit messages and notes
+    Search {
+        /// Words to search for (all must match)
+        query: Vec<String>,
 
-        /// Output file path (without extension if multiple formats)
-        #[arg(short, long)]
-        output: Option<String>,
+        /// Maximum number of results
+        #[arg(short, long, default_value_t = 20)]
+        limit: usize,
+    },
 
-        /// Output all formats (md, csv, json)
+    /// Create a project config file (.claude-time-tracker.toml)
+    Init {
+        /// Project path
+        #[arg(short, long, default_value = ".")]
+        path: String,
+        /// Overwrite an existing project config
         #[arg(long)]
-        all_formats: bool,
+        force: bool,
     },
 
     /// Show current tracking status
-    Status,
+    Status {
+        /// Also show sessions of archived projects
+        #[arg(long)]
+        include_archived: bool,
+        /// One line per active session, e.g. '{project} {elapsed}' (placeholders: project,
+        /// branch, work_item, elapsed, started, prompts, machine; {{ and }} for braces)
+        #[arg(long)]
+        format: Option<String>,
+    },
+
+    /// Close sessions whose last heartbeat is older than the idle timeout
+    Reap,
+
+    /// Close an active session by hand, ending it at its last activity
+    Abandon {
+        /// Session id (see `sessions list`)
+        #[arg(long)]
+        session: i64,
+    },
+
+    /// Fold heartbeats of old completed sessions into activity intervals to shrink the database
+    Compact {
+        /// Only compact sessions that ended more than this many days ago
+        #[arg(long, default_value_t = 30)]
+        older_than_days: u32,
+    },
+
+    /// Recreate the database from the event log (settings.event_log)
+    Rebuild {
+        /// Event log to replay, defaults to events.jsonl next to the database
+        #[arg(long)]
+        log: Option<PathBuf>,
+        /// Replace an existing database, keeping it as <name>.bak
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Maintain the database file
+    Db {
+        #[command(subcommand)]
+        action: DbAction,
+    },
+
+    /// Share sessions with other machines through a git repository or a cloud store
+    Sync {
+        /// Local clone to sync through, instead of sync.repo
+        #[arg(long, conflicts_with = "url")]
+        repo: Option<PathBuf>,
+        /// s3://bucket/prefix or WebDAV folder to sync through, instead of sync.url
+        #[arg(long)]
+        url: Option<String>,
+    },
+
+    /// Serve hooks over a local socket with one open database, closing timed-out sessions
+    /// (and compacting old ones) every interval; runs in the foreground
+    Daemon {
+        #[command(subcommand)]
+        action: Option<DaemonAction>,
+        /// How often to check, e.g. 30s, 5m, 1h
+        #[arg(long, default_value = "5m", value_parser = parse_interval)]
+        interval: Duration,
+    },
+
+    /// Print a one-line time segment for Claude Code's statusLine.command (reads its JSON on stdin)
+    Statusline,
+
+    /// Serve a JSON API on localhost for dashboards and scripts
+    Serve {
+        #[arg(long, default_value_t = 7878)]
+        port: u16,
+        /// Bearer token to require, instead of server.token
+        #[arg(long, env = "CTT_SERVER_TOKEN")]
+        token: Option<String>,
+    },
+
+    /// Send tracked time to other tools
+    Push {
+        #[command(subcommand)]
+        target: PushTarget,
+    },
+
+    /// Post a summary of tracked time
+    Notify {
+        #[command(subcommand)]
+        target: NotifyTarget,
+    },
+
+    /// List the configured webhooks
+    Webhooks {
+        /// Send a test payload to each of them
+        #[arg(long)]
+        test: bool,
+        /// Post a delivery read from stdin (how hooks hand webhooks off)
+        #[arg(long, hide = true, conflicts_with = "test")]
+        deliver: bool,
+    },
+
+    /// Write tracked time in another tool's format, or a bundle of sessions for `import --bundle`
+    Export {
+        /// Defaults to a bundle when --since is given
+        #[arg(long, value_enum, required_unless_present = "since")]
+        format: Option<ExportFormat>,
+        /// Month to export (YYYY-MM, activitywatch only), defaults to report.default_period
+        #[arg(long, conflicts_with = "since")]
+        month: Option<String>,
+        /// Bundle only the sessions that ended or changed since then
+        /// (RFC 3339 time, or YYYY-MM-DD for midnight UTC)
+        #[arg(long, value_parser = parse_since)]
+        since: Option<DateTime<Utc>>,
+        /// Output file, defaults to stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Read time recorded by other tools (window activity, or time entries that become sessions)
+    Import {
+        #[arg(long, value_enum, required_unless_present = "bundle")]
+        from: Option<ImportSource>,
+        /// Merge a bundle written by `export --since` instead; merging it again changes nothing
+        #[arg(long, conflicts_with = "from")]
+        bundle: bool,
+        /// File exported by the tool
+        file: PathBuf,
+    },
+
+    /// Generate last month's report once per month (run from a scheduler)
+    Cron {
+        /// Regenerate even if last month's report was already produced
+        #[arg(long)]
+        force: bool,
+    },
 
     /// Manage configuration
     Config {
@@ -68,6 +225,24 @@ pub enum Commands {
         #[command(subcommand)]
         action: ProjectsAction,
     },
+
+    /// Inspect and correct recorded sessions
+    Sessions {
+        #[command(subcommand)]
+        action: SessionsAction,
+    },
+
+    /// List and restore deleted projects and sessions
+    Trash {
+        #[command(subcommand)]
+        action: TrashAction,
+    },
+
+    /// Manage Claude Code hook integration
+    Hooks {
+        #[command(subcommand)]
+        action: HooksAction,
+    },
 }
 
 #[derive(Subcommand)]
@@ -78,12 +253,217 @@ pub enum ConfigAction {
     Edit,
     /// Show current configuration
     Show,
+    /// Print one setting, e.g. `report.include_commits`
+    Get {
+        /// Dotted key
+        key: String,
+    },
+    /// Change one setting in the global config file, keeping its comments
+    Set {
+        /// Dotted key, e.g. `settings.idle_timeout_minutes`
+        key: String,
+        /// New value (TOML syntax; anything else is taken as a string)
+        value: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum PushTarget {
+    /// Log time on Jira issues, one worklog per work item and day
+    Jira(PushArgs),
+    /// Log time on Jira issues through Tempo Timesheets, one worklog per work item and day
+    Tempo(PushArgs),
+    /// Log time spent on GitLab issues (`#123` work items), one time log per issue and day
+    Gitlab(PushArgs),
+    /// Create a Toggl Track time entry per completed session not pushed yet, tagged "claude"
+    Toggl(SyncArgs),
+    /// Create a Clockify time entry per completed session not pushed yet, adding missing projects
+    Clockify(SyncArgs),
+    /// Log time on Harvest tasks, one entry per task and day (see `harvest` under `[projects."<path>"]`)
+    Harvest(PushArgs),
+}
+
+#[derive(Args)]
+pub struct PushArgs {
+    /// Month to push (YYYY-MM format), defaults to report.default_period
+    #[arg(short, long)]
+    pub month: Option<String>,
+
+    /// Filter by project name or path
+    #[arg(short = 'P', long)]
+    pub project: Option<String>,
+
+    /// Show what would be sent without sending it
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Subcommand)]
+pub enum NotifyTarget {
+    /// Post to the Slack webhook in `[slack]`: a day's time per project, or one session
+    Slack(NotifyArgs),
+}
+
+#[derive(Args)]
+pub struct NotifyArgs {
+    /// Post about this session instead of a whole day
+    #[arg(long, conflicts_with = "date")]
+    pub session: Option<i64>,
+
+    /// Day to summarize (YYYY-MM-DD), defaults to today
+    #[arg(long)]
+    pub date: Option<NaiveDate>,
+
+    /// Print the message instead of posting it
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Args)]
+pub struct SyncArgs {
+    /// Only push sessions started on or after this day (YYYY-MM-DD)
+    #[arg(long)]
+    pub since: Option<NaiveDate>,
+
+    /// Filter by project name or path
+    #[arg(short = 'P', long)]
+    pub project: Option<String>,
+
+    /// Show what would be sent without sending it
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Subcommand)]
+pub enum DbAction {
+    /// Import the projects and sessions of another tracker database (e.g. from another machine)
+    Merge {
+        /// Database file to import from; it is left unchanged apart from schema upgrades
+        source: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum DaemonAction {
+    /// Run the daemon as a systemd user service (Linux) or launchd agent (macOS)
+    Install {
+        /// How often the daemon checks, e.g. 30s, 5m, 1h
+        #[arg(long, default_value = "5m", value_parser = parse_interval)]
+        interval: Duration,
+    },
+    /// Stop the service and remove its unit file or plist
+    Uninstall,
+}
+
+#[derive(Subcommand)]
+pub enum TrashAction {
+    /// List deleted projects and sessions
+    List,
+    /// Restore a deleted project (with the sessions deleted along with it) or session
+    Restore {
+        /// Session id
+        #[arg(long, conflicts_with = "project", required_unless_present = "project")]
+        session: Option<i64>,
+        /// Project path
+        #[arg(long)]
+        project: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum HooksAction {
+    /// Add tracking hooks to Claude Code's settings.json
+    Install {
+        /// Settings file to modify (project uses the current directory)
+        #[arg(long, value_enum, default_value = "user")]
+        scope: HookScope,
+        /// Also record heartbeats on file edits (enables `report --modules`)
+        #[arg(long)]
+        tool_use: bool,
+    },
+    /// Check installed hooks in user and project settings
+    Status,
+    /// Remove tracking hooks from Claude Code's settings.json
+    Uninstall {
+        /// Settings file to modify (project uses the current directory)
+        #[arg(long, value_enum, default_value = "user")]
+        scope: HookScope,
+    },
+}
+
+#[derive(Args)]
+pub struct ReportArgs {
+    /// Month to report (YYYY-MM format), defaults to report.default_period
+    #[arg(short, long)]
+    pub month: Option<String>,
+
+    /// Filter by project name or path
+    #[arg(short = 'P', long)]
+    pub project: Option<String>,
+
+    /// Output format: md, csv, json (can specify multiple, comma-separated),
+    /// defaults to report.default_format
+    #[arg(short, long)]
+    pub format: Option<String>,
+
+    /// Output file path (without extension if multiple formats; defaults to
+    /// `<report.output_dir>/<month>` for multiple formats when that is set)
+    #[arg(short, long)]
+    pub output: Option<String>,
+
+    /// Output all formats (md, csv, json)
+    #[arg(long)]
+    pub all_formats: bool,
+
+    /// Print the JSON Schema of the format instead of a report
+    #[arg(long, conflicts_with = "all_formats")]
+    pub schema: bool,
+
+    /// Include per-module (top-level directory) time breakdown
+    #[arg(long)]
+    pub modules: bool,
+
+    /// Group projects under their clients, with client subtotals
+    #[arg(long)]
+    pub by_client: bool,
+
+    /// Strip details for external sharing (client: keep display names and durations only)
+    #[arg(long, value_enum)]
+    pub redact: Option<RedactProfile>,
+
+    /// Only include sessions recorded on this machine
+    #[arg(long)]
+    pub machine: Option<String>,
+
+    /// Also report archived projects
+    #[arg(long)]
+    pub include_archived: bool,
+
+    /// Add a lines-changed column to markdown reports
+    #[arg(long)]
+    pub diff_stats: bool,
+
+    /// How to count time when sessions overlap
+    #[arg(long, value_enum, default_value = "active")]
+    pub metric: TimeMetric,
+
+    /// Day a session spanning midnight counts towards, defaults to report.attribution
+    #[arg(long, value_enum)]
+    pub attribution: Option<Attribution>,
+
+    /// Also count sessions still running, with their time so far
+    #[arg(long)]
+    pub include_active: bool,
 }
 
 #[derive(Subcommand)]
 pub enum ProjectsAction {
     /// List all tracked projects
-    List,
+    List {
+        /// Also list archived projects
+        #[arg(long)]
+        include_archived: bool,
+    },
     /// Set display name for a project
     SetName {
         /// Project path
@@ -91,4 +471,211 @@ pub enum ProjectsAction {
         /// Display name
         name: String,
     },
+    /// Set the work item pattern for a project
+    SetPattern {
+        /// Project path
+        path: String,
+        /// Regexes used to extract work items from branch names, tried in order
+        #[arg(required = true)]
+        patterns: Vec<String>,
+    },
+    /// Set a project field
+    Set {
+        /// Project path
+        path: String,
+        /// Field to set
+        #[arg(value_enum)]
+        field: ProjectField,
+        /// New value (empty string clears name, pattern, rate and client)
+        value: String,
+    },
+    /// Set a project's hourly rate from a day on (earlier days keep their rate)
+    SetRate {
+        /// Project path
+        path: String,
+        /// Hourly rate
+        rate: f64,
+        /// First day the rate applies (YYYY-MM-DD), defaults to today
+        #[arg(long)]
+        from: Option<NaiveDate>,
+        /// Currency code, e.g. EUR
+        #[arg(long)]
+        currency: Option<String>,
+    },
+    /// Remove the rate starting on a day
+    UnsetRate {
+        /// Project path
+        path: String,
+        /// First day of the rate to remove (YYYY-MM-DD)
+        #[arg(long)]
+        from: NaiveDate,
+    },
+    /// Show project details
+    Show {
+        /// Project path
+        path: String,
+    },
+    /// Show lifetime totals per project
+    Stats {
+        /// Number of top work items to show per project
+        #[arg(long, default_value_t = 3)]
+        top: usize,
+        /// Also show archived projects
+        #[arg(long)]
+        include_archived: bool,
+    },
+    /// Hide a finished project from listings, reports and status (its history is kept)
+    Archive {
+        /// Project path
+        path: String,
+    },
+    /// Bring an archived project back
+    Unarchive {
+        /// Project path
+        path: String,
+    },
+    /// Move a project and its sessions to the trash
+    Delete {
+        /// Project path
+        path: String,
+        /// Remove it for good instead
+        #[arg(long)]
+        purge: bool,
+    },
+    /// Track another path (e.g. a worktree or second clone) as the same project
+    Alias {
+        /// Project path
+        path: String,
+        /// Path to resolve to the project
+        alias: String,
+    },
+    /// Move a project to a new path, keeping its history
+    Move {
+        /// Current project path
+        old_path: String,
+        /// New project path
+        new_path: String,
+        /// Keep the old path as an alias of the project
+        #[arg(long)]
+        keep_alias: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum SessionsAction {
+    /// List recent sessions with their ids
+    List {
+        /// Maximum number of sessions to show
+        #[arg(short, long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// Show the details of a session
+    Show {
+        /// Session id (see `sessions list`)
+        id: i64,
+    },
+    /// Move a session to the trash
+    Delete {
+        /// Session id (see `sessions list`)
+        id: i64,
+        /// Remove it for good instead (also works on sessions already in the trash)
+        #[arg(long)]
+        purge: bool,
+    },
+    /// Change the work item a session is attributed to
+    SetWorkItem {
+        /// Session id (see `sessions list`)
+        id: i64,
+        /// Work item (empty string falls back to the branch name)
+        work_item: String,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ExportFormat {
+    /// ActivityWatch buckets and events (JSON), importable in aw-server
+    Activitywatch,
+    /// Finished sessions with their commits (JSON lines), for `import --bundle`
+    Bundle,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ImportSource {
+    /// ActivityWatch export or bucket events of aw-watcher-window (JSON)
+    Activitywatch,
+    /// Toggl Track detailed report (CSV)
+    Toggl,
+    /// Clockify detailed report (CSV)
+    Clockify,
+    /// Any CSV, or JSON array of objects, read with the import.columns mapping
+    GenericCsv,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ProjectField {
+    /// Display name
+    Name,
+    /// Work item regex pattern (or a JSON array of patterns tried in order)
+    Pattern,
+    /// Whether time on this project is billable (true/false)
+    Billable,
+    /// Hourly rate
+    Rate,
+    /// Client the project is invoiced to
+    Client,
+}
+
+/// Parse an interval like `90`, `30s`, `5m` or `1h` (bare numbers are seconds)
+fn parse_interval(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let (number, unit) = match value.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => value.split_at(index),
+        None => (value, "s"),
+    };
+
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("invalid interval: {}", value))?;
+    let multiplier = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        _ => return Err(format!("invalid interval unit in {} (use s, m or h)", value)),
+    };
+    let seconds = number
+        .checked_mul(multiplier)
+        .ok_or_else(|| format!("interval too long: {}", value))?;
+
+    if seconds == 0 {
+        return Err("interval must be greater than zero".to_string());
+    }
+
+    Ok(Duration::from_secs(seconds))
+}
+
+/// Parse an RFC 3339 time, or a day (YYYY-MM-DD) meaning its midnight UTC
+fn parse_since(value: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map(|day| day.and_time(chrono::NaiveTime::MIN).and_utc())
+        .map_err(|_| format!("invalid time: {} (use RFC 3339 or YYYY-MM-DD)", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_interval() {
+        assert_eq!(parse_interval("90").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_interval("30s").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse_interval("5m").unwrap(), Duration::from_secs(300));
+        assert_eq!(parse_interval("1h").unwrap(), Duration::from_secs(3600));
+        assert!(parse_interval("0m").is_err());
+        assert!(parse_interval("5d").is_err());
+        assert!(parse_interval("m").is_err());
+        assert!(parse_interval("18446744073709551615h").is_err());
+    }
 }
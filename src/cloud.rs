@@ -0,0 +1,423 @@
+//! Sharing sessions between machines through S3-compatible storage or WebDAV
+//!
+//! Each sync uploads one bundle, `<time>-<machine>.jsonl`, holding the sessions
+//! that changed since the last upload, and merges the bundles other machines
+//! uploaded since the last sync. When a session changed on both sides, the
+//! copy that ended later wins.
+
+use anyhow::{bail, Context, Result};
+use chrono::Utc;
+use regex::Regex;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use tracing::{debug, warn};
+
+use crate::config::SyncSettings;
+use crate::db::Database;
+use crate::http::Request;
+use crate::secrets;
+use crate::server::percent_decode;
+use crate::storage::Storage;
+use crate::sync::{self, Merged, SyncRecord};
+
+/// Bundles are named `<time>-<machine>.jsonl`; the time sorts them
+const BUNDLE_TIME_FORMAT: &str = "%Y%m%dT%H%M%S%.6fZ";
+const BUNDLE_TIME_LENGTH: usize = 23;
+
+const TIMEOUT_SECONDS: u64 = 120;
+
+/// Where bundles are kept
+pub trait Store {
+    /// Names of all bundles
+    fn list(&self) -> Result<Vec<String>>;
+    fn get(&self, name: &str) -> Result<String>;
+    fn put(&self, name: &str, content: String) -> Result<()>;
+}
+
+/// What a sync did
+#[derive(Debug, Default)]
+pub struct CloudStats {
+    /// Bundles of other machines merged
+    pub bundles: usize,
+    pub added: usize,
+    pub updated: usize,
+    /// Sessions uploaded, and the bundle holding them
+    pub uploaded: usize,
+    pub bundle: Option<String>,
+}
+
+/// The store `url` names: `s3://bucket/prefix` or a WebDAV folder
+pub fn open(url: &str, settings: &SyncSettings) -> Result<Box<dyn Store>> {
+    if let Some(path) = url.strip_prefix("s3://") {
+        let (bucket, prefix) = path.split_once('/').unwrap_or((path, ""));
+        let region = settings.region.clone().unwrap_or_else(|| "us-east-1".to_string());
+        let endpoint = settings
+            .endpoint
+            .clone()
+            .unwrap_or_else(|| format!("https://s3.{}.amazonaws.com", region));
+        let prefix = prefix.trim_matches('/');
+        return Ok(Box::new(S3 {
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            bucket: bucket.to_string(),
+            prefix: if prefix.is_empty() { String::new() } else { format!("{}/", prefix) },
+            region,
+            access_key_id: settings
+                .access_key_id
+                .clone()
+                .context("Set sync.access_key_id to use S3")?,
+            secret_access_key: settings
+                .secret_access_key
+                .clone()
+                .or_else(|| secrets::lookup("sync"))
+                .context("Set sync.secret_access_key (or store it in the keychain as account sync)")?,
+        }));
+    }
+    if url.starts_with("https://") || url.starts_with("http://") {
+        let credentials = settings.username.as_ref().map(|username| {
+            let password = settings.password.clone().or_else(|| secrets::lookup("sync"));
+            (username.clone(), password.unwrap_or_default())
+        });
+        return Ok(Box::new(WebDav {
+            url: url.trim_end_matches('/').to_string(),
+            credentials,
+        }));
+    }
+    bail!("sync.url must be s3://bucket/prefix or the http(s):// URL of a WebDAV folder")
+}
+
+/// Merge the other machines' new bundles, then upload what changed here
+///
+/// `location` identifies the store, so switching stores uploads everything again.
+pub fn sync(db: &Database, store: &dyn Store, location: &str, machine: &str) -> Result<CloudStats> {
+    let target = format!("sync:{}", location);
+    let own = machine_slug(machine);
+    let mut stats = CloudStats::default();
+
+    let merged = db.get_merged_bundles(location)?;
+    let mut names = store.list()?;
+    names.sort();
+    for name in names {
+        let Some(from) = bundle_machine(&name) else {
+            continue;
+        };
+        if from == own || merged.contains(&name) {
+            continue;
+        }
+        debug!("Merging bundle {}", name);
+        let content = store.get(&name)?;
+        db.atomically(|| {
+            merge_bundle(db, &target, &name, &content, &mut stats)?;
+            db.mark_bundle_merged(location, &name)
+        })?;
+        stats.bundles += 1;
+    }
+
+    let uploaded = db.get_synced_ids(&target)?;
+    let mut content = String::new();
+    let mut changed = Vec::new();
+    for session in db.get_sessions_for_sync(None, None)? {
+        let record = sync::record(db, &session)?;
+        let fingerprint = fingerprint(&record)?;
+        if uploaded.get(&session.uuid) != Some(&fingerprint) {
+            content.push_str(&serde_json::to_string(&record)?);
+            content.push('\n');
+            changed.push((session.uuid, fingerprint));
+        }
+    }
+    if changed.is_empty() {
+        return Ok(stats);
+    }
+
+    let name = format!("{}-{}.jsonl", Utc::now().format(BUNDLE_TIME_FORMAT), own);
+    store.put(&name, content)?;
+    db.atomically(|| {
+        for (uuid, fingerprint) in &changed {
+            db.mark_session_synced(&target, uuid, fingerprint)?;
+        }
+        db.mark_bundle_merged(location, &name)
+    })?;
+    stats.uploaded = changed.len();
+    stats.bundle = Some(name);
+    Ok(stats)
+}
+
+fn merge_bundle(db: &Database, target: &str, name: &str, content: &str, stats: &mut CloudStats) -> Result<()> {
+    let mut tracked = db.list_projects(true)?;
+    for (index, line) in content.lines().enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+        let record: SyncRecord = match serde_json::from_str(line) {
+            Ok(record) => record,
+            Err(e) => {
+                warn!("Skipping line {} of bundle {}: {}", index + 1, name, e);
+                continue;
+            }
+        };
+
+        let uuid = record.session.uuid.clone();
+        let incoming = (record.session.ended_at, fingerprint(&record)?);
+        let merged = sync::merge_record(db, &mut tracked, record, |local, _| {
+            // The fingerprint breaks ties the same way on every machine
+            let local = (local.ended_at, fingerprint(&sync::record(db, local)?)?);
+            Ok(incoming > local)
+        })?;
+        match merged {
+            Merged::Added => stats.added += 1,
+            Merged::Updated => stats.updated += 1,
+            Merged::Kept => continue,
+        }
+        // Taken as is, so there is nothing to upload back
+        db.mark_session_synced(target, &uuid, &incoming.1)?;
+    }
+    Ok(())
+}
+
+/// SHA-256 of what a session is made of, leaving out ids that differ between machines
+///
+/// Fields serialize in a fixed order, so the same session hashes the same on
+/// every machine and with every build.
+fn fingerprint(record: &SyncRecord) -> Result<String> {
+    let mut session = record.session.clone();
+    session.id = 0;
+    session.project_id = 0;
+    let commits: Vec<&str> = record.commits.iter().map(|c| c.hash.as_str()).collect();
+    let branches: Vec<_> = record
+        .branches
+        .iter()
+        .map(|b| (&b.branch, &b.work_item, b.active_seconds))
+        .collect();
+    let content = serde_json::to_string(&json!([session, commits, branches, record.activity]))?;
+
+    let digest = Sha256::digest(content.as_bytes());
+    Ok(digest.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// Machine name as it appears in bundle names
+fn machine_slug(machine: &str) -> String {
+    machine
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' { c } else { '_' })
+        .collect()
+}
+
+/// Machine that uploaded a bundle, `None` for other files
+fn bundle_machine(name: &str) -> Option<&str> {
+    let (time, rest) = name.split_at_checked(BUNDLE_TIME_LENGTH)?;
+    if !time.ends_with('Z') {
+        return None;
+    }
+    rest.strip_prefix('-')?.strip_suffix(".jsonl").filter(|m| !m.is_empty())
+}
+
+/// Text of every `<tag>` element, whatever its namespace prefix
+fn xml_values(xml: &str, tag: &str) -> Vec<String> {
+    let pattern = Regex::new(&format!(r"<(?:[\w-]+:)?{0}(?:\s[^>]*)?>([^<]*)</(?:[\w-]+:)?{0}>", tag))
+        .expect("valid pattern");
+    pattern
+        .captures_iter(xml)
+        .map(|c| {
+            c[1].replace("&lt;", "<")
+                .replace("&gt;", ">")
+                .replace("&quot;", "\"")
+                .replace("&apos;", "'")
+                .replace("&amp;", "&")
+        })
+        .collect()
+}
+
+/// Percent-encode a query parameter value
+fn query_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+struct S3 {
+    endpoint: String,
+    bucket: String,
+    /// Empty, or ending in `/`
+    prefix: String,
+    region: String,
+    access_key_id: String,
+    secret_access_key: String,
+}
+
+impl S3 {
+    fn request(&self, method: &'static str, url: String) -> Request {
+        Request::new(method, url)
+            .aws_sigv4(&self.region, &self.access_key_id, &self.secret_access_key)
+            .timeout(TIMEOUT_SECONDS)
+    }
+
+    fn object_url(&self, name: &str) -> String {
+        format!("{}/{}/{}{}", self.endpoint, self.bucket, self.prefix, name)
+    }
+}
+
+impl Store for S3 {
+    fn list(&self) -> Result<Vec<String>> {
+        let mut names = Vec::new();
+        let mut continuation: Option<String> = None;
+        loop {
+            let mut url = format!(
+                "{}/{}?list-type=2&prefix={}",
+                self.endpoint,
+                self.bucket,
+                query_encode(&self.prefix)
+            );
+            if let Some(ref token) = continuation {
+                url.push_str(&format!("&continuation-token={}", query_encode(token)));
+            }
+            let body = self.request("GET", url).send_text()?;
+            names.extend(
+                xml_values(&body, "Key")
+                    .iter()
+                    .filter_map(|key| key.strip_prefix(&self.prefix))
+                    .filter(|name| !name.contains('/'))
+                    .map(String::from),
+            );
+
+            continuation = xml_values(&body, "NextContinuationToken").into_iter().next();
+            if xml_values(&body, "IsTruncated").first().map(String::as_str) != Some("true") || continuation.is_none() {
+                return Ok(names);
+            }
+        }
+    }
+
+    fn get(&self, name: &str) -> Result<String> {
+        self.request("GET", self.object_url(name)).send_text()
+    }
+
+    fn put(&self, name: &str, content: String) -> Result<()> {
+        self.request("PUT", self.object_url(name))
+            .text("application/x-ndjson", content)
+            .send_text()?;
+        Ok(())
+    }
+}
+
+struct WebDav {
+    /// Folder URL without a trailing `/`
+    url: String,
+    credentials: Option<(String, String)>,
+}
+
+impl WebDav {
+    fn request(&self, method: &'static str, url: String) -> Request {
+        let request = Request::new(method, url).timeout(TIMEOUT_SECONDS);
+        match self.credentials {
+            Some((ref user, ref password)) => request.basic_auth(user, password),
+            None => request,
+        }
+    }
+}
+
+impl Store for WebDav {
+    fn list(&self) -> Result<Vec<String>> {
+        let body = r#"<?xml version="1.0" encoding="utf-8"?><propfind xmlns="DAV:"><prop><resourcetype/></prop></propfind>"#;
+        let response = self
+            .request("PROPFIND", format!("{}/", self.url))
+            .header("Depth", "1")
+            .text("application/xml", body.to_string())
+            .send_text()?;
+        Ok(xml_values(&response, "href")
+            .iter()
+            .filter_map(|href| href.trim_end_matches('/').rsplit('/').next())
+            .map(percent_decode)
+            .filter(|name| name.ends_with(".jsonl"))
+            .collect())
+    }
+
+    fn get(&self, name: &str) -> Result<String> {
+        self.request("GET", format!("{}/{}", self.url, name)).send_text()
+    }
+
+    fn put(&self, name: &str, content: String) -> Result<()> {
+        self.request("PUT", format!("{}/{}", self.url, name))
+            .text("application/x-ndjson", content)
+            .send_text()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::SessionStatus;
+    use std::cell::RefCell;
+    use std::collections::BTreeMap;
+    use tempfile::tempdir;
+
+    /// Bundles kept in memory
+    #[derive(Default)]
+    struct MemoryStore(RefCell<BTreeMap<String, String>>);
+
+    impl Store for MemoryStore {
+        fn list(&self) -> Result<Vec<String>> {
+            Ok(self.0.borrow().keys().cloned().collect())
+        }
+
+        fn get(&self, name: &str) -> Result<String> {
+            self.0.borrow().get(name).cloned().context("No such bundle")
+        }
+
+        fn put(&self, name: &str, content: String) -> Result<()> {
+            self.0.borrow_mut().insert(name.to_string(), content);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_bundle_names() {
+        assert_eq!(bundle_machine("20261016T120000.123456Z-laptop.jsonl"), Some("laptop"));
+        assert_eq!(bundle_machine("notes.jsonl"), None);
+        assert_eq!(machine_slug("Jo's Mac/mini"), "Jo_s_Mac_mini");
+
+        let listing = r#"<d:multistatus xmlns:d="DAV:"><d:response><d:href>/dav/ctt/</d:href></d:response>
+            <d:response><d:href>/dav/ctt/20261016T120000.000000Z-my%20mac.jsonl</d:href></d:response></d:multistatus>"#;
+        assert_eq!(xml_values(listing, "href")[1], "/dav/ctt/20261016T120000.000000Z-my%20mac.jsonl");
+        assert_eq!(xml_values("<Key>a&amp;b</Key>", "Key"), vec!["a&b"]);
+        assert_eq!(query_encode("ctt/x y"), "ctt%2Fx%20y");
+    }
+
+    #[test]
+    fn test_sync_resolves_conflicts() {
+        let dir = tempdir().unwrap();
+        let desktop = Database::open(&dir.path().join("desktop.db")).unwrap();
+        let laptop = Database::open(&dir.path().join("laptop.db")).unwrap();
+        let store = MemoryStore::default();
+
+        let project = laptop.get_or_create_project("/src/api", None, None, None).unwrap();
+        let session = laptop
+            .create_session(project.id, "main", None, None, None, Some("laptop"))
+            .unwrap();
+        let ended = Utc::now();
+        laptop.complete_session(session.id, None, 600, ended, SessionStatus::Completed).unwrap();
+
+        let stats = sync(&laptop, &store, "s3://b", "laptop").unwrap();
+        assert_eq!(stats.uploaded, 1);
+        assert_eq!(sync(&laptop, &store, "s3://b", "laptop").unwrap().uploaded, 0);
+
+        let stats = sync(&desktop, &store, "s3://b", "desktop").unwrap();
+        assert_eq!((stats.bundles, stats.added, stats.uploaded), (1, 1, 0));
+        let copy = desktop.get_session_by_uuid(&session.uuid).unwrap().unwrap();
+        // Both machines fingerprint the session the same way
+        let original = laptop.get_session_by_id(session.id).unwrap();
+        let print = fingerprint(&sync::record(&laptop, &original).unwrap()).unwrap();
+        assert_eq!(print, fingerprint(&sync::record(&desktop, &copy).unwrap()).unwrap());
+        assert_eq!(print.len(), 64);
+
+        // Extended on the desktop, merely relabelled on the laptop: the later end wins
+        desktop.reopen_session(copy.id, None).unwrap();
+        desktop
+            .complete_session(copy.id, None, 900, ended + chrono::Duration::minutes(5), SessionStatus::Completed)
+            .unwrap();
+        laptop.set_session_work_item(session.id, Some("ABC-1")).unwrap();
+        assert_eq!(sync(&desktop, &store, "s3://b", "desktop").unwrap().uploaded, 1);
+        let stats = sync(&laptop, &store, "s3://b", "laptop").unwrap();
+        assert_eq!((stats.bundles, stats.updated, stats.uploaded), (1, 1, 0));
+        assert_eq!(laptop.get_session_by_id(session.id).unwrap().active_seconds, Some(900));
+    }
+}
@@ -1,38 +1,432 @@
 use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use toml_edit::DocumentMut;
+use tracing::warn;
+
+use crate::events;
+use crate::git::CommitFilter;
+use crate::vcs;
+use crate::webhook::Dispatch;
+
+/// Organization-wide defaults, merged below the user's global config
+pub const SYSTEM_CONFIG_PATH: &str = "/etc/claude-time-tracker/config.toml";
+
+/// Schema version written to new global config files
+pub const CONFIG_VERSION: i64 = 1;
+
+/// Keys moved by each schema version, as (version, old dotted key, new dotted key)
+///
+/// Files older than a version get its keys moved when they are loaded.
+const RENAMED_KEYS: &[(i64, &str, &str)] = &[];
 
 /// Global configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GlobalConfig {
+    /// Schema version of the file, see [`CONFIG_VERSION`]
+    #[serde(default)]
+    pub version: i64,
     #[serde(default)]
     pub settings: Settings,
     #[serde(default)]
     pub report: ReportSettings,
+    #[serde(default)]
+    pub cron: CronSettings,
+    #[serde(default)]
+    pub jira: JiraSettings,
+    #[serde(default)]
+    pub tempo: TempoSettings,
+    #[serde(default)]
+    pub toggl: TogglSettings,
+    #[serde(default)]
+    pub clockify: ClockifySettings,
+    #[serde(default)]
+    pub harvest: HarvestSettings,
+    #[serde(default)]
+    pub slack: SlackSettings,
+    #[serde(default)]
+    pub import: ImportSettings,
+    #[serde(default)]
+    pub server: ServerSettings,
+    #[serde(default)]
+    pub sync: SyncSettings,
+    /// `[[webhooks]]` called on session lifecycle events
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub webhooks: Vec<WebhookSettings>,
+    /// Per-project settings keyed by path, for repositories without a project config file
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub projects: BTreeMap<String, GlobalProjectSettings>,
 }
 
 impl Default for GlobalConfig {
     fn default() -> Self {
         Self {
+            version: CONFIG_VERSION,
             settings: Settings::default(),
             report: ReportSettings::default(),
+            cron: CronSettings::default(),
+            jira: JiraSettings::default(),
+            tempo: TempoSettings::default(),
+            toggl: TogglSettings::default(),
+            clockify: ClockifySettings::default(),
+            harvest: HarvestSettings::default(),
+            slack: SlackSettings::default(),
+            import: ImportSettings::default(),
+            server: ServerSettings::default(),
+            sync: SyncSettings::default(),
+            webhooks: Vec::new(),
+            projects: BTreeMap::new(),
+        }
+    }
+}
+
+/// Jira site `push jira` creates worklogs on
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct JiraSettings {
+    /// e.g. `https://acme.atlassian.net`
+    pub base_url: Option<String>,
+    /// Account email; Jira Cloud authenticates with email and API token,
+    /// Jira Server/Data Center with the token alone
+    pub email: Option<String>,
+    /// API token, else taken from the keychain (account `jira`)
+    pub api_token: Option<String>,
+}
+
+/// Tempo Timesheets account `push tempo` logs work with
+///
+/// Issues are looked up on the `[jira]` site, since Tempo identifies them by id.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TempoSettings {
+    /// API root, e.g. `https://api.eu.tempo.io/4` for the EU region
+    #[serde(default = "default_tempo_url")]
+    pub base_url: String,
+    /// API token, else taken from the keychain (account `tempo`)
+    pub api_token: Option<String>,
+    /// Jira account id to log work as, else the one of the `[jira]` token
+    pub account_id: Option<String>,
+}
+
+impl Default for TempoSettings {
+    fn default() -> Self {
+        Self {
+            base_url: default_tempo_url(),
+            api_token: None,
+            account_id: None,
         }
     }
 }
 
+fn default_tempo_url() -> String {
+    "https://api.tempo.io/4".to_string()
+}
+
+/// Toggl Track workspace `push toggl` creates time entries in
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TogglSettings {
+    #[serde(default = "default_toggl_url")]
+    pub base_url: String,
+    /// API token, else taken from the keychain (account `toggl`)
+    pub api_token: Option<String>,
+    /// Workspace to use, else the default workspace of the token's user
+    pub workspace_id: Option<i64>,
+}
+
+impl Default for TogglSettings {
+    fn default() -> Self {
+        Self {
+            base_url: default_toggl_url(),
+            api_token: None,
+            workspace_id: None,
+        }
+    }
+}
+
+fn default_toggl_url() -> String {
+    "https://api.track.toggl.com/api/v9".to_string()
+}
+
+/// Clockify workspace `push clockify` creates time entries in
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClockifySettings {
+    /// API root, e.g. `https://euc1.clockify.me/api/v1` for a regional workspace
+    #[serde(default = "default_clockify_url")]
+    pub base_url: String,
+    /// API key, else taken from the keychain (account `clockify`)
+    pub api_key: Option<String>,
+    /// Workspace to use, else the default workspace of the key's user
+    pub workspace_id: Option<String>,
+}
+
+impl Default for ClockifySettings {
+    fn default() -> Self {
+        Self {
+            base_url: default_clockify_url(),
+            api_key: None,
+            workspace_id: None,
+        }
+    }
+}
+
+fn default_clockify_url() -> String {
+    "https://api.clockify.me/api/v1".to_string()
+}
+
+/// Harvest account `push harvest` creates time entries in
+///
+/// Which project and task a tracker project's time goes to is set per project
+/// with `[projects."<path>"] harvest = { project_id = …, task_id = … }`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HarvestSettings {
+    pub account_id: Option<String>,
+    /// Personal access token, else taken from the keychain (account `harvest`)
+    pub access_token: Option<String>,
+}
+
+/// Slack incoming webhook `notify slack` posts summaries to
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SlackSettings {
+    /// Webhook URL, else taken from the keychain (account `slack`)
+    pub webhook_url: Option<String>,
+    /// Post a line about every session when it stops
+    #[serde(default)]
+    pub notify_on_stop: bool,
+}
+
+/// Local API `serve` exposes
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ServerSettings {
+    /// Bearer token clients must send, else taken from the keychain (account `server`); no auth without one
+    pub token: Option<String>,
+}
+
+/// Where `sync` shares sessions between machines: a git repository or a cloud store
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SyncSettings {
+    /// Local clone of the repository; pulled from and pushed to its upstream when it has one
+    pub repo: Option<String>,
+    /// `s3://bucket/prefix` or a WebDAV folder URL; used instead of `repo` when set
+    pub url: Option<String>,
+    /// S3-compatible endpoint, defaults to AWS
+    pub endpoint: Option<String>,
+    /// S3 region, defaults to us-east-1
+    pub region: Option<String>,
+    pub access_key_id: Option<String>,
+    /// S3 secret, else taken from the keychain (account `sync`)
+    pub secret_access_key: Option<String>,
+    /// WebDAV user
+    pub username: Option<String>,
+    /// WebDAV password, else taken from the keychain (account `sync`)
+    pub password: Option<String>,
+}
+
+/// How `import` turns other time trackers' entries into sessions
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImportSettings {
+    /// Project path for each external project name; unlisted names go to the project of the same name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub projects: BTreeMap<String, String>,
+    /// Columns (or JSON keys) `import --from generic-csv` reads
+    #[serde(default)]
+    pub columns: ColumnMapping,
+}
+
+/// Where the fields of a time entry are in an exported file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ColumnMapping {
+    pub project: String,
+    /// Becomes the work item of the session
+    pub description: String,
+    pub start: String,
+    /// Column holding the start time when `start` only holds the date
+    pub start_time: Option<String>,
+    pub end: String,
+    /// Column holding the end time when `end` only holds the date
+    pub end_time: Option<String>,
+    /// Used when there is no end: seconds or H:MM:SS
+    pub duration: String,
+    /// chrono format of local times that aren't RFC 3339, e.g. `%d.%m.%Y %H:%M`
+    pub time_format: Option<String>,
+}
+
+impl Default for ColumnMapping {
+    fn default() -> Self {
+        Self {
+            project: "project".to_string(),
+            description: "description".to_string(),
+            start: "start".to_string(),
+            start_time: None,
+            end: "end".to_string(),
+            end_time: None,
+            duration: "duration".to_string(),
+            time_format: None,
+        }
+    }
+}
+
+/// A `[[webhooks]]` entry: a URL sessions are posted to as JSON
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookSettings {
+    pub url: String,
+    /// Events to send, all of them by default
+    #[serde(default = "default_webhook_events")]
+    pub events: Vec<WebhookEvent>,
+    /// Further attempts after a failed delivery, waiting 1s, 2s, 4s, … in between
+    #[serde(default = "default_webhook_retries")]
+    pub retries: u32,
+}
+
+/// Session lifecycle events webhooks can subscribe to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookEvent {
+    Start,
+    Stop,
+    Abandon,
+}
+
+impl WebhookEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookEvent::Start => "start",
+            WebhookEvent::Stop => "stop",
+            WebhookEvent::Abandon => "abandon",
+        }
+    }
+}
+
+fn default_webhook_events() -> Vec<WebhookEvent> {
+    vec![WebhookEvent::Start, WebhookEvent::Stop, WebhookEvent::Abandon]
+}
+
+fn default_webhook_retries() -> u32 {
+    3
+}
+
+/// A `[projects."<path>"]` entry of the global config
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GlobalProjectSettings {
+    /// Display name, used when the project has no config file of its own
+    pub name: Option<String>,
+    /// Client the project is grouped under in reports
+    pub client: Option<String>,
+    /// Harvest project and task `push harvest` logs the project's time on
+    pub harvest: Option<HarvestTask>,
+}
+
+/// A task of a Harvest project, e.g. `{ project_id = 14307913, task_id = 8083365 }`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct HarvestTask {
+    pub project_id: i64,
+    pub task_id: i64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Settings {
     #[serde(default = "default_idle_timeout")]
     pub idle_timeout_minutes: u32,
+    /// Minutes still credited after the last heartbeat before an idle gap
+    #[serde(default)]
+    pub idle_grace_minutes: u32,
+    /// Resume a session stopped on the same branch within this many minutes (0 disables)
+    #[serde(default)]
+    pub resume_window_minutes: u32,
+    /// Sessions running longer than this are completed and a new one started (0 disables)
+    #[serde(default = "default_max_session_hours")]
+    pub max_session_hours: u32,
     #[serde(default = "default_database_path")]
     pub database_path: String,
+    /// Machine name recorded on sessions (defaults to the hostname)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub machine: Option<String>,
+    /// IANA time zone (e.g. `Europe/Berlin`) that report months and dates are in; defaults to local time
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+    /// Hour (0-23) a working day starts at; work before it counts toward the previous day
+    #[serde(default)]
+    pub day_start_hour: u32,
+    /// Also write logs to a file next to the database
+    #[serde(default)]
+    pub log_to_file: bool,
+    /// Append tracking events and edits of sessions and projects to events.jsonl next to the database (see `rebuild`)
+    #[serde(default)]
+    pub event_log: bool,
+    /// Fold identical heartbeats arriving within this many seconds into one (0 disables)
+    #[serde(default = "default_heartbeat_coalesce")]
+    pub heartbeat_coalesce_seconds: u32,
+    /// What identifies a project: its path, or its git remote (so worktrees and clones share one)
+    #[serde(default)]
+    pub project_identity: ProjectIdentity,
+    /// Only attribute commits by `author_emails` (or the repository's user.email) to sessions
+    #[serde(default)]
+    pub only_own_commits: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub author_emails: Vec<String>,
+    /// Leave merge commits out of sessions
+    #[serde(default)]
+    pub exclude_merge_commits: bool,
+    /// Regexes for commit messages to leave out of sessions, e.g. `^chore\(release\)`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude_commit_patterns: Vec<String>,
+    /// Look up each session's pull request with `gh`/`glab` when it stops
+    #[serde(default)]
+    pub link_pull_requests: bool,
+    /// Show the titles of issue-number work items in reports, looked up with `gh` and cached
+    #[serde(default)]
+    pub fetch_issue_titles: bool,
+    /// Also record commits made inside the project's git submodules
+    #[serde(default)]
+    pub track_submodules: bool,
+    /// Globs for directories never tracked, e.g. `~/tmp/**` or `**/scratch*`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude_paths: Vec<String>,
+    /// Work item pattern(s) for projects whose config file sets none
+    pub work_item_pattern: Option<PatternList>,
+    /// Commit message work item pattern for projects whose config file sets none
+    pub commit_work_item_pattern: Option<String>,
+}
+
+/// How checkouts are matched to projects
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProjectIdentity {
+    /// Every path is its own project
+    #[default]
+    Path,
+    /// Checkouts with the same remote URL are one project; extra paths become aliases
+    Remote,
 }
 
 impl Default for Settings {
     fn default() -> Self {
         Self {
             idle_timeout_minutes: default_idle_timeout(),
+            idle_grace_minutes: 0,
+            resume_window_minutes: 0,
+            max_session_hours: default_max_session_hours(),
             database_path: default_database_path(),
+            machine: None,
+            timezone: None,
+            day_start_hour: 0,
+            log_to_file: false,
+            event_log: false,
+            heartbeat_coalesce_seconds: default_heartbeat_coalesce(),
+            project_identity: ProjectIdentity::Path,
+            only_own_commits: false,
+            author_emails: Vec::new(),
+            exclude_merge_commits: false,
+            exclude_commit_patterns: Vec::new(),
+            link_pull_requests: false,
+            fetch_issue_titles: false,
+            track_submodules: false,
+            exclude_paths: Vec::new(),
+            work_item_pattern: None,
+            commit_work_item_pattern: None,
         }
     }
 }
@@ -41,14 +435,31 @@ fn default_idle_timeout() -> u32 {
     10
 }
 
+fn default_max_session_hours() -> u32 {
+    12
+}
+
+fn default_heartbeat_coalesce() -> u32 {
+    30
+}
+
 fn default_database_path() -> String {
     "~/.local/share/claude-time-tracker/data.db".to_string()
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ReportSettings {
+    /// Format(s) used when `report` gets no `--format`, comma-separated
     #[serde(default = "default_format")]
     pub default_format: String,
+    /// Month reported when `report` gets no `--month`
+    #[serde(default)]
+    pub default_period: ReportPeriod,
+    /// Day and month the time of a session spanning midnight counts towards
+    #[serde(default)]
+    pub attribution: Attribution,
+    /// Folder `report` writes files to when several formats go out without `--output`
+    pub output_dir: Option<String>,
     #[serde(default = "default_include_commits")]
     pub include_commits: bool,
     #[serde(default = "default_max_commits")]
@@ -59,12 +470,37 @@ impl Default for ReportSettings {
     fn default() -> Self {
         Self {
             default_format: default_format(),
+            default_period: ReportPeriod::CurrentMonth,
+            attribution: Attribution::Start,
+            output_dir: None,
             include_commits: default_include_commits(),
             max_commits_per_item: default_max_commits(),
         }
     }
 }
 
+/// Month a report covers by default
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReportPeriod {
+    #[default]
+    CurrentMonth,
+    LastMonth,
+}
+
+/// Day (and so month) a session's time is reported under
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Attribution {
+    /// All of it on the day the session started
+    #[default]
+    Start,
+    /// All of it on the day the session ended
+    End,
+    /// Spread over the days the session ran, in proportion to the time on each
+    Split,
+}
+
 fn default_format() -> String {
     "markdown".to_string()
 }
@@ -77,45 +513,310 @@ fn default_max_commits() -> usize {
     10
 }
 
+/// Settings for the monthly `cron` report generation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CronSettings {
+    #[serde(default = "default_cron_output_dir")]
+    pub output_dir: String,
+    #[serde(default = "default_cron_formats")]
+    pub formats: Vec<String>,
+    #[serde(default = "default_cron_notify")]
+    pub notify: bool,
+}
+
+impl Default for CronSettings {
+    fn default() -> Self {
+        Self {
+            output_dir: default_cron_output_dir(),
+            formats: default_cron_formats(),
+            notify: default_cron_notify(),
+        }
+    }
+}
+
+fn default_cron_output_dir() -> String {
+    "~/Documents/claude-time-tracker".to_string()
+}
+
+fn default_cron_formats() -> Vec<String> {
+    vec!["md".to_string(), "csv".to_string()]
+}
+
+fn default_cron_notify() -> bool {
+    true
+}
+
+/// Time zone that report periods and dates are computed in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timezone {
+    /// The machine's local time zone
+    Local,
+    Named(Tz),
+}
+
+impl Timezone {
+    /// Parse an IANA time zone name; `local` means the machine's time zone
+    pub fn parse(name: &str) -> Result<Self> {
+        if name.eq_ignore_ascii_case("local") {
+            return Ok(Timezone::Local);
+        }
+        name.parse::<Tz>()
+            .map(Timezone::Named)
+            .map_err(|_| anyhow::anyhow!("Unknown time zone: {} (expected an IANA name like Europe/Berlin)", name))
+    }
+
+    /// Wall-clock time of an instant in this zone
+    pub fn local_time(&self, instant: DateTime<Utc>) -> NaiveDateTime {
+        match self {
+            Timezone::Local => instant.with_timezone(&chrono::Local).naive_local(),
+            Timezone::Named(tz) => instant.with_timezone(tz).naive_local(),
+        }
+    }
+
+    /// First instant at or after a wall-clock time in this zone
+    pub fn instant_at(&self, local: NaiveDateTime) -> DateTime<Utc> {
+        match self {
+            Timezone::Local => first_instant(&chrono::Local, local),
+            Timezone::Named(tz) => first_instant(tz, local),
+        }
+    }
+}
+
+fn first_instant<Z: TimeZone>(zone: &Z, local: NaiveDateTime) -> DateTime<Utc> {
+    // Where a daylight saving jump skips the time, take the moment the clocks moved to
+    zone.from_local_datetime(&local)
+        .earliest()
+        .or_else(|| zone.from_local_datetime(&(local + Duration::hours(1))).earliest())
+        .map(|dt| dt.with_timezone(&Utc))
+        .unwrap_or_else(|| Utc.from_utc_datetime(&local))
+}
+
+/// How instants fall into working days: a time zone and the hour days start at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorkCalendar {
+    pub timezone: Timezone,
+    pub day_start_hour: u32,
+}
+
+impl WorkCalendar {
+    /// Working day an instant belongs to; before `day_start_hour` it is still the previous day
+    pub fn day_of(&self, instant: DateTime<Utc>) -> NaiveDate {
+        (self.timezone.local_time(instant) - Duration::hours(self.day_start_hour as i64)).date()
+    }
+
+    /// Today's working day
+    pub fn today(&self) -> NaiveDate {
+        self.day_of(Utc::now())
+    }
+
+    /// First instant of a working day
+    pub fn day_start(&self, day: NaiveDate) -> DateTime<Utc> {
+        let start = NaiveTime::from_hms_opt(self.day_start_hour, 0, 0).unwrap_or(NaiveTime::MIN);
+        self.timezone.instant_at(day.and_time(start))
+    }
+}
+
 /// Project-specific configuration (found in project directory)
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct ProjectConfig {
     pub name: Option<String>,
-    pub work_item_pattern: Option<String>,
+    /// Client the project is invoiced to
+    pub client: Option<String>,
+    pub work_item_pattern: Option<PatternList>,
+    /// Pattern for issue keys in commit messages, used when the branch has no work item
+    pub commit_work_item_pattern: Option<String>,
+    /// Remote identifying the project, instead of the branch's upstream or `origin`
+    pub remote_name: Option<String>,
+    /// Commit message regexes to leave out, on top of the global ones
+    #[serde(default)]
+    pub exclude_commit_patterns: Vec<String>,
+    /// Monorepo sub-projects: path relative to the project root -> name
+    #[serde(default)]
+    pub subprojects: BTreeMap<String, String>,
     #[serde(default)]
     pub report: ProjectReportSettings,
 }
 
+/// A single regex, or a list of regexes tried in order
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum PatternList {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl PatternList {
+    pub fn into_vec(self) -> Vec<String> {
+        match self {
+            PatternList::One(pattern) => vec![pattern],
+            PatternList::Many(patterns) => patterns,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct ProjectReportSettings {
     pub include_commits: Option<bool>,
     pub max_commits_per_item: Option<usize>,
 }
 
+/// Overrides from global command-line flags, applied on every config load
+#[derive(Debug, Clone, Default)]
+pub struct Overrides {
+    pub database_path: Option<PathBuf>,
+    pub config_path: Option<PathBuf>,
+}
+
+static OVERRIDES: OnceLock<Overrides> = OnceLock::new();
+
+/// Install command-line overrides; only the first call takes effect
+pub fn set_overrides(overrides: Overrides) {
+    let _ = OVERRIDES.set(overrides);
+}
+
+fn overrides() -> Overrides {
+    OVERRIDES.get().cloned().unwrap_or_default()
+}
+
 /// Merged configuration for a specific project
 #[derive(Debug, Clone)]
 pub struct EffectiveConfig {
     pub idle_timeout_minutes: u32,
+    pub idle_grace_minutes: u32,
+    pub resume_window_minutes: u32,
+    pub max_session_hours: u32,
+    pub heartbeat_coalesce_seconds: u32,
+    pub project_identity: ProjectIdentity,
+    pub commit_filter: CommitFilter,
+    pub link_pull_requests: bool,
+    pub fetch_issue_titles: bool,
+    pub track_submodules: bool,
+    pub exclude_paths: Vec<glob::Pattern>,
     pub database_path: PathBuf,
+    pub machine: String,
+    pub calendar: WorkCalendar,
+    pub log_to_file: bool,
+    /// Event log to append to, if `settings.event_log` is on
+    pub event_log: Option<PathBuf>,
     pub project_name: Option<String>,
-    pub work_item_pattern: Option<String>,
+    pub client: Option<String>,
+    pub work_item_patterns: Vec<String>,
+    pub commit_work_item_pattern: Option<String>,
+    pub remote_name: Option<String>,
     pub include_commits: bool,
     pub max_commits_per_item: usize,
+    pub default_format: String,
+    pub default_period: ReportPeriod,
+    pub attribution: Attribution,
+    pub report_output_dir: Option<PathBuf>,
+    pub cron: CronSettings,
+    pub jira: JiraSettings,
+    pub tempo: TempoSettings,
+    pub toggl: TogglSettings,
+    pub clockify: ClockifySettings,
+    pub harvest: HarvestSettings,
+    pub slack: SlackSettings,
+    pub import: ImportSettings,
+    pub server: ServerSettings,
+    pub sync: SyncSettings,
+    pub webhooks: Vec<WebhookSettings>,
+    /// How webhook deliveries leave the process
+    pub webhook_dispatch: Dispatch,
+    /// `[projects]` entries of the global config, keyed by expanded path
+    pub projects: BTreeMap<PathBuf, GlobalProjectSettings>,
 }
 
 impl EffectiveConfig {
+    /// Whether a directory matches one of `settings.exclude_paths`
+    pub fn is_excluded(&self, path: &Path) -> bool {
+        matches_any(&self.exclude_paths, path)
+    }
+
     /// Load configuration with project-specific overrides
     pub fn load(project_path: Option<&Path>) -> Result<Self> {
-        let global = load_global_config()?;
+        Self::from_global(load_global_config()?, project_path)
+    }
+
+    /// Merge a global config with the project's own settings
+    pub fn from_global(global: GlobalConfig, project_path: Option<&Path>) -> Result<Self> {
         let project = project_path.and_then(|p| load_project_config(p).ok());
 
-        let database_path = expand_path(&global.settings.database_path)?;
+        let mut projects = BTreeMap::new();
+        for (path, settings) in &global.projects {
+            projects.insert(expand_path(path.trim_end_matches('/'))?, settings.clone());
+        }
+        let global_project = project_path.and_then(|p| projects.get(p));
+
+        let database_path = match overrides().database_path {
+            Some(path) => expand_path(&path.to_string_lossy())?,
+            None => expand_path(&global.settings.database_path)?,
+        };
+        let event_log = global
+            .settings
+            .event_log
+            .then(|| database_path.with_file_name(events::EVENT_LOG_FILE_NAME));
+        let timezone = match global.settings.timezone {
+            Some(ref name) => Timezone::parse(name).context("Invalid settings.timezone")?,
+            None => Timezone::Local,
+        };
+        if global.settings.day_start_hour > 23 {
+            anyhow::bail!("settings.day_start_hour must be between 0 and 23");
+        }
 
         Ok(Self {
             idle_timeout_minutes: global.settings.idle_timeout_minutes,
+            idle_grace_minutes: global.settings.idle_grace_minutes,
+            resume_window_minutes: global.settings.resume_window_minutes,
+            max_session_hours: global.settings.max_session_hours,
+            heartbeat_coalesce_seconds: global.settings.heartbeat_coalesce_seconds,
+            project_identity: global.settings.project_identity,
+            commit_filter: CommitFilter {
+                own_only: global.settings.only_own_commits,
+                author_emails: global.settings.author_emails.clone(),
+                exclude_merges: global.settings.exclude_merge_commits,
+                exclude_messages: compile_patterns(
+                    global
+                        .settings
+                        .exclude_commit_patterns
+                        .iter()
+                        .chain(project.iter().flat_map(|p| &p.exclude_commit_patterns)),
+                ),
+            },
+            link_pull_requests: global.settings.link_pull_requests,
+            fetch_issue_titles: global.settings.fetch_issue_titles,
+            track_submodules: global.settings.track_submodules,
+            exclude_paths: compile_globs(&global.settings.exclude_paths),
             database_path,
-            project_name: project.as_ref().and_then(|p| p.name.clone()),
-            work_item_pattern: project.as_ref().and_then(|p| p.work_item_pattern.clone()),
+            machine: global
+                .settings
+                .machine
+                .clone()
+                .unwrap_or_else(|| gethostname::gethostname().to_string_lossy().into_owned()),
+            calendar: WorkCalendar {
+                timezone,
+                day_start_hour: global.settings.day_start_hour,
+            },
+            log_to_file: global.settings.log_to_file,
+            event_log,
+            project_name: project
+                .as_ref()
+                .and_then(|p| p.name.clone())
+                .or_else(|| global_project.and_then(|p| p.name.clone())),
+            client: project
+                .as_ref()
+                .and_then(|p| p.client.clone())
+                .or_else(|| global_project.and_then(|p| p.client.clone())),
+            work_item_patterns: project
+                .as_ref()
+                .and_then(|p| p.work_item_pattern.clone())
+                .or_else(|| global.settings.work_item_pattern.clone())
+                .map(PatternList::into_vec)
+                .unwrap_or_default(),
+            commit_work_item_pattern: project
+                .as_ref()
+                .and_then(|p| p.commit_work_item_pattern.clone())
+                .or_else(|| global.settings.commit_work_item_pattern.clone()),
+            remote_name: project.as_ref().and_then(|p| p.remote_name.clone()),
             include_commits: project
                 .as_ref()
                 .and_then(|p| p.report.include_commits)
@@ -124,10 +825,63 @@ impl EffectiveConfig {
                 .as_ref()
                 .and_then(|p| p.report.max_commits_per_item)
                 .unwrap_or(global.report.max_commits_per_item),
+            default_format: global.report.default_format,
+            default_period: global.report.default_period,
+            attribution: global.report.attribution,
+            report_output_dir: global.report.output_dir.as_deref().map(expand_path).transpose()?,
+            cron: global.cron,
+            jira: global.jira,
+            tempo: global.tempo,
+            toggl: global.toggl,
+            clockify: global.clockify,
+            harvest: global.harvest,
+            slack: global.slack,
+            import: global.import,
+            server: global.server,
+            sync: global.sync,
+            webhooks: global.webhooks,
+            webhook_dispatch: Dispatch::default(),
+            projects,
         })
     }
 }
 
+/// Compile regexes from the config, skipping (and warning about) invalid ones
+fn compile_patterns<'a>(patterns: impl Iterator<Item = &'a String>) -> Vec<Regex> {
+    patterns
+        .filter_map(|pattern| match Regex::new(pattern) {
+            Ok(re) => Some(re),
+            Err(e) => {
+                warn!("Ignoring invalid pattern {}: {}", pattern, e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Compile path globs from the config (with `~` expanded), skipping invalid ones
+fn compile_globs(globs: &[String]) -> Vec<glob::Pattern> {
+    globs
+        .iter()
+        .filter_map(|glob| match glob::Pattern::new(&shellexpand::tilde(glob)) {
+            Ok(pattern) => Some(pattern),
+            Err(e) => {
+                warn!("Ignoring invalid path glob {}: {}", glob, e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Whether a path matches any of the globs; `*` stays within one directory, `**` spans several
+fn matches_any(globs: &[glob::Pattern], path: &Path) -> bool {
+    let options = glob::MatchOptions {
+        require_literal_separator: true,
+        ..Default::default()
+    };
+    globs.iter().any(|pattern| pattern.matches_path_with(path, options))
+}
+
 /// Get the global config directory path
 pub fn global_config_dir() -> Result<PathBuf> {
     let config_dir = dirs::config_dir()
@@ -136,35 +890,210 @@ pub fn global_config_dir() -> Result<PathBuf> {
     Ok(config_dir)
 }
 
-/// Get the global config file path
+/// Get the global config file path (honoring `--config` / `CTT_CONFIG`)
 pub fn global_config_path() -> Result<PathBuf> {
+    if let Some(path) = overrides().config_path {
+        return expand_path(&path.to_string_lossy());
+    }
     Ok(global_config_dir()?.join("config.toml"))
 }
 
-/// Load global configuration from ~/.config/claude-time-tracker/config.toml
+/// Load global configuration from ~/.config/claude-time-tracker/config.toml (or the override)
+///
+/// Settings missing there fall back to the system-wide config, if any.
 pub fn load_global_config() -> Result<GlobalConfig> {
     let config_path = global_config_path()?;
+    let system_path = Path::new(SYSTEM_CONFIG_PATH);
 
-    if !config_path.exists() {
+    // A config named on the command line must exist; defaults would hide the typo
+    if overrides().config_path.is_some() && !config_path.exists() {
+        anyhow::bail!(
+            "Config file not found: {} (run `claude-time-tracker config init` to create it)",
+            config_path.display()
+        );
+    }
+
+    if !config_path.exists() && !system_path.exists() {
         return Ok(GlobalConfig::default());
     }
 
+    let mut merged = toml::Table::new();
+    // The system file belongs to the administrator and is only upgraded in memory
+    for (path, persist) in [(system_path, false), (config_path.as_path(), true)] {
+        if !path.exists() {
+            continue;
+        }
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file: {}", path.display()))?;
+        let content = upgrade_config_file(path, content, persist)?;
+        let layer: toml::Table = toml::from_str(&content)
+            .with_context(|| format!("Failed to parse config file: {}", path.display()))?;
+        merge_config_tables(&mut merged, layer);
+    }
+
+    let config: GlobalConfig = merged
+        .try_into()
+        .with_context(|| format!("Invalid configuration in {} or {}", config_path.display(), SYSTEM_CONFIG_PATH))?;
+
+    Ok(config)
+}
+
+/// The value of a dotted key (e.g. `report.include_commits`) in a config
+pub fn config_value(config: &GlobalConfig, key: &str) -> Result<toml::Value> {
+    let mut value = toml::Value::try_from(config).context("Failed to serialize config")?;
+    for name in key.split('.') {
+        value = match value {
+            toml::Value::Table(mut table) => table.remove(name),
+            _ => None,
+        }
+        .with_context(|| format!("Unknown or unset setting: {}", key))?;
+    }
+    Ok(value)
+}
+
+/// Set a dotted key in the global config file, keeping the rest of the file as is
+///
+/// The value is read as TOML (`15`, `true`, `["a", "b"]`), falling back to a
+/// plain string. Returns the file that was written.
+pub fn set_config_value(key: &str, value: &str) -> Result<PathBuf> {
+    let config_path = init_global_config()?;
     let content = std::fs::read_to_string(&config_path)
         .with_context(|| format!("Failed to read config file: {}", config_path.display()))?;
+    let updated = set_config_key(&content, key, value)?;
 
-    let config: GlobalConfig = toml::from_str(&content)
+    std::fs::write(&config_path, updated)
+        .with_context(|| format!("Failed to write config file: {}", config_path.display()))?;
+    Ok(config_path)
+}
+
+/// Set a dotted key in config file content, checking the result is a valid config
+fn set_config_key(content: &str, key: &str, value: &str) -> Result<String> {
+    let mut doc: DocumentMut = content.parse().context("Failed to parse config file")?;
+    let value = value
+        .parse::<toml_edit::Value>()
+        .unwrap_or_else(|_| toml_edit::Value::from(value));
+
+    let (tables, name) = split_config_key(key);
+    let table = config_table(&mut doc, &tables, true)
+        .with_context(|| format!("Can't set {}: {} is not a table", key, tables.join(".")))?;
+    match table.get_mut(name).and_then(|item| item.as_value_mut()) {
+        // Keep the comment after the old value
+        Some(old) => {
+            let decor = old.decor().clone();
+            *old = value;
+            *old.decor_mut() = decor;
+        }
+        None => {
+            table.insert(name, toml_edit::value(value));
+        }
+    }
+
+    let updated = doc.to_string();
+    let config: GlobalConfig = toml::from_str(&updated).with_context(|| format!("Invalid value for {}", key))?;
+    // Unknown keys would be silently ignored
+    config_value(&config, key)?;
+    Ok(updated)
+}
+
+/// Merge a config layer over another: tables merge key by key, other values are replaced
+fn merge_config_tables(base: &mut toml::Table, layer: toml::Table) {
+    for (key, value) in layer {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(layer)) => merge_config_tables(base, layer),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Bring a config file written by an older version up to [`CONFIG_VERSION`]
+///
+/// With `persist`, the upgraded file replaces the old one, which is kept next
+/// to it as `config.toml.v<version>.bak`. Either way (or if the file can't be
+/// written) the upgrade applies for this run.
+fn upgrade_config_file(config_path: &Path, content: String, persist: bool) -> Result<String> {
+    let mut doc: DocumentMut = content
+        .parse()
         .with_context(|| format!("Failed to parse config file: {}", config_path.display()))?;
+    let version = doc.get("version").and_then(|v| v.as_integer()).unwrap_or(0);
+    if version > CONFIG_VERSION {
+        warn!(
+            "{} is from a newer version (config version {}); unknown settings are ignored",
+            config_path.display(),
+            version
+        );
+    }
+    if version >= CONFIG_VERSION {
+        return Ok(content);
+    }
 
-    Ok(config)
+    upgrade_config(&mut doc, version, RENAMED_KEYS);
+    let upgraded = doc.to_string();
+    if !persist {
+        return Ok(upgraded);
+    }
+
+    let mut backup = config_path.as_os_str().to_owned();
+    backup.push(format!(".v{}.bak", version));
+    let backup = PathBuf::from(backup);
+    let written = std::fs::copy(config_path, &backup).and_then(|_| std::fs::write(config_path, &upgraded));
+    // Printed directly: the first load happens before logging is set up
+    match written {
+        Ok(()) => eprintln!(
+            "Upgraded {} to config version {} (previous file: {})",
+            config_path.display(),
+            CONFIG_VERSION,
+            backup.display()
+        ),
+        Err(e) => eprintln!("Failed to upgrade {}: {}", config_path.display(), e),
+    }
+
+    Ok(upgraded)
 }
 
-/// Load project-specific configuration from <project>/.claude-time-tracker.toml
-pub fn load_project_config(project_path: &Path) -> Result<ProjectConfig> {
-    let config_path = project_path.join(".claude-time-tracker.toml");
+/// Move the keys renamed since `version` and stamp the current version
+fn upgrade_config(doc: &mut DocumentMut, version: i64, renames: &[(i64, &str, &str)]) {
+    for (_, old, new) in renames.iter().filter(|(v, _, _)| *v > version) {
+        let (tables, key) = split_config_key(old);
+        let Some(value) = config_table(doc, &tables, false).and_then(|t| t.remove(key)) else {
+            continue;
+        };
+        let (tables, key) = split_config_key(new);
+        match config_table(doc, &tables, true) {
+            Some(table) if !table.contains_key(key) => {
+                table.insert(key, value);
+            }
+            _ => warn!("Dropping config key {}: {} is already set", old, new),
+        }
+    }
+    doc.insert("version", toml_edit::value(CONFIG_VERSION));
+}
 
-    if !config_path.exists() {
-        return Ok(ProjectConfig::default());
+/// The config table at a path of table names, optionally creating missing ones
+fn config_table<'a>(doc: &'a mut DocumentMut, tables: &[&str], create: bool) -> Option<&'a mut toml_edit::Table> {
+    let mut table = doc.as_table_mut();
+    for name in tables {
+        if create && !table.contains_key(name) {
+            table.insert(name, toml_edit::table());
+        }
+        table = table.get_mut(name)?.as_table_mut()?;
     }
+    Some(table)
+}
+
+/// Split `report.default_format` into its tables and key
+fn split_config_key(dotted: &str) -> (Vec<&str>, &str) {
+    let mut tables: Vec<&str> = dotted.split('.').collect();
+    let key = tables.pop().unwrap_or_default();
+    (tables, key)
+}
+
+/// Load project-specific configuration (see [`find_project_config`])
+pub fn load_project_config(project_path: &Path) -> Result<ProjectConfig> {
+    let Some(config_path) = find_project_config(project_path) else {
+        return Ok(ProjectConfig::default());
+    };
 
     let content = std::fs::read_to_string(&config_path)
         .with_context(|| format!("Failed to read project config: {}", config_path.display()))?;
@@ -175,13 +1104,69 @@ pub fn load_project_config(project_path: &Path) -> Result<ProjectConfig> {
     Ok(config)
 }
 
+/// Project config file name, placed in the project root
+pub const PROJECT_CONFIG_FILE: &str = ".claude-time-tracker.toml";
+
+/// Alternative location for the project config, for repos that keep tool configs together
+const PROJECT_CONFIG_DIR_FILE: &str = ".config/claude-time-tracker.toml";
+
+/// Find the project config for a path
+///
+/// Looks in `project_path` and each parent up to the repository root, taking
+/// the first [`PROJECT_CONFIG_FILE`] or `.config/claude-time-tracker.toml` found.
+/// Outside a repository only `project_path` itself is searched.
+pub fn find_project_config(project_path: &Path) -> Option<PathBuf> {
+    let root = vcs::repo_root(project_path);
+    let dirs = project_path.ancestors();
+    let dirs: Vec<&Path> = if project_path.starts_with(&root) {
+        dirs.take_while(|dir| dir.starts_with(&root)).collect()
+    } else {
+        dirs.take(1).collect()
+    };
+
+    dirs.into_iter()
+        .flat_map(|dir| [dir.join(PROJECT_CONFIG_FILE), dir.join(PROJECT_CONFIG_DIR_FILE)])
+        .find(|path| path.is_file())
+}
+
+/// Render a starter project config with commented-out report overrides
+pub fn project_config_template(name: Option<&str>, work_item_pattern: Option<&str>) -> String {
+    let quote = |s: &str| toml::Value::String(s.to_string()).to_string();
+
+    let mut content = String::from("# claude-time-tracker project configuration\n\n");
+
+    match name {
+        Some(name) => content.push_str(&format!("name = {}\n", quote(name))),
+        None => content.push_str("# name = \"My Project\"\n"),
+    }
+
+    match work_item_pattern {
+        Some(pattern) => content.push_str(&format!("work_item_pattern = {}\n", quote(pattern))),
+        None => content.push_str("# work_item_pattern = '^(?:feature|fix|chore)/([A-Z]+-\\d+)'\n"),
+    }
+    content.push_str("# commit_work_item_pattern = '\\b([A-Z][A-Z0-9]+-\\d+)\\b'\n");
+    content.push_str("# remote_name = \"upstream\"\n");
+    content.push_str("# exclude_commit_patterns = ['^chore\\(release\\)']\n");
+
+    content.push_str("\n# Monorepo sub-projects (path relative to this file = name)\n");
+    content.push_str("# [subprojects]\n");
+    content.push_str("# \"apps/web\" = \"Web\"\n");
+
+    content.push_str("\n[report]\n");
+    content.push_str("# include_commits = true\n");
+    content.push_str("# max_commits_per_item = 10\n");
+
+    content
+}
+
 /// Initialize global config directory and create default config if not exists
 pub fn init_global_config() -> Result<PathBuf> {
-    let config_dir = global_config_dir()?;
-    std::fs::create_dir_all(&config_dir)
-        .with_context(|| format!("Failed to create config directory: {}", config_dir.display()))?;
+    let config_path = global_config_path()?;
 
-    let config_path = config_dir.join("config.toml");
+    if let Some(config_dir) = config_path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(config_dir)
+            .with_context(|| format!("Failed to create config directory: {}", config_dir.display()))?;
+    }
 
     if !config_path.exists() {
         let default_config = GlobalConfig::default();
@@ -209,7 +1194,213 @@ mod tests {
     fn test_default_config() {
         let config = GlobalConfig::default();
         assert_eq!(config.settings.idle_timeout_minutes, 10);
+        assert_eq!(config.settings.heartbeat_coalesce_seconds, 30);
+        assert_eq!(config.settings.project_identity, ProjectIdentity::Path);
         assert_eq!(config.report.default_format, "markdown");
+        assert_eq!(config.report.default_period, ReportPeriod::CurrentMonth);
+        assert_eq!(config.report.output_dir, None);
+
+        let config: GlobalConfig = toml::from_str("[report]\ndefault_period = \"last-month\"").unwrap();
+        assert_eq!(config.report.default_period, ReportPeriod::LastMonth);
+        assert_eq!(config.report.attribution, Attribution::Start);
+
+        assert_eq!(config.settings.work_item_pattern, None);
+        let config: GlobalConfig =
+            toml::from_str("[settings]\nwork_item_pattern = ['(TEAM-\\d+)', '#(\\d+)']").unwrap();
+        assert_eq!(
+            config.settings.work_item_pattern.map(PatternList::into_vec),
+            Some(vec![r"(TEAM-\d+)".to_string(), r"#(\d+)".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_project_config_template() {
+        let content = project_config_template(Some("api"), Some(r"([A-Z][A-Z0-9]+-\d+)"));
+        let config: ProjectConfig = toml::from_str(&content).unwrap();
+        assert_eq!(config.name.as_deref(), Some("api"));
+        assert_eq!(
+            config.work_item_pattern,
+            Some(PatternList::One(r"([A-Z][A-Z0-9]+-\d+)".to_string()))
+        );
+        assert!(content.contains("# include_commits = true"));
+
+        let empty: ProjectConfig = toml::from_str(&project_config_template(None, None)).unwrap();
+        assert!(empty.name.is_none());
+        assert!(empty.work_item_pattern.is_none());
+    }
+
+    #[test]
+    fn test_work_item_pattern_list() {
+        let config: ProjectConfig =
+            toml::from_str("work_item_pattern = ['([A-Z]+-\\d+)', '^(\\d+)-']").unwrap();
+        assert_eq!(
+            config.work_item_pattern.map(PatternList::into_vec),
+            Some(vec![r"([A-Z]+-\d+)".to_string(), r"^(\d+)-".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_timezone() {
+        let utc = |s: &str| DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc);
+        let day = |s: &str| NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
+
+        let taipei = WorkCalendar {
+            timezone: Timezone::parse("Asia/Taipei").unwrap(),
+            day_start_hour: 0,
+        };
+        assert_eq!(taipei.day_start(day("2026-10-01")), utc("2026-09-30T16:00:00Z"));
+        assert_eq!(taipei.day_of(utc("2026-09-30T17:00:00Z")), day("2026-10-01"));
+
+        // With days starting at 4 AM, 2 AM still belongs to the day before
+        let night_owl = WorkCalendar { day_start_hour: 4, ..taipei };
+        assert_eq!(night_owl.day_of(utc("2026-09-30T18:00:00Z")), day("2026-09-30"));
+        assert_eq!(night_owl.day_of(utc("2026-09-30T20:00:00Z")), day("2026-10-01"));
+        assert_eq!(night_owl.day_start(day("2026-10-01")), utc("2026-09-30T20:00:00Z"));
+
+        // Midnight is skipped when Chile moves to summer time; the day starts at 01:00
+        let santiago = WorkCalendar {
+            timezone: Timezone::parse("America/Santiago").unwrap(),
+            day_start_hour: 0,
+        };
+        assert_eq!(santiago.day_start(day("2024-09-08")), utc("2024-09-08T04:00:00Z"));
+
+        assert_eq!(Timezone::parse("local").unwrap(), Timezone::Local);
+        assert!(Timezone::parse("Mars/Olympus_Mons").is_err());
+    }
+
+    #[test]
+    fn test_upgrade_config() {
+        let renames = [
+            (1, "settings.format", "report.default_format"),
+            (1, "settings.idle", "settings.idle_timeout_minutes"),
+        ];
+        let mut doc: DocumentMut = "# my settings\n[settings]\nformat = \"json\"\nidle = 5\nidle_timeout_minutes = 15\n"
+            .parse()
+            .unwrap();
+        upgrade_config(&mut doc, 0, &renames);
+
+        let upgraded = doc.to_string();
+        assert!(upgraded.contains("# my settings"));
+        let config: GlobalConfig = toml::from_str(&upgraded).unwrap();
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(config.report.default_format, "json");
+        // A renamed key never overwrites one already set
+        assert_eq!(config.settings.idle_timeout_minutes, 15);
+        assert!(!upgraded.contains("idle ="));
+
+        // Files already at a version skip its renames
+        let mut doc: DocumentMut = "version = 1\n[settings]\nformat = \"json\"\n".parse().unwrap();
+        upgrade_config(&mut doc, 1, &renames);
+        assert!(doc.to_string().contains("format = \"json\""));
+    }
+
+    #[test]
+    fn test_set_config_key() {
+        let content = "version = 1\n\n[settings]\n# Minutes before a session is idle\nidle_timeout_minutes = 10 # default\n";
+        let updated = set_config_key(content, "settings.idle_timeout_minutes", "15").unwrap();
+        assert_eq!(
+            updated,
+            "version = 1\n\n[settings]\n# Minutes before a session is idle\nidle_timeout_minutes = 15 # default\n"
+        );
+
+        let updated = set_config_key(&updated, "report.default_format", "csv").unwrap();
+        let config: GlobalConfig = toml::from_str(&updated).unwrap();
+        assert_eq!(config.report.default_format, "csv");
+        assert_eq!(config_value(&config, "settings.idle_timeout_minutes").unwrap(), toml::Value::Integer(15));
+
+        assert!(set_config_key(content, "settings.idle_timeout_minutes", "soon").is_err());
+        assert!(set_config_key(content, "settings.idle_timeout", "15").is_err());
+        assert!(config_value(&config, "report.nothing").is_err());
+    }
+
+    #[test]
+    fn test_merge_config_tables() {
+        let mut merged: toml::Table = toml::from_str(
+            r#"
+            [settings]
+            database_path = "/shared/tracker.db"
+            day_start_hour = 4
+            exclude_paths = ["/scratch/**"]
+            "#,
+        )
+        .unwrap();
+        let user: toml::Table = toml::from_str(
+            r#"
+            [settings]
+            idle_timeout_minutes = 20
+            exclude_paths = ["~/tmp/**"]
+            "#,
+        )
+        .unwrap();
+        merge_config_tables(&mut merged, user);
+
+        let config: GlobalConfig = merged.try_into().unwrap();
+        assert_eq!(config.settings.database_path, "/shared/tracker.db");
+        assert_eq!(config.settings.idle_timeout_minutes, 20);
+        assert_eq!(config.settings.day_start_hour, 4);
+        // Lists are replaced, not appended to
+        assert_eq!(config.settings.exclude_paths, vec!["~/tmp/**"]);
+    }
+
+    #[test]
+    fn test_global_project_settings() {
+        let config: GlobalConfig = toml::from_str(
+            r#"
+            [projects."/src/vendor-api"]
+            name = "Vendor API"
+            client = "Acme"
+            "#,
+        )
+        .unwrap();
+        let settings = &config.projects["/src/vendor-api"];
+        assert_eq!(settings.name.as_deref(), Some("Vendor API"));
+        assert_eq!(settings.client.as_deref(), Some("Acme"));
+    }
+
+    #[test]
+    fn test_exclude_paths() {
+        let globs = compile_globs(&["/tmp/**".to_string(), "**/scratch*".to_string(), "[".to_string()]);
+        assert_eq!(globs.len(), 2);
+
+        assert!(matches_any(&globs, Path::new("/tmp/experiment")));
+        assert!(matches_any(&globs, Path::new("/tmp/a/b")));
+        assert!(matches_any(&globs, Path::new("/home/me/scratch-pad")));
+        assert!(!matches_any(&globs, Path::new("/home/me/api")));
+        assert!(!matches_any(&globs, Path::new("/home/me/scratch/api")));
+    }
+
+    #[test]
+    fn test_exclude_commit_patterns() {
+        let patterns = ["^Merge ".to_string(), "(unclosed".to_string(), r"^chore\(release\)".to_string()];
+        let filter = CommitFilter {
+            exclude_messages: compile_patterns(patterns.iter()),
+            ..Default::default()
+        };
+        assert_eq!(filter.exclude_messages.len(), 2);
+        assert!(filter.excludes_message("Merge branch 'main'"));
+        assert!(filter.excludes_message("chore(release): 1.2.0"));
+        assert!(!filter.excludes_message("Fix login redirect"));
+    }
+
+    #[test]
+    fn test_find_project_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let nested = root.join("src/api");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::create_dir_all(root.join(".config")).unwrap();
+        std::fs::write(root.join(PROJECT_CONFIG_DIR_FILE), "name = \"api\"\n").unwrap();
+
+        // Without a repository, parents aren't searched
+        assert_eq!(find_project_config(&nested), None);
+        assert_eq!(find_project_config(&root), Some(root.join(PROJECT_CONFIG_DIR_FILE)));
+
+        gix::init(&root).unwrap();
+        assert_eq!(find_project_config(&nested), Some(root.join(PROJECT_CONFIG_DIR_FILE)));
+        assert_eq!(load_project_config(&nested).unwrap().name.as_deref(), Some("api"));
+
+        std::fs::write(root.join(PROJECT_CONFIG_FILE), "name = \"root\"\n").unwrap();
+        assert_eq!(find_project_config(&nested), Some(root.join(PROJECT_CONFIG_FILE)));
     }
 
     #[test]
@@ -0,0 +1,346 @@
+//! Long-running daemon serving hook commands over a unix socket
+//!
+//! The daemon holds one database connection. `start`, `heartbeat` and `stop`
+//! forward their project and payload to it when it is running and write the
+//! database themselves otherwise.
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+use crate::config::EffectiveConfig;
+use crate::db::Database;
+use crate::hook::HookPayload;
+use crate::storage::Storage;
+use crate::tracker;
+use crate::vcs;
+
+/// Age after which the daemon compacts a completed session's heartbeats
+const COMPACT_AFTER_DAYS: u32 = 30;
+
+/// How long a client waits for a start or stop; stops read the repository and code host
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How long the daemon waits for a client to send its request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Socket of the daemon that owns the database at `database_path`
+pub fn socket_path(database_path: &Path) -> PathBuf {
+    database_path.with_file_name("daemon.sock")
+}
+
+/// A hook command, as forwarded to the daemon
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "lowercase")]
+pub enum Request {
+    Start {
+        project_path: PathBuf,
+        work_item: Option<String>,
+        payload: HookPayload,
+    },
+    Heartbeat {
+        project_path: PathBuf,
+        payload: HookPayload,
+        /// When the daemon received the heartbeat; `None` means now
+        #[serde(default)]
+        received_at: Option<DateTime<Utc>>,
+        /// Branch checked out when the heartbeat was received
+        #[serde(default)]
+        branch: Option<String>,
+    },
+    Stop {
+        project_path: PathBuf,
+        payload: HookPayload,
+    },
+}
+
+impl Request {
+    fn project_path(&self) -> &Path {
+        match self {
+            Self::Start { project_path, .. }
+            | Self::Heartbeat { project_path, .. }
+            | Self::Stop { project_path, .. } => project_path,
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Reply {
+    error: Option<String>,
+}
+
+/// Work for the thread that owns the database
+enum Job {
+    /// Already acknowledged; written with the other heartbeats waiting in the queue
+    Buffered(Request),
+    Hook(Request, Sender<Reply>),
+    Reap,
+}
+
+/// Run a hook command against the database
+pub fn apply(db: &Database, config: &EffectiveConfig, request: &Request) -> Result<()> {
+    match request {
+        Request::Start {
+            project_path,
+            work_item,
+            payload,
+        } => tracker::start_session(db, project_path, config, payload, work_item.as_deref()),
+        Request::Heartbeat {
+            project_path,
+            payload,
+            received_at: Some(received_at),
+            branch,
+        } => tracker::record_heartbeat(db, project_path, config, payload, *received_at, branch.clone()),
+        Request::Heartbeat { project_path, payload, .. } => {
+            let branch = vcs::open(project_path).current_branch().ok();
+            tracker::record_heartbeat(db, project_path, config, payload, Utc::now(), branch)
+        }
+        Request::Stop { project_path, payload } => tracker::stop_session(db, project_path, config, payload),
+    }
+}
+
+/// Forward a hook command to the daemon
+///
+/// Returns `None` when no daemon is listening, leaving the work to the caller.
+pub fn send(socket: &Path, request: &Request) -> Option<Result<()>> {
+    let stream = UnixStream::connect(socket).ok()?;
+    Some(exchange(stream, request))
+}
+
+fn exchange(mut stream: UnixStream, request: &Request) -> Result<()> {
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    let mut line = serde_json::to_string(request)?;
+    line.push('\n');
+    stream.write_all(line.as_bytes())?;
+
+    let mut reply = String::new();
+    BufReader::new(&stream)
+        .read_line(&mut reply)
+        .context("No reply from the daemon")?;
+    let reply: Reply = serde_json::from_str(&reply).context("Invalid reply from the daemon")?;
+    match reply.error {
+        Some(error) => bail!("{}", error),
+        None => Ok(()),
+    }
+}
+
+/// Serve hooks on the socket next to the database, reaping and compacting every `interval`
+pub fn run(interval: Duration) -> Result<()> {
+    let config = EffectiveConfig::load(None)?;
+    let db = Database::open(&config.database_path)?;
+    let socket = socket_path(&config.database_path);
+
+    let (sender, jobs) = mpsc::channel();
+    listen(&socket, sender.clone())?;
+    info!(
+        "Listening on {}; reaping abandoned sessions every {}s",
+        socket.display(),
+        interval.as_secs()
+    );
+    thread::spawn(move || {
+        while sender.send(Job::Reap).is_ok() {
+            thread::sleep(interval);
+        }
+    });
+
+    while let Ok(job) = jobs.recv() {
+        let mut batch = vec![job];
+        batch.extend(jobs.try_iter());
+        process(&db, batch);
+    }
+    Ok(())
+}
+
+/// Accept connections on `socket`, handing their requests to `jobs`
+fn listen(socket: &Path, jobs: Sender<Job>) -> Result<()> {
+    if UnixStream::connect(socket).is_ok() {
+        bail!("A daemon is already listening on {}", socket.display());
+    }
+    // Left behind by a daemon that did not exit cleanly
+    let _ = fs::remove_file(socket);
+
+    let listener = UnixListener::bind(socket).with_context(|| format!("Failed to listen on {}", socket.display()))?;
+    fs::set_permissions(socket, fs::Permissions::from_mode(0o600))?;
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    warn!("Failed to accept a connection: {}", e);
+                    continue;
+                }
+            };
+            let jobs = jobs.clone();
+            thread::spawn(move || {
+                if let Err(e) = handle_connection(stream, &jobs) {
+                    debug!("Connection failed: {:#}", e);
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+fn handle_connection(mut stream: UnixStream, jobs: &Sender<Job>) -> Result<()> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+
+    let stopped = || anyhow!("The daemon is shutting down");
+    let reply = match serde_json::from_str::<Request>(&line) {
+        // Heartbeats are acknowledged right away so the hook can exit. They are
+        // stamped now, since the branch may change before the batch is written.
+        Ok(Request::Heartbeat { project_path, payload, .. }) => {
+            let request = Request::Heartbeat {
+                branch: vcs::open(&project_path).current_branch().ok(),
+                received_at: Some(Utc::now()),
+                project_path,
+                payload,
+            };
+            jobs.send(Job::Buffered(request)).map_err(|_| stopped())?;
+            Reply::default()
+        }
+        Ok(request) => {
+            let (reply, result) = mpsc::channel();
+            jobs.send(Job::Hook(request, reply)).map_err(|_| stopped())?;
+            result.recv().map_err(|_| stopped())?
+        }
+        Err(e) => Reply {
+            error: Some(format!("Invalid request: {}", e)),
+        },
+    };
+
+    let mut line = serde_json::to_string(&reply)?;
+    line.push('\n');
+    stream.write_all(line.as_bytes())?;
+    Ok(())
+}
+
+/// Run queued jobs in order, writing each run of buffered heartbeats in one transaction
+fn process(db: &Database, batch: Vec<Job>) {
+    let mut heartbeats = Vec::new();
+    for job in batch {
+        match job {
+            Job::Buffered(request) => heartbeats.push(request),
+            Job::Hook(request, reply) => {
+                write_heartbeats(db, std::mem::take(&mut heartbeats));
+                let error = run_request(db, &request).err().map(|e| format!("{:#}", e));
+                let _ = reply.send(Reply { error });
+            }
+            Job::Reap => {
+                write_heartbeats(db, std::mem::take(&mut heartbeats));
+                if let Err(e) = reap(db) {
+                    warn!("Reaping or compaction failed: {:#}", e);
+                }
+            }
+        }
+    }
+    write_heartbeats(db, heartbeats);
+}
+
+fn write_heartbeats(db: &Database, requests: Vec<Request>) {
+    if requests.is_empty() {
+        return;
+    }
+    debug!("Writing {} buffered heartbeats", requests.len());
+    let result = db.atomically(|| {
+        for request in &requests {
+            // Each heartbeat gets its own savepoint, so a failing one does not undo the rest
+            if let Err(e) = db.atomically(|| run_request(db, request)) {
+                warn!("Heartbeat for {} failed: {:#}", request.project_path().display(), e);
+            }
+        }
+        Ok(())
+    });
+    if let Err(e) = result {
+        warn!("Failed to write buffered heartbeats: {:#}", e);
+    }
+}
+
+/// Run a request with its project's configuration, reloaded so changes apply without a restart
+fn run_request(db: &Database, request: &Request) -> Result<()> {
+    let config = EffectiveConfig::load(Some(request.project_path()))?;
+    apply(db, &config, request)
+}
+
+fn reap(db: &Database) -> Result<()> {
+    let config = EffectiveConfig::load(None)?;
+    tracker::close_abandoned_sessions(db, &config)?;
+    tracker::compact_sessions(db, &config, COMPACT_AFTER_DAYS)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn heartbeat() -> Request {
+        Request::Heartbeat {
+            project_path: PathBuf::from("/repo"),
+            payload: HookPayload::parse(r#"{"session_id": "abc"}"#),
+            received_at: None,
+            branch: None,
+        }
+    }
+
+    #[test]
+    fn test_client_and_listener() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("daemon.sock");
+        assert!(send(&socket, &heartbeat()).is_none());
+
+        let (sender, jobs) = mpsc::channel();
+        listen(&socket, sender).unwrap();
+        assert!(listen(&socket, mpsc::channel().0).is_err());
+
+        // Heartbeats are acknowledged before they are written
+        let sent_at = Utc::now();
+        assert!(send(&socket, &heartbeat()).unwrap().is_ok());
+        match jobs.recv().unwrap() {
+            Job::Buffered(Request::Heartbeat {
+                project_path,
+                payload,
+                received_at,
+                ..
+            }) => {
+                assert_eq!(project_path, PathBuf::from("/repo"));
+                assert_eq!(payload.session_id.as_deref(), Some("abc"));
+                // Stamped on arrival, not when the batch is written
+                assert!(received_at.unwrap() >= sent_at);
+            }
+            _ => panic!("expected a buffered heartbeat"),
+        }
+
+        // Stops wait for the outcome
+        let client = {
+            let socket = socket.clone();
+            thread::spawn(move || {
+                let stop = Request::Stop {
+                    project_path: PathBuf::from("/repo"),
+                    payload: HookPayload::default(),
+                };
+                send(&socket, &stop).unwrap()
+            })
+        };
+        match jobs.recv().unwrap() {
+            Job::Hook(Request::Stop { .. }, reply) => reply
+                .send(Reply {
+                    error: Some("Project not found".to_string()),
+                })
+                .unwrap(),
+            _ => panic!("expected a stop"),
+        }
+        let result = client.join().unwrap();
+        assert_eq!(result.unwrap_err().to_string(), "Project not found");
+    }
+}
@@ -1,9 +1,164 @@
 use anyhow::{Context, Result};
-use chrono::{DateTime, Utc};
-use rusqlite::{params, Connection, OptionalExtension};
-use std::path::Path;
+use chrono::{DateTime, NaiveDate, Utc};
+use rusqlite::{params, Connection, ErrorCode, OptionalExtension, ToSql};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{debug, info, warn};
 
-use crate::models::{Commit, Heartbeat, Project, Session, SessionStatus};
+use crate::models::{
+    Commit, DiffStats, FeedEntry, Heartbeat, HeartbeatContext, Note, Project, ProjectRate, ProjectStats,
+    PullRequest, RemoteRepo, Session, SessionBranch, SessionDetails, SessionStatus, WindowEvent,
+};
+use crate::storage::Storage;
+use crate::vcs::CommitEntry;
+
+const SESSION_COLUMNS: &str = "id, project_id, branch, work_item, start_commit, end_commit,
+     started_at, ended_at, active_seconds, status, claude_session_id, model, machine,
+     files_changed, insertions, deletions, pr_number, pr_url, pr_title,
+     uncommitted_files, uncommitted_insertions, uncommitted_deletions, uuid, deleted_at, imported_from,
+     last_heartbeat_at";
+
+const HEARTBEAT_COLUMNS: &str =
+    "id, session_id, timestamp, file_path, branch, event_type, tool, cwd, model, agent";
+
+const INTERVAL_COLUMNS: &str =
+    "id, session_id, started_at, ended_at, file_path, branch, event_type, tool, cwd, model, agent";
+
+/// Spacing of the heartbeats rebuilt from a compacted interval
+///
+/// Well under the smallest idle timeout (one minute), so a rebuilt interval
+/// is always counted in full.
+const INTERVAL_STEP_SECONDS: i64 = 30;
+
+/// Tables holding rows that belong to a session
+const SESSION_CHILD_TABLES: [&str; 7] = [
+    "heartbeats",
+    "heartbeat_intervals",
+    "commits",
+    "session_branches",
+    "session_activity",
+    "notes",
+    "session_search",
+];
+
+/// Search index row of session `s`, keyed by the session id
+const SEARCH_ROW_SQL: &str = "SELECT s.id, s.id, COALESCE(s.work_item, ''), s.branch,
+            COALESCE((SELECT group_concat(c.message, char(10)) FROM commits c WHERE c.session_id = s.id), ''),
+            COALESCE((SELECT group_concat(n.text, char(10)) FROM notes n WHERE n.session_id = s.id), '')
+     FROM sessions s
+     WHERE s.deleted_at IS NULL";
+
+/// Triggers keeping the search index in step with sessions, commits and notes:
+/// (name, event, id of the session to reindex)
+const SEARCH_TRIGGERS: [(&str, &str, &str); 9] = [
+    ("search_session_insert", "AFTER INSERT ON sessions", "NEW.id"),
+    ("search_session_update", "AFTER UPDATE OF work_item, branch, deleted_at ON sessions", "NEW.id"),
+    ("search_session_delete", "AFTER DELETE ON sessions", "OLD.id"),
+    ("search_commit_insert", "AFTER INSERT ON commits", "NEW.session_id"),
+    ("search_commit_update", "AFTER UPDATE OF message ON commits", "NEW.session_id"),
+    ("search_commit_delete", "AFTER DELETE ON commits", "OLD.session_id"),
+    ("search_note_insert", "AFTER INSERT ON notes", "NEW.session_id"),
+    ("search_note_update", "AFTER UPDATE OF text ON notes", "NEW.session_id"),
+    ("search_note_delete", "AFTER DELETE ON notes", "OLD.session_id"),
+];
+
+/// Triggers stamping a session's `updated_at` when it or what is shared along
+/// with it changes: (name, event, id of the session)
+///
+/// The running time written with every heartbeat (`active_seconds` and
+/// `last_heartbeat_at` alone) doesn't count as a change; `active_seconds` of a
+/// finished session does.
+const CHANGE_TRIGGERS: [(&str, &str, &str); 8] = [
+    (
+        "change_session_update",
+        "AFTER UPDATE OF project_id, branch, work_item, start_commit, end_commit, started_at, ended_at, status,
+             claude_session_id, model, machine, files_changed, insertions, deletions, pr_number, pr_url, pr_title,
+             uncommitted_files, uncommitted_insertions, uncommitted_deletions, uuid, deleted_at, imported_from
+         ON sessions WHEN NEW.updated_at IS OLD.updated_at",
+        "NEW.id",
+    ),
+    (
+        "change_session_time",
+        "AFTER UPDATE OF active_seconds ON sessions WHEN NEW.updated_at IS OLD.updated_at AND NEW.status != 'active'",
+        "NEW.id",
+    ),
+    ("change_commit_insert", "AFTER INSERT ON commits", "NEW.session_id"),
+    ("change_commit_delete", "AFTER DELETE ON commits", "OLD.session_id"),
+    ("change_branch_insert", "AFTER INSERT ON session_branches", "NEW.session_id"),
+    ("change_branch_delete", "AFTER DELETE ON session_branches", "OLD.session_id"),
+    ("change_activity_insert", "AFTER INSERT ON session_activity", "NEW.session_id"),
+    ("change_activity_delete", "AFTER DELETE ON session_activity", "OLD.session_id"),
+];
+
+/// Current time as stored in text columns
+const NOW_SQL: &str = "strftime('%Y-%m-%dT%H:%M:%fZ', 'now')";
+
+/// Triggers noting starts, heartbeats and stops in `event_feed`, whichever
+/// process writes them: (name, event, kind, row id, time)
+///
+/// A heartbeat folded into an earlier one counts as a heartbeat of its own.
+const FEED_TRIGGERS: [(&str, &str, &str, &str, &str); 5] = [
+    ("feed_session_start", "AFTER INSERT ON sessions WHEN NEW.status = 'active'", "start", "NEW.id", "NEW.started_at"),
+    (
+        "feed_session_resume",
+        "AFTER UPDATE OF status ON sessions WHEN NEW.status = 'active' AND OLD.status != 'active'",
+        "start",
+        "NEW.id",
+        NOW_SQL,
+    ),
+    (
+        "feed_session_stop",
+        "AFTER UPDATE OF status ON sessions WHEN OLD.status = 'active' AND NEW.status != 'active'",
+        "stop",
+        "NEW.id",
+        NOW_SQL,
+    ),
+    ("feed_heartbeat", "AFTER INSERT ON heartbeats", "heartbeat", "NEW.id", "NEW.timestamp"),
+    (
+        "feed_heartbeat_coalesced",
+        "AFTER UPDATE OF event_count ON heartbeats WHEN NEW.event_count > OLD.event_count",
+        "heartbeat",
+        "NEW.id",
+        NOW_SQL,
+    ),
+];
+
+/// Entries `event_feed` keeps; followers more than this far behind miss events
+const FEED_LENGTH: i64 = 1000;
+
+/// SQL expression producing a random (version 4) UUID
+const UUID_SQL: &str = "lower(hex(randomblob(4))) || '-' || lower(hex(randomblob(2))) || '-4' ||
+     substr(lower(hex(randomblob(2))), 2) || '-' || substr('89ab', 1 + abs(random()) % 4, 1) ||
+     substr(lower(hex(randomblob(2))), 2) || '-' || lower(hex(randomblob(6)))";
+
+/// Session commits, with what each changed when the commit cache measured it
+const COMMIT_ROWS: &str = "SELECT c.id, c.session_id, c.hash, c.message, c.committed_at, c.submodule,
+        cc.files_changed, cc.insertions, cc.deletions
+    FROM commits c
+    JOIN sessions s ON s.id = c.session_id
+    LEFT JOIN commit_cache cc
+        ON cc.project_id = s.project_id AND cc.submodule = COALESCE(c.submodule, '') AND cc.hash = c.hash";
+
+const PROJECT_COLUMNS: &str =
+    "id, path, git_remote, display_name, work_item_pattern, created_at, billable, hourly_rate, remote_urls,
+     remote_host, remote_owner, remote_repo, uuid, deleted_at, archived, client";
+
+/// Finished sessions running at some point in a range (`?1` to `?2`), as read for reports
+///
+/// `idx_sessions_report_end` covers it, so only sessions ending after the
+/// start of the range are looked at.
+const REPORT_SESSIONS_FILTER: &str = "started_at < ?2 AND COALESCE(ended_at, started_at) >= ?1
+     AND status != 'active' AND deleted_at IS NULL";
+
+/// Statements kept prepared per connection; reports run each per-session query many times
+const STATEMENT_CACHE_CAPACITY: usize = 64;
+
+/// How long a statement waits for another process to release its lock
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Attempts for a write that still finds the database locked after `BUSY_TIMEOUT`
+const WRITE_ATTEMPTS: u64 = 3;
 
 /// Database wrapper
 pub struct Database {
@@ -22,11 +177,44 @@ impl Database {
         let conn = Connection::open(path)
             .with_context(|| format!("Failed to open database: {}", path.display()))?;
 
+        // Hooks from several Claude Code windows write concurrently; WAL lets
+        // readers and a writer proceed together, and writers wait for each other
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        let journal_mode: String =
+            conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get(0))?;
+        debug!("Database journal mode: {}", journal_mode);
+        conn.set_prepared_statement_cache_capacity(STATEMENT_CACHE_CAPACITY);
+
+        debug!("Opened database {}", path.display());
+
         let db = Self { conn };
         db.initialize()?;
         Ok(db)
     }
 
+    /// File the database is stored in, for opening more connections to it
+    pub fn path(&self) -> Result<PathBuf> {
+        match self.conn.path() {
+            Some(path) if !path.is_empty() => Ok(PathBuf::from(path)),
+            _ => anyhow::bail!("Database is not stored in a file"),
+        }
+    }
+
+    /// Execute a write, retrying a few times if the database stays locked
+    fn execute(&self, sql: &str, params: &[&dyn ToSql]) -> rusqlite::Result<usize> {
+        let mut attempt = 1;
+        loop {
+            match self.conn.execute(sql, params) {
+                Err(e) if attempt < WRITE_ATTEMPTS && is_locked(&e) => {
+                    warn!("Database is locked, retrying ({}/{})", attempt, WRITE_ATTEMPTS - 1);
+                    std::thread::sleep(Duration::from_millis(200 * attempt));
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
     /// Initialize database schema
     fn initialize(&self) -> Result<()> {
         self.conn.execute_batch(
@@ -37,6 +225,23 @@ impl Database {
                 git_remote TEXT,
                 display_name TEXT,
                 work_item_pattern TEXT,
+                created_at TEXT NOT NULL DEFAULT (datetime('now')),
+                billable INTEGER NOT NULL DEFAULT 1,
+                hourly_rate REAL,
+                remote_urls TEXT,
+                remote_host TEXT,
+                remote_owner TEXT,
+                remote_repo TEXT,
+                uuid TEXT,
+                deleted_at TEXT,
+                archived INTEGER NOT NULL DEFAULT 0,
+                client TEXT
+            );
+
+            CREATE TABLE IF NOT EXISTS project_paths (
+                id INTEGER PRIMARY KEY,
+                project_id INTEGER NOT NULL REFERENCES projects(id),
+                path TEXT UNIQUE NOT NULL,
                 created_at TEXT NOT NULL DEFAULT (datetime('now'))
             );
 
@@ -50,13 +255,69 @@ impl Database {
                 started_at TEXT NOT NULL,
                 ended_at TEXT,
                 active_seconds INTEGER,
-                status TEXT NOT NULL DEFAULT 'active'
+                status TEXT NOT NULL DEFAULT 'active',
+                claude_session_id TEXT,
+                model TEXT,
+                machine TEXT,
+                files_changed INTEGER,
+                insertions INTEGER,
+                deletions INTEGER,
+                pr_number INTEGER,
+                pr_url TEXT,
+                pr_title TEXT,
+                uncommitted_files INTEGER,
+                uncommitted_insertions INTEGER,
+                uncommitted_deletions INTEGER,
+                uuid TEXT,
+                deleted_at TEXT,
+                imported_from TEXT,
+                updated_at TEXT,
+                last_heartbeat_at TEXT
             );
 
             CREATE TABLE IF NOT EXISTS heartbeats (
                 id INTEGER PRIMARY KEY,
                 session_id INTEGER NOT NULL REFERENCES sessions(id),
-                timestamp TEXT NOT NULL
+                timestamp TEXT NOT NULL,
+                file_path TEXT,
+                branch TEXT,
+                event_type TEXT,
+                tool TEXT,
+                event_count INTEGER NOT NULL DEFAULT 1,
+                cwd TEXT,
+                model TEXT,
+                agent TEXT,
+                uuid TEXT
+            );
+
+            CREATE TABLE IF NOT EXISTS heartbeat_intervals (
+                id INTEGER PRIMARY KEY,
+                session_id INTEGER NOT NULL REFERENCES sessions(id),
+                started_at TEXT NOT NULL,
+                ended_at TEXT NOT NULL,
+                file_path TEXT,
+                branch TEXT,
+                event_type TEXT,
+                tool TEXT,
+                cwd TEXT,
+                model TEXT,
+                agent TEXT,
+                event_count INTEGER NOT NULL DEFAULT 1
+            );
+
+            CREATE TABLE IF NOT EXISTS session_branches (
+                id INTEGER PRIMARY KEY,
+                session_id INTEGER NOT NULL REFERENCES sessions(id),
+                branch TEXT NOT NULL,
+                work_item TEXT,
+                active_seconds INTEGER NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS session_activity (
+                id INTEGER PRIMARY KEY,
+                session_id INTEGER NOT NULL REFERENCES sessions(id),
+                kind TEXT NOT NULL,
+                active_seconds INTEGER NOT NULL
             );
 
             CREATE TABLE IF NOT EXISTS commits (
@@ -64,394 +325,2539 @@ impl Database {
                 session_id INTEGER NOT NULL REFERENCES sessions(id),
                 hash TEXT NOT NULL,
                 message TEXT,
-                committed_at TEXT
+                committed_at TEXT,
+                submodule TEXT
+            );
+
+            CREATE TABLE IF NOT EXISTS commit_cache (
+                project_id INTEGER NOT NULL REFERENCES projects(id),
+                submodule TEXT NOT NULL,
+                hash TEXT NOT NULL,
+                message TEXT,
+                committed_at TEXT,
+                files_changed INTEGER,
+                insertions INTEGER,
+                deletions INTEGER,
+                PRIMARY KEY (project_id, submodule, hash)
+            );
+
+            CREATE TABLE IF NOT EXISTS project_rates (
+                id INTEGER PRIMARY KEY,
+                project_id INTEGER NOT NULL REFERENCES projects(id),
+                effective_from TEXT NOT NULL,
+                hourly_rate REAL NOT NULL,
+                currency TEXT,
+                UNIQUE(project_id, effective_from)
+            );
+
+            CREATE TABLE IF NOT EXISTS notes (
+                id INTEGER PRIMARY KEY,
+                session_id INTEGER NOT NULL REFERENCES sessions(id),
+                timestamp TEXT NOT NULL,
+                text TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS meta (
+                key TEXT PRIMARY KEY,
+                value TEXT NOT NULL
+            );
+
+            -- An empty title records that the code host had no such issue
+            CREATE TABLE IF NOT EXISTS issue_titles (
+                repository TEXT NOT NULL,
+                number INTEGER NOT NULL,
+                title TEXT NOT NULL,
+                fetched_at TEXT NOT NULL,
+                PRIMARY KEY (repository, number)
+            );
+
+            CREATE TABLE IF NOT EXISTS window_activity (
+                id INTEGER PRIMARY KEY,
+                bucket TEXT NOT NULL,
+                timestamp TEXT NOT NULL,
+                duration_seconds REAL NOT NULL,
+                app TEXT,
+                title TEXT,
+                UNIQUE(bucket, timestamp)
+            );
+
+            CREATE TABLE IF NOT EXISTS synced_sessions (
+                target TEXT NOT NULL,
+                session_uuid TEXT NOT NULL,
+                remote_id TEXT NOT NULL,
+                synced_at TEXT NOT NULL,
+                PRIMARY KEY (target, session_uuid)
+            );
+
+            CREATE TABLE IF NOT EXISTS merged_bundles (
+                location TEXT NOT NULL,
+                name TEXT NOT NULL,
+                merged_at TEXT NOT NULL,
+                PRIMARY KEY (location, name)
             );
 
+            -- Recent starts, heartbeats and stops, filled in by triggers
+            CREATE TABLE IF NOT EXISTS event_feed (
+                id INTEGER PRIMARY KEY,
+                kind TEXT NOT NULL,
+                row_id INTEGER NOT NULL,
+                timestamp TEXT NOT NULL
+            );
+
+            CREATE VIRTUAL TABLE IF NOT EXISTS session_search USING fts5(
+                session_id UNINDEXED,
+                work_item_text,
+                branch_text,
+                commit_text,
+                note_text
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_project_paths_project_id ON project_paths(project_id);
             CREATE INDEX IF NOT EXISTS idx_sessions_project_id ON sessions(project_id);
             CREATE INDEX IF NOT EXISTS idx_sessions_status ON sessions(status);
+            CREATE INDEX IF NOT EXISTS idx_sessions_started_at ON sessions(started_at);
+            CREATE INDEX IF NOT EXISTS idx_sessions_project_started_at ON sessions(project_id, started_at);
+            CREATE INDEX IF NOT EXISTS idx_sessions_report_end ON sessions(COALESCE(ended_at, started_at), started_at)
+                WHERE status != 'active' AND deleted_at IS NULL;
             CREATE INDEX IF NOT EXISTS idx_heartbeats_session_id ON heartbeats(session_id);
+            CREATE INDEX IF NOT EXISTS idx_heartbeats_session_timestamp ON heartbeats(session_id, timestamp);
+            CREATE INDEX IF NOT EXISTS idx_heartbeats_prompts ON heartbeats(session_id)
+                WHERE event_type = 'prompt-submit';
+            CREATE INDEX IF NOT EXISTS idx_heartbeat_intervals_session_id ON heartbeat_intervals(session_id);
             CREATE INDEX IF NOT EXISTS idx_commits_session_id ON commits(session_id);
+            CREATE INDEX IF NOT EXISTS idx_commit_cache_submodule ON commit_cache(project_id, submodule, committed_at);
+            CREATE INDEX IF NOT EXISTS idx_session_branches_session_id ON session_branches(session_id);
+            CREATE INDEX IF NOT EXISTS idx_session_activity_session_id ON session_activity(session_id);
+            CREATE INDEX IF NOT EXISTS idx_notes_session_id ON notes(session_id);
             "#,
         )
         .context("Failed to initialize database schema")?;
 
-        Ok(())
+        self.migrate()
     }
 
-    // ==================== Projects ====================
+    /// Bring databases created by older versions up to the current schema
+    fn migrate(&self) -> Result<()> {
+        // The search index can always be rebuilt, so it is simply recreated
+        let has_notes: bool = self.conn.query_row(
+            "SELECT COUNT(*) > 0 FROM pragma_table_info('session_search') WHERE name = 'note_text'",
+            [],
+            |row| row.get(0),
+        )?;
+        if !has_notes {
+            info!("Migrating database: adding notes to the search index");
+            self.conn.execute_batch(
+                "DROP TABLE session_search;
+                 CREATE VIRTUAL TABLE session_search USING fts5(
+                     session_id UNINDEXED, work_item_text, branch_text, commit_text, note_text
+                 );",
+            )?;
+        }
 
-    /// Get or create a project by path
-    pub fn get_or_create_project(
-        &self,
-        path: &str,
-        git_remote: Option<&str>,
-        display_name: Option<&str>,
-        work_item_pattern: Option<&str>,
-    ) -> Result<Project> {
-        // Try to find existing project
-        if let Some(project) = self.get_project_by_path(path)? {
-            // Update if new info provided
-            if git_remote.is_some() || display_name.is_some() || work_item_pattern.is_some() {
-                self.conn.execute(
-                    "UPDATE projects SET
-                        git_remote = COALESCE(?, git_remote),
-                        display_name = COALESCE(?, display_name),
-                        work_item_pattern = COALESCE(?, work_item_pattern)
-                    WHERE id = ?",
-                    params![git_remote, display_name, work_item_pattern, project.id],
-                )?;
-                return self.get_project_by_id(project.id);
-            }
-            return Ok(project);
+        // The commit cache used to be keyed by hash alone, losing commits shared by two submodules
+        let has_stats: bool = self.conn.query_row(
+            "SELECT COUNT(*) > 0 FROM pragma_table_info('commit_cache') WHERE name = 'insertions'",
+            [],
+            |row| row.get(0),
+        )?;
+        if !has_stats {
+            info!("Migrating database: rebuilding the commit cache");
+            self.conn.execute_batch(
+                "DROP TABLE commit_cache;
+                 CREATE TABLE commit_cache (
+                     project_id INTEGER NOT NULL REFERENCES projects(id),
+                     submodule TEXT NOT NULL,
+                     hash TEXT NOT NULL,
+                     message TEXT,
+                     committed_at TEXT,
+                     files_changed INTEGER,
+                     insertions INTEGER,
+                     deletions INTEGER,
+                     PRIMARY KEY (project_id, submodule, hash)
+                 );
+                 CREATE INDEX idx_commit_cache_submodule ON commit_cache(project_id, submodule, committed_at);",
+            )?;
         }
 
-        // Create new project
-        let now = Utc::now();
-        self.conn.execute(
-            "INSERT INTO projects (path, git_remote, display_name, work_item_pattern, created_at)
-             VALUES (?, ?, ?, ?, ?)",
-            params![
-                path,
-                git_remote,
-                display_name,
-                work_item_pattern,
-                now.to_rfc3339()
-            ],
+        self.add_column_if_missing("projects", "billable", "INTEGER NOT NULL DEFAULT 1")?;
+        self.add_column_if_missing("projects", "hourly_rate", "REAL")?;
+        self.add_column_if_missing("projects", "remote_urls", "TEXT")?;
+        self.add_column_if_missing("projects", "remote_host", "TEXT")?;
+        self.add_column_if_missing("projects", "remote_owner", "TEXT")?;
+        self.add_column_if_missing("projects", "remote_repo", "TEXT")?;
+        self.add_column_if_missing("heartbeats", "file_path", "TEXT")?;
+        self.add_column_if_missing("heartbeats", "branch", "TEXT")?;
+        self.add_column_if_missing("heartbeats", "event_type", "TEXT")?;
+        self.add_column_if_missing("heartbeats", "tool", "TEXT")?;
+        self.add_column_if_missing("heartbeats", "event_count", "INTEGER NOT NULL DEFAULT 1")?;
+        self.add_column_if_missing("heartbeats", "cwd", "TEXT")?;
+        self.add_column_if_missing("heartbeats", "model", "TEXT")?;
+        self.add_column_if_missing("heartbeats", "agent", "TEXT")?;
+        self.add_column_if_missing("sessions", "model", "TEXT")?;
+        self.add_column_if_missing("sessions", "machine", "TEXT")?;
+        self.add_column_if_missing("sessions", "claude_session_id", "TEXT")?;
+        self.add_column_if_missing("sessions", "files_changed", "INTEGER")?;
+        self.add_column_if_missing("sessions", "insertions", "INTEGER")?;
+        self.add_column_if_missing("sessions", "deletions", "INTEGER")?;
+        self.add_column_if_missing("sessions", "pr_number", "INTEGER")?;
+        self.add_column_if_missing("sessions", "pr_url", "TEXT")?;
+        self.add_column_if_missing("sessions", "pr_title", "TEXT")?;
+        self.add_column_if_missing("sessions", "uncommitted_files", "INTEGER")?;
+        self.add_column_if_missing("sessions", "uncommitted_insertions", "INTEGER")?;
+        self.add_column_if_missing("sessions", "uncommitted_deletions", "INTEGER")?;
+        self.add_column_if_missing("projects", "deleted_at", "TEXT")?;
+        self.add_column_if_missing("sessions", "deleted_at", "TEXT")?;
+        self.add_column_if_missing("projects", "archived", "INTEGER NOT NULL DEFAULT 0")?;
+        self.add_column_if_missing("projects", "client", "TEXT")?;
+        self.add_column_if_missing("sessions", "imported_from", "TEXT")?;
+        self.add_column_if_missing("sessions", "updated_at", "TEXT")?;
+        self.add_column_if_missing("sessions", "last_heartbeat_at", "TEXT")?;
+        self.add_column_if_missing("commits", "submodule", "TEXT")?;
+        // The session trigger used to fire on every heartbeat too
+        let stale_trigger: bool = self.conn.query_row(
+            "SELECT COUNT(*) > 0 FROM sqlite_master
+             WHERE type = 'trigger' AND name = ? AND sql NOT LIKE '%UPDATE OF%'",
+            params![CHANGE_TRIGGERS[0].0],
+            |row| row.get(0),
         )?;
+        if stale_trigger {
+            self.conn.execute_batch(&format!("DROP TRIGGER {}", CHANGE_TRIGGERS[0].0))?;
+        }
+        for (name, event, session_id) in CHANGE_TRIGGERS {
+            self.conn.execute_batch(&format!(
+                "CREATE TRIGGER IF NOT EXISTS {name} {event} BEGIN
+                     UPDATE sessions SET updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now') WHERE id = {session_id};
+                 END;"
+            ))?;
+        }
 
-        let id = self.conn.last_insert_rowid();
-        self.get_project_by_id(id)
-    }
+        // Rows from before globally unique ids get one now
+        for table in ["projects", "sessions", "heartbeats"] {
+            self.add_column_if_missing(table, "uuid", "TEXT")?;
+            let filled = self
+                .conn
+                .execute(&format!("UPDATE {} SET uuid = {} WHERE uuid IS NULL", table, UUID_SQL), [])?;
+            if filled > 0 {
+                info!("Migrating database: assigned ids to {} {}", filled, table);
+            }
+            self.conn.execute(
+                &format!("CREATE UNIQUE INDEX IF NOT EXISTS idx_{0}_uuid ON {0}(uuid)", table),
+                [],
+            )?;
+        }
 
-    /// Get project by ID
-    pub fn get_project_by_id(&self, id: i64) -> Result<Project> {
-        self.conn
-            .query_row(
-                "SELECT id, path, git_remote, display_name, work_item_pattern, created_at
-                 FROM projects WHERE id = ?",
-                params![id],
-                |row| {
-                    Ok(Project {
-                        id: row.get(0)?,
-                        path: row.get(1)?,
-                        git_remote: row.get(2)?,
-                        display_name: row.get(3)?,
-                        work_item_pattern: row.get(4)?,
-                        created_at: parse_datetime(row.get::<_, String>(5)?),
-                    })
-                },
-            )
-            .context("Project not found")
-    }
+        // Older databases rebuilt the index before every search instead of keeping it current
+        let has_triggers: bool = self.conn.query_row(
+            "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'trigger' AND name = ?",
+            params![SEARCH_TRIGGERS[0].0],
+            |row| row.get(0),
+        )?;
+        if !has_triggers {
+            info!("Migrating database: keeping the search index up to date");
+            for (name, event, session_id) in SEARCH_TRIGGERS {
+                self.conn.execute_batch(&format!(
+                    "CREATE TRIGGER IF NOT EXISTS {name} {event} BEGIN
+                         DELETE FROM session_search WHERE rowid = {session_id};
+                         INSERT INTO session_search (rowid, session_id, work_item_text, branch_text, commit_text, note_text)
+                         {SEARCH_ROW_SQL} AND s.id = {session_id};
+                     END;"
+                ))?;
+            }
+            self.rebuild_search_index()?;
+        }
 
-    /// Get project by path
-    pub fn get_project_by_path(&self, path: &str) -> Result<Option<Project>> {
-        self.conn
-            .query_row(
-                "SELECT id, path, git_remote, display_name, work_item_pattern, created_at
-                 FROM projects WHERE path = ?",
-                params![path],
-                |row| {
-                    Ok(Project {
-                        id: row.get(0)?,
-                        path: row.get(1)?,
-                        git_remote: row.get(2)?,
-                        display_name: row.get(3)?,
-                        work_item_pattern: row.get(4)?,
-                        created_at: parse_datetime(row.get::<_, String>(5)?),
-                    })
-                },
-            )
-            .optional()
-            .context("Failed to query project")
+        for (name, event, kind, row_id, timestamp) in FEED_TRIGGERS {
+            self.conn.execute_batch(&format!(
+                "CREATE TRIGGER IF NOT EXISTS {name} {event} BEGIN
+                     INSERT INTO event_feed (kind, row_id, timestamp) VALUES ('{kind}', {row_id}, {timestamp});
+                     DELETE FROM event_feed WHERE id <= (SELECT MAX(id) FROM event_feed) - {FEED_LENGTH};
+                 END;"
+            ))?;
+        }
+
+        Ok(())
     }
 
-    /// List all projects
-    pub fn list_projects(&self) -> Result<Vec<Project>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT id, path, git_remote, display_name, work_item_pattern, created_at
-             FROM projects ORDER BY path",
+    fn add_column_if_missing(&self, table: &str, column: &str, definition: &str) -> Result<()> {
+        let exists: bool = self.conn.query_row(
+            "SELECT COUNT(*) > 0 FROM pragma_table_info(?) WHERE name = ?",
+            params![table, column],
+            |row| row.get(0),
         )?;
 
-        let projects = stmt
-            .query_map([], |row| {
-                Ok(Project {
-                    id: row.get(0)?,
-                    path: row.get(1)?,
-                    git_remote: row.get(2)?,
-                    display_name: row.get(3)?,
-                    work_item_pattern: row.get(4)?,
-                    created_at: parse_datetime(row.get::<_, String>(5)?),
-                })
-            })?
-            .collect::<Result<Vec<_>, _>>()?;
+        if !exists {
+            info!("Migrating database: adding column {}.{}", table, column);
+            self.conn
+                .execute(
+                    &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
+                    [],
+                )
+                .with_context(|| format!("Failed to add column {}.{}", table, column))?;
+        }
 
-        Ok(projects)
+        Ok(())
     }
 
-    // ==================== Sessions ====================
-
-    /// Create a new session
-    pub fn create_session(
-        &self,
-        project_id: i64,
-        branch: &str,
-        work_item: Option<&str>,
-        start_commit: Option<&str>,
-    ) -> Result<Session> {
-        let now = Utc::now();
-        self.conn.execute(
-            "INSERT INTO sessions (project_id, branch, work_item, start_commit, started_at, status)
-             VALUES (?, ?, ?, ?, ?, ?)",
-            params![
-                project_id,
-                branch,
-                work_item,
-                start_commit,
-                now.to_rfc3339(),
-                SessionStatus::Active.as_str()
-            ],
-        )?;
+    // ==================== Meta ====================
 
-        let id = self.conn.last_insert_rowid();
-        self.get_session_by_id(id)
+    /// Get a stored key/value setting
+    pub fn get_meta(&self, key: &str) -> Result<Option<String>> {
+        self.conn
+            .query_row("SELECT value FROM meta WHERE key = ?", params![key], |row| row.get(0))
+            .optional()
+            .context("Failed to query meta")
     }
 
-    /// Get session by ID
-    pub fn get_session_by_id(&self, id: i64) -> Result<Session> {
-        self.conn
-            .query_row(
-                "SELECT id, project_id, branch, work_item, start_commit, end_commit,
-                        started_at, ended_at, active_seconds, status
-                 FROM sessions WHERE id = ?",
-                params![id],
-                row_to_session,
-            )
-            .context("Session not found")
+    /// Store a key/value setting
+    pub fn set_meta(&self, key: &str, value: &str) -> Result<()> {
+        self.execute(
+            "INSERT INTO meta (key, value) VALUES (?, ?)
+             ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+            params![key, value],
+        )?;
+        Ok(())
     }
 
-    /// Get active session for a project
-    pub fn get_active_session(&self, project_id: i64) -> Result<Option<Session>> {
-        self.conn
+    /// Cached title of an issue on a code host (`repository` is `host/owner/repo`)
+    ///
+    /// `Some(None)` means the last lookup found no title; that is only trusted
+    /// for `missing_ttl`, so the lookup is retried after it.
+    pub fn get_issue_title(&self, repository: &str, number: u64, missing_ttl: chrono::Duration) -> Result<Option<Option<String>>> {
+        let cached: Option<(String, String)> = self
+            .conn
             .query_row(
-                "SELECT id, project_id, branch, work_item, start_commit, end_commit,
-                        started_at, ended_at, active_seconds, status
-                 FROM sessions WHERE project_id = ? AND status = 'active'
-                 ORDER BY started_at DESC LIMIT 1",
-                params![project_id],
-                row_to_session,
+                "SELECT title, fetched_at FROM issue_titles WHERE repository = ? AND number = ?",
+                params![repository, number as i64],
+                |row| Ok((row.get(0)?, row.get(1)?)),
             )
             .optional()
-            .context("Failed to query active session")
+            .context("Failed to query issue title")?;
+
+        Ok(match cached {
+            Some((title, _)) if !title.is_empty() => Some(Some(title)),
+            Some((_, fetched_at)) if parse_datetime(fetched_at.clone()) + missing_ttl > Utc::now() => Some(None),
+            _ => None,
+        })
     }
 
-    /// Get all active sessions (for cleanup)
-    pub fn get_all_active_sessions(&self) -> Result<Vec<Session>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT id, project_id, branch, work_item, start_commit, end_commit,
-                    started_at, ended_at, active_seconds, status
-             FROM sessions WHERE status = 'active'",
+    /// Cache the title of an issue, or that it has none (`None`)
+    pub fn set_issue_title(&self, repository: &str, number: u64, title: Option<&str>) -> Result<()> {
+        self.execute(
+            "INSERT INTO issue_titles (repository, number, title, fetched_at) VALUES (?, ?, ?, ?)
+             ON CONFLICT(repository, number) DO UPDATE SET title = excluded.title, fetched_at = excluded.fetched_at",
+            params![repository, number as i64, title.unwrap_or_default(), Utc::now().to_rfc3339()],
         )?;
+        Ok(())
+    }
 
-        let sessions = stmt
-            .query_map([], row_to_session)?
-            .collect::<Result<Vec<_>, _>>()?;
+    // ==================== Sync state ====================
 
+    /// Completed sessions not yet sent to a time tracking service (`target`), oldest first
+    pub fn get_unsynced_sessions(&self, target: &str, since: Option<DateTime<Utc>>) -> Result<Vec<Session>> {
+        let query = format!(
+            "SELECT {} FROM sessions
+             WHERE status != 'active' AND deleted_at IS NULL AND started_at >= ?
+               AND uuid NOT IN (SELECT session_uuid FROM synced_sessions WHERE target = ?)
+             ORDER BY started_at",
+            SESSION_COLUMNS
+        );
+        let since = since.map(|s| s.to_rfc3339()).unwrap_or_default();
+        let mut stmt = self.conn.prepare(&query)?;
+        let sessions = stmt
+            .query_map(params![since, target], row_to_session)?
+            .collect::<Result<Vec<_>, _>>()
+            .context("Failed to query unsynced sessions")?;
         Ok(sessions)
     }
 
-    /// Update session end state
-    pub fn complete_session(
-        &self,
-        session_id: i64,
-        end_commit: Option<&str>,
-        active_seconds: i64,
-        status: SessionStatus,
-    ) -> Result<()> {
-        let now = Utc::now();
-        self.conn.execute(
-            "UPDATE sessions SET ended_at = ?, end_commit = ?, active_seconds = ?, status = ?
-             WHERE id = ?",
-            params![
-                now.to_rfc3339(),
-                end_commit,
-                active_seconds,
-                status.as_str(),
-                session_id
-            ],
+    /// Remember that a session was sent to a service, as the entry `remote_id` there
+    pub fn mark_session_synced(&self, target: &str, session_uuid: &str, remote_id: &str) -> Result<()> {
+        self.execute(
+            "INSERT INTO synced_sessions (target, session_uuid, remote_id, synced_at) VALUES (?, ?, ?, ?)
+             ON CONFLICT(target, session_uuid) DO UPDATE SET remote_id = excluded.remote_id, synced_at = excluded.synced_at",
+            params![target, session_uuid, remote_id, Utc::now().to_rfc3339()],
         )?;
         Ok(())
     }
 
-    /// Get sessions within a time range
-    pub fn get_sessions_in_range(
-        &self,
-        start: DateTime<Utc>,
-        end: DateTime<Utc>,
-        project_id: Option<i64>,
-    ) -> Result<Vec<Session>> {
-        let query = if project_id.is_some() {
-            "SELECT id, project_id, branch, work_item, start_commit, end_commit,
-                    started_at, ended_at, active_seconds, status
-             FROM sessions
-             WHERE started_at >= ? AND started_at < ? AND project_id = ? AND status != 'active'
-             ORDER BY started_at"
-        } else {
-            "SELECT id, project_id, branch, work_item, start_commit, end_commit,
-                    started_at, ended_at, active_seconds, status
-             FROM sessions
-             WHERE started_at >= ? AND started_at < ? AND status != 'active'
-             ORDER BY started_at"
-        };
-
-        let mut stmt = self.conn.prepare(query)?;
-
-        let sessions = if let Some(pid) = project_id {
-            stmt.query_map(
-                params![start.to_rfc3339(), end.to_rfc3339(), pid],
-                row_to_session,
-            )?
-        } else {
-            stmt.query_map(params![start.to_rfc3339(), end.to_rfc3339()], row_to_session)?
-        };
-
-        sessions.collect::<Result<Vec<_>, _>>().context("Failed to query sessions")
+    /// What each session sent to `target` was sent as, keyed by session uuid
+    pub fn get_synced_ids(&self, target: &str) -> Result<HashMap<String, String>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT session_uuid, remote_id FROM synced_sessions WHERE target = ?")?;
+        let ids = stmt
+            .query_map(params![target], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<HashMap<_, _>, _>>()?;
+        Ok(ids)
     }
 
-    // ==================== Heartbeats ====================
-
-    /// Record a heartbeat
-    pub fn record_heartbeat(&self, session_id: i64) -> Result<Heartbeat> {
-        let now = Utc::now();
-        self.conn.execute(
-            "INSERT INTO heartbeats (session_id, timestamp) VALUES (?, ?)",
-            params![session_id, now.to_rfc3339()],
-        )?;
-
-        Ok(Heartbeat {
-            id: self.conn.last_insert_rowid(),
-            session_id,
-            timestamp: now,
-        })
+    /// Names of the sync bundles at `location` that were merged here (or uploaded from here)
+    pub fn get_merged_bundles(&self, location: &str) -> Result<HashSet<String>> {
+        let mut stmt = self.conn.prepare("SELECT name FROM merged_bundles WHERE location = ?")?;
+        let names = stmt
+            .query_map(params![location], |row| row.get(0))?
+            .collect::<Result<HashSet<_>, _>>()?;
+        Ok(names)
     }
 
-    /// Get heartbeats for a session
-    pub fn get_heartbeats(&self, session_id: i64) -> Result<Vec<Heartbeat>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT id, session_id, timestamp FROM heartbeats
-             WHERE session_id = ? ORDER BY timestamp",
+    pub fn mark_bundle_merged(&self, location: &str, name: &str) -> Result<()> {
+        self.execute(
+            "INSERT OR IGNORE INTO merged_bundles (location, name, merged_at) VALUES (?, ?, ?)",
+            params![location, name, Utc::now().to_rfc3339()],
         )?;
-
-        let heartbeats = stmt
-            .query_map(params![session_id], |row| {
-                Ok(Heartbeat {
-                    id: row.get(0)?,
-                    session_id: row.get(1)?,
-                    timestamp: parse_datetime(row.get::<_, String>(2)?),
-                })
-            })?
-            .collect::<Result<Vec<_>, _>>()?;
-
-        Ok(heartbeats)
+        Ok(())
     }
 
-    /// Get last heartbeat for a session
-    pub fn get_last_heartbeat(&self, session_id: i64) -> Result<Option<Heartbeat>> {
+    // ==================== Projects ====================
+
+    /// Get project by its globally unique id, including projects in the trash
+    pub fn get_project_by_uuid(&self, uuid: &str) -> Result<Option<Project>> {
         self.conn
             .query_row(
-                "SELECT id, session_id, timestamp FROM heartbeats
-                 WHERE session_id = ? ORDER BY timestamp DESC LIMIT 1",
-                params![session_id],
-                |row| {
-                    Ok(Heartbeat {
-                        id: row.get(0)?,
-                        session_id: row.get(1)?,
-                        timestamp: parse_datetime(row.get::<_, String>(2)?),
-                    })
-                },
+                &format!("SELECT {} FROM projects WHERE uuid = ?", PROJECT_COLUMNS),
+                params![uuid],
+                row_to_project,
             )
             .optional()
-            .context("Failed to query last heartbeat")
+            .context("Failed to query project")
     }
 
-    // ==================== Commits ====================
+    /// Add a project from another database under a new id, keeping its uuid
+    pub fn import_project(&self, project: &Project) -> Result<Project> {
+        self.execute(
+            &format!(
+                "INSERT INTO projects (path, git_remote, display_name, work_item_pattern, created_at, billable,
+                                       hourly_rate, remote_urls, remote_host, remote_owner, remote_repo, uuid,
+                                       archived, client)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, COALESCE(NULLIF(?, ''), {}), ?, ?)",
+                UUID_SQL
+            ),
+            params![
+                project.path,
+                project.git_remote,
+                project.display_name,
+                project.work_item_pattern,
+                project.created_at.to_rfc3339(),
+                project.billable,
+                project.hourly_rate,
+                serde_json::to_string(&project.remote_urls)?,
+                project.remote_host,
+                project.remote_owner,
+                project.remote_repo,
+                project.uuid,
+                project.archived,
+                project.client
+            ],
+        )?;
+
+        let id = self.conn.last_insert_rowid();
+        self.get_project_by_id(id)
+    }
+
+    /// Archive or unarchive a project, returning whether its state changed
+    pub fn set_project_archived(&self, project_id: i64, archived: bool) -> Result<bool> {
+        let changed = self.execute(
+            "UPDATE projects SET archived = ?1 WHERE id = ?2 AND archived != ?1",
+            params![archived, project_id],
+        )?;
+        Ok(changed > 0)
+    }
+
+    /// Move a project to a new path, optionally keeping the old path as an alias
+    ///
+    /// If the new path is already tracked as a separate project (e.g. a hook fired
+    /// after the directory was moved), its sessions are folded into the moved project.
+    pub fn move_project(&self, old_path: &str, new_path: &str, keep_alias: bool) -> Result<Project> {
+        let project = self
+            .get_project_by_path(old_path)?
+            .with_context(|| format!("Project not found: {}", old_path))?;
+
+        self.atomically(|| {
+            if let Some(existing) = self.get_project_by_path(new_path)? {
+                if existing.id != project.id {
+                    self.conn.execute(
+                        "UPDATE sessions SET project_id = ? WHERE project_id = ?",
+                        params![project.id, existing.id],
+                    )?;
+                    self.conn.execute(
+                        "UPDATE project_paths SET project_id = ? WHERE project_id = ?",
+                        params![project.id, existing.id],
+                    )?;
+                    // The moved project's own rates win over those of the one folded in
+                    self.conn.execute("DELETE FROM project_rates WHERE project_id = ?", params![existing.id])?;
+                    self.conn.execute("DELETE FROM commit_cache WHERE project_id = ?", params![existing.id])?;
+                    self.conn.execute("DELETE FROM projects WHERE id = ?", params![existing.id])?;
+                }
+            }
+
+            self.conn.execute("DELETE FROM project_paths WHERE path = ?", params![new_path])?;
+            self.conn.execute(
+                "UPDATE projects SET path = ? WHERE id = ?",
+                params![new_path, project.id],
+            )?;
+
+            if keep_alias {
+                self.conn.execute(
+                    "INSERT OR IGNORE INTO project_paths (project_id, path, created_at) VALUES (?, ?, ?)",
+                    params![project.id, old_path, Utc::now().to_rfc3339()],
+                )?;
+            }
+
+            self.get_project_by_id(project.id)
+        })
+    }
+
+    /// Get alias paths recorded for a project
+    pub fn get_project_aliases(&self, project_id: i64) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT path FROM project_paths WHERE project_id = ? ORDER BY path",
+        )?;
+
+        let paths = stmt
+            .query_map(params![project_id], |row| row.get(0))?
+            .collect::<Result<Vec<String>, _>>()?;
+
+        Ok(paths)
+    }
+
+    /// Set a project's hourly rate from a day on, replacing any rate starting that day
+    pub fn set_project_rate(
+        &self,
+        project_id: i64,
+        effective_from: NaiveDate,
+        hourly_rate: f64,
+        currency: Option<&str>,
+    ) -> Result<()> {
+        self.execute(
+            "INSERT INTO project_rates (project_id, effective_from, hourly_rate, currency)
+             VALUES (?, ?, ?, ?)
+             ON CONFLICT(project_id, effective_from) DO UPDATE SET
+                 hourly_rate = excluded.hourly_rate, currency = excluded.currency",
+            params![project_id, effective_from.to_string(), hourly_rate, currency],
+        )?;
+        Ok(())
+    }
+
+    /// Get a project's dated rates, oldest first
+    pub fn get_project_rates(&self, project_id: i64) -> Result<Vec<ProjectRate>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT project_id, effective_from, hourly_rate, currency FROM project_rates
+             WHERE project_id = ? ORDER BY effective_from",
+        )?;
+
+        let rates = stmt
+            .query_map(params![project_id], row_to_rate)?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(rates)
+    }
+
+    /// Get the dated rate in effect for a project on a day, if any
+    pub fn get_project_rate_at(&self, project_id: i64, date: NaiveDate) -> Result<Option<ProjectRate>> {
+        self.conn
+            .query_row(
+                "SELECT project_id, effective_from, hourly_rate, currency FROM project_rates
+                 WHERE project_id = ? AND effective_from <= ?
+                 ORDER BY effective_from DESC LIMIT 1",
+                params![project_id, date.to_string()],
+                row_to_rate,
+            )
+            .optional()
+            .context("Failed to query project rate")
+    }
+
+    /// Remove the rate starting on a day, returning whether there was one
+    pub fn delete_project_rate(&self, project_id: i64, effective_from: NaiveDate) -> Result<bool> {
+        let deleted = self.execute(
+            "DELETE FROM project_rates WHERE project_id = ? AND effective_from = ?",
+            params![project_id, effective_from.to_string()],
+        )?;
+        Ok(deleted > 0)
+    }
+
+    /// Get lifetime totals for a project
+    pub fn get_project_stats(&self, project_id: i64, top_work_items: usize) -> Result<ProjectStats> {
+        let (first, last, session_count, total_seconds) = self.conn.query_row(
+            "SELECT MIN(started_at), MAX(COALESCE(ended_at, started_at)), COUNT(*),
+                    COALESCE(SUM(active_seconds), 0)
+             FROM sessions WHERE project_id = ? AND deleted_at IS NULL",
+            params![project_id],
+            |row| {
+                Ok((
+                    row.get::<_, Option<String>>(0)?,
+                    row.get::<_, Option<String>>(1)?,
+                    row.get::<_, i64>(2)?,
+                    row.get::<_, i64>(3)?,
+                ))
+            },
+        )?;
+
+        let mut stmt = self.conn.prepare(
+            "SELECT COALESCE(work_item, branch) AS item, COALESCE(SUM(active_seconds), 0) AS total
+             FROM sessions WHERE project_id = ? AND deleted_at IS NULL
+             GROUP BY item ORDER BY total DESC, item LIMIT ?",
+        )?;
+
+        let top = stmt
+            .query_map(params![project_id, top_work_items as i64], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })?
+            .collect::<Result<Vec<(String, i64)>, _>>()?;
+
+        Ok(ProjectStats {
+            project_id,
+            first_session: first.map(parse_datetime),
+            last_session: last.map(parse_datetime),
+            session_count,
+            total_seconds,
+            top_work_items: top,
+        })
+    }
+
+    // ==================== Sessions ====================
+
+    /// Add a finished session recorded by another time tracker
+    ///
+    /// Returns `None` when an entry of the same tracker, project and start was
+    /// imported before, so importing an export again adds only what is new.
+    pub fn import_session(
+        &self,
+        project_id: i64,
+        source: &str,
+        branch: &str,
+        work_item: Option<&str>,
+        started_at: DateTime<Utc>,
+        seconds: i64,
+    ) -> Result<Option<Session>> {
+        let known = self
+            .conn
+            .query_row(
+                "SELECT 1 FROM sessions WHERE imported_from = ? AND project_id = ? AND started_at = ?",
+                params![source, project_id, started_at.to_rfc3339()],
+                |_| Ok(()),
+            )
+            .optional()?;
+        if known.is_some() {
+            return Ok(None);
+        }
+
+        self.execute(
+            &format!(
+                "INSERT INTO sessions (project_id, branch, work_item, started_at, ended_at, active_seconds, status,
+                                       imported_from, uuid)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, {})",
+                UUID_SQL
+            ),
+            params![
+                project_id,
+                branch,
+                work_item,
+                started_at.to_rfc3339(),
+                (started_at + chrono::Duration::seconds(seconds)).to_rfc3339(),
+                seconds,
+                SessionStatus::Completed.as_str(),
+                source
+            ],
+        )?;
+        self.get_session_by_id(self.conn.last_insert_rowid()).map(Some)
+    }
+
+    /// Active seconds of a project's finished sessions started since an instant
+    pub fn finished_seconds_since(&self, project_id: i64, since: DateTime<Utc>) -> Result<i64> {
+        let seconds = self.conn.query_row(
+            "SELECT COALESCE(SUM(active_seconds), 0) FROM sessions
+             WHERE project_id = ? AND started_at >= ? AND status != 'active' AND deleted_at IS NULL",
+            params![project_id, since.to_rfc3339()],
+            |row| row.get(0),
+        )?;
+        Ok(seconds)
+    }
+
+    /// Get sessions within a time range
+    pub fn get_sessions_in_range(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        project_id: Option<i64>,
+    ) -> Result<Vec<Session>> {
+        let filter = if project_id.is_some() { "AND project_id = ?" } else { "" };
+        let query = format!(
+            "SELECT {} FROM sessions
+             WHERE started_at >= ? AND started_at < ? {} AND status != 'active' AND deleted_at IS NULL
+             ORDER BY started_at",
+            SESSION_COLUMNS, filter
+        );
+
+        let mut stmt = self.conn.prepare_cached(&query)?;
+
+        let sessions = if let Some(pid) = project_id {
+            stmt.query_map(
+                params![start.to_rfc3339(), end.to_rfc3339(), pid],
+                row_to_session,
+            )?
+        } else {
+            stmt.query_map(params![start.to_rfc3339(), end.to_rfc3339()], row_to_session)?
+        };
+
+        sessions.collect::<Result<Vec<_>, _>>().context("Failed to query sessions")
+    }
+
+    // ==================== Heartbeats ====================
+
+    /// Number of prompts submitted in a session, including coalesced ones
+    pub fn count_prompts(&self, session_id: i64) -> Result<i64> {
+        let count = self.conn.prepare_cached(
+            "SELECT COALESCE(SUM(event_count), 0) FROM (
+                 SELECT event_count FROM heartbeats WHERE session_id = ?1 AND event_type = 'prompt-submit'
+                 UNION ALL
+                 SELECT event_count FROM heartbeat_intervals WHERE session_id = ?1 AND event_type = 'prompt-submit'
+             )",
+        )?
+        .query_row(params![session_id], |row| row.get(0))?;
+        Ok(count)
+    }
+
+    /// Compacted intervals of a session as (heartbeat at its start, end time)
+    fn get_heartbeat_intervals(&self, session_id: i64) -> Result<Vec<(Heartbeat, DateTime<Utc>)>> {
+        let mut stmt = self.conn.prepare_cached(&format!(
+            "SELECT {} FROM heartbeat_intervals WHERE session_id = ? ORDER BY started_at",
+            INTERVAL_COLUMNS
+        ))?;
+
+        let intervals = stmt
+            .query_map(params![session_id], row_to_interval)?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(intervals)
+    }
+
+    /// Get a heartbeat by id
+    pub fn get_heartbeat(&self, id: i64) -> Result<Heartbeat> {
+        self.conn
+            .query_row(
+                &format!("SELECT {} FROM heartbeats WHERE id = ?", HEARTBEAT_COLUMNS),
+                params![id],
+                row_to_heartbeat,
+            )
+            .context("Heartbeat not found")
+    }
+
+    /// Feed entries after the one with id `after_id`, oldest first
+    pub fn get_feed_after(&self, after_id: i64) -> Result<Vec<FeedEntry>> {
+        let mut stmt = self
+            .conn
+            .prepare_cached("SELECT id, kind, row_id, timestamp FROM event_feed WHERE id > ? ORDER BY id")?;
+
+        let entries = stmt
+            .query_map(params![after_id], |row| {
+                Ok(FeedEntry {
+                    id: row.get(0)?,
+                    kind: row.get(1)?,
+                    row_id: row.get(2)?,
+                    timestamp: parse_datetime(row.get(3)?),
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(entries)
+    }
+
+    /// Id of the newest feed entry, or 0 if there are none
+    pub fn last_feed_id(&self) -> Result<i64> {
+        let id = self
+            .conn
+            .query_row("SELECT COALESCE(MAX(id), 0) FROM event_feed", [], |row| row.get(0))?;
+        Ok(id)
+    }
+
+    /// Reclaim the space freed by deleted rows
+    pub fn vacuum(&self) -> Result<()> {
+        self.conn.execute_batch("VACUUM")?;
+        Ok(())
+    }
+
+    // ==================== Commits ====================
+
+    fn insert_commits(
+        &self,
+        session_id: i64,
+        submodule: Option<&str>,
+        commits: &[(String, String, Option<DateTime<Utc>>)],
+    ) -> Result<()> {
+        self.atomically(|| {
+            {
+                let mut stmt = self.conn.prepare_cached(
+                    "INSERT INTO commits (session_id, hash, message, committed_at, submodule) VALUES (?, ?, ?, ?, ?)",
+                )?;
+                for (hash, message, committed_at) in commits {
+                    stmt.execute(params![
+                        session_id,
+                        hash,
+                        message,
+                        committed_at.map(|dt| dt.to_rfc3339()),
+                        submodule
+                    ])?;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Finished sessions that ran at some point in a range, by start time
+    pub fn get_sessions_overlapping(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<Session>> {
+        // Sorted afterwards (`+`), or the start time index would be scanned from the first session
+        let mut stmt = self.conn.prepare_cached(&format!(
+            "SELECT {} FROM sessions WHERE {} ORDER BY +started_at",
+            SESSION_COLUMNS, REPORT_SESSIONS_FILTER
+        ))?;
+        let sessions = stmt
+            .query_map(params![start.to_rfc3339(), end.to_rfc3339()], row_to_session)?
+            .collect::<Result<Vec<_>, _>>()
+            .context("Failed to query sessions")?;
+        Ok(sessions)
+    }
+
+    /// Prompts, activity split, branch split and commits of the sessions that
+    /// ran in a range, by session id, read with one query each
+    pub fn get_session_details_in_range(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<HashMap<i64, SessionDetails>> {
+        let range = params![start.to_rfc3339(), end.to_rfc3339()];
+        let mut details: HashMap<i64, SessionDetails> = HashMap::new();
+
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT session_id, SUM(event_count) FROM (
+                 SELECT session_id, event_count FROM heartbeats
+                 WHERE event_type = 'prompt-submit' AND session_id IN (SELECT id FROM sessions WHERE {0})
+                 UNION ALL
+                 SELECT session_id, event_count FROM heartbeat_intervals
+                 WHERE event_type = 'prompt-submit' AND session_id IN (SELECT id FROM sessions WHERE {0})
+             )
+             GROUP BY session_id",
+            REPORT_SESSIONS_FILTER
+        ))?;
+        let prompts = stmt.query_map(range, |row| Ok((row.get::<_, i64>(0)?, row.get(1)?)))?;
+        for row in prompts {
+            let (session_id, count) = row?;
+            details.entry(session_id).or_default().prompts = count;
+        }
+
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT session_id, kind, active_seconds FROM session_activity
+             WHERE session_id IN (SELECT id FROM sessions WHERE {}) ORDER BY session_id, id",
+            REPORT_SESSIONS_FILTER
+        ))?;
+        let activity = stmt.query_map(range, |row| Ok((row.get::<_, i64>(0)?, row.get(1)?, row.get(2)?)))?;
+        for row in activity {
+            let (session_id, kind, seconds) = row?;
+            details.entry(session_id).or_default().activity.push((kind, seconds));
+        }
+
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT session_id, branch, work_item, active_seconds FROM session_branches
+             WHERE session_id IN (SELECT id FROM sessions WHERE {}) ORDER BY session_id, id",
+            REPORT_SESSIONS_FILTER
+        ))?;
+        for branch in stmt.query_map(range, row_to_session_branch)? {
+            let branch = branch?;
+            details.entry(branch.session_id).or_default().branches.push(branch);
+        }
+
+        let mut stmt = self.conn.prepare(&format!(
+            "{} WHERE c.session_id IN (SELECT id FROM sessions WHERE {}) ORDER BY c.session_id, c.committed_at",
+            COMMIT_ROWS, REPORT_SESSIONS_FILTER
+        ))?;
+        for commit in stmt.query_map(range, row_to_commit)? {
+            let commit = commit?;
+            details.entry(commit.session_id).or_default().commits.push(commit);
+        }
+
+        Ok(details)
+    }
+
+    // ==================== Trash ====================
+
+    /// Move a session to the trash, returning whether it was there to move
+    pub fn delete_session(&self, session_id: i64) -> Result<bool> {
+        let deleted = self.execute(
+            "UPDATE sessions SET deleted_at = ? WHERE id = ? AND deleted_at IS NULL",
+            params![Utc::now().to_rfc3339(), session_id],
+        )?;
+        Ok(deleted > 0)
+    }
+
+    /// Move a project and its sessions to the trash
+    ///
+    /// The sessions share the project's deletion time, so restoring the
+    /// project brings back exactly the sessions trashed with it.
+    pub fn delete_project(&self, project_id: i64) -> Result<bool> {
+        let now = Utc::now().to_rfc3339();
+        self.atomically(|| {
+            let deleted = self.conn.execute(
+                "UPDATE projects SET deleted_at = ? WHERE id = ? AND deleted_at IS NULL",
+                params![now, project_id],
+            )?;
+            if deleted > 0 {
+                self.conn.execute(
+                    "UPDATE sessions SET deleted_at = ? WHERE project_id = ? AND deleted_at IS NULL",
+                    params![now, project_id],
+                )?;
+            }
+            Ok(deleted > 0)
+        })
+    }
+
+    /// Take a session out of the trash
+    pub fn undelete_session(&self, session_id: i64) -> Result<bool> {
+        let restored = self.execute(
+            "UPDATE sessions SET deleted_at = NULL WHERE id = ? AND deleted_at IS NOT NULL",
+            params![session_id],
+        )?;
+        Ok(restored > 0)
+    }
+
+    /// Take a project out of the trash, with the sessions deleted along with it
+    pub fn undelete_project(&self, project_id: i64) -> Result<bool> {
+        self.atomically(|| {
+            self.conn.execute(
+                "UPDATE sessions SET deleted_at = NULL
+                 WHERE project_id = ?1 AND deleted_at = (SELECT deleted_at FROM projects WHERE id = ?1)",
+                params![project_id],
+            )?;
+            let restored = self.conn.execute(
+                "UPDATE projects SET deleted_at = NULL WHERE id = ? AND deleted_at IS NOT NULL",
+                params![project_id],
+            )?;
+            Ok(restored > 0)
+        })
+    }
+
+    /// Projects in the trash, most recently deleted first
+    pub fn get_deleted_projects(&self) -> Result<Vec<Project>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM projects WHERE deleted_at IS NOT NULL ORDER BY deleted_at DESC",
+            PROJECT_COLUMNS
+        ))?;
+
+        let projects = stmt
+            .query_map([], row_to_project)?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(projects)
+    }
+
+    /// All sessions of a project, trashed ones included, oldest first
+    pub fn get_project_sessions(&self, project_id: i64) -> Result<Vec<Session>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM sessions WHERE project_id = ? ORDER BY started_at, id",
+            SESSION_COLUMNS
+        ))?;
+
+        let sessions = stmt
+            .query_map(params![project_id], row_to_session)?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(sessions)
+    }
+
+    /// Sessions in the trash, most recently deleted first
+    pub fn get_deleted_sessions(&self) -> Result<Vec<Session>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM sessions WHERE deleted_at IS NOT NULL ORDER BY deleted_at DESC, id",
+            SESSION_COLUMNS
+        ))?;
+
+        let sessions = stmt
+            .query_map([], row_to_session)?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(sessions)
+    }
+
+    /// Remove a session and everything recorded for it, for good
+    pub fn purge_session(&self, session_id: i64) -> Result<()> {
+        self.atomically(|| {
+            for table in SESSION_CHILD_TABLES {
+                self.conn.execute(&format!("DELETE FROM {} WHERE session_id = ?", table), params![session_id])?;
+            }
+            self.conn.execute("DELETE FROM sessions WHERE id = ?", params![session_id])?;
+            Ok(())
+        })
+    }
+
+    /// Remove a project with all its sessions, aliases and rates, for good
+    pub fn purge_project(&self, project_id: i64) -> Result<()> {
+        self.atomically(|| {
+            for table in SESSION_CHILD_TABLES {
+                self.conn.execute(
+                    &format!(
+                        "DELETE FROM {} WHERE session_id IN (SELECT id FROM sessions WHERE project_id = ?)",
+                        table
+                    ),
+                    params![project_id],
+                )?;
+            }
+            self.conn.execute("DELETE FROM sessions WHERE project_id = ?", params![project_id])?;
+            self.conn.execute("DELETE FROM project_paths WHERE project_id = ?", params![project_id])?;
+            self.conn.execute("DELETE FROM project_rates WHERE project_id = ?", params![project_id])?;
+            self.conn.execute("DELETE FROM commit_cache WHERE project_id = ?", params![project_id])?;
+            self.conn.execute("DELETE FROM projects WHERE id = ?", params![project_id])?;
+            Ok(())
+        })
+    }
+
+    // ==================== Restore ====================
+
+    /// Write a project with its own id, replacing any project with that id
+    pub fn restore_project(&self, project: &Project) -> Result<()> {
+        self.execute(
+            &format!(
+                "INSERT OR REPLACE INTO projects ({})
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, COALESCE(NULLIF(?, ''), {}), ?, ?, ?)",
+                PROJECT_COLUMNS, UUID_SQL
+            ),
+            params![
+                project.id,
+                project.path,
+                project.git_remote,
+                project.display_name,
+                project.work_item_pattern,
+                project.created_at.to_rfc3339(),
+                project.billable,
+                project.hourly_rate,
+                serde_json::to_string(&project.remote_urls)?,
+                project.remote_host,
+                project.remote_owner,
+                project.remote_repo,
+                project.uuid,
+                project.deleted_at.map(|dt| dt.to_rfc3339()),
+                project.archived,
+                project.client
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Write a session with its own id, replacing its commits, branch split and activity
+    pub fn restore_session(
+        &self,
+        session: &Session,
+        commits: &[Commit],
+        branches: &[SessionBranch],
+        activity: &[(String, i64)],
+    ) -> Result<()> {
+        let diff = session.diff_stats;
+        let uncommitted = session.uncommitted;
+        let pull_request = session.pull_request.as_ref();
+
+        self.atomically(|| {
+            self.conn.execute(
+                &format!(
+                    "INSERT OR REPLACE INTO sessions ({})
+                     VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?,
+                             COALESCE(NULLIF(?, ''), {}), ?, ?, ?)",
+                    SESSION_COLUMNS, UUID_SQL
+                ),
+                params![
+                    session.id,
+                    session.project_id,
+                    session.branch,
+                    session.work_item,
+                    session.start_commit,
+                    session.end_commit,
+                    session.started_at.to_rfc3339(),
+                    session.ended_at.map(|dt| dt.to_rfc3339()),
+                    session.active_seconds,
+                    session.status.as_str(),
+                    session.claude_session_id,
+                    session.model,
+                    session.machine,
+                    diff.map(|d| d.files_changed),
+                    diff.map(|d| d.insertions),
+                    diff.map(|d| d.deletions),
+                    pull_request.map(|pr| pr.number),
+                    pull_request.map(|pr| &pr.url),
+                    pull_request.map(|pr| &pr.title),
+                    uncommitted.map(|d| d.files_changed),
+                    uncommitted.map(|d| d.insertions),
+                    uncommitted.map(|d| d.deletions),
+                    session.uuid,
+                    session.deleted_at.map(|dt| dt.to_rfc3339()),
+                    session.imported_from,
+                    session.last_heartbeat_at.map(|dt| dt.to_rfc3339())
+                ],
+            )?;
+
+            self.conn.execute("DELETE FROM commits WHERE session_id = ?", params![session.id])?;
+            for commit in commits {
+                self.conn.execute(
+                    "INSERT INTO commits (session_id, hash, message, committed_at, submodule) VALUES (?, ?, ?, ?, ?)",
+                    params![
+                        session.id,
+                        commit.hash,
+                        commit.message,
+                        commit.committed_at.map(|dt| dt.to_rfc3339()),
+                        commit.submodule
+                    ],
+                )?;
+            }
+            self.conn.execute("DELETE FROM session_branches WHERE session_id = ?", params![session.id])?;
+            for branch in branches {
+                self.conn.execute(
+                    "INSERT INTO session_branches (session_id, branch, work_item, active_seconds) VALUES (?, ?, ?, ?)",
+                    params![session.id, branch.branch, branch.work_item, branch.active_seconds],
+                )?;
+            }
+            self.conn.execute("DELETE FROM session_activity WHERE session_id = ?", params![session.id])?;
+            for (kind, active_seconds) in activity {
+                self.conn.execute(
+                    "INSERT INTO session_activity (session_id, kind, active_seconds) VALUES (?, ?, ?)",
+                    params![session.id, kind, active_seconds],
+                )?;
+            }
+            Ok(())
+        })
+    }
+
+    // ==================== Sync ====================
+
+    /// Finished sessions, trash included; with a machine, only those recorded on
+    /// it, and with `since`, only those that ended or changed at or after it
+    pub fn get_sessions_for_sync(&self, machine: Option<&str>, since: Option<DateTime<Utc>>) -> Result<Vec<Session>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM sessions WHERE status != 'active' AND (?1 IS NULL OR machine = ?1)
+               AND (?2 IS NULL OR julianday(COALESCE(updated_at, ended_at)) >= julianday(?2))
+             ORDER BY started_at, id",
+            SESSION_COLUMNS
+        ))?;
+
+        let sessions = stmt
+            .query_map(params![machine, since.map(|dt| dt.to_rfc3339())], row_to_session)?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(sessions)
+    }
+
+    /// Record `machine` on the sessions recorded before machines were; returns how many
+    pub fn claim_sessions(&self, machine: &str) -> Result<usize> {
+        Ok(self.execute("UPDATE sessions SET machine = ? WHERE machine IS NULL", params![machine])?)
+    }
+
+    pub fn get_session_by_uuid(&self, uuid: &str) -> Result<Option<Session>> {
+        self.conn
+            .query_row(
+                &format!("SELECT {} FROM sessions WHERE uuid = ?", SESSION_COLUMNS),
+                params![uuid],
+                row_to_session,
+            )
+            .optional()
+            .context("Failed to query session")
+    }
+
+    /// Id for a session written with [`Database::restore_session`] that must not replace another
+    pub fn next_session_id(&self) -> Result<i64> {
+        let id = self
+            .conn
+            .query_row("SELECT COALESCE(MAX(id), 0) + 1 FROM sessions", [], |row| row.get(0))?;
+        Ok(id)
+    }
+
+    // ==================== Merge ====================
+
+    /// Run `f` with another tracker database attached as `source`
+    ///
+    /// SQLite only attaches outside transactions, so `f` starts its own.
+    pub fn with_attached<T>(&self, source: &Path, f: impl FnOnce() -> Result<T>) -> Result<T> {
+        let source = source.to_str().context("Invalid database path")?;
+        self.conn
+            .execute("ATTACH DATABASE ? AS source", params![source])
+            .with_context(|| format!("Failed to open {}", source))?;
+
+        let result = f();
+        self.conn.execute("DETACH DATABASE source", [])?;
+        result
+    }
+
+    /// Copy the sessions of the attached `source` database into this one
+    ///
+    /// `project_ids` maps the other database's project ids to ours; sessions of
+    /// unmapped projects and sessions in the trash are left out. A session already
+    /// present (same uuid, or same project, start time and branch) is skipped, so
+    /// merging the same file twice changes nothing. Everything recorded for a
+    /// session comes along under its new id. Returns (sessions copied, skipped).
+    pub fn merge_sessions(&self, project_ids: &HashMap<i64, i64>) -> Result<(usize, usize)> {
+        self.atomically(|| {
+            let mut copied = 0;
+            let mut skipped = 0;
+
+            let candidates = {
+                let mut stmt = self.conn.prepare(
+                    "SELECT id, project_id, uuid, started_at, branch FROM source.sessions
+                     WHERE deleted_at IS NULL ORDER BY started_at",
+                )?;
+                let rows = stmt.query_map([], |row| {
+                    Ok((
+                        row.get::<_, i64>(0)?,
+                        row.get::<_, i64>(1)?,
+                        row.get::<_, Option<String>>(2)?,
+                        row.get::<_, String>(3)?,
+                        row.get::<_, String>(4)?,
+                    ))
+                })?;
+                rows.collect::<Result<Vec<_>, _>>()?
+            };
+
+            let session_columns = self.copied_columns("sessions", "project_id")?;
+            for (source_id, source_project_id, uuid, started_at, branch) in candidates {
+                let Some(&project_id) = project_ids.get(&source_project_id) else {
+                    continue;
+                };
+
+                let present: bool = self.conn.query_row(
+                    "SELECT COUNT(*) > 0 FROM main.sessions
+                     WHERE uuid = ?1 OR (project_id = ?2 AND started_at = ?3 AND branch = ?4)",
+                    params![uuid, project_id, started_at, branch],
+                    |row| row.get(0),
+                )?;
+                if present {
+                    skipped += 1;
+                    continue;
+                }
+
+                self.conn.execute(
+                    &format!(
+                        "INSERT INTO main.sessions (project_id, {0}) SELECT ?, {0} FROM source.sessions WHERE id = ?",
+                        session_columns
+                    ),
+                    params![project_id, source_id],
+                )?;
+                let session_id = self.conn.last_insert_rowid();
+
+                // The search index follows through its triggers
+                for table in SESSION_CHILD_TABLES.iter().filter(|t| **t != "session_search") {
+                    let columns = self.copied_columns(table, "session_id")?;
+                    self.conn.execute(
+                        &format!(
+                            "INSERT INTO main.{0} (session_id, {1}) SELECT ?, {1} FROM source.{0} WHERE session_id = ?",
+                            table, columns
+                        ),
+                        params![session_id, source_id],
+                    )?;
+                }
+                copied += 1;
+            }
+
+            Ok((copied, skipped))
+        })
+    }
+
+    /// Columns of a table other than its id and the given key, comma-separated
+    fn copied_columns(&self, table: &str, key: &str) -> Result<String> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT name FROM pragma_table_info(?, 'main') WHERE name NOT IN ('id', ?)")?;
+        let columns = stmt
+            .query_map(params![table, key], |row| row.get(0))?
+            .collect::<Result<Vec<String>, _>>()?;
+
+        Ok(columns.join(", "))
+    }
+
+    // ==================== Notes ====================
+
+    /// Attach a note to a session
+    pub fn add_note(&self, session_id: i64, text: &str) -> Result<Note> {
+        let now = Utc::now();
+        self.execute(
+            "INSERT INTO notes (session_id, timestamp, text) VALUES (?, ?, ?)",
+            params![session_id, now.to_rfc3339(), text],
+        )?;
+
+        Ok(Note {
+            id: self.conn.last_insert_rowid(),
+            session_id,
+            timestamp: now,
+            text: text.to_string(),
+        })
+    }
+
+    /// Get a session's notes, oldest first
+    pub fn get_notes(&self, session_id: i64) -> Result<Vec<Note>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, session_id, timestamp, text FROM notes WHERE session_id = ? ORDER BY timestamp, id",
+        )?;
+
+        let notes = stmt
+            .query_map(params![session_id], |row| {
+                Ok(Note {
+                    id: row.get(0)?,
+                    session_id: row.get(1)?,
+                    timestamp: parse_datetime(row.get::<_, String>(2)?),
+                    text: row.get(3)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(notes)
+    }
+
+    /// Delete a note, returning whether it existed
+    pub fn delete_note(&self, note_id: i64) -> Result<bool> {
+        let deleted = self.execute("DELETE FROM notes WHERE id = ?", params![note_id])?;
+        Ok(deleted > 0)
+    }
+
+    // ==================== Window activity ====================
+
+    /// Store imported window events, skipping ones imported before; returns how many were new
+    pub fn record_window_activity(&self, events: &[WindowEvent]) -> Result<usize> {
+        self.atomically(|| {
+            let mut added = 0;
+            for event in events {
+                added += self.execute(
+                    "INSERT OR IGNORE INTO window_activity (bucket, timestamp, duration_seconds, app, title)
+                     VALUES (?, ?, ?, ?, ?)",
+                    params![
+                        event.bucket,
+                        event.timestamp.to_rfc3339(),
+                        event.duration_seconds,
+                        event.app,
+                        event.title
+                    ],
+                )?;
+            }
+            Ok(added)
+        })
+    }
+
+    /// Window events starting within a time range, oldest first
+    pub fn get_window_activity(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<WindowEvent>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT bucket, timestamp, duration_seconds, app, title FROM window_activity
+             WHERE timestamp >= ? AND timestamp < ? ORDER BY timestamp",
+        )?;
+        let events = stmt
+            .query_map(params![start.to_rfc3339(), end.to_rfc3339()], |row| {
+                Ok(WindowEvent {
+                    bucket: row.get(0)?,
+                    timestamp: parse_datetime(row.get::<_, String>(1)?),
+                    duration_seconds: row.get(2)?,
+                    app: row.get(3)?,
+                    title: row.get(4)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(events)
+    }
+
+    // ==================== Search ====================
+
+    /// Rebuild the full-text search index from sessions, their commits and notes
+    ///
+    /// Triggers keep the index current; this is only needed when they were missing.
+    pub fn rebuild_search_index(&self) -> Result<()> {
+        self.atomically(|| {
+            self.conn.execute("DELETE FROM session_search", [])?;
+            self.conn.execute(
+                &format!(
+                    "INSERT INTO session_search (rowid, session_id, work_item_text, branch_text, commit_text, note_text)
+                     {}",
+                    SEARCH_ROW_SQL
+                ),
+                [],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Search sessions by work item, branch, commit messages and notes
+    ///
+    /// Returns matching sessions (newest first) with a snippet of the matched text.
+    pub fn search_sessions(&self, query: &str, limit: usize) -> Result<Vec<(Session, String)>> {
+        let Some(fts_query) = fts_query(query) else {
+            return Ok(Vec::new());
+        };
+
+        // Session and search columns don't overlap, so the session columns need no prefix
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {}, snippet(session_search, -1, '[', ']', '...', 10)
+             FROM session_search
+             JOIN sessions ON sessions.id = session_search.rowid
+             WHERE session_search MATCH ?
+             ORDER BY started_at DESC
+             LIMIT ?",
+            SESSION_COLUMNS
+        ))?;
+        let snippet_column = SESSION_COLUMNS.split(',').count();
+
+        let hits = stmt
+            .query_map(params![fts_query, limit as i64], |row| {
+                Ok((row_to_session(row)?, row.get(snippet_column)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()
+            .context("Failed to search sessions")?;
+
+        Ok(hits)
+    }
+}
+
+impl Storage for Database {
+    fn atomically<T>(&self, f: impl FnOnce() -> Result<T>) -> Result<T> {
+        let outermost = self.conn.is_autocommit();
+        self.conn
+            .execute_batch(if outermost { "BEGIN IMMEDIATE" } else { "SAVEPOINT atomically" })?;
+
+        match f() {
+            Ok(value) => {
+                self.conn
+                    .execute_batch(if outermost { "COMMIT" } else { "RELEASE atomically" })?;
+                Ok(value)
+            }
+            Err(e) => {
+                let rollback = if outermost {
+                    "ROLLBACK"
+                } else {
+                    "ROLLBACK TO atomically; RELEASE atomically"
+                };
+                if let Err(rollback_error) = self.conn.execute_batch(rollback) {
+                    warn!("Failed to roll back: {}", rollback_error);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    fn get_or_create_project(
+        &self,
+        path: &str,
+        git_remote: Option<&str>,
+        display_name: Option<&str>,
+        work_item_pattern: Option<&str>,
+    ) -> Result<Project> {
+        // Try to find existing project
+        if let Some(project) = self.get_project_by_path(path)? {
+            // Update if new info provided
+            if git_remote.is_some() || display_name.is_some() || work_item_pattern.is_some() {
+                self.execute(
+                    "UPDATE projects SET
+                        git_remote = COALESCE(?, git_remote),
+                        display_name = COALESCE(?, display_name),
+                        work_item_pattern = COALESCE(?, work_item_pattern)
+                    WHERE id = ?",
+                    params![git_remote, display_name, work_item_pattern, project.id],
+                )?;
+                return self.get_project_by_id(project.id);
+            }
+            return Ok(project);
+        }
+
+        // Create new project
+        let now = Utc::now();
+        self.execute(
+            &format!(
+                "INSERT INTO projects (path, git_remote, display_name, work_item_pattern, created_at, uuid)
+                 VALUES (?, ?, ?, ?, ?, {})",
+                UUID_SQL
+            ),
+            params![
+                path,
+                git_remote,
+                display_name,
+                work_item_pattern,
+                now.to_rfc3339()
+            ],
+        )?;
+
+        let id = self.conn.last_insert_rowid();
+        self.get_project_by_id(id)
+    }
+
+    fn set_project_remotes(&self, project_id: i64, remote_urls: &[String]) -> Result<()> {
+        self.execute(
+            "UPDATE projects SET remote_urls = ? WHERE id = ?",
+            params![serde_json::to_string(remote_urls)?, project_id],
+        )?;
+        Ok(())
+    }
+
+    fn set_project_repository(&self, project_id: i64, repository: &RemoteRepo) -> Result<()> {
+        self.execute(
+            "UPDATE projects SET remote_host = ?, remote_owner = ?, remote_repo = ? WHERE id = ?",
+            params![repository.host, repository.owner, repository.repo, project_id],
+        )?;
+        Ok(())
+    }
+
+    fn get_project_by_id(&self, id: i64) -> Result<Project> {
+        self.conn
+            .query_row(
+                &format!("SELECT {} FROM projects WHERE id = ?", PROJECT_COLUMNS),
+                params![id],
+                row_to_project,
+            )
+            .context("Project not found")
+    }
+
+    fn get_project_by_path(&self, path: &str) -> Result<Option<Project>> {
+        self.conn
+            .query_row(
+                &format!(
+                    "SELECT {} FROM projects
+                     WHERE path = ?1 OR id = (SELECT project_id FROM project_paths WHERE path = ?1)
+                     ORDER BY path = ?1 DESC LIMIT 1",
+                    PROJECT_COLUMNS
+                ),
+                params![path],
+                row_to_project,
+            )
+            .optional()
+            .context("Failed to query project")
+    }
+
+    fn list_projects(&self, include_archived: bool) -> Result<Vec<Project>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM projects WHERE deleted_at IS NULL AND (archived = 0 OR ?) ORDER BY path",
+            PROJECT_COLUMNS
+        ))?;
+
+        let projects = stmt
+            .query_map(params![include_archived], row_to_project)?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(projects)
+    }
+
+    fn update_project(&self, project: &Project) -> Result<()> {
+        self.execute(
+            "UPDATE projects SET display_name = ?, work_item_pattern = ?, billable = ?, hourly_rate = ?, client = ?
+             WHERE id = ?",
+            params![
+                project.display_name,
+                project.work_item_pattern,
+                project.billable,
+                project.hourly_rate,
+                project.client,
+                project.id
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn add_project_alias(&self, project_id: i64, path: &str) -> Result<()> {
+        self.execute(
+            "INSERT OR IGNORE INTO project_paths (project_id, path, created_at) VALUES (?, ?, ?)",
+            params![project_id, path, Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    fn create_session(
+        &self,
+        project_id: i64,
+        branch: &str,
+        work_item: Option<&str>,
+        start_commit: Option<&str>,
+        claude_session_id: Option<&str>,
+        machine: Option<&str>,
+    ) -> Result<Session> {
+        let now = Utc::now();
+        self.execute(
+            &format!(
+                "INSERT INTO sessions (project_id, branch, work_item, start_commit, started_at, status,
+                                       claude_session_id, machine, uuid)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, {})",
+                UUID_SQL
+            ),
+            params![
+                project_id,
+                branch,
+                work_item,
+                start_commit,
+                now.to_rfc3339(),
+                SessionStatus::Active.as_str(),
+                claude_session_id,
+                machine
+            ],
+        )?;
+
+        let id = self.conn.last_insert_rowid();
+        self.get_session_by_id(id)
+    }
+
+    fn get_session_by_id(&self, id: i64) -> Result<Session> {
+        self.conn
+            .query_row(
+                &format!("SELECT {} FROM sessions WHERE id = ?", SESSION_COLUMNS),
+                params![id],
+                row_to_session,
+            )
+            .context("Session not found")
+    }
+
+    fn get_active_session(
+        &self,
+        project_id: i64,
+        claude_session_id: Option<&str>,
+    ) -> Result<Option<Session>> {
+        let query = if claude_session_id.is_some() {
+            format!(
+                "SELECT {} FROM sessions
+                 WHERE project_id = ?1 AND status = 'active' AND deleted_at IS NULL
+                   AND (claude_session_id = ?2 OR claude_session_id IS NULL)
+                 ORDER BY claude_session_id IS NULL, started_at DESC LIMIT 1",
+                SESSION_COLUMNS
+            )
+        } else {
+            format!(
+                "SELECT {} FROM sessions
+                 WHERE project_id = ?1 AND status = 'active' AND deleted_at IS NULL
+                 ORDER BY started_at DESC LIMIT 1",
+                SESSION_COLUMNS
+            )
+        };
+
+        let result = if let Some(id) = claude_session_id {
+            self.conn.query_row(&query, params![project_id, id], row_to_session)
+        } else {
+            self.conn.query_row(&query, params![project_id], row_to_session)
+        };
+
+        result.optional().context("Failed to query active session")
+    }
+
+    fn get_latest_session(&self, project_id: i64, claude_session_id: &str) -> Result<Option<Session>> {
+        self.conn
+            .query_row(
+                &format!(
+                    "SELECT {} FROM sessions
+                     WHERE project_id = ?1 AND claude_session_id = ?2 AND deleted_at IS NULL
+                     ORDER BY started_at DESC LIMIT 1",
+                    SESSION_COLUMNS
+                ),
+                params![project_id, claude_session_id],
+                row_to_session,
+            )
+            .optional()
+            .context("Failed to query latest session")
+    }
+
+    fn get_all_active_sessions(&self) -> Result<Vec<Session>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM sessions WHERE status = 'active' AND deleted_at IS NULL",
+            SESSION_COLUMNS
+        ))?;
+
+        let sessions = stmt
+            .query_map([], row_to_session)?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(sessions)
+    }
+
+    fn set_session_work_item(&self, session_id: i64, work_item: Option<&str>) -> Result<()> {
+        let updated = self.execute(
+            "UPDATE sessions SET work_item = ? WHERE id = ?",
+            params![work_item, session_id],
+        )?;
+        if updated == 0 {
+            anyhow::bail!("Session not found: {}", session_id);
+        }
+        Ok(())
+    }
+
+    fn complete_session(
+        &self,
+        session_id: i64,
+        end_commit: Option<&str>,
+        active_seconds: i64,
+        ended_at: DateTime<Utc>,
+        status: SessionStatus,
+    ) -> Result<bool> {
+        let updated = self.execute(
+            "UPDATE sessions SET ended_at = ?, end_commit = ?, active_seconds = ?, status = ?
+             WHERE id = ? AND status = 'active'",
+            params![
+                ended_at.to_rfc3339(),
+                end_commit,
+                active_seconds,
+                status.as_str(),
+                session_id
+            ],
+        )?;
+        Ok(updated > 0)
+    }
+
+    fn get_resumable_session(
+        &self,
+        project_id: i64,
+        branch: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Option<Session>> {
+        self.conn
+            .query_row(
+                &format!(
+                    "SELECT {} FROM sessions
+                     WHERE project_id = ? AND branch = ? AND status = 'completed' AND ended_at >= ?
+                       AND deleted_at IS NULL
+                     ORDER BY ended_at DESC LIMIT 1",
+                    SESSION_COLUMNS
+                ),
+                params![project_id, branch, since.to_rfc3339()],
+                row_to_session,
+            )
+            .optional()
+            .context("Failed to query resumable session")
+    }
 
-    /// Record commits for a session
-    pub fn record_commits(&self, session_id: i64, commits: &[(String, String, Option<DateTime<Utc>>)]) -> Result<()> {
-        for (hash, message, committed_at) in commits {
+    fn reopen_session(&self, session_id: i64, claude_session_id: Option<&str>) -> Result<()> {
+        self.atomically(|| {
             self.conn.execute(
-                "INSERT INTO commits (session_id, hash, message, committed_at) VALUES (?, ?, ?, ?)",
+                "UPDATE sessions SET status = ?, ended_at = NULL, end_commit = NULL, active_seconds = NULL,
+                                     last_heartbeat_at = NULL, files_changed = NULL, insertions = NULL, deletions = NULL,
+                                     uncommitted_files = NULL, uncommitted_insertions = NULL,
+                                     uncommitted_deletions = NULL,
+                                     claude_session_id = ?
+                 WHERE id = ?",
+                params![SessionStatus::Active.as_str(), claude_session_id, session_id],
+            )?;
+            self.conn.execute("DELETE FROM commits WHERE session_id = ?", params![session_id])?;
+            self.conn.execute("DELETE FROM session_branches WHERE session_id = ?", params![session_id])?;
+            self.conn.execute("DELETE FROM session_activity WHERE session_id = ?", params![session_id])?;
+            Ok(())
+        })
+    }
+
+    fn get_recent_sessions(&self, limit: usize) -> Result<Vec<Session>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM sessions WHERE deleted_at IS NULL ORDER BY started_at DESC LIMIT ?",
+            SESSION_COLUMNS
+        ))?;
+
+        let sessions = stmt
+            .query_map(params![limit as i64], row_to_session)?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(sessions)
+    }
+
+    fn set_session_diff_stats(&self, session_id: i64, stats: &DiffStats) -> Result<()> {
+        self.execute(
+            "UPDATE sessions SET files_changed = ?, insertions = ?, deletions = ? WHERE id = ?",
+            params![stats.files_changed, stats.insertions, stats.deletions, session_id],
+        )?;
+        Ok(())
+    }
+
+    fn set_session_uncommitted(&self, session_id: i64, stats: &DiffStats) -> Result<()> {
+        self.execute(
+            "UPDATE sessions SET uncommitted_files = ?, uncommitted_insertions = ?, uncommitted_deletions = ?
+             WHERE id = ?",
+            params![stats.files_changed, stats.insertions, stats.deletions, session_id],
+        )?;
+        Ok(())
+    }
+
+    fn set_session_pull_request(&self, session_id: i64, pull_request: &PullRequest) -> Result<()> {
+        self.execute(
+            "UPDATE sessions SET pr_number = ?, pr_url = ?, pr_title = ? WHERE id = ?",
+            params![pull_request.number, pull_request.url, pull_request.title, session_id],
+        )?;
+        Ok(())
+    }
+
+    fn set_session_model(&self, session_id: i64, model: &str) -> Result<()> {
+        self.execute(
+            "UPDATE sessions SET model = ? WHERE id = ?",
+            params![model, session_id],
+        )?;
+        Ok(())
+    }
+
+    fn record_session_branches(
+        &self,
+        session_id: i64,
+        allocations: &[(String, Option<String>, i64)],
+    ) -> Result<()> {
+        self.atomically(|| {
+            self.conn.execute("DELETE FROM session_branches WHERE session_id = ?", params![session_id])?;
+            for (branch, work_item, active_seconds) in allocations {
+                self.conn.execute(
+                    "INSERT INTO session_branches (session_id, branch, work_item, active_seconds)
+                     VALUES (?, ?, ?, ?)",
+                    params![session_id, branch, work_item, active_seconds],
+                )?;
+            }
+            Ok(())
+        })
+    }
+
+    fn get_session_branches(&self, session_id: i64) -> Result<Vec<SessionBranch>> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT session_id, branch, work_item, active_seconds FROM session_branches
+             WHERE session_id = ? ORDER BY id",
+        )?;
+
+        let branches = stmt
+            .query_map(params![session_id], row_to_session_branch)?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(branches)
+    }
+
+    fn record_session_activity(&self, session_id: i64, breakdown: &[(String, i64)]) -> Result<()> {
+        self.atomically(|| {
+            self.conn.execute("DELETE FROM session_activity WHERE session_id = ?", params![session_id])?;
+            for (kind, active_seconds) in breakdown {
+                self.conn.execute(
+                    "INSERT INTO session_activity (session_id, kind, active_seconds) VALUES (?, ?, ?)",
+                    params![session_id, kind, active_seconds],
+                )?;
+            }
+            Ok(())
+        })
+    }
+
+    fn get_session_activity(&self, session_id: i64) -> Result<Vec<(String, i64)>> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT kind, active_seconds FROM session_activity WHERE session_id = ? ORDER BY id",
+        )?;
+
+        let activity = stmt
+            .query_map(params![session_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(activity)
+    }
+
+    fn record_heartbeat(&self, session_id: i64, context: &HeartbeatContext) -> Result<Heartbeat> {
+        let mut recorded = self.record_heartbeats(session_id, &[(Utc::now(), context.clone())])?;
+        recorded.pop().context("Heartbeat was not recorded")
+    }
+
+    fn record_heartbeats(
+        &self,
+        session_id: i64,
+        heartbeats: &[(DateTime<Utc>, HeartbeatContext)],
+    ) -> Result<Vec<Heartbeat>> {
+        self.atomically(|| {
+            let mut recorded = Vec::with_capacity(heartbeats.len());
+            {
+                let mut stmt = self.conn.prepare_cached(&format!(
+                    "INSERT INTO heartbeats (session_id, timestamp, file_path, branch, event_type, tool, cwd,
+                                             model, agent, uuid)
+                     VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, {})",
+                    UUID_SQL
+                ))?;
+                for (timestamp, context) in heartbeats {
+                    let id = stmt.insert(params![
+                        session_id,
+                        timestamp.to_rfc3339(),
+                        context.file_path,
+                        context.branch,
+                        context.event_type,
+                        context.tool,
+                        context.cwd,
+                        context.model,
+                        context.agent
+                    ])?;
+                    recorded.push(Heartbeat {
+                        id,
+                        session_id,
+                        timestamp: *timestamp,
+                        file_path: context.file_path.clone(),
+                        branch: context.branch.clone(),
+                        event_type: context.event_type.clone(),
+                        tool: context.tool.clone(),
+                        cwd: context.cwd.clone(),
+                        model: context.model.clone(),
+                        agent: context.agent.clone(),
+                    });
+                }
+            }
+            Ok(recorded)
+        })
+    }
+
+    fn record_heartbeat_coalesced(
+        &self,
+        session_id: i64,
+        timestamp: DateTime<Utc>,
+        context: &HeartbeatContext,
+        window_seconds: u32,
+    ) -> Result<Option<Heartbeat>> {
+        if window_seconds > 0 {
+            let since = timestamp - chrono::Duration::seconds(window_seconds as i64);
+            let updated = self.execute(
+                "UPDATE heartbeats SET event_count = event_count + 1
+                 WHERE id = (
+                     SELECT id FROM heartbeats
+                     WHERE session_id = ? AND timestamp >= ?
+                       AND file_path IS ? AND branch IS ? AND event_type IS ? AND tool IS ?
+                       AND cwd IS ? AND model IS ? AND agent IS ?
+                     ORDER BY timestamp DESC LIMIT 1
+                 )",
                 params![
                     session_id,
-                    hash,
-                    message,
-                    committed_at.map(|dt| dt.to_rfc3339())
+                    since.to_rfc3339(),
+                    context.file_path,
+                    context.branch,
+                    context.event_type,
+                    context.tool,
+                    context.cwd,
+                    context.model,
+                    context.agent
                 ],
             )?;
+            if updated > 0 {
+                return Ok(None);
+            }
         }
+
+        let mut recorded = self.record_heartbeats(session_id, &[(timestamp, context.clone())])?;
+        Ok(recorded.pop())
+    }
+
+    fn set_running_active_time(&self, session_id: i64, active_seconds: i64, last_heartbeat_at: DateTime<Utc>) -> Result<()> {
+        self.execute(
+            "UPDATE sessions SET active_seconds = ?, last_heartbeat_at = ? WHERE id = ? AND status = 'active'",
+            params![active_seconds, last_heartbeat_at.to_rfc3339(), session_id],
+        )?;
+        Ok(())
+    }
+
+    fn advance_running_active_time(
+        &self,
+        session_id: i64,
+        timestamp: DateTime<Utc>,
+        idle_timeout_minutes: u32,
+        idle_grace_minutes: u32,
+    ) -> Result<()> {
+        self.execute(
+            "UPDATE sessions SET
+                 active_seconds = COALESCE(active_seconds, 0) + (
+                     SELECT CASE WHEN gap <= ?2 THEN gap ELSE MIN(gap, ?3) END
+                     FROM (SELECT CAST(ROUND((julianday(?1) - julianday(last_heartbeat_at)) * 86400000) AS INTEGER)
+                                  / 1000 AS gap)
+                 ),
+                 last_heartbeat_at = ?1
+             WHERE id = ?4 AND status = 'active' AND julianday(last_heartbeat_at) < julianday(?1)",
+            params![
+                timestamp.to_rfc3339(),
+                idle_timeout_minutes as i64 * 60,
+                idle_grace_minutes as i64 * 60,
+                session_id
+            ],
+        )?;
         Ok(())
     }
 
-    /// Get commits for a session
-    pub fn get_commits(&self, session_id: i64) -> Result<Vec<Commit>> {
+    fn get_heartbeats(&self, session_id: i64) -> Result<Vec<Heartbeat>> {
+        let mut stmt = self.conn.prepare_cached(&format!(
+            "SELECT {} FROM heartbeats WHERE session_id = ? ORDER BY timestamp",
+            HEARTBEAT_COLUMNS
+        ))?;
+
+        let mut heartbeats = stmt
+            .query_map(params![session_id], row_to_heartbeat)?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let intervals = self.get_heartbeat_intervals(session_id)?;
+        if !intervals.is_empty() {
+            for (heartbeat, ended_at) in intervals {
+                let mut timestamp = heartbeat.timestamp;
+                while timestamp < ended_at {
+                    heartbeats.push(Heartbeat { timestamp, ..heartbeat.clone() });
+                    timestamp += chrono::Duration::seconds(INTERVAL_STEP_SECONDS);
+                }
+                heartbeats.push(Heartbeat { timestamp: ended_at, ..heartbeat });
+            }
+            heartbeats.sort_by_key(|h| h.timestamp);
+        }
+
+        Ok(heartbeats)
+    }
+
+    fn get_last_heartbeat(&self, session_id: i64) -> Result<Option<Heartbeat>> {
+        let last = self
+            .conn
+            .query_row(
+                &format!(
+                    "SELECT {} FROM heartbeats WHERE session_id = ? ORDER BY timestamp DESC LIMIT 1",
+                    HEARTBEAT_COLUMNS
+                ),
+                params![session_id],
+                row_to_heartbeat,
+            )
+            .optional()
+            .context("Failed to query last heartbeat")?;
+
+        // A reopened session may have compacted intervals after its last heartbeat row
+        let last_interval = self
+            .conn
+            .query_row(
+                &format!(
+                    "SELECT {} FROM heartbeat_intervals WHERE session_id = ? ORDER BY ended_at DESC LIMIT 1",
+                    INTERVAL_COLUMNS
+                ),
+                params![session_id],
+                row_to_interval,
+            )
+            .optional()
+            .context("Failed to query last heartbeat interval")?
+            .map(|(heartbeat, ended_at)| Heartbeat { timestamp: ended_at, ..heartbeat });
+
+        Ok(match (last, last_interval) {
+            (Some(a), Some(b)) => Some(if b.timestamp > a.timestamp { b } else { a }),
+            (a, b) => a.or(b),
+        })
+    }
+
+    fn get_compactable_sessions(&self, ended_before: DateTime<Utc>) -> Result<Vec<i64>> {
         let mut stmt = self.conn.prepare(
-            "SELECT id, session_id, hash, message, committed_at FROM commits
-             WHERE session_id = ? ORDER BY committed_at",
+            "SELECT id FROM sessions
+             WHERE status != ? AND ended_at < ?
+               AND EXISTS (SELECT 1 FROM heartbeats h WHERE h.session_id = sessions.id)
+             ORDER BY id",
         )?;
 
-        let commits = stmt
-            .query_map(params![session_id], |row| {
-                Ok(Commit {
-                    id: row.get(0)?,
-                    session_id: row.get(1)?,
-                    hash: row.get(2)?,
-                    message: row.get(3)?,
-                    committed_at: row
-                        .get::<_, Option<String>>(4)?
-                        .map(parse_datetime),
+        let ids = stmt
+            .query_map(
+                params![SessionStatus::Active.as_str(), ended_before.to_rfc3339()],
+                |row| row.get(0),
+            )?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(ids)
+    }
+
+    fn compact_heartbeats(&self, session_id: i64, max_gap_seconds: i64) -> Result<(usize, usize)> {
+        self.atomically(|| {
+            let rows = {
+                let mut stmt = self.conn.prepare(&format!(
+                    "SELECT {}, event_count FROM heartbeats WHERE session_id = ? ORDER BY timestamp",
+                    HEARTBEAT_COLUMNS
+                ))?;
+                let rows = stmt
+                    .query_map(params![session_id], |row| Ok((row_to_heartbeat(row)?, row.get::<_, i64>(10)?)))?
+                    .collect::<Result<Vec<_>, _>>()?;
+                rows
+            };
+            let runs = heartbeat_runs(rows, max_gap_seconds);
+
+            {
+                let mut insert = self.conn.prepare_cached(
+                    "INSERT INTO heartbeat_intervals (session_id, started_at, ended_at, file_path, branch,
+                                                      event_type, tool, cwd, model, agent, event_count)
+                     VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                )?;
+                for (first, ended_at, event_count) in &runs {
+                    insert.execute(params![
+                        session_id,
+                        first.timestamp.to_rfc3339(),
+                        ended_at.to_rfc3339(),
+                        first.file_path,
+                        first.branch,
+                        first.event_type,
+                        first.tool,
+                        first.cwd,
+                        first.model,
+                        first.agent,
+                        event_count
+                    ])?;
+                }
+            }
+            let removed = self.conn.execute("DELETE FROM heartbeats WHERE session_id = ?", params![session_id])?;
+
+            Ok((removed, runs.len()))
+        })
+    }
+
+    fn record_commits(&self, session_id: i64, commits: &[(String, String, Option<DateTime<Utc>>)]) -> Result<()> {
+        self.insert_commits(session_id, None, commits)
+    }
+
+    fn record_submodule_commits(
+        &self,
+        session_id: i64,
+        submodule: &str,
+        commits: &[(String, String, Option<DateTime<Utc>>)],
+    ) -> Result<()> {
+        self.insert_commits(session_id, Some(submodule), commits)
+    }
+
+    fn cache_commits(&self, project_id: i64, submodule: &str, commits: &[CommitEntry]) -> Result<()> {
+        self.atomically(|| {
+            let mut stmt = self.conn.prepare_cached(
+                "INSERT OR IGNORE INTO commit_cache (project_id, hash, submodule, message, committed_at)
+                 VALUES (?, ?, ?, ?, ?)",
+            )?;
+            for (hash, message, committed_at) in commits {
+                stmt.execute(params![
+                    project_id,
+                    hash,
+                    submodule,
+                    message,
+                    committed_at.map(|dt| dt.to_rfc3339())
+                ])?;
+            }
+            Ok(())
+        })
+    }
+
+    fn get_commit_diff_stats(&self, project_id: i64, submodule: &str, hash: &str) -> Result<Option<DiffStats>> {
+        let stats = self
+            .conn
+            .prepare_cached(
+                "SELECT files_changed, insertions, deletions FROM commit_cache
+                 WHERE project_id = ? AND submodule = ? AND hash = ? AND insertions IS NOT NULL",
+            )?
+            .query_row(params![project_id, submodule, hash], |row| {
+                Ok(DiffStats {
+                    files_changed: row.get(0)?,
+                    insertions: row.get(1)?,
+                    deletions: row.get(2)?,
                 })
+            })
+            .optional()?;
+        Ok(stats)
+    }
+
+    fn set_commit_diff_stats(&self, project_id: i64, submodule: &str, hash: &str, stats: &DiffStats) -> Result<()> {
+        self.execute(
+            "UPDATE commit_cache SET files_changed = ?, insertions = ?, deletions = ?
+             WHERE project_id = ? AND submodule = ? AND hash = ?",
+            params![stats.files_changed, stats.insertions, stats.deletions, project_id, submodule, hash],
+        )?;
+        Ok(())
+    }
+
+    fn latest_cached_commit(&self, project_id: i64, submodule: &str) -> Result<Option<String>> {
+        let hash = self
+            .conn
+            .prepare_cached(
+                "SELECT hash FROM commit_cache WHERE project_id = ? AND submodule = ?
+                 ORDER BY julianday(committed_at) DESC, rowid DESC LIMIT 1",
+            )?
+            .query_row(params![project_id, submodule], |row| row.get(0))
+            .optional()?;
+        Ok(hash)
+    }
+
+    fn get_cached_commits(
+        &self,
+        project_id: i64,
+        submodule: &str,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<CommitEntry>> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT hash, message, committed_at FROM commit_cache
+             WHERE project_id = ? AND submodule = ?
+               AND julianday(committed_at) BETWEEN julianday(?) AND julianday(?)
+             ORDER BY julianday(committed_at), rowid",
+        )?;
+        let commits = stmt
+            .query_map(params![project_id, submodule, since.to_rfc3339(), until.to_rfc3339()], |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+                    row.get::<_, Option<String>>(2)?.map(parse_datetime),
+                ))
             })?
             .collect::<Result<Vec<_>, _>>()?;
+        Ok(commits)
+    }
+
+    fn get_commits(&self, session_id: i64) -> Result<Vec<Commit>> {
+        let mut stmt = self
+            .conn
+            .prepare_cached(&format!("{} WHERE c.session_id = ? ORDER BY c.committed_at", COMMIT_ROWS))?;
+
+        let commits = stmt
+            .query_map(params![session_id], row_to_commit)?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(commits)
+    }
+}
+
+/// Turn free text into an FTS5 query: every word must match as a prefix
+///
+/// Words are quoted so input like `ABC-123` or `fix:` isn't parsed as FTS syntax.
+fn fts_query(query: &str) -> Option<String> {
+    let terms: Vec<String> = query
+        .split_whitespace()
+        .map(|term| format!("\"{}\"*", term.replace('"', "\"\"")))
+        .collect();
+
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" "))
+    }
+}
+
+/// Group heartbeats (with their event counts) into runs of the same context
+///
+/// Returns each run's first heartbeat, end time and total event count.
+fn heartbeat_runs(rows: Vec<(Heartbeat, i64)>, max_gap_seconds: i64) -> Vec<(Heartbeat, DateTime<Utc>, i64)> {
+    let same_context = |a: &Heartbeat, b: &Heartbeat| {
+        a.file_path == b.file_path
+            && a.branch == b.branch
+            && a.event_type == b.event_type
+            && a.tool == b.tool
+            && a.cwd == b.cwd
+            && a.model == b.model
+            && a.agent == b.agent
+    };
+
+    let mut runs: Vec<(Heartbeat, DateTime<Utc>, i64)> = Vec::new();
+    for (heartbeat, event_count) in rows {
+        if let Some((first, ended_at, count)) = runs.last_mut() {
+            let gap = (heartbeat.timestamp - *ended_at).num_seconds();
+            if gap <= max_gap_seconds && same_context(first, &heartbeat) {
+                *ended_at = heartbeat.timestamp;
+                *count += event_count;
+                continue;
+            }
+        }
+        let timestamp = heartbeat.timestamp;
+        runs.push((heartbeat, timestamp, event_count));
+    }
+    runs
+}
+
+/// Whether an error means another connection holds the lock
+fn is_locked(error: &rusqlite::Error) -> bool {
+    matches!(
+        error.sqlite_error_code(),
+        Some(ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked)
+    )
+}
+
+fn row_to_project(row: &rusqlite::Row) -> rusqlite::Result<Project> {
+    Ok(Project {
+        id: row.get(0)?,
+        path: row.get(1)?,
+        git_remote: row.get(2)?,
+        display_name: row.get(3)?,
+        work_item_pattern: row.get(4)?,
+        created_at: parse_datetime(row.get::<_, String>(5)?),
+        billable: row.get(6)?,
+        hourly_rate: row.get(7)?,
+        remote_urls: row
+            .get::<_, Option<String>>(8)?
+            .and_then(|urls| serde_json::from_str(&urls).ok())
+            .unwrap_or_default(),
+        remote_host: row.get(9)?,
+        remote_owner: row.get(10)?,
+        remote_repo: row.get(11)?,
+        uuid: row.get::<_, Option<String>>(12)?.unwrap_or_default(),
+        deleted_at: row.get::<_, Option<String>>(13)?.map(parse_datetime),
+        archived: row.get(14)?,
+        client: row.get(15)?,
+    })
+}
+
+fn row_to_rate(row: &rusqlite::Row) -> rusqlite::Result<ProjectRate> {
+    let effective_from: String = row.get(1)?;
+    Ok(ProjectRate {
+        project_id: row.get(0)?,
+        effective_from: NaiveDate::parse_from_str(&effective_from, "%Y-%m-%d").map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(1, rusqlite::types::Type::Text, Box::new(e))
+        })?,
+        hourly_rate: row.get(2)?,
+        currency: row.get(3)?,
+    })
+}
+
+fn row_to_heartbeat(row: &rusqlite::Row) -> rusqlite::Result<Heartbeat> {
+    Ok(Heartbeat {
+        id: row.get(0)?,
+        session_id: row.get(1)?,
+        timestamp: parse_datetime(row.get::<_, String>(2)?),
+        file_path: row.get(3)?,
+        branch: row.get(4)?,
+        event_type: row.get(5)?,
+        tool: row.get(6)?,
+        cwd: row.get(7)?,
+        model: row.get(8)?,
+        agent: row.get(9)?,
+    })
+}
+
+fn row_to_interval(row: &rusqlite::Row) -> rusqlite::Result<(Heartbeat, DateTime<Utc>)> {
+    let heartbeat = Heartbeat {
+        id: 0,
+        session_id: row.get(1)?,
+        timestamp: parse_datetime(row.get::<_, String>(2)?),
+        file_path: row.get(4)?,
+        branch: row.get(5)?,
+        event_type: row.get(6)?,
+        tool: row.get(7)?,
+        cwd: row.get(8)?,
+        model: row.get(9)?,
+        agent: row.get(10)?,
+    };
+    Ok((heartbeat, parse_datetime(row.get::<_, String>(3)?)))
+}
+
+fn row_to_commit(row: &rusqlite::Row) -> rusqlite::Result<Commit> {
+    Ok(Commit {
+        id: row.get(0)?,
+        session_id: row.get(1)?,
+        hash: row.get(2)?,
+        message: row.get(3)?,
+        committed_at: row.get::<_, Option<String>>(4)?.map(parse_datetime),
+        submodule: row.get(5)?,
+        diff_stats: match row.get::<_, Option<i64>>(7)? {
+            Some(insertions) => Some(DiffStats {
+                files_changed: row.get::<_, Option<i64>>(6)?.unwrap_or_default(),
+                insertions,
+                deletions: row.get::<_, Option<i64>>(8)?.unwrap_or_default(),
+            }),
+            None => None,
+        },
+    })
+}
+
+fn row_to_session_branch(row: &rusqlite::Row) -> rusqlite::Result<SessionBranch> {
+    Ok(SessionBranch {
+        session_id: row.get(0)?,
+        branch: row.get(1)?,
+        work_item: row.get(2)?,
+        active_seconds: row.get(3)?,
+    })
+}
+
+fn row_to_session(row: &rusqlite::Row) -> rusqlite::Result<Session> {
+    Ok(Session {
+        id: row.get(0)?,
+        project_id: row.get(1)?,
+        branch: row.get(2)?,
+        work_item: row.get(3)?,
+        start_commit: row.get(4)?,
+        end_commit: row.get(5)?,
+        started_at: parse_datetime(row.get::<_, String>(6)?),
+        ended_at: row.get::<_, Option<String>>(7)?.map(parse_datetime),
+        active_seconds: row.get(8)?,
+        status: SessionStatus::parse(&row.get::<_, String>(9)?).unwrap_or(SessionStatus::Active),
+        claude_session_id: row.get(10)?,
+        model: row.get(11)?,
+        machine: row.get(12)?,
+        diff_stats: diff_stats_at(row, 13)?,
+        pull_request: match (row.get(16)?, row.get(17)?) {
+            (Some(number), Some(url)) => Some(PullRequest {
+                number,
+                url,
+                title: row.get::<_, Option<String>>(18)?.unwrap_or_default(),
+            }),
+            _ => None,
+        },
+        uncommitted: diff_stats_at(row, 19)?,
+        uuid: row.get::<_, Option<String>>(22)?.unwrap_or_default(),
+        deleted_at: row.get::<_, Option<String>>(23)?.map(parse_datetime),
+        imported_from: row.get(24)?,
+        last_heartbeat_at: row.get::<_, Option<String>>(25)?.map(parse_datetime),
+    })
+}
+
+/// Read the diff stats stored in three consecutive columns starting at `index`
+fn diff_stats_at(row: &rusqlite::Row, index: usize) -> rusqlite::Result<Option<DiffStats>> {
+    Ok(match (row.get(index)?, row.get(index + 1)?, row.get(index + 2)?) {
+        (Some(files_changed), Some(insertions), Some(deletions)) => Some(DiffStats {
+            files_changed,
+            insertions,
+            deletions,
+        }),
+        _ => None,
+    })
+}
+
+fn parse_datetime(s: String) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(&s)
+        .map(|dt| dt.with_timezone(&Utc))
+        .unwrap_or_else(|_| Utc::now())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_database_creation() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+        let db = Database::open(&db_path).unwrap();
+
+        // Should be able to create a project
+        let project = db.get_or_create_project("/test/path", None, None, None).unwrap();
+        assert_eq!(project.path, "/test/path");
+    }
+
+    #[test]
+    fn test_uuids() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+        let db = Database::open(&db_path).unwrap();
+
+        let project = db.get_or_create_project("/test/path", None, None, None).unwrap();
+        let first = db.create_session(project.id, "main", None, None, None, None).unwrap();
+        let second = db.create_session(project.id, "main", None, None, None, None).unwrap();
+        db.record_heartbeat(first.id, &HeartbeatContext::default()).unwrap();
+
+        assert_eq!(project.uuid.len(), 36);
+        assert_eq!(&first.uuid[14..15], "4");
+        assert!("89ab".contains(&first.uuid[19..20]));
+        assert_ne!(first.uuid, second.uuid);
+
+        // Rows written before the column existed get an id on the next open
+        db.conn.execute("UPDATE sessions SET uuid = NULL WHERE id = ?", params![first.id]).unwrap();
+        db.conn.execute("UPDATE heartbeats SET uuid = NULL", []).unwrap();
+        drop(db);
+        let db = Database::open(&db_path).unwrap();
+        assert_eq!(db.get_session_by_id(first.id).unwrap().uuid.len(), 36);
+        let missing: i64 = db
+            .conn
+            .query_row("SELECT COUNT(*) FROM heartbeats WHERE uuid IS NULL", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(missing, 0);
+    }
+
+    #[test]
+    fn test_concurrent_writer() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+        let db = Database::open(&db_path).unwrap();
+        let journal_mode: String = db.conn.query_row("PRAGMA journal_mode", [], |row| row.get(0)).unwrap();
+        assert_eq!(journal_mode, "wal");
+
+        let project = db.get_or_create_project("/test/path", None, None, None).unwrap();
+        let session = db.create_session(project.id, "main", None, None, None, None).unwrap();
+
+        // Another process holds the write lock for a moment
+        let other = Connection::open(&db_path).unwrap();
+        other.execute_batch("BEGIN IMMEDIATE").unwrap();
+        let holder = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(300));
+            other.execute_batch("COMMIT").unwrap();
+        });
+
+        db.record_heartbeat(session.id, &HeartbeatContext::default()).unwrap();
+        holder.join().unwrap();
+        assert_eq!(db.get_heartbeats(session.id).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_record_heartbeats_batch() {
+        let dir = tempdir().unwrap();
+        let db = Database::open(&dir.path().join("test.db")).unwrap();
+        let project = db.get_or_create_project("/test/path", None, None, None).unwrap();
+        let session = db.create_session(project.id, "main", None, None, None, None).unwrap();
 
-        Ok(commits)
+        let start = Utc::now() - chrono::Duration::minutes(10);
+        let batch: Vec<_> = (0..3)
+            .map(|i| {
+                let context = HeartbeatContext {
+                    file_path: Some(format!("src/file{}.rs", i)),
+                    ..Default::default()
+                };
+                (start + chrono::Duration::minutes(i), context)
+            })
+            .collect();
+
+        let recorded = db.record_heartbeats(session.id, &batch).unwrap();
+        assert_eq!(recorded.len(), 3);
+        let stored = db.get_heartbeats(session.id).unwrap();
+        assert_eq!(stored.len(), 3);
+        assert_eq!(stored[2].file_path.as_deref(), Some("src/file2.rs"));
+        assert_eq!(stored[0].id, recorded[0].id);
     }
-}
 
-fn row_to_session(row: &rusqlite::Row) -> rusqlite::Result<Session> {
-    Ok(Session {
-        id: row.get(0)?,
-        project_id: row.get(1)?,
-        branch: row.get(2)?,
-        work_item: row.get(3)?,
-        start_commit: row.get(4)?,
-        end_commit: row.get(5)?,
-        started_at: parse_datetime(row.get::<_, String>(6)?),
-        ended_at: row.get::<_, Option<String>>(7)?.map(parse_datetime),
-        active_seconds: row.get(8)?,
-        status: SessionStatus::from_str(&row.get::<_, String>(9)?).unwrap_or(SessionStatus::Active),
-    })
-}
+    #[test]
+    fn test_compact_heartbeats() {
+        let dir = tempdir().unwrap();
+        let db = Database::open(&dir.path().join("test.db")).unwrap();
+        let project = db.get_or_create_project("/test/path", None, None, None).unwrap();
+        let session = db.create_session(project.id, "main", None, None, None, None).unwrap();
 
-fn parse_datetime(s: String) -> DateTime<Utc> {
-    DateTime::parse_from_rfc3339(&s)
-        .map(|dt| dt.with_timezone(&Utc))
-        .unwrap_or_else(|_| Utc::now())
-}
+        let start = Utc::now() - chrono::Duration::days(40);
+        let context = |event_type: &str, file: Option<&str>| HeartbeatContext {
+            event_type: Some(event_type.to_string()),
+            file_path: file.map(String::from),
+            ..Default::default()
+        };
+        // Minutes since start: a prompt, edits to one file, a long break, then another prompt
+        let batch = vec![
+            (start, context("prompt-submit", None)),
+            (start + chrono::Duration::minutes(1), context("prompt-submit", None)),
+            (start + chrono::Duration::minutes(3), context("tool-use", Some("src/a.rs"))),
+            (start + chrono::Duration::minutes(8), context("tool-use", Some("src/a.rs"))),
+            (start + chrono::Duration::minutes(12), context("tool-use", Some("src/a.rs"))),
+            (start + chrono::Duration::minutes(60), context("prompt-submit", None)),
+        ];
+        db.record_heartbeats(session.id, &batch).unwrap();
+        db.complete_session(session.id, None, 0, start + chrono::Duration::minutes(60), SessionStatus::Completed)
+            .unwrap();
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use tempfile::tempdir;
+        // Active time as the tracker counts it, with a 10 minute idle timeout
+        let active = |heartbeats: &[Heartbeat]| -> i64 {
+            heartbeats
+                .windows(2)
+                .map(|w| (w[1].timestamp - w[0].timestamp).num_seconds())
+                .filter(|gap| *gap <= 600)
+                .sum()
+        };
+        let before = db.get_heartbeats(session.id).unwrap();
+
+        assert_eq!(db.get_compactable_sessions(Utc::now()).unwrap(), vec![session.id]);
+        assert_eq!(db.compact_heartbeats(session.id, 600).unwrap(), (6, 3));
+        assert!(db.get_compactable_sessions(Utc::now()).unwrap().is_empty());
+
+        let after = db.get_heartbeats(session.id).unwrap();
+        assert_eq!(active(&after), active(&before));
+        assert_eq!(after.first().unwrap().timestamp, before.first().unwrap().timestamp);
+        assert_eq!(after.last().unwrap().timestamp, before.last().unwrap().timestamp);
+        assert_eq!(db.count_prompts(session.id).unwrap(), 3);
+        assert_eq!(
+            db.get_last_heartbeat(session.id).unwrap().unwrap().timestamp,
+            before.last().unwrap().timestamp
+        );
+    }
 
     #[test]
-    fn test_database_creation() {
+    fn test_range_queries_use_indexes() {
         let dir = tempdir().unwrap();
-        let db_path = dir.path().join("test.db");
-        let db = Database::open(&db_path).unwrap();
+        let db = Database::open(&dir.path().join("test.db")).unwrap();
 
-        // Should be able to create a project
+        let plan = |sql: &str| -> String {
+            let mut stmt = db.conn.prepare(&format!("EXPLAIN QUERY PLAN {}", sql)).unwrap();
+            let rows = stmt.query_map([], |row| row.get::<_, String>(3)).unwrap();
+            rows.map(|r| r.unwrap()).collect::<Vec<_>>().join("\n")
+        };
+
+        assert!(plan("SELECT id FROM sessions WHERE started_at >= '2025-01' AND started_at < '2025-02'")
+            .contains("idx_sessions_started_at"));
+        assert!(plan("SELECT id FROM sessions WHERE project_id = 1 AND started_at >= '2025-01'")
+            .contains("idx_sessions_project_started_at"));
+        assert!(plan("SELECT id FROM heartbeats WHERE session_id = 1 ORDER BY timestamp")
+            .contains("idx_heartbeats_session_timestamp"));
+        let report = REPORT_SESSIONS_FILTER.replace("?1", "'2025-01'").replace("?2", "'2025-02'");
+        assert!(plan(&format!("SELECT id FROM sessions WHERE {} ORDER BY +started_at", report))
+            .contains("idx_sessions_report_end"));
+    }
+
+    /// A database with a project and one finished session
+    fn finished_session() -> (tempfile::TempDir, Database, Session) {
+        let dir = tempdir().unwrap();
+        let db = Database::open(&dir.path().join("test.db")).unwrap();
         let project = db.get_or_create_project("/test/path", None, None, None).unwrap();
-        assert_eq!(project.path, "/test/path");
+        let session = db.create_session(project.id, "main", None, None, None, None).unwrap();
+        db.complete_session(session.id, None, 3600, Utc::now(), SessionStatus::Completed).unwrap();
+        (dir, db, session)
     }
 
     #[test]
@@ -463,21 +2869,660 @@ mod tests {
         let project = db.get_or_create_project("/test/path", None, None, None).unwrap();
 
         // Create session
-        let session = db.create_session(project.id, "main", None, None).unwrap();
+        let session = db.create_session(project.id, "main", None, None, None, Some("laptop")).unwrap();
         assert_eq!(session.status, SessionStatus::Active);
+        assert_eq!(session.machine.as_deref(), Some("laptop"));
 
         // Record heartbeats
-        db.record_heartbeat(session.id).unwrap();
-        db.record_heartbeat(session.id).unwrap();
+        db.record_heartbeat(session.id, &HeartbeatContext::default()).unwrap();
+        let context = HeartbeatContext {
+            file_path: Some("/test/path/src/main.rs".to_string()),
+            branch: Some("main".to_string()),
+            event_type: Some("tool-use".to_string()),
+            tool: Some("Edit".to_string()),
+            cwd: Some("/test/path".to_string()),
+            ..Default::default()
+        };
+        db.record_heartbeat(session.id, &context).unwrap();
+
+        // Repeats within the window are folded into the previous heartbeat
+        assert!(db.record_heartbeat_coalesced(session.id, Utc::now(), &context, 30).unwrap().is_none());
+        let other_file = HeartbeatContext {
+            file_path: Some("/test/path/src/db.rs".to_string()),
+            ..context.clone()
+        };
+        assert!(db.record_heartbeat_coalesced(session.id, Utc::now(), &other_file, 30).unwrap().is_some());
+        assert!(db.record_heartbeat_coalesced(session.id, Utc::now(), &context, 0).unwrap().is_some());
 
         let heartbeats = db.get_heartbeats(session.id).unwrap();
-        assert_eq!(heartbeats.len(), 2);
+        assert_eq!(heartbeats.len(), 4);
+
+        let prompt = HeartbeatContext {
+            event_type: Some("prompt-submit".to_string()),
+            ..Default::default()
+        };
+        db.record_heartbeat_coalesced(session.id, Utc::now(), &prompt, 30).unwrap();
+        db.record_heartbeat_coalesced(session.id, Utc::now(), &prompt, 30).unwrap();
+        assert_eq!(db.count_prompts(session.id).unwrap(), 2);
+        assert_eq!(heartbeats[1].file_path.as_deref(), Some("/test/path/src/main.rs"));
+        assert_eq!(heartbeats[1].branch.as_deref(), Some("main"));
+        assert_eq!(heartbeats[1].tool.as_deref(), Some("Edit"));
+
+        // Branch split
+        let split = vec![
+            ("main".to_string(), None, 1200),
+            ("feature/ABC-1".to_string(), Some("ABC-1".to_string()), 2400),
+        ];
+        db.record_session_branches(session.id, &split).unwrap();
+        db.record_session_branches(session.id, &split).unwrap();
+        let branches = db.get_session_branches(session.id).unwrap();
+        assert_eq!(branches.len(), 2);
+        assert_eq!(branches[1].work_item.as_deref(), Some("ABC-1"));
 
         // Complete session
-        db.complete_session(session.id, None, 3600, SessionStatus::Completed).unwrap();
+        db.complete_session(session.id, None, 3600, Utc::now(), SessionStatus::Completed).unwrap();
 
         let completed = db.get_session_by_id(session.id).unwrap();
         assert_eq!(completed.status, SessionStatus::Completed);
         assert_eq!(completed.active_seconds, Some(3600));
     }
+
+    #[test]
+    fn test_session_work_item() {
+        let (_dir, db, session) = finished_session();
+
+        db.set_session_work_item(session.id, Some("ABC-1")).unwrap();
+        assert_eq!(db.get_session_by_id(session.id).unwrap().work_item.as_deref(), Some("ABC-1"));
+        db.set_session_work_item(session.id, None).unwrap();
+        assert!(db.get_session_by_id(session.id).unwrap().work_item.is_none());
+        assert!(db.set_session_work_item(9999, None).is_err());
+    }
+
+    #[test]
+    fn test_session_model() {
+        let (_dir, db, session) = finished_session();
+
+        let context = HeartbeatContext {
+            model: Some("claude-opus-4".to_string()),
+            agent: Some("code-reviewer".to_string()),
+            ..Default::default()
+        };
+        db.record_heartbeat(session.id, &context).unwrap();
+        let heartbeat = db.get_last_heartbeat(session.id).unwrap().unwrap();
+        assert_eq!(heartbeat.model.as_deref(), Some("claude-opus-4"));
+        assert_eq!(heartbeat.agent.as_deref(), Some("code-reviewer"));
+
+        db.set_session_model(session.id, "claude-opus-4").unwrap();
+        assert_eq!(db.get_session_by_id(session.id).unwrap().model.as_deref(), Some("claude-opus-4"));
+    }
+
+    #[test]
+    fn test_session_activity() {
+        let (_dir, db, session) = finished_session();
+
+        // Recording again replaces the split
+        let activity = vec![("prompting".to_string(), 600), ("tool".to_string(), 3000)];
+        db.record_session_activity(session.id, &activity).unwrap();
+        db.record_session_activity(session.id, &activity).unwrap();
+        assert_eq!(db.get_session_activity(session.id).unwrap(), activity);
+    }
+
+    #[test]
+    fn test_session_diff_stats() {
+        let (_dir, db, session) = finished_session();
+        assert!(db.get_session_by_id(session.id).unwrap().diff_stats.is_none());
+
+        let stats = DiffStats {
+            files_changed: 3,
+            insertions: 120,
+            deletions: 7,
+        };
+        db.set_session_diff_stats(session.id, &stats).unwrap();
+        assert_eq!(db.get_session_by_id(session.id).unwrap().diff_stats, Some(stats));
+    }
+
+    #[test]
+    fn test_session_pull_request() {
+        let (_dir, db, session) = finished_session();
+        assert!(db.get_session_by_id(session.id).unwrap().pull_request.is_none());
+
+        let pull_request = PullRequest {
+            number: 42,
+            url: "https://github.com/acme/api/pull/42".to_string(),
+            title: "Add login".to_string(),
+        };
+        db.set_session_pull_request(session.id, &pull_request).unwrap();
+        assert_eq!(db.get_session_by_id(session.id).unwrap().pull_request, Some(pull_request));
+    }
+
+    #[test]
+    fn test_session_uncommitted() {
+        let (_dir, db, session) = finished_session();
+        assert!(db.get_session_by_id(session.id).unwrap().uncommitted.is_none());
+
+        let uncommitted = DiffStats {
+            files_changed: 1,
+            insertions: 5,
+            deletions: 0,
+        };
+        db.set_session_uncommitted(session.id, &uncommitted).unwrap();
+        let session = db.get_session_by_id(session.id).unwrap();
+        assert_eq!(session.uncommitted, Some(uncommitted));
+        // Kept apart from the committed change
+        assert!(session.diff_stats.is_none());
+    }
+
+    #[test]
+    fn test_concurrent_sessions() {
+        let dir = tempdir().unwrap();
+        let db = Database::open(&dir.path().join("test.db")).unwrap();
+        let project = db.get_or_create_project("/test/path", None, None, None).unwrap();
+
+        let first = db.create_session(project.id, "main", None, None, Some("window-1"), None).unwrap();
+        let second = db.create_session(project.id, "main", None, None, Some("window-2"), None).unwrap();
+
+        let active = |id| db.get_active_session(project.id, id).unwrap().map(|s| s.id);
+        assert_eq!(active(Some("window-1")), Some(first.id));
+        assert_eq!(active(Some("window-2")), Some(second.id));
+        assert_eq!(active(Some("window-3")), None);
+
+        // Stopping one window leaves the other running
+        db.complete_session(first.id, None, 60, Utc::now(), SessionStatus::Completed).unwrap();
+        assert_eq!(active(Some("window-1")), None);
+        assert_eq!(active(None), Some(second.id));
+
+        // Hooks without an id fall back to sessions started without one
+        let legacy = db.create_session(project.id, "main", None, None, None, None).unwrap();
+        assert_eq!(active(Some("window-3")), Some(legacy.id));
+        assert_eq!(active(Some("window-2")), Some(second.id));
+    }
+
+    #[test]
+    fn test_resume_session() {
+        let dir = tempdir().unwrap();
+        let db = Database::open(&dir.path().join("test.db")).unwrap();
+        let project = db.get_or_create_project("/test/path", None, None, None).unwrap();
+
+        let session = db.create_session(project.id, "main", None, Some("abc"), Some("old"), None).unwrap();
+        db.record_commits(session.id, &[("abc123".to_string(), "Fix".to_string(), None)]).unwrap();
+        db.complete_session(session.id, None, 600, Utc::now(), SessionStatus::Completed).unwrap();
+        db.set_session_diff_stats(session.id, &DiffStats::default()).unwrap();
+
+        let since = Utc::now() - chrono::Duration::minutes(5);
+        assert!(db.get_resumable_session(project.id, "other", since).unwrap().is_none());
+        let later = Utc::now() + chrono::Duration::minutes(5);
+        assert!(db.get_resumable_session(project.id, "main", later).unwrap().is_none());
+
+        let found = db.get_resumable_session(project.id, "main", since).unwrap().unwrap();
+        assert_eq!(found.id, session.id);
+
+        db.reopen_session(session.id, Some("new")).unwrap();
+        let active = db.get_active_session(project.id, Some("new")).unwrap().unwrap();
+        assert_eq!(active.id, session.id);
+        assert!(active.ended_at.is_none());
+        assert!(db.get_commits(session.id).unwrap().is_empty());
+        assert!(active.diff_stats.is_none());
+    }
+
+    #[test]
+    fn test_meta() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+        let db = Database::open(&db_path).unwrap();
+
+        assert_eq!(db.get_meta("cron.last_period").unwrap(), None);
+        db.set_meta("cron.last_period", "2025-01").unwrap();
+        db.set_meta("cron.last_period", "2025-02").unwrap();
+        assert_eq!(db.get_meta("cron.last_period").unwrap().as_deref(), Some("2025-02"));
+    }
+
+    #[test]
+    fn test_update_project() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+        let db = Database::open(&db_path).unwrap();
+
+        let mut project = db.get_or_create_project("/test/path", None, None, None).unwrap();
+        assert!(project.billable);
+        assert_eq!(project.hourly_rate, None);
+
+        project.work_item_pattern = Some(r"([A-Z]+-\d+)".to_string());
+        project.billable = false;
+        project.hourly_rate = Some(95.0);
+        db.update_project(&project).unwrap();
+
+        let updated = db.get_project_by_id(project.id).unwrap();
+        assert_eq!(updated.work_item_pattern.as_deref(), Some(r"([A-Z]+-\d+)"));
+        assert!(!updated.billable);
+        assert_eq!(updated.hourly_rate, Some(95.0));
+    }
+
+    #[test]
+    fn test_project_rates() {
+        let dir = tempdir().unwrap();
+        let db = Database::open(&dir.path().join("test.db")).unwrap();
+
+        let project = db.get_or_create_project("/test/path", None, None, None).unwrap();
+        let date = |s: &str| NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
+
+        db.set_project_rate(project.id, date("2025-01-01"), 90.0, Some("EUR")).unwrap();
+        db.set_project_rate(project.id, date("2025-07-01"), 100.0, Some("EUR")).unwrap();
+        // Setting the same day again replaces the rate
+        db.set_project_rate(project.id, date("2025-07-01"), 110.0, Some("EUR")).unwrap();
+
+        assert_eq!(db.get_project_rates(project.id).unwrap().len(), 2);
+        assert!(db.get_project_rate_at(project.id, date("2024-12-31")).unwrap().is_none());
+        assert_eq!(db.get_project_rate_at(project.id, date("2025-06-30")).unwrap().unwrap().hourly_rate, 90.0);
+        let current = db.get_project_rate_at(project.id, date("2025-07-01")).unwrap().unwrap();
+        assert_eq!(current.hourly_rate, 110.0);
+        assert_eq!(current.currency.as_deref(), Some("EUR"));
+
+        assert!(db.delete_project_rate(project.id, date("2025-07-01")).unwrap());
+        assert!(!db.delete_project_rate(project.id, date("2025-07-01")).unwrap());
+        assert_eq!(db.get_project_rate_at(project.id, date("2025-12-01")).unwrap().unwrap().hourly_rate, 90.0);
+    }
+
+    #[test]
+    fn test_project_stats() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+        let db = Database::open(&db_path).unwrap();
+
+        let project = db.get_or_create_project("/test/path", None, None, None).unwrap();
+
+        let empty = db.get_project_stats(project.id, 3).unwrap();
+        assert_eq!(empty.session_count, 0);
+        assert!(empty.first_session.is_none());
+
+        for (branch, work_item, seconds) in [
+            ("feature/ABC-1", Some("ABC-1"), 600),
+            ("feature/ABC-1-more", Some("ABC-1"), 1200),
+            ("main", None, 300),
+        ] {
+            let session = db.create_session(project.id, branch, work_item, None, None, None).unwrap();
+            db.complete_session(session.id, None, seconds, Utc::now(), SessionStatus::Completed).unwrap();
+        }
+
+        let stats = db.get_project_stats(project.id, 3).unwrap();
+        assert_eq!(stats.session_count, 3);
+        assert_eq!(stats.total_seconds, 2100);
+        assert!(stats.first_session.is_some());
+        assert_eq!(
+            stats.top_work_items,
+            vec![("ABC-1".to_string(), 1800), ("main".to_string(), 300)]
+        );
+    }
+
+    #[test]
+    fn test_search_sessions() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+        let db = Database::open(&db_path).unwrap();
+
+        let project = db.get_or_create_project("/test/path", None, None, None).unwrap();
+        let oauth = db.create_session(project.id, "feature/ABC-123-oauth", Some("ABC-123"), None, None, None).unwrap();
+        let commits = vec![("abc".to_string(), "Refactor OAuth token refresh".to_string(), None)];
+        db.record_commits(oauth.id, &commits).unwrap();
+        let main = db.create_session(project.id, "main", None, None, None, None).unwrap();
+
+        let hits = db.search_sessions("oauth refactor", 10).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].0.id, oauth.id);
+
+        assert_eq!(db.search_sessions("ABC-123", 10).unwrap().len(), 1);
+        assert_eq!(db.search_sessions("main", 10).unwrap().len(), 1);
+        assert!(db.search_sessions("billing", 10).unwrap().is_empty());
+        assert!(db.search_sessions("  ", 10).unwrap().is_empty());
+
+        // The index follows later edits and deletions
+        db.set_session_work_item(main.id, Some("XYZ-9")).unwrap();
+        assert_eq!(db.search_sessions("XYZ-9", 10).unwrap()[0].0.id, main.id);
+        db.delete_session(oauth.id).unwrap();
+        assert!(db.search_sessions("oauth", 10).unwrap().is_empty());
+        db.undelete_session(oauth.id).unwrap();
+        assert_eq!(db.search_sessions("oauth", 10).unwrap().len(), 1);
+        db.purge_session(oauth.id).unwrap();
+        assert!(db.search_sessions("oauth", 10).unwrap().is_empty());
+
+        // Indexes built before the triggers existed are rebuilt on open
+        db.conn.execute_batch("DROP TRIGGER search_session_insert; DELETE FROM session_search;").unwrap();
+        drop(db);
+        let db = Database::open(&db_path).unwrap();
+        assert_eq!(db.search_sessions("XYZ-9", 10).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_notes() {
+        let dir = tempdir().unwrap();
+        let db = Database::open(&dir.path().join("test.db")).unwrap();
+
+        let project = db.get_or_create_project("/test/path", None, None, None).unwrap();
+        let session = db.create_session(project.id, "main", None, None, None, None).unwrap();
+
+        let first = db.add_note(session.id, "Paired with Dana on the billing export").unwrap();
+        db.add_note(session.id, "Blocked on API keys").unwrap();
+        let notes = db.get_notes(session.id).unwrap();
+        assert_eq!(notes.len(), 2);
+        assert_eq!(notes[0].text, "Paired with Dana on the billing export");
+
+        assert_eq!(db.search_sessions("billing", 10).unwrap().len(), 1);
+
+        assert!(db.delete_note(first.id).unwrap());
+        assert!(!db.delete_note(first.id).unwrap());
+        assert!(db.search_sessions("billing", 10).unwrap().is_empty());
+        assert_eq!(db.get_notes(session.id).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_trash() {
+        let dir = tempdir().unwrap();
+        let db = Database::open(&dir.path().join("test.db")).unwrap();
+
+        let project = db.get_or_create_project("/test/path", None, None, None).unwrap();
+        let kept = db.create_session(project.id, "main", None, None, None, None).unwrap();
+        let trashed = db.create_session(project.id, "fix", None, None, None, None).unwrap();
+        db.record_heartbeat(trashed.id, &HeartbeatContext::default()).unwrap();
+
+        assert!(db.delete_session(trashed.id).unwrap());
+        assert!(!db.delete_session(trashed.id).unwrap());
+        assert_eq!(db.get_recent_sessions(10).unwrap().len(), 1);
+        assert_eq!(db.get_deleted_sessions().unwrap().len(), 1);
+
+        // Restoring the project brings back only the sessions trashed with it
+        assert!(db.delete_project(project.id).unwrap());
+        assert!(db.list_projects(true).unwrap().is_empty());
+        assert!(db.get_recent_sessions(10).unwrap().is_empty());
+        assert!(db.undelete_project(project.id).unwrap());
+        let sessions = db.get_recent_sessions(10).unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].id, kept.id);
+
+        assert!(db.undelete_session(trashed.id).unwrap());
+        assert_eq!(db.get_recent_sessions(10).unwrap().len(), 2);
+
+        db.purge_session(trashed.id).unwrap();
+        assert!(db.get_session_by_id(trashed.id).is_err());
+        assert!(db.get_heartbeats(trashed.id).unwrap().is_empty());
+        db.purge_project(project.id).unwrap();
+        assert!(db.get_project_by_path("/test/path").unwrap().is_none());
+        assert!(db.get_session_by_id(kept.id).is_err());
+    }
+
+    #[test]
+    fn test_archive_project() {
+        let dir = tempdir().unwrap();
+        let db = Database::open(&dir.path().join("test.db")).unwrap();
+
+        let project = db.get_or_create_project("/test/path", None, None, None).unwrap();
+        db.get_or_create_project("/test/other", None, None, None).unwrap();
+
+        assert!(db.set_project_archived(project.id, true).unwrap());
+        assert!(!db.set_project_archived(project.id, true).unwrap());
+        assert!(db.get_project_by_id(project.id).unwrap().archived);
+        assert_eq!(db.list_projects(false).unwrap().len(), 1);
+        assert_eq!(db.list_projects(true).unwrap().len(), 2);
+
+        assert!(db.set_project_archived(project.id, false).unwrap());
+        assert_eq!(db.list_projects(false).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_issue_titles() {
+        let dir = tempdir().unwrap();
+        let db = Database::open(&dir.path().join("test.db")).unwrap();
+
+        let ttl = chrono::Duration::hours(1);
+        assert_eq!(db.get_issue_title("github.com/acme/api", 12, ttl).unwrap(), None);
+        db.set_issue_title("github.com/acme/api", 12, Some("Login fails")).unwrap();
+        db.set_issue_title("github.com/acme/api", 12, Some("Login fails on Safari")).unwrap();
+        assert_eq!(
+            db.get_issue_title("github.com/acme/api", 12, ttl).unwrap(),
+            Some(Some("Login fails on Safari".to_string()))
+        );
+        assert_eq!(db.get_issue_title("github.com/acme/web", 12, ttl).unwrap(), None);
+
+        // A missing issue is remembered until the TTL runs out
+        db.set_issue_title("github.com/acme/api", 13, None).unwrap();
+        assert_eq!(db.get_issue_title("github.com/acme/api", 13, ttl).unwrap(), Some(None));
+        assert_eq!(db.get_issue_title("github.com/acme/api", 13, chrono::Duration::zero()).unwrap(), None);
+    }
+
+    #[test]
+    fn test_running_time_is_not_a_change() {
+        let dir = tempdir().unwrap();
+        let db = Database::open(&dir.path().join("test.db")).unwrap();
+        let project = db.get_or_create_project("/test/path", None, None, None).unwrap();
+        let session = db.create_session(project.id, "main", None, None, None, None).unwrap();
+        let updated_at = |db: &Database| -> Option<String> {
+            db.conn
+                .query_row("SELECT updated_at FROM sessions WHERE id = ?", params![session.id], |row| row.get(0))
+                .unwrap()
+        };
+
+        db.conn.execute("UPDATE sessions SET updated_at = NULL", []).unwrap();
+        db.set_running_active_time(session.id, 120, Utc::now()).unwrap();
+        assert_eq!(updated_at(&db), None);
+
+        db.set_session_model(session.id, "claude-sonnet").unwrap();
+        assert!(updated_at(&db).is_some());
+
+        // Once finished, its active time is shared like the rest of it
+        db.complete_session(session.id, None, 120, Utc::now(), SessionStatus::Completed).unwrap();
+        db.conn.execute("UPDATE sessions SET updated_at = NULL", []).unwrap();
+        db.conn.execute("UPDATE sessions SET active_seconds = 90", []).unwrap();
+        assert!(updated_at(&db).is_some());
+    }
+
+    #[test]
+    fn test_unsynced_sessions() {
+        let dir = tempdir().unwrap();
+        let db = Database::open(&dir.path().join("test.db")).unwrap();
+
+        let project = db.get_or_create_project("/test/path", None, None, None).unwrap();
+        let first = db.create_session(project.id, "main", None, None, None, None).unwrap();
+        db.complete_session(first.id, None, 600, Utc::now(), SessionStatus::Completed).unwrap();
+        let second = db.create_session(project.id, "main", None, None, None, None).unwrap();
+        db.complete_session(second.id, None, 300, Utc::now(), SessionStatus::Completed).unwrap();
+        db.create_session(project.id, "main", None, None, None, None).unwrap();
+
+        let unsynced = db.get_unsynced_sessions("toggl", None).unwrap();
+        assert_eq!(unsynced.iter().map(|s| s.id).collect::<Vec<_>>(), vec![first.id, second.id]);
+
+        db.mark_session_synced("toggl", &first.uuid, "4001").unwrap();
+        let unsynced = db.get_unsynced_sessions("toggl", None).unwrap();
+        assert_eq!(unsynced.iter().map(|s| s.id).collect::<Vec<_>>(), vec![second.id]);
+        assert_eq!(db.get_unsynced_sessions("clockify", None).unwrap().len(), 2);
+        assert!(db.get_unsynced_sessions("toggl", Some(Utc::now() + chrono::Duration::hours(1))).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_window_activity() {
+        let dir = tempdir().unwrap();
+        let db = Database::open(&dir.path().join("test.db")).unwrap();
+
+        let start: DateTime<Utc> = "2025-01-15T09:00:00Z".parse().unwrap();
+        let event = |minutes: i64| WindowEvent {
+            bucket: "aw-watcher-window_laptop".to_string(),
+            timestamp: start + chrono::Duration::minutes(minutes),
+            duration_seconds: 30.5,
+            app: Some("Terminal".to_string()),
+            title: None,
+        };
+        assert_eq!(db.record_window_activity(&[event(0), event(5)]).unwrap(), 2);
+        // Importing the same export again adds only what is new
+        assert_eq!(db.record_window_activity(&[event(5), event(10)]).unwrap(), 1);
+
+        let stored = db.get_window_activity(start, start + chrono::Duration::minutes(10)).unwrap();
+        assert_eq!(stored, vec![event(0), event(5)]);
+    }
+
+    #[test]
+    fn test_atomically() {
+        let dir = tempdir().unwrap();
+        let db = Database::open(&dir.path().join("test.db")).unwrap();
+
+        let project = db.get_or_create_project("/test/path", None, None, None).unwrap();
+        let session = db.create_session(project.id, "main", None, None, Some("abc"), None).unwrap();
+
+        // A failure undoes everything written before it, including nested work
+        let result: Result<()> = db.atomically(|| {
+            db.record_commits(session.id, &[("aaa".to_string(), "First".to_string(), None)])?;
+            db.atomically(|| db.complete_session(session.id, None, 60, Utc::now(), SessionStatus::Completed))?;
+            anyhow::bail!("stop failed")
+        });
+        assert!(result.is_err());
+        assert!(db.get_commits(session.id).unwrap().is_empty());
+        assert_eq!(db.get_session_by_id(session.id).unwrap().status, SessionStatus::Active);
+
+        // An inner failure only undoes the inner writes
+        db.atomically(|| {
+            db.record_commits(session.id, &[("aaa".to_string(), "First".to_string(), None)])?;
+            let _ = db.atomically(|| -> Result<()> {
+                db.complete_session(session.id, None, 60, Utc::now(), SessionStatus::Completed)?;
+                anyhow::bail!("inner failed")
+            });
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(db.get_commits(session.id).unwrap().len(), 1);
+        assert_eq!(db.get_session_by_id(session.id).unwrap().status, SessionStatus::Active);
+
+        db.complete_session(session.id, None, 60, Utc::now(), SessionStatus::Completed).unwrap();
+        assert!(db.get_active_session(project.id, Some("abc")).unwrap().is_none());
+        let latest = db.get_latest_session(project.id, "abc").unwrap().unwrap();
+        assert_eq!((latest.id, latest.status), (session.id, SessionStatus::Completed));
+        assert!(db.get_latest_session(project.id, "other").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_move_project() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+        let db = Database::open(&db_path).unwrap();
+
+        let project = db.get_or_create_project("/old/path", None, Some("Old"), None).unwrap();
+        db.create_session(project.id, "main", None, None, None, None).unwrap();
+
+        // A project accidentally created at the new location gets folded in
+        let stray = db.get_or_create_project("/new/path", None, None, None).unwrap();
+        db.create_session(stray.id, "main", None, None, None, None).unwrap();
+
+        let moved = db.move_project("/old/path", "/new/path", true).unwrap();
+        assert_eq!(moved.id, project.id);
+        assert_eq!(moved.path, "/new/path");
+        assert_eq!(moved.display_name.as_deref(), Some("Old"));
+        assert_eq!(db.get_project_by_path("/old/path").unwrap().unwrap().id, project.id);
+        assert_eq!(db.get_project_aliases(project.id).unwrap(), vec!["/old/path".to_string()]);
+        assert_eq!(db.list_projects(false).unwrap().len(), 1);
+
+        let sessions = db.get_all_active_sessions().unwrap();
+        assert_eq!(sessions.len(), 2);
+        assert!(sessions.iter().all(|s| s.project_id == project.id));
+    }
+
+    #[test]
+    fn test_project_alias() {
+        let dir = tempdir().unwrap();
+        let db = Database::open(&dir.path().join("test.db")).unwrap();
+
+        let project = db.get_or_create_project("/src/api", None, None, None).unwrap();
+        assert!(db.get_project_by_path("/src/api-worktree").unwrap().is_none());
+
+        db.add_project_alias(project.id, "/src/api-worktree").unwrap();
+        db.add_project_alias(project.id, "/src/api-worktree").unwrap();
+        let found = db.get_project_by_path("/src/api-worktree").unwrap().unwrap();
+        assert_eq!((found.id, found.path.as_str()), (project.id, "/src/api"));
+        assert_eq!(db.get_project_aliases(project.id).unwrap().len(), 1);
+
+        // Hooks fired from an alias keep adding to the same project
+        let again = db.get_or_create_project("/src/api-worktree", None, None, None).unwrap();
+        assert_eq!(again.id, project.id);
+        assert_eq!(db.list_projects(false).unwrap().len(), 1);
+
+        // A path that is a project of its own wins over an alias pointing elsewhere
+        let other = db.get_or_create_project("/src/web", None, None, None).unwrap();
+        db.add_project_alias(project.id, "/src/web").unwrap();
+        assert_eq!(db.get_project_by_path("/src/web").unwrap().unwrap().id, other.id);
+    }
+
+    #[test]
+    fn test_project_remotes() {
+        let dir = tempdir().unwrap();
+        let db = Database::open(&dir.path().join("test.db")).unwrap();
+
+        let project = db.get_or_create_project("/src/api", None, None, None).unwrap();
+        assert!(project.remote_urls.is_empty());
+
+        let urls = vec![
+            "git@github.com:acme/api.git".to_string(),
+            "git@github.com:me/api.git".to_string(),
+        ];
+        db.set_project_remotes(project.id, &urls).unwrap();
+        assert_eq!(db.get_project_by_id(project.id).unwrap().remote_urls, urls);
+    }
+
+    #[test]
+    fn test_project_repository() {
+        let dir = tempdir().unwrap();
+        let db = Database::open(&dir.path().join("test.db")).unwrap();
+
+        let project = db.get_or_create_project("/src/api", None, None, None).unwrap();
+        assert_eq!(project.repository(), None);
+        assert_eq!(project.name(), "/src/api");
+
+        let repository = RemoteRepo {
+            host: "github.com".to_string(),
+            owner: "acme".to_string(),
+            repo: "api".to_string(),
+        };
+        db.set_project_repository(project.id, &repository).unwrap();
+        let project = db.get_project_by_id(project.id).unwrap();
+        assert_eq!(project.repository(), Some(repository));
+        assert_eq!(project.name(), "acme/api");
+    }
+
+    #[test]
+    fn test_commit_cache() {
+        let dir = tempdir().unwrap();
+        let db = Database::open(&dir.path().join("test.db")).unwrap();
+        let project = db.get_or_create_project("/src/app", None, None, None).unwrap();
+        let at = |hour: u32| Some(parse_datetime(format!("2025-01-10T{:02}:00:00Z", hour)));
+
+        assert_eq!(db.latest_cached_commit(project.id, "vendor/lib").unwrap(), None);
+        let commits = vec![
+            ("aaa".to_string(), "First".to_string(), at(9)),
+            ("bbb".to_string(), "Second".to_string(), at(11)),
+        ];
+        db.cache_commits(project.id, "vendor/lib", &commits).unwrap();
+        db.cache_commits(project.id, "vendor/lib", &commits[1..]).unwrap();
+        assert_eq!(db.latest_cached_commit(project.id, "vendor/lib").unwrap().as_deref(), Some("bbb"));
+        assert_eq!(db.latest_cached_commit(project.id, "vendor/other").unwrap(), None);
+
+        let since = at(10).unwrap();
+        let found = db.get_cached_commits(project.id, "vendor/lib", since, since + chrono::Duration::hours(2)).unwrap();
+        assert_eq!(found, commits[1..]);
+        let found = db.get_cached_commits(project.id, "vendor/lib", at(8).unwrap(), since).unwrap();
+        assert_eq!(found, commits[..1]);
+
+        // Another submodule can hold the same commit
+        db.cache_commits(project.id, "vendor/copy", &commits[..1]).unwrap();
+        assert_eq!(db.latest_cached_commit(project.id, "vendor/copy").unwrap().as_deref(), Some("aaa"));
+
+        // What a commit changed is kept per submodule and shows up on session commits
+        let stats = DiffStats {
+            files_changed: 2,
+            insertions: 10,
+            deletions: 3,
+        };
+        assert_eq!(db.get_commit_diff_stats(project.id, "vendor/lib", "aaa").unwrap(), None);
+        db.set_commit_diff_stats(project.id, "vendor/lib", "aaa", &stats).unwrap();
+        assert_eq!(db.get_commit_diff_stats(project.id, "vendor/lib", "aaa").unwrap(), Some(stats));
+        assert_eq!(db.get_commit_diff_stats(project.id, "vendor/copy", "aaa").unwrap(), None);
+
+        let session = db.create_session(project.id, "main", None, None, None, None).unwrap();
+        db.record_submodule_commits(session.id, "vendor/lib", &commits).unwrap();
+        let recorded = db.get_commits(session.id).unwrap();
+        assert_eq!(recorded[0].diff_stats, Some(stats));
+        assert_eq!(recorded[1].diff_stats, None);
+    }
 }
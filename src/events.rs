@@ -0,0 +1,383 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use tracing::warn;
+
+use crate::config::EffectiveConfig;
+use crate::db::Database;
+use crate::models::{Commit, HeartbeatContext, Project, ProjectRate, Session, SessionBranch};
+use crate::storage::Storage;
+
+/// Event log file, kept next to the database
+pub const EVENT_LOG_FILE_NAME: &str = "events.jsonl";
+
+/// One line of the append-only event log
+///
+/// Events carry the state the tracker wrote, not the hook input, so replaying
+/// them needs neither the repositories nor the configuration of the time.
+/// Besides tracking, every change made to this database's sessions and
+/// projects is logged as the state it left; sessions synced or merged in from
+/// other machines are left to those machines' logs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// A session was opened or resumed
+    Start { project: Box<Project>, session: Session },
+    Heartbeat {
+        session_id: i64,
+        timestamp: DateTime<Utc>,
+        context: HeartbeatContext,
+    },
+    /// A session was completed or abandoned
+    Stop {
+        session: Session,
+        commits: Vec<Commit>,
+        branches: Vec<SessionBranch>,
+        activity: Vec<(String, i64)>,
+    },
+    /// A session was changed outside tracking: edited, trashed, restored,
+    /// imported or entered by hand
+    Session {
+        project: Box<Project>,
+        session: Session,
+        commits: Vec<Commit>,
+        branches: Vec<SessionBranch>,
+        activity: Vec<(String, i64)>,
+    },
+    /// A project's settings, aliases or rates changed, or it was archived or moved
+    Project {
+        project: Box<Project>,
+        aliases: Vec<String>,
+        rates: Vec<ProjectRate>,
+    },
+    /// A session was removed for good
+    Purge { session_id: i64 },
+    /// A project was removed for good, with all its sessions
+    PurgeProject { project_id: i64 },
+}
+
+impl Event {
+    /// Snapshot a session as it stands after stopping
+    pub fn stop(db: &impl Storage, session_id: i64) -> Result<Self> {
+        Ok(Event::Stop {
+            session: db.get_session_by_id(session_id)?,
+            commits: db.get_commits(session_id)?,
+            branches: db.get_session_branches(session_id)?,
+            activity: db.get_session_activity(session_id)?,
+        })
+    }
+
+    /// Snapshot a session, with its project, after a change
+    pub fn session(db: &impl Storage, session_id: i64) -> Result<Self> {
+        let session = db.get_session_by_id(session_id)?;
+        Ok(Event::Session {
+            project: Box::new(db.get_project_by_id(session.project_id)?),
+            commits: db.get_commits(session_id)?,
+            branches: db.get_session_branches(session_id)?,
+            activity: db.get_session_activity(session_id)?,
+            session,
+        })
+    }
+
+    /// Snapshot a project after a change
+    pub fn project(db: &Database, project_id: i64) -> Result<Self> {
+        Ok(Event::Project {
+            project: Box::new(db.get_project_by_id(project_id)?),
+            aliases: db.get_project_aliases(project_id)?,
+            rates: db.get_project_rates(project_id)?,
+        })
+    }
+}
+
+/// Append an event to the event log, if enabled
+///
+/// The database stays the source of truth: a failed append only warns.
+pub fn log(config: &EffectiveConfig, event: impl FnOnce() -> Result<Event>) {
+    log_all(config, || Ok(vec![event()?]));
+}
+
+/// Append events to the event log, if enabled, as [`log`] does
+pub fn log_all(config: &EffectiveConfig, events: impl FnOnce() -> Result<Vec<Event>>) {
+    let Some(ref path) = config.event_log else {
+        return;
+    };
+    let appended = events().and_then(|events| events.iter().try_for_each(|event| append(path, event)));
+    if let Err(e) = appended {
+        warn!("Failed to append to event log {}: {:#}", path.display(), e);
+    }
+}
+
+/// Log a project after a change that also touched its sessions (trashing,
+/// restoring or moving it), followed by all of them
+pub fn log_project(config: &EffectiveConfig, db: &Database, project_id: i64) {
+    log_all(config, || {
+        let mut events = vec![Event::project(db, project_id)?];
+        for session in db.get_project_sessions(project_id)? {
+            events.push(Event::session(db, session.id)?);
+        }
+        Ok(events)
+    });
+}
+
+/// Counts from replaying an event log
+#[derive(Debug, Default)]
+pub struct ReplayStats {
+    pub sessions: usize,
+    pub heartbeats: usize,
+    /// Events for sessions started before the log was enabled, or unreadable lines
+    pub skipped: usize,
+}
+
+/// Append an event as one line
+pub fn append(path: &Path, event: &Event) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+    }
+
+    let mut line = serde_json::to_string(event)?;
+    line.push('\n');
+
+    // A single write of the whole line keeps concurrent hooks from interleaving
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open event log: {}", path.display()))?;
+    file.write_all(line.as_bytes())
+        .with_context(|| format!("Failed to write event log: {}", path.display()))?;
+
+    Ok(())
+}
+
+/// Heartbeats of one session waiting to be written together
+type HeartbeatBatch = (i64, Vec<(DateTime<Utc>, HeartbeatContext)>);
+
+/// Replay an event log into a database, keeping the logged ids
+pub fn replay(path: &Path, db: &Database) -> Result<ReplayStats> {
+    let file = fs::File::open(path).with_context(|| format!("Failed to open event log: {}", path.display()))?;
+
+    let mut stats = ReplayStats::default();
+    let mut started: HashSet<i64> = HashSet::new();
+    // Heartbeats of the same session are written in one batch
+    let mut pending: Option<HeartbeatBatch> = None;
+
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let event: Event = match serde_json::from_str(&line) {
+            Ok(event) => event,
+            Err(e) => {
+                // A crash mid-append can leave a truncated last line
+                warn!("Skipping line {} of {}: {}", index + 1, path.display(), e);
+                stats.skipped += 1;
+                continue;
+            }
+        };
+
+        if let Event::Heartbeat {
+            session_id,
+            timestamp,
+            context,
+        } = event
+        {
+            if !started.contains(&session_id) {
+                stats.skipped += 1;
+                continue;
+            }
+            match pending {
+                Some((id, ref mut batch)) if id == session_id => batch.push((timestamp, context)),
+                _ => {
+                    stats.heartbeats += flush(db, pending.take())?;
+                    pending = Some((session_id, vec![(timestamp, context)]));
+                }
+            }
+            continue;
+        }
+
+        stats.heartbeats += flush(db, pending.take())?;
+        match event {
+            Event::Start { project, session } => {
+                db.restore_project(&project)?;
+                db.restore_session(&session, &[], &[], &[])?;
+                if started.insert(session.id) {
+                    stats.sessions += 1;
+                }
+            }
+            Event::Stop {
+                session,
+                commits,
+                branches,
+                activity,
+            } => {
+                if !started.contains(&session.id) {
+                    stats.skipped += 1;
+                    continue;
+                }
+                db.restore_session(&session, &commits, &branches, &activity)?;
+            }
+            // Sessions entered by hand or imported never started, so these apply regardless
+            Event::Session {
+                project,
+                session,
+                commits,
+                branches,
+                activity,
+            } => {
+                db.restore_project(&project)?;
+                db.restore_session(&session, &commits, &branches, &activity)?;
+                if started.insert(session.id) {
+                    stats.sessions += 1;
+                }
+            }
+            Event::Project { project, aliases, rates } => {
+                db.restore_project(&project)?;
+                for alias in aliases {
+                    db.add_project_alias(project.id, &alias)?;
+                }
+                for old in db.get_project_rates(project.id)? {
+                    if !rates.iter().any(|rate| rate.effective_from == old.effective_from) {
+                        db.delete_project_rate(project.id, old.effective_from)?;
+                    }
+                }
+                for rate in rates {
+                    db.set_project_rate(project.id, rate.effective_from, rate.hourly_rate, rate.currency.as_deref())?;
+                }
+            }
+            Event::Purge { session_id } => {
+                started.remove(&session_id);
+                db.purge_session(session_id)?;
+            }
+            Event::PurgeProject { project_id } => {
+                for session in db.get_project_sessions(project_id)? {
+                    started.remove(&session.id);
+                }
+                db.purge_project(project_id)?;
+            }
+            Event::Heartbeat { .. } => unreachable!("heartbeats are batched above"),
+        }
+    }
+    stats.heartbeats += flush(db, pending)?;
+
+    Ok(stats)
+}
+
+fn flush(db: &Database, pending: Option<HeartbeatBatch>) -> Result<usize> {
+    match pending {
+        Some((session_id, batch)) => Ok(db.record_heartbeats(session_id, &batch)?.len()),
+        None => Ok(0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_replay_rebuilds_sessions() {
+        let dir = tempdir().unwrap();
+        let log = dir.path().join(EVENT_LOG_FILE_NAME);
+
+        let source = Database::open(&dir.path().join("source.db")).unwrap();
+        let project = source.get_or_create_project("/test/path", None, None, None).unwrap();
+        let session = source.create_session(project.id, "feature/ABC-1", Some("ABC-1"), Some("aaa"), None, None).unwrap();
+        append(&log, &Event::Start { project: Box::new(project.clone()), session: session.clone() }).unwrap();
+        for _ in 0..3 {
+            let context = HeartbeatContext {
+                event_type: Some("prompt-submit".to_string()),
+                ..Default::default()
+            };
+            let heartbeat = source.record_heartbeat(session.id, &context).unwrap();
+            append(
+                &log,
+                &Event::Heartbeat {
+                    session_id: session.id,
+                    timestamp: heartbeat.timestamp,
+                    context,
+                },
+            )
+            .unwrap();
+        }
+        source.record_commits(session.id, &[("bbb".to_string(), "ABC-1 Add login".to_string(), None)]).unwrap();
+        source
+            .complete_session(session.id, Some("bbb"), 600, Utc::now(), crate::models::SessionStatus::Completed)
+            .unwrap();
+        append(&log, &Event::stop(&source, session.id).unwrap()).unwrap();
+        // A heartbeat for a session the log never saw start, and a torn last line
+        append(
+            &log,
+            &Event::Heartbeat {
+                session_id: 99,
+                timestamp: Utc::now(),
+                context: HeartbeatContext::default(),
+            },
+        )
+        .unwrap();
+        fs::OpenOptions::new().append(true).open(&log).unwrap().write_all(b"{\"event\":\"sta").unwrap();
+
+        let rebuilt = Database::open(&dir.path().join("rebuilt.db")).unwrap();
+        let stats = replay(&log, &rebuilt).unwrap();
+        assert_eq!((stats.sessions, stats.heartbeats, stats.skipped), (1, 3, 2));
+
+        let restored = rebuilt.get_session_by_id(session.id).unwrap();
+        assert_eq!(restored.work_item.as_deref(), Some("ABC-1"));
+        assert_eq!(restored.active_seconds, Some(600));
+        assert_eq!(rebuilt.get_project_by_id(project.id).unwrap().path, "/test/path");
+        assert_eq!(rebuilt.get_commits(session.id).unwrap().len(), 1);
+        assert_eq!(rebuilt.count_prompts(session.id).unwrap(), 3);
+    }
+
+    #[test]
+    fn test_replay_changes() {
+        let dir = tempdir().unwrap();
+        let log = dir.path().join(EVENT_LOG_FILE_NAME);
+        let append = |event: Result<Event>| append(&log, &event.unwrap()).unwrap();
+
+        let source = Database::open(&dir.path().join("source.db")).unwrap();
+        let project = source.get_or_create_project("/test/path", None, None, None).unwrap();
+        let mut ids = Vec::new();
+        for _ in 0..2 {
+            let session = source.create_session(project.id, "main", None, None, None, None).unwrap();
+            append(Ok(Event::Start { project: Box::new(project.clone()), session: session.clone() }));
+            source.complete_session(session.id, None, 600, Utc::now(), crate::models::SessionStatus::Completed).unwrap();
+            append(Event::stop(&source, session.id));
+            ids.push(session.id);
+        }
+
+        // Edited, then trashed
+        source.set_session_work_item(ids[0], Some("ABC-2")).unwrap();
+        append(Event::session(&source, ids[0]));
+        source.delete_session(ids[0]).unwrap();
+        append(Event::session(&source, ids[0]));
+        // Entered by hand
+        let manual = source
+            .import_session(project.id, "manual", "manual", Some("ABC-3"), Utc::now(), 1800)
+            .unwrap()
+            .unwrap();
+        append(Event::session(&source, manual.id));
+        // Purged
+        source.purge_session(ids[1]).unwrap();
+        append(Ok(Event::Purge { session_id: ids[1] }));
+        // A new rate
+        let from = chrono::NaiveDate::from_ymd_opt(2025, 1, 1).unwrap();
+        source.set_project_rate(project.id, from, 90.0, Some("EUR")).unwrap();
+        append(Event::project(&source, project.id));
+
+        let rebuilt = Database::open(&dir.path().join("rebuilt.db")).unwrap();
+        replay(&log, &rebuilt).unwrap();
+
+        let trashed = rebuilt.get_session_by_id(ids[0]).unwrap();
+        assert_eq!(trashed.work_item.as_deref(), Some("ABC-2"));
+        assert_eq!(trashed.deleted_at, source.get_session_by_id(ids[0]).unwrap().deleted_at);
+        assert!(rebuilt.get_session_by_id(ids[1]).is_err());
+        assert_eq!(rebuilt.get_session_by_id(manual.id).unwrap().imported_from.as_deref(), Some("manual"));
+        assert_eq!(rebuilt.get_project_rates(project.id).unwrap().len(), 1);
+    }
+}
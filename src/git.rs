@@ -1,6 +1,12 @@
 use anyhow::{Context, Result};
 use chrono::{DateTime, TimeZone, Utc};
-use std::path::Path;
+use regex::Regex;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tracing::debug;
+
+use crate::models::{DiffStats, RemoteRepo};
+use crate::vcs;
 
 /// Git repository information
 #[derive(Debug, Clone)]
@@ -8,11 +14,33 @@ pub struct GitInfo {
     pub branch: String,
     pub head_commit: Option<String>,
     pub remote_url: Option<String>,
+    /// Fetch URLs of all remotes, `remote_url` first
+    pub remote_urls: Vec<String>,
+}
+
+/// Open the repository containing `path`, honoring `GIT_DIR` and friends
+///
+/// Works from any subdirectory of a work tree, and from linked worktrees
+/// whose `.git` is a file pointing at the main repository.
+fn open_repo(path: &Path) -> Result<gix::Repository> {
+    let repo = gix::ThreadSafeRepository::discover_with_environment_overrides(path)
+        .context("Failed to open git repository")?;
+    Ok(repo.to_thread_local())
+}
+
+/// Root of the work tree containing `path`; `None` outside a repository or in a bare one
+pub fn find_root(path: &Path) -> Option<PathBuf> {
+    let repo = open_repo(path).ok()?;
+    let root = repo.work_dir()?;
+    Some(root.canonicalize().unwrap_or_else(|_| root.to_path_buf()))
 }
 
 /// Get git information for a repository path
-pub fn get_git_info(path: &Path) -> Result<GitInfo> {
-    let repo = gix::open(path).context("Failed to open git repository")?;
+///
+/// `remote_name` picks the remote to report; otherwise the branch's upstream
+/// remote, `origin` or the first remote is used, in that order.
+pub fn get_git_info(path: &Path, remote_name: Option<&str>) -> Result<GitInfo> {
+    let repo = open_repo(path)?;
 
     // Get current branch name
     let branch = get_branch_name(&repo)?;
@@ -20,16 +48,35 @@ pub fn get_git_info(path: &Path) -> Result<GitInfo> {
     // Get HEAD commit
     let head_commit = get_head_commit(&repo)?;
 
-    // Get remote URL
-    let remote_url = get_remote_url(&repo)?;
+    // Get remote URLs, preferred remote first
+    let upstream = repo
+        .config_snapshot()
+        .string(format!("branch.{}.remote", branch).as_str())
+        .map(|name| name.to_string());
+    let remote_urls = order_remotes(get_remotes(&repo), &[remote_name, upstream.as_deref(), Some("origin")]);
+    let remote_url = remote_urls.first().cloned();
+
+    debug!(
+        "Git info for {}: branch {}, head {:?}",
+        path.display(),
+        branch,
+        head_commit
+    );
 
     Ok(GitInfo {
         branch,
         head_commit,
         remote_url,
+        remote_urls,
     })
 }
 
+/// Get the currently checked out branch name
+pub fn current_branch(path: &Path) -> Result<String> {
+    let repo = open_repo(path)?;
+    get_branch_name(&repo)
+}
+
 fn get_branch_name(repo: &gix::Repository) -> Result<String> {
     let head = repo.head().context("Failed to get HEAD")?;
 
@@ -60,24 +107,108 @@ fn get_head_commit(repo: &gix::Repository) -> Result<Option<String>> {
     Ok(None)
 }
 
-fn get_remote_url(repo: &gix::Repository) -> Result<Option<String>> {
-    // Try to get origin remote
-    if let Ok(remote) = repo.find_remote("origin") {
-        if let Some(url) = remote.url(gix::remote::Direction::Fetch) {
-            return Ok(Some(url.to_bstring().to_string()));
+/// All remotes with a fetch URL as (name, url), sorted by name
+fn get_remotes(repo: &gix::Repository) -> Vec<(String, String)> {
+    repo.remote_names()
+        .iter()
+        .filter_map(|name| {
+            let remote = repo.find_remote(name.as_ref()).ok()?;
+            let url = remote.url(gix::remote::Direction::Fetch)?;
+            Some((name.to_string(), url.to_bstring().to_string()))
+        })
+        .collect()
+}
+
+/// Remote URLs with the first remote named in `preferred` moved to the front
+///
+/// Unset preferences are skipped; without a match the remotes keep their order.
+pub fn order_remotes(remotes: Vec<(String, String)>, preferred: &[Option<&str>]) -> Vec<String> {
+    let mut remotes = remotes;
+    let chosen = preferred
+        .iter()
+        .flatten()
+        .find_map(|name| remotes.iter().position(|(remote, _)| remote == name));
+    if let Some(index) = chosen {
+        let remote = remotes.remove(index);
+        remotes.insert(0, remote);
+    }
+    remotes.into_iter().map(|(_, url)| url).collect()
+}
+
+/// Which commits in a range are attributed to a session
+#[derive(Debug, Clone, Default)]
+pub struct CommitFilter {
+    /// Only keep commits authored by one of `author_emails`, or by the repository's `user.email`
+    pub own_only: bool,
+    pub author_emails: Vec<String>,
+    /// Skip commits with more than one parent
+    pub exclude_merges: bool,
+    /// Skip commits whose message title matches any of these
+    pub exclude_messages: Vec<Regex>,
+}
+
+impl CommitFilter {
+    /// Whether every commit is kept, so that a range's change is its net diff
+    pub fn keeps_all(&self) -> bool {
+        !self.own_only && !self.exclude_merges && self.exclude_messages.is_empty()
+    }
+
+    /// Whether a commit with this message title is left out
+    pub fn excludes_message(&self, title: &str) -> bool {
+        self.exclude_messages.iter().any(|re| re.is_match(title))
+    }
+
+    /// Resolve which authors' commits are kept in the repository at `path`
+    ///
+    /// `user_email` reads the repository's `user.email`; it's only asked when
+    /// `own_only` is set without `author_emails`.
+    pub fn authors(&self, path: &Path, user_email: impl FnOnce() -> Option<String>) -> AuthorFilter {
+        if !self.own_only {
+            return AuthorFilter::default();
+        }
+
+        let mut emails: Vec<String> = self.author_emails.iter().map(|e| e.trim().to_lowercase()).collect();
+        if emails.is_empty() {
+            match user_email().map(|e| e.trim().to_lowercase()).filter(|e| !e.is_empty()) {
+                Some(email) => emails.push(email),
+                None => debug!("No user.email configured for {}; keeping all authors", path.display()),
+            }
+        }
+        AuthorFilter {
+            emails: Some(emails).filter(|e| !e.is_empty()),
         }
     }
+}
 
-    Ok(None)
+/// Authors whose commits a [`CommitFilter`] keeps in one repository
+#[derive(Debug, Default)]
+pub struct AuthorFilter {
+    /// Lowercased emails, or `None` to keep every author
+    emails: Option<Vec<String>>,
+}
+
+impl AuthorFilter {
+    /// Whether a commit by this author email is kept
+    pub fn keeps(&self, email: &str) -> bool {
+        match self.emails {
+            Some(ref emails) => emails.contains(&email.trim().to_lowercase()),
+            None => true,
+        }
+    }
 }
 
-/// Get commits between two commit hashes (exclusive start, inclusive end)
+/// Get commits up to `end_commit` (inclusive), walking back until one of `stop_at`
+///
+/// Without stops, the latest 100 commits are listed.
 pub fn get_commits_between(
     path: &Path,
-    start_commit: Option<&str>,
+    stop_at: &[&str],
     end_commit: Option<&str>,
-) -> Result<Vec<(String, String, Option<DateTime<Utc>>)>> {
-    let repo = gix::open(path).context("Failed to open git repository")?;
+    filter: &CommitFilter,
+) -> Result<Vec<vcs::CommitEntry>> {
+    let repo = open_repo(path)?;
+
+    let authors = filter.authors(path, || repo.config_snapshot().string("user.email").map(|e| e.to_string()));
 
     let end_oid = if let Some(end) = end_commit {
         repo.rev_parse_single(end)
@@ -91,7 +222,10 @@ pub fn get_commits_between(
             .detach()
     };
 
-    let start_oid = start_commit.and_then(|s| repo.rev_parse_single(s).ok().map(|o| o.detach()));
+    let stop_oids: Vec<_> = stop_at
+        .iter()
+        .filter_map(|s| repo.rev_parse_single(*s).ok().map(|o| o.detach()))
+        .collect();
 
     let mut commits = Vec::new();
     let mut walk = repo
@@ -104,18 +238,28 @@ pub fn get_commits_between(
         let info = info.context("Failed to get commit info")?;
         let oid = info.id;
 
-        // Stop if we've reached the start commit
-        if let Some(ref start) = start_oid {
-            if oid == *start {
-                break;
-            }
+        // Stop once a commit known from before is reached
+        if stop_oids.contains(&oid) {
+            break;
         }
 
         let commit = info.object().context("Failed to get commit object")?;
+
+        if filter.exclude_merges && commit.parent_ids().count() > 1 {
+            continue;
+        }
+        let email = commit.author().map(|a| a.email.to_string()).unwrap_or_default();
+        if !authors.keeps(&email) {
+            continue;
+        }
+
         let message = commit
             .message()
             .map(|m| m.title.to_string())
             .unwrap_or_default();
+        if filter.excludes_message(&message) {
+            continue;
+        }
 
         let time = commit.time().ok().map(|t| {
             Utc.timestamp_opt(t.seconds, 0)
@@ -125,8 +269,8 @@ pub fn get_commits_between(
 
         commits.push((oid.to_string(), message, time));
 
-        // Limit to last 100 commits if no start specified
-        if start_oid.is_none() && commits.len() >= 100 {
+        // Limit to last 100 commits if no stop was found
+        if stop_oids.is_empty() && commits.len() >= 100 {
             break;
         }
     }
@@ -136,9 +280,295 @@ pub fn get_commits_between(
     Ok(commits)
 }
 
+/// Count files changed and lines inserted/deleted between two commits
+///
+/// Binary files count as changed but contribute no lines. When `filter`
+/// leaves commits out, only what the kept ones changed is counted.
+pub fn diff_stats(path: &Path, start_commit: &str, end_commit: &str, filter: &CommitFilter) -> Result<DiffStats> {
+    if !filter.keeps_all() {
+        let commits: Vec<String> = get_commits_between(path, &[start_commit], Some(end_commit), filter)?
+            .into_iter()
+            .map(|(hash, _, _)| hash)
+            .collect();
+        return Ok(DiffStats::total(commit_diff_stats(path, &commits)?));
+    }
+
+    let repo = open_repo(path)?;
+
+    let start_tree = repo
+        .rev_parse_single(start_commit)
+        .context("Failed to parse start commit")?
+        .object()?
+        .peel_to_tree()
+        .context("Start commit has no tree")?;
+    let end_tree = repo
+        .rev_parse_single(end_commit)
+        .context("Failed to parse end commit")?
+        .object()?
+        .peel_to_tree()
+        .context("End commit has no tree")?;
+
+    let mut resource_cache = repo
+        .diff_resource_cache(gix::diff::blob::pipeline::Mode::ToGit, Default::default())
+        .context("Failed to prepare diff")?;
+
+    tree_diff_stats(&start_tree, &end_tree, &mut resource_cache)
+}
+
+/// Count what each of `commits` changed against its first parent, opening the repository once
+///
+/// A repository's first commit is compared with the empty tree. Commits that
+/// can't be read are left out.
+pub fn commit_diff_stats(path: &Path, commits: &[String]) -> Result<Vec<(String, DiffStats)>> {
+    let repo = open_repo(path)?;
+    let mut resource_cache = repo
+        .diff_resource_cache(gix::diff::blob::pipeline::Mode::ToGit, Default::default())
+        .context("Failed to prepare diff")?;
+
+    let mut measured = Vec::new();
+    for hash in commits {
+        let stats = repo
+            .rev_parse_single(hash.as_str())
+            .context("Failed to parse commit")
+            .and_then(|id| Ok(id.object()?.try_into_commit()?))
+            .and_then(|commit| {
+                let tree = commit.tree()?;
+                let parent_tree = match commit.parent_ids().next() {
+                    Some(parent) => parent.object()?.peel_to_tree()?,
+                    None => repo.empty_tree(),
+                };
+                tree_diff_stats(&parent_tree, &tree, &mut resource_cache)
+            });
+        match stats {
+            Ok(stats) => measured.push((hash.clone(), stats)),
+            Err(e) => debug!("Not measuring commit {}: {:#}", hash, e),
+        }
+    }
+    Ok(measured)
+}
+
+/// Files and lines changed from one tree to another
+fn tree_diff_stats(from: &gix::Tree<'_>, to: &gix::Tree<'_>, resource_cache: &mut gix::diff::blob::Platform) -> Result<DiffStats> {
+    let mut stats = DiffStats::default();
+    from.changes()?
+        .track_rewrites(None)
+        .for_each_to_obtain_tree(to, |change| {
+            if change.event.entry_mode().is_blob_or_symlink() {
+                stats.files_changed += 1;
+                if let Some(counts) = change
+                    .diff(resource_cache)
+                    .ok()
+                    .and_then(|mut platform| platform.line_counts().ok())
+                    .flatten()
+                {
+                    stats.insertions += i64::from(counts.insertions);
+                    stats.deletions += i64::from(counts.removals);
+                }
+                resource_cache.clear_resource_cache();
+            }
+            Ok::<_, std::convert::Infallible>(gix::object::tree::diff::Action::Continue)
+        })
+        .context("Failed to diff commits")?;
+
+    Ok(stats)
+}
+
+/// Count working tree changes not committed yet
+///
+/// Lines come from tracked files; untracked files only add to the file count.
+/// This shells out to `git`, which is faster than comparing the worktree by hand.
+pub fn uncommitted_changes(path: &Path) -> Result<DiffStats> {
+    let shortstat = run_git(path, &["diff", "HEAD", "--shortstat"])?;
+    let mut stats = vcs::parse_diff_summary(&shortstat).unwrap_or_default();
+
+    let untracked = run_git(path, &["ls-files", "--others", "--exclude-standard"])?;
+    stats.files_changed += untracked.lines().count() as i64;
+
+    Ok(stats)
+}
+
+pub fn run_git(path: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(path)
+        .output()
+        .context("Failed to run git")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Branches checked out between `since` and `until`, oldest first, from the HEAD reflog
+pub fn branch_checkouts(
+    path: &Path,
+    since: DateTime<Utc>,
+    until: DateTime<Utc>,
+) -> Result<Vec<(DateTime<Utc>, String)>> {
+    let repo = open_repo(path)?;
+    let head = repo.find_reference("HEAD").context("Failed to find HEAD")?;
+
+    let mut platform = head.log_iter();
+    let Some(lines) = platform.all().context("Failed to read HEAD reflog")? else {
+        return Ok(Vec::new());
+    };
+
+    let mut checkouts = Vec::new();
+    for line in lines {
+        let line = line.context("Failed to parse reflog entry")?;
+        let Some(time) = Utc.timestamp_opt(line.signature.time.seconds, 0).single() else {
+            continue;
+        };
+        if time < since || time > until {
+            continue;
+        }
+        if let Some(branch) = checkout_target(&line.message.to_string()) {
+            checkouts.push((time, branch));
+        }
+    }
+
+    Ok(checkouts)
+}
+
+/// Branch a reflog message like `checkout: moving from main to feature/x` switched to
+///
+/// Detached checkouts are named like `get_branch_name` names a detached HEAD.
+fn checkout_target(message: &str) -> Option<String> {
+    let (_, target) = message.strip_prefix("checkout: moving from ")?.rsplit_once(" to ")?;
+    let target = target.trim();
+
+    let is_commit = target.len() == 40 && target.chars().all(|c| c.is_ascii_hexdigit());
+    if is_commit {
+        Some(format!("detached-{}", &target[..8]))
+    } else {
+        Some(target.to_string())
+    }
+}
+
+/// List local branch names, most recently committed first
+pub fn list_branches(path: &Path) -> Result<Vec<String>> {
+    let repo = open_repo(path)?;
+    let references = repo.references().context("Failed to read references")?;
+
+    let mut branches = Vec::new();
+    for reference in references.local_branches().context("Failed to list branches")? {
+        let Ok(mut reference) = reference else {
+            continue;
+        };
+
+        let name = reference.name().shorten().to_string();
+        let time = reference
+            .peel_to_id_in_place()
+            .ok()
+            .and_then(|id| id.object().ok())
+            .and_then(|object| object.try_into_commit().ok())
+            .and_then(|commit| commit.time().ok())
+            .map(|t| t.seconds)
+            .unwrap_or(0);
+
+        branches.push((time, name));
+    }
+
+    branches.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+    Ok(branches.into_iter().map(|(_, name)| name).collect())
+}
+
+/// Paths of the submodules declared in `.gitmodules` at the repository root
+pub fn submodule_paths(root: &Path) -> Vec<String> {
+    let Ok(content) = std::fs::read_to_string(root.join(".gitmodules")) else {
+        return Vec::new();
+    };
+    parse_gitmodules(&content)
+}
+
+fn parse_gitmodules(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once('=')?;
+            (key.trim() == "path").then(|| value.trim().trim_matches('"').to_string())
+        })
+        .filter(|path| !path.is_empty())
+        .collect()
+}
+
+/// `branch.<name>.description`, as set by `git branch --edit-description`
+pub fn branch_description(path: &Path, branch: &str) -> Option<String> {
+    let repo = open_repo(path).ok()?;
+    let description = repo
+        .config_snapshot()
+        .string(format!("branch.{}.description", branch).as_str())?;
+    Some(description.to_string())
+}
+
+/// Derive a project name from a remote URL (e.g. `git@github.com:acme/api.git` -> `api`)
+pub fn project_name_from_remote(url: &str) -> Option<String> {
+    let name = url
+        .trim_end_matches('/')
+        .rsplit(['/', ':'])
+        .next()?
+        .trim_end_matches(".git");
+
+    if name.is_empty() {
+        None
+    } else {
+        Some(name.to_string())
+    }
+}
+
+/// Reduce a remote URL to `host/owner/repo` so SSH and HTTPS forms of a remote compare equal
+pub fn normalize_remote_url(url: &str) -> String {
+    let url = url.trim().trim_end_matches('/').trim_end_matches(".git");
+    let (url, has_scheme) = match url.split_once("://") {
+        Some((_, rest)) => (rest, true),
+        None => (url, false),
+    };
+    let url = url.split_once('@').map(|(_, rest)| rest).unwrap_or(url);
+
+    let (host, path) = if has_scheme {
+        // host[:port]/owner/repo; the port is dropped
+        let (authority, path) = url.split_once('/').unwrap_or((url, ""));
+        (authority.split_once(':').map_or(authority, |(host, _)| host), path)
+    } else {
+        // scp-like syntax (host:owner/repo)
+        url.split_once(':').or_else(|| url.split_once('/')).unwrap_or((url, ""))
+    };
+    let path = path.trim_start_matches('/');
+
+    if path.is_empty() {
+        host.to_lowercase()
+    } else {
+        format!("{}/{}", host.to_lowercase(), path)
+    }
+}
+
+/// Split a remote URL into host, owner and repository name
+///
+/// Returns `None` for local paths and URLs without an owner.
+pub fn parse_remote_url(url: &str) -> Option<RemoteRepo> {
+    let normalized = normalize_remote_url(url);
+    let (host, path) = normalized.split_once('/')?;
+    let (owner, repo) = path.rsplit_once('/')?;
+    if host.is_empty() || owner.is_empty() || repo.is_empty() {
+        return None;
+    }
+
+    Some(RemoteRepo {
+        host: host.to_string(),
+        owner: owner.to_string(),
+        repo: repo.to_string(),
+    })
+}
+
 /// Check if path is inside a git repository
 pub fn is_git_repo(path: &Path) -> bool {
-    gix::open(path).is_ok()
+    open_repo(path).is_ok()
 }
 
 #[cfg(test)]
@@ -154,4 +584,256 @@ mod tests {
         // Just verify the function doesn't panic
         let _ = is_git_repo(&cwd);
     }
+
+    #[test]
+    fn test_find_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        gix::init(&root).unwrap();
+        std::fs::create_dir_all(root.join("src/nested")).unwrap();
+
+        assert_eq!(find_root(&root.join("src/nested")), Some(root.clone()));
+        assert_eq!(find_root(&root), Some(root));
+
+        let bare = tempfile::tempdir().unwrap();
+        gix::init_bare(bare.path()).unwrap();
+        assert_eq!(find_root(bare.path()), None);
+    }
+
+    #[test]
+    fn test_project_name_from_remote() {
+        assert_eq!(
+            project_name_from_remote("git@github.com:acme/api.git"),
+            Some("api".to_string())
+        );
+        assert_eq!(
+            project_name_from_remote("https://gitlab.com/group/sub/web-app/"),
+            Some("web-app".to_string())
+        );
+        assert_eq!(project_name_from_remote(""), None);
+    }
+
+    #[test]
+    fn test_checkout_target() {
+        assert_eq!(
+            checkout_target("checkout: moving from main to feature/ABC-1"),
+            Some("feature/ABC-1".to_string())
+        );
+        assert_eq!(
+            checkout_target("checkout: moving from main to 0123456789abcdef0123456789abcdef01234567"),
+            Some("detached-01234567".to_string())
+        );
+        assert_eq!(checkout_target("commit: Fix login"), None);
+        assert_eq!(checkout_target("rebase (finish): returning to refs/heads/main"), None);
+    }
+
+    #[test]
+    fn test_parse_remote_url() {
+        let repo = parse_remote_url("git@github.com:acme/api.git").unwrap();
+        assert_eq!((repo.host.as_str(), repo.owner.as_str(), repo.repo.as_str()), ("github.com", "acme", "api"));
+        assert_eq!(parse_remote_url("https://github.com/acme/api"), Some(repo));
+
+        let nested = parse_remote_url("https://gitlab.com/group/sub/web-app/").unwrap();
+        assert_eq!(nested.slug(), "group/sub/web-app");
+        assert_eq!(nested.issue_url("#7").as_deref(), Some("https://gitlab.com/group/sub/web-app/-/issues/7"));
+        assert_eq!(nested.issue_url("ABC-7"), None);
+
+        assert_eq!(parse_remote_url("/srv/git/api.git"), None);
+        assert_eq!(parse_remote_url("https://example.com/api"), None);
+    }
+
+    #[test]
+    fn test_parse_gitmodules() {
+        let content = "[submodule \"vendor/engine\"]\n\
+                       \tpath = vendor/engine\n\
+                       \turl = git@github.com:acme/engine.git\n\
+                       [submodule \"docs\"]\n\
+                       \tpath = \"docs/site\"\n\
+                       \turl = ../site.git\n";
+        assert_eq!(parse_gitmodules(content), vec!["vendor/engine", "docs/site"]);
+        assert!(parse_gitmodules("").is_empty());
+    }
+
+    #[test]
+    fn test_order_remotes() {
+        let remotes = || {
+            vec![
+                ("fork".to_string(), "git@github.com:me/api.git".to_string()),
+                ("origin".to_string(), "git@github.com:acme/api.git".to_string()),
+                ("upstream".to_string(), "git@github.com:org/api.git".to_string()),
+            ]
+        };
+        let first = |preferred: &[Option<&str>]| order_remotes(remotes(), preferred)[0].clone();
+
+        assert_eq!(first(&[None, None, Some("origin")]), "git@github.com:acme/api.git");
+        assert_eq!(first(&[None, Some("upstream"), Some("origin")]), "git@github.com:org/api.git");
+        assert_eq!(first(&[Some("fork"), Some("upstream"), Some("origin")]), "git@github.com:me/api.git");
+        assert_eq!(first(&[Some("missing"), None, Some("gone")]), "git@github.com:me/api.git");
+        assert_eq!(order_remotes(remotes(), &[Some("upstream")]).len(), 3);
+        assert!(order_remotes(Vec::new(), &[Some("origin")]).is_empty());
+    }
+
+    #[test]
+    fn test_commit_filter_authors() {
+        let path = Path::new("/repo");
+        let unset = || -> Option<String> { None };
+
+        // Not restricted: every author is kept and user.email isn't read
+        let filter = CommitFilter::default();
+        assert!(filter.authors(path, || panic!("user.email read")).keeps("someone@example.com"));
+
+        // Explicit emails win over user.email and compare case-insensitively
+        let filter = CommitFilter {
+            own_only: true,
+            author_emails: vec!["Me@Example.com".to_string()],
+            ..Default::default()
+        };
+        let authors = filter.authors(path, || panic!("user.email read"));
+        assert!(authors.keeps("me@example.COM"));
+        assert!(!authors.keeps("bot@example.com"));
+
+        // Otherwise the repository's user.email decides
+        let filter = CommitFilter {
+            own_only: true,
+            ..Default::default()
+        };
+        let authors = filter.authors(path, || Some("me@example.com\n".to_string()));
+        assert!(authors.keeps("ME@example.com"));
+        assert!(!authors.keeps("bot@example.com"));
+
+        // And without one nothing is filtered out
+        assert!(filter.authors(path, unset).keeps("bot@example.com"));
+    }
+
+    #[test]
+    fn test_get_commits_between_filters_authors() {
+        let dir = tempfile::tempdir().unwrap();
+        let git = |args: &[&str]| {
+            let status = Command::new("git").arg("-C").arg(dir.path()).args(args).status().unwrap();
+            assert!(status.success(), "git {:?} failed", args);
+        };
+        git(&["init", "-q"]);
+        git(&["config", "user.name", "Me"]);
+        git(&["config", "user.email", "me@example.com"]);
+        git(&["commit", "-q", "--allow-empty", "-m", "ABC-1 Mine"]);
+        git(&["-c", "user.email=bot@example.com", "commit", "-q", "--allow-empty", "-m", "Bump deps"]);
+
+        let titles = |filter: &CommitFilter| -> Vec<String> {
+            get_commits_between(dir.path(), &[], None, filter)
+                .unwrap()
+                .into_iter()
+                .map(|(_, title, _)| title.trim().to_string())
+                .collect()
+        };
+
+        assert_eq!(titles(&CommitFilter::default()).len(), 2);
+        let own = CommitFilter {
+            own_only: true,
+            ..Default::default()
+        };
+        assert_eq!(titles(&own), vec!["ABC-1 Mine"]);
+        let bot = CommitFilter {
+            own_only: true,
+            author_emails: vec!["BOT@example.com".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(titles(&bot), vec!["Bump deps"]);
+    }
+
+    #[test]
+    fn test_commit_diff_stats() {
+        let dir = tempfile::tempdir().unwrap();
+        let git = |args: &[&str]| {
+            let status = Command::new("git").arg("-C").arg(dir.path()).args(args).status().unwrap();
+            assert!(status.success(), "git {:?} failed", args);
+        };
+        git(&["init", "-q"]);
+        git(&["config", "user.name", "Me"]);
+        git(&["config", "user.email", "me@example.com"]);
+        std::fs::write(dir.path().join("a.txt"), "one\ntwo\nthree\n").unwrap();
+        git(&["add", "a.txt"]);
+        git(&["commit", "-q", "-m", "Add a"]);
+        std::fs::write(dir.path().join("a.txt"), "one\n2\nthree\n").unwrap();
+        git(&["commit", "-q", "-am", "Change a"]);
+
+        let hashes: Vec<String> = get_commits_between(dir.path(), &[], None, &CommitFilter::default())
+            .unwrap()
+            .into_iter()
+            .map(|(hash, _, _)| hash)
+            .chain(["0000000000000000000000000000000000000000".to_string()])
+            .collect();
+        let measured = commit_diff_stats(dir.path(), &hashes).unwrap();
+        // The first commit is measured against nothing; unknown commits are left out
+        assert_eq!(
+            measured,
+            vec![
+                (
+                    hashes[0].clone(),
+                    DiffStats {
+                        files_changed: 1,
+                        insertions: 3,
+                        deletions: 0,
+                    }
+                ),
+                (
+                    hashes[1].clone(),
+                    DiffStats {
+                        files_changed: 1,
+                        insertions: 1,
+                        deletions: 1,
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_stats_filters_commits() {
+        let dir = tempfile::tempdir().unwrap();
+        let git = |args: &[&str]| {
+            let status = Command::new("git").arg("-C").arg(dir.path()).args(args).status().unwrap();
+            assert!(status.success(), "git {:?} failed", args);
+        };
+        git(&["init", "-q"]);
+        git(&["config", "user.name", "Me"]);
+        git(&["config", "user.email", "me@example.com"]);
+        git(&["commit", "-q", "--allow-empty", "-m", "Start"]);
+        std::fs::write(dir.path().join("mine.txt"), "one\ntwo\n").unwrap();
+        git(&["add", "mine.txt"]);
+        git(&["commit", "-q", "-m", "ABC-1 Mine"]);
+        std::fs::write(dir.path().join("deps.txt"), "one\n").unwrap();
+        git(&["add", "deps.txt"]);
+        git(&["-c", "user.email=bot@example.com", "commit", "-q", "-m", "Bump deps"]);
+
+        let stats = |filter: &CommitFilter| diff_stats(dir.path(), "HEAD~2", "HEAD", filter).unwrap();
+        assert_eq!(stats(&CommitFilter::default()).files_changed, 2);
+        let own = CommitFilter {
+            own_only: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            stats(&own),
+            DiffStats {
+                files_changed: 1,
+                insertions: 2,
+                deletions: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_normalize_remote_url() {
+        let expected = "github.com/acme/api";
+        assert_eq!(normalize_remote_url("git@github.com:acme/api.git"), expected);
+        assert_eq!(normalize_remote_url("https://github.com/acme/api"), expected);
+        assert_eq!(normalize_remote_url("https://user@GitHub.com/acme/api.git/"), expected);
+        assert_eq!(normalize_remote_url("ssh://git@github.com:22/acme/api.git"), expected);
+        assert_ne!(normalize_remote_url("git@github.com:acme/web.git"), expected);
+
+        // Digits in the owner or repository are part of the name
+        let expected = "github.com/1password/web3-app";
+        assert_eq!(normalize_remote_url("git@github.com:1password/web3-app.git"), expected);
+        assert_eq!(normalize_remote_url("https://github.com/1password/web3-app"), expected);
+        assert_eq!(normalize_remote_url("ssh://git@github.com:22/1password/web3-app.git"), expected);
+    }
 }
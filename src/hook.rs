@@ -0,0 +1,169 @@
+use serde::{Deserialize, Serialize};
+use std::io::{IsTerminal, Read};
+
+/// JSON payload Claude Code pipes to hook commands (and the statusline command) on stdin
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct HookPayload {
+    pub session_id: Option<String>,
+    pub cwd: Option<String>,
+    pub hook_event_name: Option<String>,
+    /// Model the session runs on: a plain id or an object with an `id`
+    pub model: Option<serde_json::Value>,
+    /// Set when the hook fires inside a subagent
+    pub agent_type: Option<String>,
+    pub tool_name: Option<String>,
+    pub tool_input: Option<serde_json::Value>,
+    /// Only in statusline input
+    pub workspace: Option<Workspace>,
+}
+
+/// Directories of the session, as sent to the statusline command
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Workspace {
+    pub current_dir: Option<String>,
+    pub project_dir: Option<String>,
+}
+
+impl HookPayload {
+    /// Read the payload from stdin if one was piped in
+    ///
+    /// Hooks must never fail because of an unexpected payload, so anything
+    /// unreadable is treated as an empty payload.
+    pub fn from_stdin() -> Self {
+        let stdin = std::io::stdin();
+        if stdin.is_terminal() {
+            return Self::default();
+        }
+
+        let mut input = String::new();
+        if stdin.lock().read_to_string(&mut input).is_err() {
+            return Self::default();
+        }
+
+        Self::parse(&input)
+    }
+
+    /// Parse a payload, falling back to an empty one on invalid JSON
+    pub fn parse(input: &str) -> Self {
+        serde_json::from_str(input).unwrap_or_default()
+    }
+
+    /// Short event type stored with heartbeats, derived from the hook event name
+    pub fn event_type(&self) -> Option<&'static str> {
+        match self.hook_event_name.as_deref()? {
+            "UserPromptSubmit" => Some("prompt-submit"),
+            "PreToolUse" | "PostToolUse" => Some("tool-use"),
+            "Notification" => Some("notification"),
+            "SessionStart" => Some("session-start"),
+            "Stop" | "SubagentStop" => Some("stop"),
+            _ => None,
+        }
+    }
+
+    /// Id of the Claude model, if the payload reports one
+    pub fn model(&self) -> Option<&str> {
+        let model = self.model.as_ref()?;
+        model.as_str().or_else(|| model.get("id")?.as_str())
+    }
+
+    /// Agent behind the event: "main", or the subagent's type
+    pub fn agent(&self) -> Option<String> {
+        if let Some(agent) = &self.agent_type {
+            return Some(agent.clone());
+        }
+
+        if self.tool_name.as_deref() == Some("Task") {
+            let subagent = self.tool_input.as_ref()?.get("subagent_type")?.as_str()?;
+            return Some(subagent.to_string());
+        }
+
+        match self.hook_event_name.as_deref()? {
+            "SubagentStop" => Some("subagent".to_string()),
+            _ => Some("main".to_string()),
+        }
+    }
+
+    /// Directory the session works in: the workspace's project directory, else the cwd
+    pub fn directory(&self) -> Option<&str> {
+        let workspace = self.workspace.as_ref();
+        workspace
+            .and_then(|w| w.project_dir.as_deref().or(w.current_dir.as_deref()))
+            .or(self.cwd.as_deref())
+    }
+
+    /// File path touched by the tool call (Edit, Write, Read, NotebookEdit, ...)
+    pub fn file_path(&self) -> Option<&str> {
+        let input = self.tool_input.as_ref()?;
+
+        ["file_path", "notebook_path", "path"]
+            .iter()
+            .find_map(|key| input.get(key).and_then(|v| v.as_str()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_post_tool_use_payload() {
+        let payload = HookPayload::parse(
+            r#"{
+                "session_id": "abc123",
+                "cwd": "/repo",
+                "hook_event_name": "PostToolUse",
+                "tool_name": "Edit",
+                "tool_input": { "file_path": "/repo/src/main.rs", "old_string": "a" }
+            }"#,
+        );
+
+        assert_eq!(payload.session_id.as_deref(), Some("abc123"));
+        assert_eq!(payload.cwd.as_deref(), Some("/repo"));
+        assert_eq!(payload.event_type(), Some("tool-use"));
+        assert_eq!(payload.tool_name.as_deref(), Some("Edit"));
+        assert_eq!(payload.file_path(), Some("/repo/src/main.rs"));
+    }
+
+    #[test]
+    fn test_model_and_agent() {
+        let payload = HookPayload::parse(
+            r#"{"hook_event_name": "SessionStart", "model": {"id": "claude-opus-4", "display_name": "Opus"}}"#,
+        );
+        assert_eq!(payload.model(), Some("claude-opus-4"));
+        assert_eq!(payload.agent().as_deref(), Some("main"));
+
+        let payload = HookPayload::parse(
+            r#"{
+                "hook_event_name": "PostToolUse",
+                "model": "claude-sonnet-4",
+                "tool_name": "Task",
+                "tool_input": { "subagent_type": "code-reviewer", "prompt": "..." }
+            }"#,
+        );
+        assert_eq!(payload.model(), Some("claude-sonnet-4"));
+        assert_eq!(payload.agent().as_deref(), Some("code-reviewer"));
+    }
+
+    #[test]
+    fn test_statusline_directory() {
+        let payload = HookPayload::parse(
+            r#"{
+                "session_id": "abc123",
+                "cwd": "/repo/src",
+                "model": {"id": "claude-opus-4", "display_name": "Opus"},
+                "workspace": {"current_dir": "/repo/src", "project_dir": "/repo"}
+            }"#,
+        );
+        assert_eq!(payload.directory(), Some("/repo"));
+        assert_eq!(HookPayload::parse(r#"{"cwd": "/repo/src"}"#).directory(), Some("/repo/src"));
+    }
+
+    #[test]
+    fn test_parse_invalid_payload() {
+        let payload = HookPayload::parse("not json");
+        assert!(payload.file_path().is_none());
+        assert!(payload.event_type().is_none());
+        assert!(payload.model().is_none());
+        assert!(payload.agent().is_none());
+    }
+}
@@ -0,0 +1,195 @@
+use anyhow::{bail, Context, Result};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use tracing::debug;
+
+/// A JSON request to a web API, sent with the `curl` command line tool
+///
+/// Options go to curl on stdin rather than as arguments, so tokens don't show
+/// up in the process list.
+#[derive(Debug, Clone)]
+pub struct Request {
+    method: &'static str,
+    url: String,
+    headers: Vec<String>,
+    user: Option<String>,
+    /// Provider string for curl's AWS Signature Version 4 signing
+    aws_sigv4: Option<String>,
+    body: Option<String>,
+    timeout_seconds: Option<u64>,
+}
+
+impl Request {
+    pub fn get(url: impl Into<String>) -> Self {
+        Self::new("GET", url.into())
+    }
+
+    pub fn post(url: impl Into<String>, body: &impl Serialize) -> Result<Self> {
+        Self::new("POST", url.into()).json(body)
+    }
+
+    pub fn put(url: impl Into<String>, body: &impl Serialize) -> Result<Self> {
+        Self::new("PUT", url.into()).json(body)
+    }
+
+    pub fn patch(url: impl Into<String>, body: &impl Serialize) -> Result<Self> {
+        Self::new("PATCH", url.into()).json(body)
+    }
+
+    /// A request with any method, such as WebDAV's `PROPFIND`
+    pub fn new(method: &'static str, url: impl Into<String>) -> Self {
+        Self {
+            method,
+            url: url.into(),
+            headers: vec!["Accept: application/json".to_string()],
+            user: None,
+            aws_sigv4: None,
+            body: None,
+            timeout_seconds: None,
+        }
+    }
+
+    fn json(mut self, body: &impl Serialize) -> Result<Self> {
+        self.body = Some(serde_json::to_string(body).context("Failed to encode request body")?);
+        Ok(self.header("Content-Type", "application/json"))
+    }
+
+    /// Send `body` as is
+    pub fn text(mut self, content_type: &str, body: String) -> Self {
+        self.body = Some(body);
+        self.header("Content-Type", content_type)
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push(format!("{}: {}", name, value));
+        self
+    }
+
+    pub fn bearer_auth(self, token: &str) -> Self {
+        self.header("Authorization", &format!("Bearer {}", token))
+    }
+
+    pub fn basic_auth(mut self, user: &str, password: &str) -> Self {
+        self.user = Some(format!("{}:{}", user, password));
+        self
+    }
+
+    /// Sign the request for S3 (or a compatible store) in `region`
+    pub fn aws_sigv4(mut self, region: &str, access_key_id: &str, secret_access_key: &str) -> Self {
+        self.aws_sigv4 = Some(format!("aws:amz:{}:s3", region));
+        self.user = Some(format!("{}:{}", access_key_id, secret_access_key));
+        self
+    }
+
+    /// Give up when the request takes longer than this
+    pub fn timeout(mut self, seconds: u64) -> Self {
+        self.timeout_seconds = Some(seconds);
+        self
+    }
+
+    /// Send the request and decode the JSON response (`null` when it has no body)
+    pub fn send<T: DeserializeOwned>(self) -> Result<T> {
+        let body = self.send_text()?;
+        let body = if body.trim().is_empty() { "null" } else { &body };
+        serde_json::from_str(body).with_context(|| format!("Unexpected response from {}", self.url))
+    }
+
+    /// Send the request and return the response body as is
+    pub fn send_text(&self) -> Result<String> {
+        debug!("{} {}", self.method, self.url);
+        let mut child = Command::new("curl")
+            .args(["--silent", "--show-error", "--config", "-"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context("Failed to run curl (is it installed?)")?;
+        child
+            .stdin
+            .take()
+            .context("Failed to open curl's stdin")?
+            .write_all(self.curl_config().as_bytes())
+            .context("Failed to pass the request to curl")?;
+        let output = child.wait_with_output().context("Failed to run curl")?;
+        if !output.status.success() {
+            bail!(
+                "{} {} failed: {}",
+                self.method,
+                self.url,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let (status, body) = parse_output(&stdout)?;
+        if !(200..300).contains(&status) {
+            bail!("{} {} returned HTTP {}: {}", self.method, self.url, status, body.trim());
+        }
+        Ok(body.to_string())
+    }
+
+    /// curl options for the request, in curl's config file syntax
+    fn curl_config(&self) -> String {
+        let mut config = format!("request = {}\nurl = {}\n", quote(self.method), quote(&self.url));
+        for header in &self.headers {
+            config.push_str(&format!("header = {}\n", quote(header)));
+        }
+        if let Some(ref user) = self.user {
+            config.push_str(&format!("user = {}\n", quote(user)));
+        }
+        if let Some(ref provider) = self.aws_sigv4 {
+            config.push_str(&format!("aws-sigv4 = {}\n", quote(provider)));
+        }
+        if let Some(ref body) = self.body {
+            config.push_str(&format!("data-binary = {}\n", quote(body)));
+        }
+        if let Some(seconds) = self.timeout_seconds {
+            config.push_str(&format!("max-time = {}\n", seconds));
+        }
+        // The status code goes on a line of its own after the body
+        config.push_str("write-out = \"\\n%{http_code}\"\n");
+        config
+    }
+}
+
+/// Split curl's output into the status code and the response body
+fn parse_output(output: &str) -> Result<(u16, &str)> {
+    let (body, status) = output.rsplit_once('\n').unwrap_or(("", output));
+    let status = status.trim().parse().with_context(|| format!("Unexpected curl output: {}", output))?;
+    Ok((status, body))
+}
+
+/// Quote a value for curl's config file syntax
+fn quote(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+        .replace('\t', "\\t");
+    format!("\"{}\"", escaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_curl_config() {
+        let body = serde_json::json!({"comment": "say \"hi\""});
+        let request = Request::post("https://jira.example.com/worklog", &body)
+            .unwrap()
+            .basic_auth("me@example.com", "secret");
+        let config = request.curl_config();
+        assert!(config.starts_with("request = \"POST\"\nurl = \"https://jira.example.com/worklog\"\n"));
+        assert!(config.contains("header = \"Content-Type: application/json\"\n"));
+        assert!(config.contains("user = \"me@example.com:secret\"\n"));
+        assert!(config.contains("data-binary = \"{\\\"comment\\\":\\\"say \\\\\\\"hi\\\\\\\"\\\"}\"\n"));
+
+        assert_eq!(parse_output("{\"id\":1}\n201").unwrap(), (201, "{\"id\":1}"));
+        assert_eq!(parse_output("\n204").unwrap(), (204, ""));
+        assert!(parse_output("<html>").is_err());
+    }
+}
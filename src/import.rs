@@ -0,0 +1,319 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+
+use crate::config::{self, ColumnMapping, ImportSettings, Timezone};
+use crate::db::Database;
+use crate::models::Project;
+use crate::storage::Storage;
+
+/// Local time formats tried when the mapping names none
+const TIME_FORMATS: &[&str] = &[
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%d %H:%M",
+    "%Y-%m-%dT%H:%M:%S",
+    "%m/%d/%Y %I:%M:%S %p",
+    "%m/%d/%Y %I:%M %p",
+    "%m/%d/%Y %H:%M:%S",
+    "%m/%d/%Y %H:%M",
+];
+
+/// A time entry read from another tracker's export
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExternalEntry {
+    pub project: String,
+    pub description: Option<String>,
+    pub start: DateTime<Utc>,
+    pub seconds: i64,
+}
+
+/// What an import added
+#[derive(Debug, Default)]
+pub struct ImportStats {
+    /// Sessions added
+    pub imported: Vec<i64>,
+    /// Entries imported before
+    pub known: usize,
+    /// External projects matching no tracker project, whose entries were skipped
+    pub unmapped: BTreeSet<String>,
+}
+
+/// Columns of Toggl Track's detailed report CSV
+pub fn toggl_columns() -> ColumnMapping {
+    ColumnMapping {
+        project: "Project".to_string(),
+        description: "Description".to_string(),
+        start: "Start date".to_string(),
+        start_time: Some("Start time".to_string()),
+        end: "End date".to_string(),
+        end_time: Some("End time".to_string()),
+        duration: "Duration".to_string(),
+        time_format: None,
+    }
+}
+
+/// Columns of Clockify's detailed report CSV
+pub fn clockify_columns() -> ColumnMapping {
+    ColumnMapping {
+        duration: "Duration (h)".to_string(),
+        ..toggl_columns()
+    }
+}
+
+/// Time entries of a CSV file, or of a JSON array of objects when `json` is set
+///
+/// Local times are read in `timezone`.
+pub fn parse_entries(content: &str, json: bool, columns: &ColumnMapping, timezone: Timezone) -> Result<Vec<ExternalEntry>> {
+    let rows = if json { json_rows(content)? } else { csv_rows(content)? };
+
+    let mut entries = Vec::new();
+    for (index, row) in rows.iter().enumerate() {
+        let entry = parse_row(row, columns, timezone).with_context(|| format!("Entry {}", index + 1))?;
+        if entry.seconds > 0 {
+            entries.push(entry);
+        }
+    }
+    Ok(entries)
+}
+
+/// Rows by lowercase column name
+fn csv_rows(content: &str) -> Result<Vec<HashMap<String, String>>> {
+    let mut reader = csv::Reader::from_reader(content.trim_start_matches('\u{feff}').as_bytes());
+    let headers: Vec<String> = reader
+        .headers()
+        .context("Failed to read the CSV header")?
+        .iter()
+        .map(|h| h.trim().to_lowercase())
+        .collect();
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = record.context("Failed to read a CSV row")?;
+        rows.push(headers.iter().cloned().zip(record.iter().map(String::from)).collect());
+    }
+    Ok(rows)
+}
+
+/// Objects of a JSON array by lowercase key, with numbers as text
+fn json_rows(content: &str) -> Result<Vec<HashMap<String, String>>> {
+    let objects: Vec<serde_json::Map<String, serde_json::Value>> =
+        serde_json::from_str(content).context("Expected a JSON array of objects")?;
+    Ok(objects
+        .into_iter()
+        .map(|object| {
+            object
+                .into_iter()
+                .filter_map(|(key, value)| {
+                    let text = match value {
+                        serde_json::Value::Null => return None,
+                        serde_json::Value::String(text) => text,
+                        other => other.to_string(),
+                    };
+                    Some((key.to_lowercase(), text))
+                })
+                .collect()
+        })
+        .collect())
+}
+
+fn parse_row(row: &HashMap<String, String>, columns: &ColumnMapping, timezone: Timezone) -> Result<ExternalEntry> {
+    let field = |column: &str| row.get(&column.to_lowercase()).map(|v| v.trim()).filter(|v| !v.is_empty());
+    let time = |date: &str, time: &Option<String>| -> Option<String> {
+        let date = field(date)?;
+        Some(match time.as_deref().and_then(field) {
+            Some(time) => format!("{} {}", date, time),
+            None => date.to_string(),
+        })
+    };
+    let format = columns.time_format.as_deref();
+
+    let start = time(&columns.start, &columns.start_time)
+        .with_context(|| format!("No start time in column {}", columns.start))?;
+    let start = parse_time(&start, format, timezone)?;
+    let seconds = match time(&columns.end, &columns.end_time) {
+        Some(end) => (parse_time(&end, format, timezone)? - start).num_seconds(),
+        None => {
+            let duration = field(&columns.duration)
+                .with_context(|| format!("Neither an end nor a duration in columns {} and {}", columns.end, columns.duration))?;
+            parse_duration(duration)?
+        }
+    };
+
+    Ok(ExternalEntry {
+        project: field(&columns.project).unwrap_or_default().to_string(),
+        description: field(&columns.description).map(String::from),
+        start,
+        seconds,
+    })
+}
+
+/// An RFC 3339 instant, or a local time in `format` (else one of the usual formats)
+fn parse_time(value: &str, format: Option<&str>, timezone: Timezone) -> Result<DateTime<Utc>> {
+    if let Ok(instant) = DateTime::parse_from_rfc3339(value) {
+        return Ok(instant.with_timezone(&Utc));
+    }
+    let local = match format {
+        Some(format) => NaiveDateTime::parse_from_str(value, format).ok(),
+        None => TIME_FORMATS.iter().find_map(|format| NaiveDateTime::parse_from_str(value, format).ok()),
+    };
+    local
+        .map(|local| timezone.instant_at(local))
+        .with_context(|| format!("Unrecognized time: {} (set import.columns.time_format)", value))
+}
+
+/// Seconds, or H:MM:SS / H:MM
+fn parse_duration(value: &str) -> Result<i64> {
+    if let Ok(seconds) = value.parse::<f64>() {
+        return Ok(seconds.round() as i64);
+    }
+    let parts: Vec<i64> = value
+        .split(':')
+        .map(|part| part.parse::<i64>())
+        .collect::<Result<_, _>>()
+        .with_context(|| format!("Unrecognized duration: {}", value))?;
+    match parts[..] {
+        [hours, minutes, seconds] => Ok(hours * 3600 + minutes * 60 + seconds),
+        [hours, minutes] => Ok(hours * 3600 + minutes * 60),
+        _ => anyhow::bail!("Unrecognized duration: {}", value),
+    }
+}
+
+/// Add the entries as completed sessions flagged with their source
+///
+/// An entry goes to the project `import.projects` maps its project name to,
+/// else to the tracker project with that name or directory name (ignoring
+/// case). The description becomes the work item; entries without one are
+/// filed under a branch named after the source.
+pub fn import(db: &Database, settings: &ImportSettings, source: &str, entries: &[ExternalEntry]) -> Result<ImportStats> {
+    let mut stats = ImportStats::default();
+    let known_projects = db.list_projects(true)?;
+    let mut projects: HashMap<String, Option<Project>> = HashMap::new();
+
+    db.atomically(|| {
+        for entry in entries {
+            if !projects.contains_key(&entry.project) {
+                let project = find_project(db, settings, &known_projects, &entry.project)?;
+                projects.insert(entry.project.clone(), project);
+            }
+            let Some(project) = &projects[&entry.project] else {
+                stats.unmapped.insert(entry.project.clone());
+                continue;
+            };
+
+            let session = db.import_session(
+                project.id,
+                source,
+                source,
+                entry.description.as_deref(),
+                entry.start,
+                entry.seconds,
+            )?;
+            match session {
+                Some(session) => stats.imported.push(session.id),
+                None => stats.known += 1,
+            }
+        }
+        Ok(())
+    })?;
+
+    Ok(stats)
+}
+
+fn find_project(db: &Database, settings: &ImportSettings, known: &[Project], name: &str) -> Result<Option<Project>> {
+    if let Some(path) = settings.projects.get(name) {
+        let path = config::expand_path(path)?;
+        let path = path.canonicalize().unwrap_or(path);
+        return db.get_or_create_project(&path.to_string_lossy(), None, None, None).map(Some);
+    }
+    let name = name.to_lowercase();
+    let matches = |project: &&Project| {
+        let directory = Path::new(&project.path).file_name().map(|d| d.to_string_lossy().to_lowercase());
+        project.name().to_lowercase() == name || directory.as_deref() == Some(name.as_str())
+    };
+    Ok(known.iter().find(matches).cloned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn utc() -> Timezone {
+        Timezone::parse("UTC").unwrap()
+    }
+
+    #[test]
+    fn test_parse_toggl_csv() {
+        let csv = "\u{feff}User,Email,Client,Project,Task,Description,Billable,Start date,Start time,End date,End time,Duration,Tags\n\
+                   Ann,ann@example.com,Acme,Acme API,,ABC-1 login,Yes,2025-01-15,09:00:00,2025-01-15,10:30:00,01:30:00,\n\
+                   Ann,ann@example.com,Acme,Acme API,,,Yes,2025-01-15,11:00:00,2025-01-15,11:00:00,00:00:00,\n";
+        let entries = parse_entries(csv, false, &toggl_columns(), utc()).unwrap();
+        assert_eq!(
+            entries,
+            vec![ExternalEntry {
+                project: "Acme API".to_string(),
+                description: Some("ABC-1 login".to_string()),
+                start: "2025-01-15T09:00:00Z".parse().unwrap(),
+                seconds: 5400,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_parse_mapped_entries() {
+        let columns = ColumnMapping {
+            project: "Client project".to_string(),
+            start: "Began".to_string(),
+            duration: "Spent".to_string(),
+            time_format: Some("%d.%m.%Y %H:%M".to_string()),
+            ..ColumnMapping::default()
+        };
+        let csv = "client project,began,spent\nweb,15.01.2025 09:00,1:15\n";
+        let entries = parse_entries(csv, false, &columns, Timezone::parse("Asia/Taipei").unwrap()).unwrap();
+        assert_eq!(entries[0].start, "2025-01-15T01:00:00Z".parse::<DateTime<Utc>>().unwrap());
+        assert_eq!(entries[0].seconds, 4500);
+        assert_eq!(entries[0].description, None);
+
+        let json = r#"[{"project": "web", "start": "2025-01-15T09:00:00+01:00", "duration": 600, "description": null}]"#;
+        let entries = parse_entries(json, true, &ColumnMapping::default(), utc()).unwrap();
+        assert_eq!(entries[0].start, "2025-01-15T08:00:00Z".parse::<DateTime<Utc>>().unwrap());
+        assert_eq!(entries[0].seconds, 600);
+
+        let error = parse_entries("project,start\nweb,soon\n", false, &ColumnMapping::default(), utc());
+        assert!(matches!(error, Err(e) if format!("{:#}", e).contains("Unrecognized time: soon")));
+    }
+
+    #[test]
+    fn test_import() {
+        let dir = tempdir().unwrap();
+        let db = Database::open(&dir.path().join("test.db")).unwrap();
+        let api = db.get_or_create_project("/src/acme-api", None, None, None).unwrap();
+        let settings = ImportSettings {
+            projects: [("Website".to_string(), "/src/web".to_string())].into(),
+            ..ImportSettings::default()
+        };
+        let entry = |project: &str, description: Option<&str>| ExternalEntry {
+            project: project.to_string(),
+            description: description.map(String::from),
+            start: "2025-01-15T09:00:00Z".parse().unwrap(),
+            seconds: 3600,
+        };
+        let entries = vec![entry("Acme-API", Some("ABC-1")), entry("Website", None), entry("Other", None)];
+
+        let stats = import(&db, &settings, "toggl", &entries).unwrap();
+        assert_eq!((stats.imported.len(), stats.known), (2, 0));
+        assert_eq!(stats.unmapped, BTreeSet::from(["Other".to_string()]));
+
+        let start = "2025-01-01T00:00:00Z".parse().unwrap();
+        let end = "2025-02-01T00:00:00Z".parse().unwrap();
+        let sessions = db.get_sessions_in_range(start, end, Some(api.id)).unwrap();
+        assert_eq!(sessions[0].work_item.as_deref(), Some("ABC-1"));
+        assert_eq!(sessions[0].active_seconds, Some(3600));
+        assert_eq!(sessions[0].imported_from.as_deref(), Some("toggl"));
+        assert!(db.get_project_by_path("/src/web").unwrap().is_some());
+
+        // Importing the same file again adds nothing
+        let stats = import(&db, &settings, "toggl", &entries).unwrap();
+        assert_eq!((stats.imported.len(), stats.known), (0, 2));
+    }
+}
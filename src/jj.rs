@@ -0,0 +1,265 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tracing::debug;
+
+use crate::git::{self, CommitFilter, GitInfo};
+use crate::models::DiffStats;
+use crate::vcs::{self, CommitEntry, Vcs};
+
+/// Template printing one tab-separated line per commit for `parse_log`
+const LOG_TEMPLATE: &str = r#"commit_id ++ "\t" ++ author.email() ++ "\t" ++ parents.len() ++ "\t" ++ committer.timestamp().utc().format("%Y-%m-%dT%H:%M:%SZ") ++ "\t" ++ description.first_line() ++ "\n""#;
+
+/// Number of commits listed when a session has no start commit
+const MAX_COMMITS: &str = "100";
+
+/// Whether a path is the root of a jj repository, native or colocated with git
+pub fn is_jj_repo(path: &Path) -> bool {
+    path.join(".jj").is_dir()
+}
+
+/// Closest directory at or above `path` holding a jj repository
+pub fn find_root(path: &Path) -> Option<PathBuf> {
+    path.ancestors().find(|dir| is_jj_repo(dir)).map(Path::to_path_buf)
+}
+
+/// A Jujutsu repository, read through the `jj` CLI
+pub struct Jujutsu {
+    path: PathBuf,
+}
+
+/// A commit as listed by `LOG_TEMPLATE`
+#[derive(Debug, Clone, PartialEq)]
+struct LogEntry {
+    commit_id: String,
+    author_email: String,
+    parents: usize,
+    committed_at: Option<DateTime<Utc>>,
+    description: String,
+}
+
+impl Jujutsu {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+        }
+    }
+
+    fn run(&self, args: &[&str]) -> Result<String> {
+        let output = Command::new("jj")
+            .args(["--no-pager", "--color", "never"])
+            .args(args)
+            .current_dir(&self.path)
+            .output()
+            .context("Failed to run jj")?;
+
+        if !output.status.success() {
+            anyhow::bail!(
+                "jj {} failed: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// All remotes as (name, url), in the order jj lists them
+    fn remotes(&self) -> Vec<(String, String)> {
+        let Ok(output) = self.run(&["git", "remote", "list"]) else {
+            return Vec::new();
+        };
+        output
+            .lines()
+            .filter_map(|line| {
+                let (name, url) = line.split_once(' ')?;
+                Some((name.to_string(), url.trim().to_string()))
+            })
+            .collect()
+    }
+}
+
+impl Vcs for Jujutsu {
+    fn info(&self, remote_name: Option<&str>) -> Result<GitInfo> {
+        let output = self.run(&["log", "-r", "@", "--no-graph", "-T", r#"change_id.short() ++ "\t" ++ commit_id"#])?;
+        let (change_id, commit_id) = output
+            .trim()
+            .split_once('\t')
+            .context("Unexpected jj log output")?;
+
+        let remote_urls = git::order_remotes(self.remotes(), &[remote_name, Some("origin")]);
+
+        debug!("jj info for {}: change {}, commit {}", self.path.display(), change_id, commit_id);
+
+        Ok(GitInfo {
+            branch: change_id.to_string(),
+            head_commit: Some(commit_id.to_string()),
+            remote_url: remote_urls.first().cloned(),
+            remote_urls,
+        })
+    }
+
+    fn current_branch(&self) -> Result<String> {
+        // Skip the working copy snapshot: heartbeats only need the change id
+        let output = self.run(&["--ignore-working-copy", "log", "-r", "@", "--no-graph", "-T", "change_id.short()"])?;
+        Ok(output.trim().to_string())
+    }
+
+    fn commits_between(
+        &self,
+        stop_at: &[&str],
+        end_commit: Option<&str>,
+        filter: &CommitFilter,
+    ) -> Result<Vec<CommitEntry>> {
+        let end = end_commit.unwrap_or("@");
+        let revset = if stop_at.is_empty() {
+            format!("::{}", end)
+        } else {
+            format!("({})..{}", stop_at.join(" | "), end)
+        };
+        let output = self.run(&["log", "-r", &revset, "--no-graph", "--limit", MAX_COMMITS, "-T", LOG_TEMPLATE])?;
+
+        let authors = filter.authors(&self.path, || self.run(&["config", "get", "user.email"]).ok());
+
+        let mut commits: Vec<CommitEntry> = parse_log(&output)
+            .into_iter()
+            // Changes without a description (like the working copy) aren't commits yet
+            .filter(|entry| !entry.description.is_empty())
+            .filter(|entry| !(filter.exclude_merges && entry.parents > 1))
+            .filter(|entry| authors.keeps(&entry.author_email))
+            .filter(|entry| !filter.excludes_message(&entry.description))
+            .map(|entry| (entry.commit_id, entry.description, entry.committed_at))
+            .collect();
+
+        // jj lists newest first
+        commits.reverse();
+        Ok(commits)
+    }
+
+    fn diff_stats(&self, start_commit: &str, end_commit: &str, filter: &CommitFilter) -> Result<DiffStats> {
+        if !filter.keeps_all() {
+            let commits: Vec<String> = self
+                .commits_between(&[start_commit], Some(end_commit), filter)?
+                .into_iter()
+                .map(|(hash, _, _)| hash)
+                .collect();
+            return Ok(DiffStats::total(self.commit_diff_stats(&commits)?));
+        }
+        let output = self.run(&["diff", "--from", start_commit, "--to", end_commit, "--stat"])?;
+        Ok(vcs::parse_diff_summary(&output).unwrap_or_default())
+    }
+
+    fn commit_diff_stats(&self, commits: &[String]) -> Result<Vec<(String, DiffStats)>> {
+        if commits.is_empty() {
+            return Ok(Vec::new());
+        }
+        // One log lists every commit's id followed by its diffstat
+        let revset = commits.join(" | ");
+        let output = self.run(&["log", "-r", &revset, "--no-graph", "--stat", "-T", r#"commit_id ++ "\n""#])?;
+        Ok(parse_stat_log(&output, commits))
+    }
+
+    fn uncommitted_changes(&self) -> Result<DiffStats> {
+        // The working copy is a change of its own
+        let output = self.run(&["diff", "-r", "@", "--stat"])?;
+        Ok(vcs::parse_diff_summary(&output).unwrap_or_default())
+    }
+
+    fn branch_checkouts(&self, _since: DateTime<Utc>, _until: DateTime<Utc>) -> Result<Vec<(DateTime<Utc>, String)>> {
+        // Every change is its own "branch" in jj, so switches show up in the heartbeats already
+        Ok(Vec::new())
+    }
+
+    fn list_branches(&self) -> Result<Vec<String>> {
+        let output = self.run(&["bookmark", "list", "-T", r#"name ++ "\n""#])?;
+        Ok(output.lines().map(str::trim).filter(|l| !l.is_empty()).map(String::from).collect())
+    }
+
+    fn branch_description(&self, _branch: &str) -> Option<String> {
+        // Changes carry their description in the commit message; there is nothing separate
+        None
+    }
+}
+
+fn parse_log(output: &str) -> Vec<LogEntry> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(5, '\t');
+            Some(LogEntry {
+                commit_id: fields.next()?.to_string(),
+                author_email: fields.next()?.to_string(),
+                parents: fields.next()?.parse().ok()?,
+                committed_at: DateTime::parse_from_rfc3339(fields.next()?)
+                    .ok()
+                    .map(|dt| dt.with_timezone(&Utc)),
+                description: fields.next().unwrap_or_default().trim().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Split `jj log --stat` output into the diffstat of each of `commits`
+fn parse_stat_log(output: &str, commits: &[String]) -> Vec<(String, DiffStats)> {
+    let mut measured: Vec<(String, String)> = Vec::new();
+    for line in output.lines() {
+        match commits.iter().find(|commit| *commit == line.trim()) {
+            Some(commit) => measured.push((commit.clone(), String::new())),
+            None => {
+                if let Some((_, stat)) = measured.last_mut() {
+                    stat.push_str(line);
+                    stat.push('\n');
+                }
+            }
+        }
+    }
+    measured
+        .into_iter()
+        .map(|(commit, stat)| (commit, vcs::parse_diff_summary(&stat).unwrap_or_default()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_log() {
+        let output = "abc123\tme@example.com\t1\t2025-01-15T10:00:00Z\tABC-1 Add login\n\
+                      def456\tme@example.com\t2\t2025-01-15T11:00:00Z\tMerge\n\
+                      789abc\tme@example.com\t1\t2025-01-15T12:00:00Z\t\n";
+        let entries = parse_log(output);
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].commit_id, "abc123");
+        assert_eq!(entries[0].description, "ABC-1 Add login");
+        assert_eq!(entries[1].parents, 2);
+        assert_eq!(
+            entries[0].committed_at.unwrap().to_rfc3339(),
+            "2025-01-15T10:00:00+00:00"
+        );
+        assert!(entries[2].description.is_empty());
+    }
+
+    #[test]
+    fn test_parse_stat_log() {
+        let commits = vec!["abc123".to_string(), "def456".to_string()];
+        let output = "def456\n\
+                      src/main.rs | 3 ++-\n\
+                      1 file changed, 2 insertions(+), 1 deletion(-)\n\
+                      abc123\n\
+                      0 files changed, 0 insertions(+), 0 deletions(-)\n";
+        let measured = parse_stat_log(output, &commits);
+        assert_eq!(measured.len(), 2);
+        assert_eq!(measured[0].0, "def456");
+        assert_eq!(
+            measured[0].1,
+            DiffStats {
+                files_changed: 1,
+                insertions: 2,
+                deletions: 1,
+            }
+        );
+        assert_eq!(measured[1], ("abc123".to_string(), DiffStats::default()));
+    }
+}
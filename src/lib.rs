@@ -0,0 +1,91 @@
+//! Claude Code time tracking, as used by the `claude-time-tracker` binary
+//!
+//! Other crates can write reports in formats of their own: implement
+//! [`ReportFormat`] and add it to a [`FormatRegistry`]. The remaining modules
+//! are public for the binary and may change between releases.
+//!
+//! ```
+//! use claude_time_tracker::{FormatRegistry, MonthlyReport, RenderOptions, ReportFormat};
+//! use std::io::Write;
+//!
+//! struct Total;
+//!
+//! impl ReportFormat for Total {
+//!     fn name(&self) -> &'static str {
+//!         "total"
+//!     }
+//!     fn extension(&self) -> &'static str {
+//!         "txt"
+//!     }
+//!     fn content_type(&self) -> &'static str {
+//!         "text/plain"
+//!     }
+//!     fn generate(&self, report: &MonthlyReport, _: RenderOptions, writer: &mut dyn Write) -> anyhow::Result<()> {
+//!         writeln!(writer, "{}", report.total_seconds)?;
+//!         Ok(())
+//!     }
+//! }
+//!
+//! let mut registry = FormatRegistry::default();
+//! registry.register(Total);
+//! assert!(registry.names().contains(&"total"));
+//! ```
+
+#[doc(hidden)]
+pub mod activitywatch;
+#[doc(hidden)]
+pub mod claude_settings;
+#[doc(hidden)]
+pub mod cli;
+#[doc(hidden)]
+pub mod cloud;
+#[doc(hidden)]
+pub mod config;
+#[doc(hidden)]
+pub mod daemon;
+#[doc(hidden)]
+pub mod db;
+#[doc(hidden)]
+pub mod events;
+#[doc(hidden)]
+pub mod git;
+#[doc(hidden)]
+pub mod hook;
+#[doc(hidden)]
+pub mod http;
+#[doc(hidden)]
+pub mod import;
+#[doc(hidden)]
+pub mod jj;
+#[doc(hidden)]
+pub mod logging;
+#[doc(hidden)]
+pub mod merge;
+pub mod models;
+#[doc(hidden)]
+pub mod pull_request;
+#[doc(hidden)]
+pub mod push;
+pub mod report;
+#[doc(hidden)]
+pub mod secrets;
+#[doc(hidden)]
+pub mod server;
+#[doc(hidden)]
+pub mod service;
+#[doc(hidden)]
+pub mod slack;
+pub mod storage;
+#[doc(hidden)]
+pub mod sync;
+#[doc(hidden)]
+pub mod template;
+#[doc(hidden)]
+pub mod tracker;
+#[doc(hidden)]
+pub mod vcs;
+#[doc(hidden)]
+pub mod webhook;
+
+pub use models::MonthlyReport;
+pub use report::format::{FormatRegistry, RenderOptions, ReportFormat};
@@ -0,0 +1,51 @@
+use std::fs::OpenOptions;
+use std::io::IsTerminal;
+use std::path::Path;
+use std::sync::Mutex;
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::{fmt, prelude::*};
+
+/// Log file name, created next to the database when `settings.log_to_file` is enabled
+pub const LOG_FILE_NAME: &str = "claude-time-tracker.log";
+
+/// Set up logging to stderr (by verbosity) and optionally to a file
+///
+/// The log file records at least debug-level events, so hook runs that lose
+/// time still leave a trace even though their stderr is never seen.
+pub fn init(verbosity: u8, quiet: bool, log_file: Option<&Path>) {
+    let stderr_level = if quiet {
+        LevelFilter::ERROR
+    } else {
+        match verbosity {
+            0 => LevelFilter::INFO,
+            1 => LevelFilter::DEBUG,
+            _ => LevelFilter::TRACE,
+        }
+    };
+
+    let stderr_layer = fmt::layer()
+        .with_writer(std::io::stderr)
+        .with_ansi(std::io::stderr().is_terminal())
+        .without_time()
+        .with_target(false)
+        .with_filter(stderr_level);
+
+    let file_layer = log_file
+        .and_then(|path| {
+            if let Some(parent) = path.parent() {
+                let _ = std::fs::create_dir_all(parent);
+            }
+            OpenOptions::new().create(true).append(true).open(path).ok()
+        })
+        .map(|file| {
+            fmt::layer()
+                .with_writer(Mutex::new(file))
+                .with_ansi(false)
+                .with_filter(stderr_level.max(LevelFilter::DEBUG))
+        });
+
+    tracing_subscriber::registry()
+        .with(stderr_layer)
+        .with(file_layer)
+        .init();
+}
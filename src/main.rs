@@ -1,46 +1,156 @@
-mod cli;
-mod config;
-mod db;
-mod git;
-mod models;
-mod report;
-mod tracker;
-
 use anyhow::{Context, Result};
-use chrono::Utc;
+use chrono::{NaiveDate, Utc};
 use clap::Parser;
+use rayon::prelude::*;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 
-use cli::{Cli, Commands, ConfigAction, ProjectsAction};
-use config::EffectiveConfig;
+use claude_time_tracker::{
+    activitywatch, claude_settings, cli, cloud, config, daemon, db, events, git, hook, import, logging, merge, models, push, report, secrets, server, service, slack, storage, sync, template, tracker, vcs, webhook,
+};
+use cli::{
+    Cli, Commands, ConfigAction, DaemonAction, DbAction, ExportFormat, HooksAction, ImportSource, NotifyArgs, NotifyTarget, ProjectField, ProjectsAction, PushArgs, PushTarget, ReportArgs,
+    SessionsAction, SyncArgs, TrashAction,
+};
+use config::{EffectiveConfig, ReportPeriod};
 use db::Database;
+use events::Event;
+use storage::Storage;
+use hook::HookPayload;
+use report::format::{FormatRegistry, RenderOptions, ReportFormat};
+use template::Template;
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
 
-    match cli.command {
-        Commands::Start { path } => cmd_start(&path),
+    config::set_overrides(config::Overrides {
+        database_path: cli.db.clone(),
+        config_path: cli.config.clone(),
+    });
+
+    let log_file = EffectiveConfig::load(None)
+        .ok()
+        .filter(|c| c.log_to_file)
+        .and_then(|c| c.database_path.parent().map(|p| p.join(logging::LOG_FILE_NAME)));
+    logging::init(cli.verbose, cli.quiet, log_file.as_deref());
+
+    let result = match cli.command {
+        Commands::Start { path, work_item } => cmd_start(&path, work_item.as_deref()),
         Commands::Heartbeat { path } => cmd_heartbeat(&path),
         Commands::Stop { path } => cmd_stop(&path),
-        Commands::Report {
-            month,
-            project,
+        Commands::Report(args) => cmd_report(args),
+        Commands::Search { query, limit } => cmd_search(&query.join(" "), limit),
+        Commands::Init { path, force } => cmd_init(&path, force),
+        Commands::Status { include_archived, format } => cmd_status(include_archived, format.as_deref()),
+        Commands::Reap => cmd_reap(),
+        Commands::Abandon { session } => cmd_abandon(session),
+        Commands::Compact { older_than_days } => cmd_compact(older_than_days),
+        Commands::Rebuild { log, force } => cmd_rebuild(log, force),
+        Commands::Db { action } => match action {
+            DbAction::Merge { source } => cmd_db_merge(&source),
+        },
+        Commands::Sync { repo, url } => cmd_sync(repo, url),
+        Commands::Daemon { action, interval } => match action {
+            None => daemon::run(interval),
+            Some(DaemonAction::Install { interval }) => cmd_daemon_install(interval, cli.db.as_deref(), cli.config.as_deref()),
+            Some(DaemonAction::Uninstall) => cmd_daemon_uninstall(),
+        },
+        Commands::Statusline => cmd_statusline(),
+        Commands::Serve { port, token } => cmd_serve(port, token),
+        Commands::Push { target } => match target {
+            PushTarget::Jira(args) => cmd_push_jira(args),
+            PushTarget::Tempo(args) => cmd_push_tempo(args),
+            PushTarget::Gitlab(args) => cmd_push_gitlab(args),
+            PushTarget::Toggl(args) => cmd_push_toggl(args),
+            PushTarget::Clockify(args) => cmd_push_clockify(args),
+            PushTarget::Harvest(args) => cmd_push_harvest(args),
+        },
+        Commands::Notify { target } => match target {
+            NotifyTarget::Slack(args) => cmd_notify_slack(args),
+        },
+        Commands::Webhooks { deliver: true, .. } => webhook::deliver_from(std::io::stdin().lock()),
+        Commands::Webhooks { test, .. } => cmd_webhooks(test),
+        Commands::Export {
             format,
+            month,
+            since,
             output,
-            all_formats,
-        } => cmd_report(month, project, format, output, all_formats),
-        Commands::Status => cmd_status(),
+        } => match format {
+            Some(ExportFormat::Activitywatch) if since.is_some() => {
+                anyhow::bail!("--since applies to bundles only")
+            }
+            // clap can't make --month conflict with one value of --format
+            Some(ExportFormat::Bundle) if month.is_some() => {
+                anyhow::bail!("--month applies to activitywatch exports only; bundles take --since")
+            }
+            Some(ExportFormat::Activitywatch) => cmd_export_activitywatch(month.as_deref(), output.as_deref()),
+            Some(ExportFormat::Bundle) | None => cmd_export_bundle(since, output.as_deref()),
+        },
+        // Without --from, clap has required --bundle
+        Commands::Import { from, file, .. } => match from {
+            None => cmd_import_bundle(&file),
+            Some(ImportSource::Activitywatch) => cmd_import_activitywatch(&file),
+            Some(ImportSource::Toggl) => cmd_import_entries("toggl", Some(import::toggl_columns()), &file),
+            Some(ImportSource::Clockify) => cmd_import_entries("clockify", Some(import::clockify_columns()), &file),
+            Some(ImportSource::GenericCsv) => cmd_import_entries("generic-csv", None, &file),
+        },
+        Commands::Cron { force } => cmd_cron(force),
         Commands::Config { action } => match action {
             ConfigAction::Init => cmd_config_init(),
             ConfigAction::Edit => cmd_config_edit(),
             ConfigAction::Show => cmd_config_show(),
+            ConfigAction::Get { key } => cmd_config_get(&key),
+            ConfigAction::Set { key, value } => cmd_config_set(&key, &value),
         },
         Commands::Projects { action } => match action {
-            ProjectsAction::List => cmd_projects_list(),
+            ProjectsAction::List { include_archived } => cmd_projects_list(include_archived),
             ProjectsAction::SetName { path, name } => cmd_projects_set_name(&path, &name),
+            ProjectsAction::SetPattern { path, patterns } => cmd_projects_set_pattern(&path, &patterns),
+            ProjectsAction::Set { path, field, value } => cmd_projects_set(&path, field, &value),
+            ProjectsAction::SetRate {
+                path,
+                rate,
+                from,
+                currency,
+            } => cmd_projects_set_rate(&path, rate, from, currency.as_deref()),
+            ProjectsAction::UnsetRate { path, from } => cmd_projects_unset_rate(&path, from),
+            ProjectsAction::Show { path } => cmd_projects_show(&path),
+            ProjectsAction::Stats { top, include_archived } => cmd_projects_stats(top, include_archived),
+            ProjectsAction::Archive { path } => cmd_projects_archive(&path, true),
+            ProjectsAction::Unarchive { path } => cmd_projects_archive(&path, false),
+            ProjectsAction::Delete { path, purge } => cmd_projects_delete(&path, purge),
+            ProjectsAction::Alias { path, alias } => cmd_projects_alias(&path, &alias),
+            ProjectsAction::Move {
+                old_path,
+                new_path,
+                keep_alias,
+            } => cmd_projects_move(&old_path, &new_path, keep_alias),
+        },
+        Commands::Sessions { action } => match action {
+            SessionsAction::List { limit } => cmd_sessions_list(limit),
+            SessionsAction::Show { id } => cmd_sessions_show(id),
+            SessionsAction::Delete { id, purge } => cmd_sessions_delete(id, purge),
+            SessionsAction::SetWorkItem { id, work_item } => cmd_sessions_set_work_item(id, &work_item),
+        },
+        Commands::Trash { action } => match action {
+            TrashAction::List => cmd_trash_list(),
+            TrashAction::Restore { session, project } => cmd_trash_restore(session, project.as_deref()),
+        },
+        Commands::Hooks { action } => match action {
+            HooksAction::Install { scope, tool_use } => cmd_hooks_install(scope, tool_use),
+            HooksAction::Status => cmd_hooks_status(),
+            HooksAction::Uninstall { scope } => cmd_hooks_uninstall(scope),
         },
+    };
+
+    if let Err(ref e) = result {
+        tracing::debug!("Command failed: {:#}", e);
     }
+
+    result
 }
 
 fn get_db() -> Result<Database> {
@@ -48,235 +158,1751 @@ fn get_db() -> Result<Database> {
     Database::open(&config.database_path)
 }
 
-fn cmd_start(path: &str) -> Result<()> {
-    let project_path = PathBuf::from(path).canonicalize()
-        .with_context(|| format!("Invalid path: {}", path))?;
-
+/// Project root and configuration for a hook, or `None` if the directory is excluded
+fn hook_target(path: &str) -> Result<Option<(PathBuf, EffectiveConfig)>> {
+    let project_path = project_root(path)?;
     let config = EffectiveConfig::load(Some(&project_path))?;
+
+    // Both the directory the hook ran in and its repository can be excluded
+    let dir = PathBuf::from(path).canonicalize().unwrap_or_else(|_| project_path.clone());
+    if config.is_excluded(&dir) || config.is_excluded(&project_path) {
+        tracing::debug!("Not tracking {}: it matches settings.exclude_paths", dir.display());
+        return Ok(None);
+    }
+
+    Ok(Some((project_path, config)))
+}
+
+/// Hand a hook to the daemon if one is running, otherwise write the database directly
+fn run_hook(config: &EffectiveConfig, request: daemon::Request) -> Result<()> {
+    if let Some(result) = daemon::send(&daemon::socket_path(&config.database_path), &request) {
+        return result;
+    }
     let db = Database::open(&config.database_path)?;
+    daemon::apply(&db, config, &request)
+}
+
+fn cmd_start(path: &str, work_item: Option<&str>) -> Result<()> {
+    let Some((project_path, config)) = hook_target(path)? else {
+        return Ok(());
+    };
 
-    tracker::start_session(&db, &project_path, &config)
+    let payload = HookPayload::from_stdin();
+    let request = daemon::Request::Start {
+        project_path,
+        work_item: work_item.map(String::from),
+        payload,
+    };
+    run_hook(&config, request)
 }
 
 fn cmd_heartbeat(path: &str) -> Result<()> {
-    let project_path = PathBuf::from(path).canonicalize()
-        .with_context(|| format!("Invalid path: {}", path))?;
-
-    let config = EffectiveConfig::load(Some(&project_path))?;
-    let db = Database::open(&config.database_path)?;
+    let Some((project_path, config)) = hook_target(path)? else {
+        return Ok(());
+    };
 
-    tracker::record_heartbeat(&db, &project_path)
+    let payload = HookPayload::from_stdin();
+    let request = daemon::Request::Heartbeat {
+        project_path,
+        payload,
+        received_at: None,
+        branch: None,
+    };
+    run_hook(&config, request)
 }
 
 fn cmd_stop(path: &str) -> Result<()> {
-    let project_path = PathBuf::from(path).canonicalize()
-        .with_context(|| format!("Invalid path: {}", path))?;
-
-    let config = EffectiveConfig::load(Some(&project_path))?;
-    let db = Database::open(&config.database_path)?;
+    let Some((project_path, config)) = hook_target(path)? else {
+        return Ok(());
+    };
 
-    tracker::stop_session(&db, &project_path, &config)
+    let payload = HookPayload::from_stdin();
+    run_hook(&config, daemon::Request::Stop { project_path, payload })
 }
 
-fn cmd_report(
-    month: Option<String>,
-    project_filter: Option<String>,
-    format: String,
-    output: Option<String>,
-    all_formats: bool,
-) -> Result<()> {
+fn cmd_report(args: ReportArgs) -> Result<()> {
     let config = EffectiveConfig::load(None)?;
+    if args.schema {
+        let name = args.format.as_deref().unwrap_or(&config.default_format);
+        let schema = match FormatRegistry::default().get(name) {
+            Some(format) => format.schema()?,
+            None => anyhow::bail!("Unknown format: {}", name),
+        };
+        println!("{}", schema.with_context(|| format!("Format {} has no schema", name))?);
+        return Ok(());
+    }
     let db = Database::open(&config.database_path)?;
 
-    // Parse month
-    let (year, month_num) = if let Some(ref m) = month {
-        report::parse_month(m)?
-    } else {
-        report::current_month()
-    };
+    let (year, month_num) = report_month(args.month.as_deref(), &config)?;
 
     // Generate report data
-    let report_data = report::generate_report(
-        &db,
-        year,
-        month_num,
-        project_filter.as_deref(),
-        config.max_commits_per_item,
-    )?;
+    let options = report::ReportOptions {
+        project_filter: args.project,
+        machine: args.machine,
+        include_archived: args.include_archived,
+        include_modules: args.modules,
+        group_by_client: args.by_client,
+        metric: args.metric,
+        attribution: args.attribution.unwrap_or(config.attribution),
+        include_active: args.include_active,
+        ..report::ReportOptions::from_config(&config)
+    };
+    let mut report_data = report::generate_report(&db, year, month_num, &options)?;
+
+    // Redacted reports never carry commit messages
+    let render_options = RenderOptions {
+        include_commits: config.include_commits && args.redact.is_none(),
+        include_diff_stats: args.diff_stats,
+    };
+    if let Some(profile) = args.redact {
+        report::redact::apply(&mut report_data, profile);
+    }
 
     // Determine formats to output
-    let formats: Vec<&str> = if all_formats {
-        vec!["md", "csv", "tsv", "json"]
+    let registry = FormatRegistry::default();
+    let formats: Vec<&str> = if args.all_formats {
+        registry.names()
     } else {
-        format.split(',').map(|s| s.trim()).collect()
+        args.format
+            .as_deref()
+            .unwrap_or(&config.default_format)
+            .split(',')
+            .map(|s| s.trim())
+            .collect()
     };
 
     let multiple_formats = formats.len() > 1;
+    let output = match args.output {
+        Some(output) => Some(output),
+        None if multiple_formats => match config.report_output_dir {
+            Some(ref dir) => {
+                fs::create_dir_all(dir)
+                    .with_context(|| format!("Failed to create report directory: {}", dir.display()))?;
+                Some(dir.join(&report_data.period).to_string_lossy().to_string())
+            }
+            None => None,
+        },
+        None => None,
+    };
 
-    // Generate and output reports
+    // Resolve each format and where it goes
+    let mut targets = Vec::new();
     for fmt in formats {
-        let content = match fmt {
-            "md" | "markdown" => report::markdown::generate(&report_data, config.include_commits),
-            "csv" => report::csv::generate_string(&report_data, config.include_commits)?,
-            "tsv" => report::tsv::generate_string(&report_data, config.include_commits)?,
-            "json" => report::json::generate(&report_data)?,
-            _ => {
-                eprintln!("Unknown format: {}", fmt);
-                continue;
-            }
+        let Some(format) = registry.get(fmt) else {
+            eprintln!("Unknown format: {} (available: {})", fmt, registry.names().join(", "));
+            continue;
         };
+        let ext = format.extension();
 
-        if let Some(ref base_path) = output {
-            let ext = match fmt {
-                "md" | "markdown" => "md",
-                "csv" => "csv",
-                "tsv" => "tsv",
-                "json" => "json",
-                _ => fmt,
-            };
-            let file_path = if multiple_formats {
+        let file_path = output.as_ref().map(|base_path| {
+            if multiple_formats {
                 format!("{}.{}", base_path, ext)
             } else if base_path.ends_with(&format!(".{}", ext)) {
                 base_path.clone()
             } else {
                 format!("{}.{}", base_path, ext)
-            };
+            }
+        });
+        targets.push((format, file_path));
+    }
 
-            fs::write(&file_path, &content)
-                .with_context(|| format!("Failed to write report to {}", file_path))?;
-            eprintln!("Report written to: {}", file_path);
-        } else {
-            println!("{}", content);
+    // Generate all formats in parallel; what goes to stdout is kept until then
+    let results: Vec<Result<Vec<u8>>> = targets
+        .par_iter()
+        .map(|(format, file_path)| match file_path {
+            Some(file_path) => write_report(*format, &report_data, render_options, Path::new(file_path)).map(|()| Vec::new()),
+            None => {
+                let mut content = Vec::new();
+                format.generate(&report_data, render_options, &mut content).map(|()| content)
+            }
+        })
+        .collect();
+
+    // Output in the order the formats were given
+    for ((_, file_path), result) in targets.iter().zip(results) {
+        let content = result?;
+        match file_path {
+            Some(file_path) => eprintln!("Report written to: {}", file_path),
+            None => {
+                let mut stdout = std::io::BufWriter::new(std::io::stdout().lock());
+                stdout.write_all(&content)?;
+                writeln!(stdout)?;
+                stdout.flush()?;
+            }
         }
     }
 
     Ok(())
 }
 
-fn cmd_status() -> Result<()> {
-    let config = EffectiveConfig::load(None)?;
+fn write_report(format: &dyn ReportFormat, report: &models::MonthlyReport, options: RenderOptions, path: &Path) -> Result<()> {
+    let file = fs::File::create(path).with_context(|| format!("Failed to write report to {}", path.display()))?;
+    let mut writer = std::io::BufWriter::new(file);
+    format
+        .generate(report, options, &mut writer)
+        .and_then(|()| Ok(writer.flush()?))
+        .with_context(|| format!("Failed to write report to {}", path.display()))
+}
+
+/// Month given on the command line, else the one `report.default_period` points at
+fn report_month(month: Option<&str>, config: &EffectiveConfig) -> Result<(i32, u32)> {
+    match month {
+        Some(month) => report::parse_month(month),
+        None => Ok(match config.default_period {
+            ReportPeriod::CurrentMonth => report::current_month(config.calendar),
+            ReportPeriod::LastMonth => report::previous_month(config.calendar),
+        }),
+    }
+}
+
+/// Report of the month and projects to push
+fn push_report(args: &PushArgs, config: &EffectiveConfig) -> Result<models::MonthlyReport> {
     let db = Database::open(&config.database_path)?;
 
-    let active_sessions = db.get_all_active_sessions()?;
+    let (year, month) = report_month(args.month.as_deref(), config)?;
+    let options = report::ReportOptions {
+        project_filter: args.project.clone(),
+        ..report::ReportOptions::from_config(config)
+    };
+    report::generate_report(&db, year, month, &options)
+}
 
-    if active_sessions.is_empty() {
-        println!("No active tracking sessions.");
-        return Ok(());
+/// Worklogs per Jira issue and day for a month, or `None` after printing them for a dry run
+fn issue_worklogs(args: PushArgs, config: &EffectiveConfig) -> Result<Option<(String, Vec<push::jira::Worklog>)>> {
+    let report_data = push_report(&args, config)?;
+    let (worklogs, skipped) = push::jira::plan(&push::daily_entries(&report_data), config.calendar);
+
+    if !skipped.is_empty() {
+        let skipped: Vec<&str> = skipped.iter().map(|s| s.as_str()).collect();
+        eprintln!("Not Jira issues, left out: {}", skipped.join(", "));
+    }
+    if args.dry_run {
+        for worklog in &worklogs {
+            println!("{}  {:<12} {}", worklog.date, worklog.issue, tracker::format_duration(worklog.seconds));
+        }
+        println!("{} worklogs for {} (dry run, nothing sent)", worklogs.len(), report_data.period);
+        return Ok(None);
     }
+    Ok(Some((report_data.period, worklogs)))
+}
 
-    println!("Active tracking sessions:\n");
+fn cmd_push_jira(args: PushArgs) -> Result<()> {
+    let config = EffectiveConfig::load(None)?;
+    let Some((period, worklogs)) = issue_worklogs(args, &config)? else {
+        return Ok(());
+    };
 
-    for session in active_sessions {
-        let project = db.get_project_by_id(session.project_id)?;
-        let heartbeats = db.get_heartbeats(session.id)?;
+    let client = push::jira::Client::from_settings(&config.jira)?;
+    let stats = push::jira::push(&client, &worklogs)?;
+    println!(
+        "Jira worklogs for {}: {} created, {} updated, {} unchanged",
+        period, stats.created, stats.updated, stats.unchanged
+    );
+    Ok(())
+}
+
+fn cmd_push_tempo(args: PushArgs) -> Result<()> {
+    let config = EffectiveConfig::load(None)?;
+    let Some((period, worklogs)) = issue_worklogs(args, &config)? else {
+        return Ok(());
+    };
 
-        let elapsed = calculate_active_time_with_current(&heartbeats, config.idle_timeout_minutes);
+    let jira = push::jira::Client::from_settings(&config.jira)?;
+    let client = push::tempo::Client::from_settings(&config.tempo, &jira)?;
+    let stats = push::tempo::push(&client, &jira, &worklogs)?;
+    println!(
+        "Tempo worklogs for {}: {} created, {} updated, {} unchanged",
+        period, stats.created, stats.updated, stats.unchanged
+    );
+    Ok(())
+}
 
-        println!(
-            "  Project: {}",
-            project.display_name.as_deref().unwrap_or(&project.path)
-        );
-        println!("  Branch:  {}", session.branch);
-        println!("  Started: {}", session.started_at);
-        println!("  Active:  {}", tracker::format_duration(elapsed));
-        println!();
+fn cmd_push_gitlab(args: PushArgs) -> Result<()> {
+    let config = EffectiveConfig::load(None)?;
+    let report_data = push_report(&args, &config)?;
+    let (logs, skipped) = push::gitlab::plan(&report_data, config.calendar);
+
+    if !skipped.is_empty() {
+        let skipped: Vec<&str> = skipped.iter().map(|s| s.as_str()).collect();
+        eprintln!("Not GitLab issues, left out: {}", skipped.join(", "));
+    }
+    if args.dry_run {
+        for log in &logs {
+            println!(
+                "{}  {:<24} {}",
+                log.date,
+                push::gitlab::issue_name(log),
+                tracker::format_duration(log.seconds)
+            );
+        }
+        println!("{} time logs for {} (dry run, nothing sent)", logs.len(), report_data.period);
+        return Ok(());
     }
 
+    let stats = push::gitlab::push(&logs)?;
+    println!(
+        "GitLab time logs for {}: {} created, {} updated, {} unchanged",
+        report_data.period, stats.created, stats.updated, stats.unchanged
+    );
     Ok(())
 }
 
-/// Calculate active time including time since last heartbeat (for status display)
-fn calculate_active_time_with_current(heartbeats: &[models::Heartbeat], idle_timeout_minutes: u32) -> i64 {
-    if heartbeats.is_empty() {
-        return 0;
+fn cmd_push_harvest(args: PushArgs) -> Result<()> {
+    let config = EffectiveConfig::load(None)?;
+    let report_data = push_report(&args, &config)?;
+
+    let db = Database::open(&config.database_path)?;
+    let options = report::ReportOptions::from_config(&config);
+    let mut tasks = HashMap::new();
+    for project in &report_data.projects {
+        let Some(stored) = db.get_project_by_path(&project.path)? else {
+            continue;
+        };
+        if let Some(task) = report::configured_project(&db, &stored, &options.projects)?.harvest {
+            tasks.insert(project.path.clone(), task);
+        }
+    }
+    let (entries, unmapped) = push::harvest::plan(&report_data, &tasks);
+
+    if !unmapped.is_empty() {
+        let unmapped: Vec<&str> = unmapped.iter().map(|s| s.as_str()).collect();
+        eprintln!("No Harvest task configured, left out: {}", unmapped.join(", "));
+    }
+    if args.dry_run {
+        for entry in &entries {
+            let projects: Vec<&str> = entry.projects.iter().map(|s| s.as_str()).collect();
+            println!(
+                "{}  {:<24} {:>5.2}h  (task {} of project {})",
+                entry.date,
+                projects.join(", "),
+                entry.hours(),
+                entry.task.task_id,
+                entry.task.project_id
+            );
+        }
+        println!("{} time entries for {} (dry run, nothing sent)", entries.len(), report_data.period);
+        return Ok(());
     }
 
-    let timeout_seconds = (idle_timeout_minutes as i64) * 60;
-    let mut total_seconds: i64 = 0;
+    let client = push::harvest::Client::from_settings(&config.harvest)?;
+    let stats = push::harvest::push(&client, &entries)?;
+    println!(
+        "Harvest time entries for {}: {} created, {} updated, {} unchanged",
+        report_data.period, stats.created, stats.updated, stats.unchanged
+    );
+    Ok(())
+}
 
-    for window in heartbeats.windows(2) {
-        let interval = (window[1].timestamp - window[0].timestamp).num_seconds();
+/// Completed sessions not pushed to `target` yet, or `None` when there is nothing to send
+///
+/// A dry run prints the entries and returns `None`.
+fn session_entries(
+    args: SyncArgs,
+    config: &EffectiveConfig,
+    db: &Database,
+    target: &str,
+) -> Result<Option<Vec<push::SessionEntry>>> {
+    let options = report::ReportOptions::from_config(config);
+    let since = args.since.map(|date| config.calendar.day_start(date));
+    let filter = args.project.map(|p| p.to_lowercase());
 
-        if interval <= timeout_seconds {
-            total_seconds += interval;
+    let mut names = HashMap::new();
+    let mut entries = Vec::new();
+    for session in db.get_unsynced_sessions(target, since)? {
+        if let Entry::Vacant(slot) = names.entry(session.project_id) {
+            let project = db.get_project_by_id(session.project_id)?;
+            let (name, _) = report::project_labels(db, &project, &options.projects)?;
+            let matches = filter.as_ref().is_none_or(|f| {
+                name.to_lowercase().contains(f) || project.path.to_lowercase().contains(f)
+            });
+            slot.insert(matches.then_some(name));
+        }
+        if let Some(Some(name)) = names.get(&session.project_id) {
+            entries.extend(push::SessionEntry::for_session(&session, name));
         }
     }
 
-    // Add time from last heartbeat to now (if within timeout)
-    if let Some(last) = heartbeats.last() {
-        let since_last = (Utc::now() - last.timestamp).num_seconds();
-        if since_last <= timeout_seconds {
-            total_seconds += since_last;
+    if args.dry_run {
+        for entry in &entries {
+            println!(
+                "{}  {:<16} {:<16} {}",
+                config.calendar.timezone.local_time(entry.start).format("%Y-%m-%d %H:%M"),
+                entry.project,
+                entry.description,
+                tracker::format_duration(entry.seconds)
+            );
         }
+        println!("{} time entries (dry run, nothing sent)", entries.len());
+        return Ok(None);
     }
+    if entries.is_empty() {
+        println!("No new sessions to push");
+        return Ok(None);
+    }
+    Ok(Some(entries))
+}
+
+fn cmd_push_toggl(args: SyncArgs) -> Result<()> {
+    let config = EffectiveConfig::load(None)?;
+    let db = Database::open(&config.database_path)?;
+    let Some(entries) = session_entries(args, &config, &db, push::toggl::TARGET)? else {
+        return Ok(());
+    };
 
-    total_seconds
+    let client = push::toggl::Client::from_settings(&config.toggl)?;
+    let stats = push::toggl::push(&client, &db, &entries)?;
+    println!("Toggl time entries: {} created", stats.created);
+    Ok(())
 }
 
-fn cmd_config_init() -> Result<()> {
-    let path = config::init_global_config()?;
-    println!("Configuration initialized at: {}", path.display());
+fn cmd_push_clockify(args: SyncArgs) -> Result<()> {
+    let config = EffectiveConfig::load(None)?;
+    let db = Database::open(&config.database_path)?;
+    let Some(entries) = session_entries(args, &config, &db, push::clockify::TARGET)? else {
+        return Ok(());
+    };
+
+    let client = push::clockify::Client::from_settings(&config.clockify)?;
+    let stats = push::clockify::push(&client, &db, &entries)?;
+    println!("Clockify time entries: {} created", stats.created);
     Ok(())
 }
 
-fn cmd_config_edit() -> Result<()> {
-    let path = config::global_config_path()?;
+fn cmd_notify_slack(args: NotifyArgs) -> Result<()> {
+    let config = EffectiveConfig::load(None)?;
+    let db = Database::open(&config.database_path)?;
 
-    if !path.exists() {
-        config::init_global_config()?;
+    let text = match args.session {
+        Some(id) => {
+            let session = db.get_session_by_id(id)?;
+            let project = db.get_project_by_id(session.project_id)?;
+            let (name, _) = report::project_labels(&db, &project, &config.projects)?;
+            slack::session_summary(&session, &name, db.get_commits(id)?.len())
+        }
+        None => {
+            let day = args.date.unwrap_or_else(|| config.calendar.today());
+            match slack::daily_summary(&db, &config, day)? {
+                Some(text) => text,
+                None => {
+                    println!("Nothing tracked on {}", day);
+                    return Ok(());
+                }
+            }
+        }
+    };
+
+    if args.dry_run {
+        println!("{}", text);
+        return Ok(());
     }
+    let url = slack::webhook_url(&config.slack)
+        .context("Set slack.webhook_url in the config or store it in the keychain (account `slack`)")?;
+    slack::post(&url, &text)?;
+    println!("Posted to Slack");
+    Ok(())
+}
 
-    let editor = std::env::var("EDITOR").unwrap_or_else(|_| "vim".to_string());
+fn cmd_webhooks(test: bool) -> Result<()> {
+    let config = EffectiveConfig::load(None)?;
+    if config.webhooks.is_empty() {
+        println!("No webhooks configured (add [[webhooks]] entries to the config)");
+        return Ok(());
+    }
 
-    std::process::Command::new(&editor)
-        .arg(&path)
-        .status()
-        .with_context(|| format!("Failed to open editor: {}", editor))?;
+    let payload = webhook::test_payload();
+    let mut failed = 0;
+    for hook in &config.webhooks {
+        let events: Vec<&str> = hook.events.iter().map(|e| e.as_str()).collect();
+        println!("{}  ({})", hook.url, events.join(", "));
+        if test {
+            match webhook::deliver(hook, &payload) {
+                Ok(()) => println!("  test delivered"),
+                Err(e) => {
+                    println!("  test failed: {:#}", e);
+                    failed += 1;
+                }
+            }
+        }
+    }
 
+    if failed > 0 {
+        anyhow::bail!("{} of {} webhooks failed", failed, config.webhooks.len());
+    }
     Ok(())
 }
 
-fn cmd_config_show() -> Result<()> {
-    let config = config::load_global_config()?;
-    let toml = toml::to_string_pretty(&config)?;
-    println!("{}", toml);
+fn cmd_export_activitywatch(month: Option<&str>, output: Option<&Path>) -> Result<()> {
+    let config = EffectiveConfig::load(None)?;
+    let db = Database::open(&config.database_path)?;
+    let (year, month) = report_month(month, &config)?;
+    let (start, end) = report::month_range(year, month, config.calendar)?;
+
+    let sessions = activity_spans(&db, &config, start, end)?;
+    let export = activitywatch::export(&sessions, &config.machine);
+    let content = serde_json::to_string_pretty(&export)?;
+    match output {
+        Some(path) => {
+            fs::write(path, content).with_context(|| format!("Failed to write {}", path.display()))?;
+            let events: usize = export.buckets.values().map(|b| b.events.len()).sum();
+            eprintln!("Exported {} events to {}", events, path.display());
+        }
+        None => println!("{}", content),
+    }
     Ok(())
 }
 
-fn cmd_projects_list() -> Result<()> {
-    let db = get_db()?;
-    let projects = db.list_projects()?;
+fn cmd_export_bundle(since: Option<chrono::DateTime<Utc>>, output: Option<&Path>) -> Result<()> {
+    let config = EffectiveConfig::load(None)?;
+    let db = Database::open(&config.database_path)?;
+    // Taken before reading, so a change made meanwhile is in the next bundle too
+    let now = Utc::now();
+    let (bundle, count) = sync::export_bundle(&db, since)?;
+    match output {
+        Some(path) => {
+            fs::write(path, bundle).with_context(|| format!("Failed to write {}", path.display()))?;
+            eprintln!("Exported {} sessions to {}", count, path.display());
+        }
+        None => print!("{}", bundle),
+    }
+    eprintln!("Next time, export with --since {}", now.to_rfc3339_opts(chrono::SecondsFormat::Secs, true));
+    Ok(())
+}
 
-    if projects.is_empty() {
-        println!("No tracked projects yet.");
+fn cmd_import_bundle(file: &Path) -> Result<()> {
+    let config = EffectiveConfig::load(None)?;
+    let db = Database::open(&config.database_path)?;
+    let content = fs::read_to_string(file).with_context(|| format!("Failed to read {}", file.display()))?;
+    let (added, updated) = sync::import_bundle(&db, &content, &file.display().to_string())?;
+    println!("Imported {} new and {} updated sessions", added, updated);
+    Ok(())
+}
+
+fn cmd_import_activitywatch(file: &Path) -> Result<()> {
+    let config = EffectiveConfig::load(None)?;
+    let db = Database::open(&config.database_path)?;
+    let content = fs::read_to_string(file).with_context(|| format!("Failed to read {}", file.display()))?;
+    let file_name = file.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let events = activitywatch::parse_window_events(&content, &file_name)?;
+    let (Some(first), Some(last)) = (events.first(), events.last()) else {
+        println!("No window events in {}", file.display());
         return Ok(());
+    };
+
+    let added = db.record_window_activity(&events)?;
+    println!("Imported {} window events ({} already known)", added, events.len() - added);
+
+    let start = config.calendar.day_start(config.calendar.day_of(first.timestamp));
+    let end = config.calendar.day_start(config.calendar.day_of(last.timestamp) + chrono::Days::new(1));
+    let windows = db.get_window_activity(start, end)?;
+    let claude: Vec<_> = activity_spans(&db, &config, start, end)?
+        .into_iter()
+        .flat_map(|(_, _, spans)| spans)
+        .collect();
+
+    println!();
+    println!("{:<12} {:>10} {:>10} {:>10}", "Day", "Claude", "Computer", "Overlap");
+    for day in activitywatch::compare(&claude, &windows, config.calendar) {
+        println!(
+            "{:<12} {:>10} {:>10} {:>10}",
+            day.day.to_string(),
+            tracker::format_duration(day.claude_seconds),
+            tracker::format_duration(day.window_seconds),
+            tracker::format_duration(day.overlap_seconds)
+        );
     }
+    Ok(())
+}
 
-    println!("Tracked projects:\n");
+/// Import another time tracker's entries as sessions
+///
+/// `columns` is the tracker's own layout; without it the `import.columns`
+/// mapping of the config is used.
+fn cmd_import_entries(source: &str, columns: Option<config::ColumnMapping>, file: &Path) -> Result<()> {
+    let config = EffectiveConfig::load(None)?;
+    let db = Database::open(&config.database_path)?;
+    let content = fs::read_to_string(file).with_context(|| format!("Failed to read {}", file.display()))?;
+    let json = file.extension().is_some_and(|e| e.eq_ignore_ascii_case("json"));
+    let columns = columns.unwrap_or_else(|| config.import.columns.clone());
 
-    for project in projects {
-        let name = project.display_name.as_deref().unwrap_or("-");
-        println!("  Path: {}", project.path);
-        println!("  Name: {}", name);
-        if let Some(ref remote) = project.git_remote {
-            println!("  Remote: {}", remote);
+    let entries = import::parse_entries(&content, json, &columns, config.calendar.timezone)
+        .with_context(|| format!("Failed to read {}", file.display()))?;
+    let stats = import::import(&db, &config.import, source, &entries)?;
+    events::log_all(&config, || stats.imported.iter().map(|&id| Event::session(&db, id)).collect());
+
+    println!("Imported {} entries ({} already imported)", stats.imported.len(), stats.known);
+    if !stats.unmapped.is_empty() {
+        let names: Vec<&str> = stats.unmapped.iter().map(|s| s.as_str()).collect();
+        println!("Skipped entries of projects not tracked here: {}", names.join(", "));
+        println!("Map them to a project path under [import.projects] in the config");
+    }
+    Ok(())
+}
+
+/// Sessions started in a range with their project name and active spans
+fn activity_spans(
+    db: &Database,
+    config: &EffectiveConfig,
+    start: chrono::DateTime<Utc>,
+    end: chrono::DateTime<Utc>,
+) -> Result<Vec<activitywatch::SessionSpans>> {
+    let mut names = HashMap::new();
+    let mut sessions = Vec::new();
+    for session in db.get_sessions_in_range(start, end, None)? {
+        if let Entry::Vacant(slot) = names.entry(session.project_id) {
+            slot.insert(db.get_project_by_id(session.project_id)?.name());
         }
-        println!();
+        let heartbeats = db.get_heartbeats(session.id)?;
+        let spans = tracker::active_spans(&heartbeats, config.idle_timeout_minutes, config.idle_grace_minutes);
+        let name = names[&session.project_id].clone();
+        sessions.push((session, name, spans));
+    }
+    Ok(sessions)
+}
+
+fn cmd_serve(port: u16, token: Option<String>) -> Result<()> {
+    let config = EffectiveConfig::load(None)?;
+    let token = token
+        .or(config.server.token)
+        .or_else(|| secrets::lookup("server"));
+    server::serve(port, token)
+}
+
+fn cmd_cron(force: bool) -> Result<()> {
+    let config = EffectiveConfig::load(None)?;
+    let db = Database::open(&config.database_path)?;
+
+    let (year, month) = report::previous_month(config.calendar);
+    let period = format!("{}-{:02}", year, month);
+
+    if !force && db.get_meta(CRON_LAST_PERIOD_KEY)?.as_deref() == Some(period.as_str()) {
+        return Ok(());
+    }
+
+    let report_data = report::generate_report(&db, year, month, &report::ReportOptions::from_config(&config))?;
+
+    let output_dir = config::expand_path(&config.cron.output_dir)?;
+    fs::create_dir_all(&output_dir)
+        .with_context(|| format!("Failed to create report directory: {}", output_dir.display()))?;
+
+    let registry = FormatRegistry::default();
+    let render_options = RenderOptions {
+        include_commits: config.include_commits,
+        include_diff_stats: false,
+    };
+    let mut written = Vec::new();
+    for fmt in &config.cron.formats {
+        let Some(format) = registry.get(fmt) else {
+            eprintln!("Unknown format: {} (available: {})", fmt, registry.names().join(", "));
+            continue;
+        };
+
+        let file_path = output_dir.join(format!("{}.{}", period, format.extension()));
+        write_report(format, &report_data, render_options, &file_path)?;
+        println!("Report written to: {}", file_path.display());
+        written.push(file_path);
+    }
+
+    db.set_meta(CRON_LAST_PERIOD_KEY, &period)?;
+
+    if config.cron.notify && !written.is_empty() {
+        notify(
+            "Claude time tracker",
+            &format!(
+                "Timesheet for {} is ready ({})",
+                period,
+                tracker::format_duration(report_data.total_seconds)
+            ),
+        );
     }
 
     Ok(())
 }
 
-fn cmd_projects_set_name(path: &str, name: &str) -> Result<()> {
+/// Meta key recording the last period `cron` generated a report for
+const CRON_LAST_PERIOD_KEY: &str = "cron.last_period";
+
+/// Show a desktop notification, ignoring failures (e.g. on headless machines)
+fn notify(title: &str, message: &str) {
+    let result = if cfg!(target_os = "macos") {
+        // Passed as arguments rather than spliced into the script, so no quoting is needed
+        std::process::Command::new("osascript")
+            .args(["-e", "on run argv"])
+            .args(["-e", "display notification (item 1 of argv) with title (item 2 of argv)"])
+            .args(["-e", "end run"])
+            .args([message, title])
+            .status()
+    } else {
+        std::process::Command::new("notify-send")
+            .arg(title)
+            .arg(message)
+            .status()
+    };
+
+    if result.is_err() {
+        eprintln!("{}: {}", title, message);
+    }
+}
+
+fn cmd_search(query: &str, limit: usize) -> Result<()> {
     let db = get_db()?;
 
-    let project_path = PathBuf::from(path).canonicalize()
-        .with_context(|| format!("Invalid path: {}", path))?;
+    let hits = db.search_sessions(query, limit)?;
 
-    let path_str = project_path.to_str().context("Invalid path")?;
+    if hits.is_empty() {
+        println!("No sessions match \"{}\".", query);
+        return Ok(());
+    }
 
-    db.get_or_create_project(path_str, None, Some(name), None)?;
+    for (session, snippet) in hits {
+        let project = db.get_project_by_id(session.project_id)?;
+        println!(
+            "{}  {:<8} {}  {}",
+            session.started_at.format("%Y-%m-%d"),
+            tracker::format_duration(session.active_seconds.unwrap_or(0)),
+            project.name(),
+            session.work_item.as_deref().unwrap_or(&session.branch)
+        );
+        println!("    {}", snippet.replace('\n', " "));
+    }
 
-    println!("Set display name for {} to: {}", path_str, name);
     Ok(())
 }
+
+fn cmd_init(path: &str, force: bool) -> Result<()> {
+    let project_path = project_root(path)?;
+    let config_path = project_path.join(config::PROJECT_CONFIG_FILE);
+
+    if let Some(existing) = config::find_project_config(&project_path).filter(|_| !force) {
+        anyhow::bail!(
+            "{} already exists (use --force to overwrite)",
+            existing.display()
+        );
+    }
+
+    let repo = vcs::open(&project_path);
+    let name = repo
+        .info(None)
+        .ok()
+        .and_then(|g| g.remote_url)
+        .and_then(|url| git::project_name_from_remote(&url));
+    // A pattern from the global config already applies to every project
+    let pattern = match config::load_global_config()?.settings.work_item_pattern {
+        Some(_) => None,
+        None => tracker::suggest_work_item_pattern(&repo.list_branches().unwrap_or_default()),
+    };
+
+    let content = config::project_config_template(name.as_deref(), pattern);
+    fs::write(&config_path, content)
+        .with_context(|| format!("Failed to write {}", config_path.display()))?;
+
+    println!("Created {}", config_path.display());
+    println!("  name:              {}", name.as_deref().unwrap_or("(not detected)"));
+    println!("  work_item_pattern: {}", pattern.unwrap_or("(not detected)"));
+    Ok(())
+}
+
+fn cmd_status(include_archived: bool, format: Option<&str>) -> Result<()> {
+    // Parsed first so a typo fails before touching the database
+    let template = format.map(|f| Template::parse(f, STATUS_PLACEHOLDERS)).transpose()?;
+    let config = EffectiveConfig::load(None)?;
+    let db = Database::open(&config.database_path)?;
+
+    let mut active_sessions = Vec::new();
+    for session in db.get_all_active_sessions()? {
+        let project = db.get_project_by_id(session.project_id)?;
+        if include_archived || !project.archived {
+            active_sessions.push((session, project));
+        }
+    }
+
+    if let Some(template) = template {
+        return print_status_lines(&db, &config, &template, &active_sessions);
+    }
+
+    if active_sessions.is_empty() {
+        println!("No active tracking sessions.");
+        return Ok(());
+    }
+
+    println!("Active tracking sessions:\n");
+
+    for (session, project) in active_sessions {
+        let elapsed = tracker::running_active_seconds(&db, &session, config.idle_timeout_minutes, config.idle_grace_minutes)?;
+
+        println!(
+            "  Project: {}",
+            project.name()
+        );
+        println!("  Branch:  {}", session.branch);
+        println!("  Started: {}", session.started_at);
+        println!("  Active:  {}", tracker::format_duration(elapsed));
+        println!("  Prompts: {}", db.count_prompts(session.id)?);
+        println!();
+    }
+
+    Ok(())
+}
+
+/// Placeholders of `status --format`
+const STATUS_PLACEHOLDERS: &[&str] = &["project", "branch", "work_item", "elapsed", "started", "prompts", "machine"];
+
+/// `status --format` output for prompts and status bars: one line per session, nothing when idle
+///
+/// Prompts are only counted when the template shows them.
+fn print_status_lines(
+    db: &Database,
+    config: &EffectiveConfig,
+    template: &Template,
+    sessions: &[(models::Session, models::Project)],
+) -> Result<()> {
+    for (session, project) in sessions {
+        let elapsed = if template.uses("elapsed") {
+            tracker::running_active_seconds(db, session, config.idle_timeout_minutes, config.idle_grace_minutes)?
+        } else {
+            0
+        };
+        let prompts = if template.uses("prompts") { db.count_prompts(session.id)? } else { 0 };
+
+        let line = template.render(|name| match name {
+            "project" => project.name(),
+            "branch" => session.branch.clone(),
+            "work_item" => session.work_item.clone().unwrap_or_else(|| session.branch.clone()),
+            "elapsed" => tracker::format_duration(elapsed),
+            "started" => config.calendar.timezone.local_time(session.started_at).format("%H:%M").to_string(),
+            "prompts" => prompts.to_string(),
+            _ => session.machine.clone().unwrap_or_default(),
+        });
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+fn cmd_statusline() -> Result<()> {
+    // A status line must never show an error, so anything unexpected prints nothing
+    match statusline_segment(&HookPayload::from_stdin()) {
+        Ok(Some(segment)) => println!("{}", segment),
+        Ok(None) => println!(),
+        Err(e) => {
+            tracing::debug!("No statusline segment: {:#}", e);
+            println!();
+        }
+    }
+    Ok(())
+}
+
+/// Today's time on the session's project and what it is being spent on, e.g. `⏱ 1h 23m · ABC-123`
+///
+/// Finished sessions count with their stored active time, the running one with
+/// its running total.
+fn statusline_segment(payload: &HookPayload) -> Result<Option<String>> {
+    let Some(directory) = payload.directory() else {
+        return Ok(None);
+    };
+    let project_path = project_root(directory)?;
+    let config = EffectiveConfig::load(Some(&project_path))?;
+    let db = Database::open(&config.database_path)?;
+    let Some(project) = db.get_project_by_path(&project_path.to_string_lossy())? else {
+        return Ok(None);
+    };
+
+    let today = config.calendar.day_start(config.calendar.today());
+    let mut seconds = db.finished_seconds_since(project.id, today)?;
+    let session = db.get_active_session(project.id, payload.session_id.as_deref())?;
+    if let Some(ref session) = session {
+        let (timeout, grace) = (config.idle_timeout_minutes, config.idle_grace_minutes);
+        seconds += if session.started_at >= today {
+            tracker::running_active_seconds(&db, session, timeout, grace)?
+        } else {
+            // Started before today: its time before then belongs to earlier days
+            tracker::running_active_seconds_since(&db, session, today, timeout, grace)?
+        };
+    }
+    if seconds == 0 && session.is_none() {
+        return Ok(None);
+    }
+
+    let mut segment = format!("⏱ {}", tracker::format_duration(seconds));
+    if let Some(session) = session {
+        segment.push_str(&format!(" · {}", session.work_item.unwrap_or(session.branch)));
+    }
+    Ok(Some(segment))
+}
+
+fn cmd_reap() -> Result<()> {
+    let config = EffectiveConfig::load(None)?;
+    let db = Database::open(&config.database_path)?;
+
+    let closed = tracker::close_abandoned_sessions(&db, &config)?;
+    println!("Closed {} abandoned session(s).", closed);
+
+    Ok(())
+}
+
+fn cmd_compact(older_than_days: u32) -> Result<()> {
+    let config = EffectiveConfig::load(None)?;
+    let db = Database::open(&config.database_path)?;
+
+    let (sessions, removed, written) = tracker::compact_sessions(&db, &config, older_than_days)?;
+    if sessions > 0 {
+        db.vacuum()?;
+    }
+    println!(
+        "Compacted {} session(s): {} heartbeats into {} intervals.",
+        sessions, removed, written
+    );
+
+    Ok(())
+}
+
+fn cmd_rebuild(log: Option<PathBuf>, force: bool) -> Result<()> {
+    let config = EffectiveConfig::load(None)?;
+    let log = log.unwrap_or_else(|| config.database_path.with_file_name(events::EVENT_LOG_FILE_NAME));
+    if !log.exists() {
+        anyhow::bail!("No event log at {} (enable settings.event_log to record one)", log.display());
+    }
+
+    let db_path = &config.database_path;
+    if db_path.exists() {
+        if !force {
+            anyhow::bail!("{} already exists; pass --force to replace it", db_path.display());
+        }
+        // Move the WAL files along (as <name>.bak-wal) so the old database stays consistent
+        for suffix in ["", "-wal", "-shm"] {
+            let file = PathBuf::from(format!("{}{}", db_path.display(), suffix));
+            if file.exists() {
+                let backup = PathBuf::from(format!("{}.bak{}", db_path.display(), suffix));
+                fs::rename(&file, &backup)
+                    .with_context(|| format!("Failed to move {} to {}", file.display(), backup.display()))?;
+            }
+        }
+        eprintln!("Previous database kept as {}.bak", db_path.display());
+    }
+
+    let db = Database::open(db_path)?;
+    let stats = events::replay(&log, &db)?;
+    println!(
+        "Rebuilt {} from {}: {} session(s), {} heartbeats ({} events skipped).",
+        db_path.display(),
+        log.display(),
+        stats.sessions,
+        stats.heartbeats,
+        stats.skipped
+    );
+
+    Ok(())
+}
+
+fn cmd_db_merge(source: &Path) -> Result<()> {
+    let config = EffectiveConfig::load(None)?;
+    let source = source
+        .canonicalize()
+        .with_context(|| format!("Invalid database path: {}", source.display()))?;
+    if config.database_path.canonicalize().is_ok_and(|path| path == source) {
+        anyhow::bail!("{} is the database in use; pass another file", source.display());
+    }
+    let db = Database::open(&config.database_path)?;
+
+    let stats = merge::merge(&db, &source)?;
+    println!(
+        "Merged {}: {} new project(s), {} matched, {} session(s) ({} already present).",
+        source.display(),
+        stats.projects_added,
+        stats.projects_matched,
+        stats.sessions,
+        stats.sessions_skipped
+    );
+
+    Ok(())
+}
+
+fn cmd_abandon(session_id: i64) -> Result<()> {
+    let config = EffectiveConfig::load(None)?;
+    let db = Database::open(&config.database_path)?;
+
+    let active_seconds = tracker::abandon_session(&db, session_id, &config)?;
+    println!(
+        "Abandoned session {} (active time: {})",
+        session_id,
+        tracker::format_duration(active_seconds)
+    );
+
+    Ok(())
+}
+
+/// Sync through `--repo`, `--url`, sync.url or sync.repo, in that order
+fn cmd_sync(repo: Option<PathBuf>, url: Option<String>) -> Result<()> {
+    let config = EffectiveConfig::load(None)?;
+    let db = Database::open(&config.database_path)?;
+
+    if let Some(url) = url.or_else(|| config.sync.url.clone()).filter(|_| repo.is_none()) {
+        let store = cloud::open(&url, &config.sync)?;
+        let stats = cloud::sync(&db, store.as_ref(), &url, &config.machine)?;
+        println!(
+            "Merged {} bundles from other machines: {} new, {} updated sessions",
+            stats.bundles, stats.added, stats.updated
+        );
+        match stats.bundle {
+            Some(bundle) => println!("Uploaded {} changed sessions as {}", stats.uploaded, bundle),
+            None => println!("Nothing changed here since the last upload"),
+        }
+        return Ok(());
+    }
+
+    let repo = match repo {
+        Some(repo) => repo,
+        None => config::expand_path(config.sync.repo.as_deref().context(
+            "Nowhere to sync: set sync.repo to a local git clone or sync.url to a cloud store",
+        )?)?,
+    };
+
+    let stats = sync::sync(&db, &repo, &config.machine)?;
+    println!(
+        "Merged from other machines: {} new, {} updated sessions",
+        stats.added, stats.updated
+    );
+    println!(
+        "Wrote {} sessions of {}{}",
+        stats.exported,
+        config.machine,
+        match (stats.committed, stats.remote) {
+            (true, true) => "; committed and pushed",
+            (true, false) => "; committed (no remote to push to)",
+            (false, _) => "; nothing changed",
+        }
+    );
+    Ok(())
+}
+
+/// Install the daemon as a service, passing on `--db` and `--config` given to this command
+fn cmd_daemon_install(interval: std::time::Duration, db: Option<&Path>, config_file: Option<&Path>) -> Result<()> {
+    let config = EffectiveConfig::load(None)?;
+    let cwd = std::env::current_dir()?;
+
+    let mut command = vec![current_binary()?];
+    for (flag, path) in [("--db", db), ("--config", config_file)] {
+        if let Some(path) = path {
+            command.push(flag.to_string());
+            command.push(cwd.join(path).to_string_lossy().into_owned());
+        }
+    }
+    command.extend(["daemon".to_string(), "--interval".to_string(), format!("{}s", interval.as_secs())]);
+
+    let spec = service::ServiceSpec {
+        command,
+        path_env: std::env::var("PATH").ok(),
+        log_file: config.database_path.with_file_name("daemon.log"),
+    };
+    let file = service::install(&spec)?;
+    println!("Installed {}", file.display());
+    println!("The daemon is running; check it with: {}", service::status_hint());
+    Ok(())
+}
+
+fn cmd_daemon_uninstall() -> Result<()> {
+    match service::uninstall()? {
+        Some(file) => println!("Stopped the daemon and removed {}", file.display()),
+        None => println!("The daemon service is not installed"),
+    }
+    Ok(())
+}
+
+fn cmd_config_init() -> Result<()> {
+    let path = config::init_global_config()?;
+    println!("Configuration initialized at: {}", path.display());
+    Ok(())
+}
+
+fn cmd_config_edit() -> Result<()> {
+    let path = config::global_config_path()?;
+
+    if !path.exists() {
+        config::init_global_config()?;
+    }
+
+    let editor = std::env::var("EDITOR").unwrap_or_else(|_| "vim".to_string());
+
+    std::process::Command::new(&editor)
+        .arg(&path)
+        .status()
+        .with_context(|| format!("Failed to open editor: {}", editor))?;
+
+    Ok(())
+}
+
+fn cmd_config_show() -> Result<()> {
+    let config = config::load_global_config()?;
+    let toml = toml::to_string_pretty(&config)?;
+    println!("{}", toml);
+    Ok(())
+}
+
+fn cmd_config_get(key: &str) -> Result<()> {
+    let config = config::load_global_config()?;
+    match config::config_value(&config, key)? {
+        toml::Value::String(value) => println!("{}", value),
+        toml::Value::Table(table) => print!("{}", toml::to_string_pretty(&table)?),
+        value => println!("{}", value),
+    }
+    Ok(())
+}
+
+fn cmd_config_set(key: &str, value: &str) -> Result<()> {
+    let path = config::set_config_value(key, value)?;
+    println!("Set {} in {}", key, path.display());
+    Ok(())
+}
+
+fn cmd_projects_list(include_archived: bool) -> Result<()> {
+    let db = get_db()?;
+    let projects = db.list_projects(include_archived)?;
+
+    if projects.is_empty() {
+        println!("No tracked projects yet.");
+        return Ok(());
+    }
+
+    println!("Tracked projects:\n");
+
+    for project in projects {
+        let name = project.display_name.as_deref().unwrap_or("-");
+        println!("  Path: {}", project.path);
+        println!("  Name: {}", name);
+        if let Some(ref remote) = project.git_remote {
+            println!("  Remote: {}", remote);
+        }
+        for alias in db.get_project_aliases(project.id)? {
+            println!("  Alias: {}", alias);
+        }
+        if project.archived {
+            println!("  Archived");
+        }
+        println!();
+    }
+
+    Ok(())
+}
+
+fn cmd_projects_set_name(path: &str, name: &str) -> Result<()> {
+    let config = EffectiveConfig::load(None)?;
+    let db = Database::open(&config.database_path)?;
+
+    let project_path = PathBuf::from(path).canonicalize()
+        .with_context(|| format!("Invalid path: {}", path))?;
+
+    let path_str = project_path.to_str().context("Invalid path")?;
+
+    let project = db.get_or_create_project(path_str, None, Some(name), None)?;
+    events::log(&config, || Event::project(&db, project.id));
+
+    println!("Set display name for {} to: {}", path_str, name);
+    Ok(())
+}
+
+fn cmd_projects_set_pattern(path: &str, patterns: &[String]) -> Result<()> {
+    let value = models::encode_patterns(patterns).unwrap_or_default();
+    cmd_projects_set(path, ProjectField::Pattern, &value)
+}
+
+fn cmd_projects_set(path: &str, field: ProjectField, value: &str) -> Result<()> {
+    let config = EffectiveConfig::load(None)?;
+    let db = Database::open(&config.database_path)?;
+    let path_str = canonical_path(path)?;
+    let mut project = db.get_or_create_project(&path_str, None, None, None)?;
+
+    let optional = |v: &str| if v.is_empty() { None } else { Some(v.to_string()) };
+
+    match field {
+        ProjectField::Name => project.display_name = optional(value),
+        ProjectField::Pattern => {
+            // A single regex, or a JSON array of regexes tried in order
+            for pattern in models::decode_patterns(optional(value).as_deref()) {
+                regex::Regex::new(&pattern)
+                    .with_context(|| format!("Invalid work item pattern: {}", pattern))?;
+            }
+            project.work_item_pattern = optional(value);
+        }
+        ProjectField::Billable => {
+            project.billable = value
+                .parse()
+                .with_context(|| format!("Invalid billable flag: {} (expected true/false)", value))?;
+        }
+        ProjectField::Rate => {
+            project.hourly_rate = if value.is_empty() {
+                None
+            } else {
+                Some(value.parse().with_context(|| format!("Invalid rate: {}", value))?)
+            };
+        }
+        ProjectField::Client => project.client = optional(value),
+    }
+
+    db.update_project(&project)?;
+    events::log(&config, || Event::project(&db, project.id));
+
+    let shown = if value.is_empty() { "(unset)" } else { value };
+    println!("Set {} for {} to: {}", field_name(field), path_str, shown);
+    Ok(())
+}
+
+fn field_name(field: ProjectField) -> &'static str {
+    match field {
+        ProjectField::Name => "display name",
+        ProjectField::Pattern => "work item pattern",
+        ProjectField::Billable => "billable",
+        ProjectField::Rate => "hourly rate",
+        ProjectField::Client => "client",
+    }
+}
+
+fn cmd_projects_set_rate(path: &str, rate: f64, from: Option<NaiveDate>, currency: Option<&str>) -> Result<()> {
+    let config = EffectiveConfig::load(None)?;
+    let db = Database::open(&config.database_path)?;
+    let path_str = canonical_path(path)?;
+    let project = db.get_or_create_project(&path_str, None, None, None)?;
+
+    let from = from.unwrap_or_else(|| config.calendar.today());
+    db.set_project_rate(project.id, from, rate, currency)?;
+    events::log(&config, || Event::project(&db, project.id));
+
+    println!(
+        "Set hourly rate for {} to {:.2}{} from {}",
+        path_str,
+        rate,
+        currency.map(|c| format!(" {}", c)).unwrap_or_default(),
+        from
+    );
+    Ok(())
+}
+
+fn cmd_projects_unset_rate(path: &str, from: NaiveDate) -> Result<()> {
+    let config = EffectiveConfig::load(None)?;
+    let db = Database::open(&config.database_path)?;
+    let path_str = canonical_path(path)?;
+
+    let project = db
+        .get_project_by_path(&path_str)?
+        .with_context(|| format!("Project not tracked: {}", path_str))?;
+
+    if !db.delete_project_rate(project.id, from)? {
+        anyhow::bail!("No rate starting {} for {}", from, path_str);
+    }
+    events::log(&config, || Event::project(&db, project.id));
+    println!("Removed the rate starting {} for {}", from, path_str);
+    Ok(())
+}
+
+fn cmd_projects_show(path: &str) -> Result<()> {
+    let config = EffectiveConfig::load(None)?;
+    let db = Database::open(&config.database_path)?;
+    let path_str = canonical_path(path)?;
+
+    let project = db
+        .get_project_by_path(&path_str)?
+        .with_context(|| format!("Project not tracked: {}", path_str))?;
+
+    println!("  Path:     {}", project.path);
+    println!("  UUID:     {}", project.uuid);
+    println!("  Name:     {}", project.display_name.as_deref().unwrap_or("-"));
+    println!("  Remote:   {}", project.git_remote.as_deref().unwrap_or("-"));
+    for url in project.remote_urls.iter().filter(|url| project.git_remote.as_ref() != Some(*url)) {
+        println!("  Remote:   {}", url);
+    }
+    if let Some(repository) = project.repository() {
+        println!("  Web:      {}", repository.web_url());
+    }
+    let patterns = project.work_item_patterns();
+    if patterns.is_empty() {
+        println!("  Pattern:  -");
+    }
+    for pattern in patterns {
+        println!("  Pattern:  {}", pattern);
+    }
+    if let Some(ref client) = project.client {
+        println!("  Client:   {}", client);
+    }
+    println!("  Billable: {}", if project.billable { "yes" } else { "no" });
+    if project.archived {
+        println!("  Archived: yes");
+    }
+    // A dated rate in effect today wins over the flat rate
+    match db.get_project_rate_at(project.id, config.calendar.today())? {
+        Some(rate) => println!(
+            "  Rate:     {:.2}{}/h",
+            rate.hourly_rate,
+            rate.currency.map(|c| format!(" {}", c)).unwrap_or_default()
+        ),
+        None => match project.hourly_rate {
+            Some(rate) => println!("  Rate:     {:.2}/h", rate),
+            None => println!("  Rate:     -"),
+        },
+    }
+    for rate in db.get_project_rates(project.id)? {
+        println!(
+            "  From {}: {:.2}{}/h",
+            rate.effective_from,
+            rate.hourly_rate,
+            rate.currency.map(|c| format!(" {}", c)).unwrap_or_default()
+        );
+    }
+    println!("  Created:  {}", project.created_at.format("%Y-%m-%d"));
+    for alias in db.get_project_aliases(project.id)? {
+        println!("  Alias:    {}", alias);
+    }
+
+    Ok(())
+}
+
+fn cmd_projects_delete(path: &str, purge: bool) -> Result<()> {
+    let config = EffectiveConfig::load(None)?;
+    let db = Database::open(&config.database_path)?;
+    let path_str = tracked_path(path)?;
+
+    let project = db
+        .get_project_by_path(&path_str)?
+        .with_context(|| format!("Project not tracked: {}", path_str))?;
+
+    if purge {
+        db.purge_project(project.id)?;
+        events::log(&config, || Ok(Event::PurgeProject { project_id: project.id }));
+        println!("Purged {} and all its sessions", path_str);
+    } else if db.delete_project(project.id)? {
+        events::log_project(&config, &db, project.id);
+        println!("Moved {} and its sessions to the trash (restore with `trash restore --project`)", path_str);
+    } else {
+        println!("{} is already in the trash", path_str);
+    }
+    Ok(())
+}
+
+fn cmd_projects_archive(path: &str, archived: bool) -> Result<()> {
+    let config = EffectiveConfig::load(None)?;
+    let db = Database::open(&config.database_path)?;
+    let path_str = tracked_path(path)?;
+
+    let project = db
+        .get_project_by_path(&path_str)?
+        .with_context(|| format!("Project not tracked: {}", path_str))?;
+
+    let changed = db.set_project_archived(project.id, archived)?;
+    if changed {
+        events::log(&config, || Event::project(&db, project.id));
+    }
+    match (changed, archived) {
+        (true, true) => println!("Archived {} (list it with --include-archived)", path_str),
+        (true, false) => println!("Unarchived {}", path_str),
+        (false, true) => println!("{} is already archived", path_str),
+        (false, false) => println!("{} is not archived", path_str),
+    }
+    Ok(())
+}
+
+fn cmd_projects_stats(top: usize, include_archived: bool) -> Result<()> {
+    let db = get_db()?;
+
+    let mut stats = Vec::new();
+    for project in db.list_projects(include_archived)? {
+        let project_stats = db.get_project_stats(project.id, top)?;
+        if project_stats.session_count > 0 {
+            stats.push((project, project_stats));
+        }
+    }
+
+    if stats.is_empty() {
+        println!("No tracked sessions yet.");
+        return Ok(());
+    }
+
+    stats.sort_by_key(|(_, s)| std::cmp::Reverse(s.total_seconds));
+
+    println!("Project statistics:\n");
+
+    for (project, project_stats) in stats {
+        let format_date = |dt: Option<chrono::DateTime<Utc>>| {
+            dt.map(|d| d.format("%Y-%m-%d").to_string())
+                .unwrap_or_else(|| "-".to_string())
+        };
+
+        println!(
+            "  Project:  {}",
+            project.name()
+        );
+        println!("  Total:    {}", tracker::format_duration(project_stats.total_seconds));
+        println!("  Sessions: {}", project_stats.session_count);
+        println!(
+            "  Period:   {} ~ {}",
+            format_date(project_stats.first_session),
+            format_date(project_stats.last_session)
+        );
+        for (item, seconds) in &project_stats.top_work_items {
+            println!("    {:<24} {}", item, tracker::format_duration(*seconds));
+        }
+        println!();
+    }
+
+    Ok(())
+}
+
+/// Resolve a hook's working directory to the root of its repository
+///
+/// Claude Code may be started in a subdirectory; tracking that directory
+/// would create a separate project for it.
+fn project_root(path: &str) -> Result<PathBuf> {
+    let path = PathBuf::from(path).canonicalize()
+        .with_context(|| format!("Invalid path: {}", path))?;
+    let root = vcs::repo_root(&path);
+    if root != path {
+        tracing::debug!("Tracking {} as its repository root {}", path.display(), root.display());
+    }
+    Ok(root)
+}
+
+/// Canonicalize a user-supplied project path
+fn canonical_path(path: &str) -> Result<String> {
+    let project_path = PathBuf::from(path).canonicalize()
+        .with_context(|| format!("Invalid path: {}", path))?;
+
+    Ok(project_path.to_str().context("Invalid path")?.to_string())
+}
+
+/// Canonical form of a path that may no longer exist, falling back to the path as given
+fn tracked_path(path: &str) -> Result<String> {
+    let path = PathBuf::from(path)
+        .canonicalize()
+        .unwrap_or_else(|_| PathBuf::from(path.trim_end_matches('/')));
+
+    Ok(path.to_str().context("Invalid path")?.to_string())
+}
+
+fn cmd_projects_alias(path: &str, alias: &str) -> Result<()> {
+    let config = EffectiveConfig::load(None)?;
+    let db = Database::open(&config.database_path)?;
+    let path_str = tracked_path(path)?;
+    let alias_str = canonical_path(alias)?;
+
+    let project = db
+        .get_project_by_path(&path_str)?
+        .with_context(|| format!("Project not tracked: {}", path_str))?;
+
+    if let Some(existing) = db.get_project_by_path(&alias_str)? {
+        if existing.id == project.id {
+            println!("{} already resolves to {}", alias_str, project.path);
+            return Ok(());
+        }
+        anyhow::bail!(
+            "{} is tracked as {}; use `projects move` to merge its history",
+            alias_str,
+            existing.path
+        );
+    }
+
+    db.add_project_alias(project.id, &alias_str)?;
+    events::log(&config, || Event::project(&db, project.id));
+    println!("{} now resolves to {}", alias_str, project.path);
+    Ok(())
+}
+
+fn cmd_projects_move(old_path: &str, new_path: &str, keep_alias: bool) -> Result<()> {
+    let config = EffectiveConfig::load(None)?;
+    let db = Database::open(&config.database_path)?;
+
+    // The old directory is usually gone by now
+    let old_str = &tracked_path(old_path)?;
+    let new_path = PathBuf::from(new_path).canonicalize()
+        .with_context(|| format!("Invalid path: {}", new_path))?;
+
+    let new_str = new_path.to_str().context("Invalid path")?;
+
+    let project = db.move_project(old_str, new_str, keep_alias)?;
+    // Sessions of a project folded into the moved one now belong to it
+    events::log_project(&config, &db, project.id);
+
+    println!("Moved project {} -> {}", old_str, new_str);
+    if keep_alias {
+        println!("Kept {} as an alias", old_str);
+    }
+    Ok(())
+}
+
+fn cmd_sessions_list(limit: usize) -> Result<()> {
+    let db = get_db()?;
+    let sessions = db.get_recent_sessions(limit)?;
+
+    if sessions.is_empty() {
+        println!("No sessions recorded yet.");
+        return Ok(());
+    }
+
+    for session in sessions {
+        let project = db.get_project_by_id(session.project_id)?;
+        println!(
+            "{:>6}  {}  {:<9} {:<8} {:<12} {}  {}",
+            session.id,
+            session.started_at.format("%Y-%m-%d %H:%M"),
+            session.status.as_str(),
+            tracker::format_duration(session.active_seconds.unwrap_or(0)),
+            session.machine.as_deref().unwrap_or("-"),
+            project.name(),
+            session.work_item.as_deref().unwrap_or(&session.branch)
+        );
+    }
+
+    Ok(())
+}
+
+fn cmd_sessions_show(id: i64) -> Result<()> {
+    let db = get_db()?;
+    let session = db.get_session_by_id(id)?;
+    let project = db.get_project_by_id(session.project_id)?;
+
+    println!("  Session:   {}", session.id);
+    println!("  UUID:      {}", session.uuid);
+    println!("  Project:   {}", project.name());
+    println!("  Branch:    {}", session.branch);
+    println!("  Work item: {}", session.work_item.as_deref().unwrap_or("-"));
+    println!("  Status:    {}", session.status.as_str());
+    println!("  Started:   {}", session.started_at.format("%Y-%m-%d %H:%M"));
+    match session.ended_at {
+        Some(ended) => println!("  Ended:     {}", ended.format("%Y-%m-%d %H:%M")),
+        None => println!("  Ended:     -"),
+    }
+    println!("  Active:    {}", tracker::format_duration(session.active_seconds.unwrap_or(0)));
+    println!("  Model:     {}", session.model.as_deref().unwrap_or("-"));
+    println!("  Machine:   {}", session.machine.as_deref().unwrap_or("-"));
+    if let Some(ref source) = session.imported_from {
+        println!("  Imported:  from {}", source);
+    }
+    match session.diff_stats {
+        Some(stats) => println!(
+            "  Changes:   {} files, +{} -{}",
+            stats.files_changed, stats.insertions, stats.deletions
+        ),
+        None => println!("  Changes:   -"),
+    }
+    if let Some(stats) = session.uncommitted {
+        println!(
+            "  Uncommitted: {} files, +{} -{}",
+            stats.files_changed, stats.insertions, stats.deletions
+        );
+    }
+    if let Some(pr) = session.pull_request {
+        println!("  PR:        #{} {} ({})", pr.number, pr.title, pr.url);
+    }
+
+    for branch in db.get_session_branches(session.id)? {
+        println!(
+            "  Split:     {} {}",
+            branch.branch,
+            tracker::format_duration(branch.active_seconds)
+        );
+    }
+    for (kind, seconds) in db.get_session_activity(session.id)? {
+        println!("  Activity:  {} {}", kind, tracker::format_duration(seconds));
+    }
+    for commit in db.get_commits(session.id)? {
+        let submodule = commit.submodule.map(|path| format!("[{}] ", path)).unwrap_or_default();
+        println!(
+            "  Commit:    {} {}{}",
+            &commit.hash[..8.min(commit.hash.len())],
+            submodule,
+            commit.message.unwrap_or_default()
+        );
+    }
+
+    Ok(())
+}
+
+fn cmd_sessions_delete(id: i64, purge: bool) -> Result<()> {
+    let config = EffectiveConfig::load(None)?;
+    let db = Database::open(&config.database_path)?;
+    let session = db.get_session_by_id(id)?;
+
+    if purge {
+        db.purge_session(session.id)?;
+        events::log(&config, || Ok(Event::Purge { session_id: session.id }));
+        println!("Purged session {}", session.id);
+    } else if db.delete_session(session.id)? {
+        events::log(&config, || Event::session(&db, session.id));
+        println!("Moved session {} to the trash (restore with `trash restore --session {}`)", session.id, session.id);
+    } else {
+        println!("Session {} is already in the trash", session.id);
+    }
+    Ok(())
+}
+
+fn cmd_sessions_set_work_item(id: i64, work_item: &str) -> Result<()> {
+    let config = EffectiveConfig::load(None)?;
+    let db = Database::open(&config.database_path)?;
+    let session = db.get_session_by_id(id)?;
+
+    let work_item = if work_item.is_empty() { None } else { Some(work_item) };
+    db.set_session_work_item(session.id, work_item)?;
+    // A manual work item covers the whole session, replacing any branch split
+    db.record_session_branches(session.id, &[])?;
+    events::log(&config, || Event::session(&db, session.id));
+
+    println!(
+        "Session {} now attributed to: {}",
+        session.id,
+        work_item.unwrap_or(&session.branch)
+    );
+    Ok(())
+}
+
+fn cmd_trash_list() -> Result<()> {
+    let db = get_db()?;
+    let projects = db.get_deleted_projects()?;
+    let sessions = db.get_deleted_sessions()?;
+
+    if projects.is_empty() && sessions.is_empty() {
+        println!("The trash is empty.");
+        return Ok(());
+    }
+
+    for project in &projects {
+        let count = sessions
+            .iter()
+            .filter(|s| s.project_id == project.id && s.deleted_at == project.deleted_at)
+            .count();
+        println!(
+            "  Project  {}  {} ({} sessions)",
+            project.deleted_at.map(|dt| dt.format("%Y-%m-%d %H:%M").to_string()).unwrap_or_default(),
+            project.path,
+            count
+        );
+    }
+
+    // Sessions trashed with their project are counted with it above
+    let with_project = |s: &models::Session| {
+        projects.iter().any(|p| p.id == s.project_id && p.deleted_at == s.deleted_at)
+    };
+    for session in sessions.iter().filter(|s| !with_project(s)) {
+        let project = db.get_project_by_id(session.project_id)?;
+        println!(
+            "  Session  {}  {:>5}  {}  {}  {}",
+            session.deleted_at.map(|dt| dt.format("%Y-%m-%d %H:%M").to_string()).unwrap_or_default(),
+            session.id,
+            session.started_at.format("%Y-%m-%d"),
+            project.name(),
+            session.work_item.as_deref().unwrap_or(&session.branch)
+        );
+    }
+
+    Ok(())
+}
+
+fn cmd_trash_restore(session_id: Option<i64>, project_path: Option<&str>) -> Result<()> {
+    let config = EffectiveConfig::load(None)?;
+    let db = Database::open(&config.database_path)?;
+
+    if let Some(id) = session_id {
+        let session = db.get_session_by_id(id)?;
+        let project = db.get_project_by_id(session.project_id)?;
+        if project.deleted_at.is_some() {
+            anyhow::bail!("Session {} belongs to {}, which is in the trash; restore the project", id, project.path);
+        }
+        if !db.undelete_session(id)? {
+            anyhow::bail!("Session {} is not in the trash", id);
+        }
+        events::log(&config, || Event::session(&db, id));
+        println!("Restored session {}", id);
+    }
+
+    if let Some(path) = project_path {
+        let path_str = tracked_path(path)?;
+        let project = db
+            .get_project_by_path(&path_str)?
+            .with_context(|| format!("Project not tracked: {}", path_str))?;
+        if !db.undelete_project(project.id)? {
+            anyhow::bail!("{} is not in the trash", path_str);
+        }
+        events::log_project(&config, &db, project.id);
+        println!("Restored {}", path_str);
+    }
+
+    Ok(())
+}
+
+fn cmd_hooks_install(scope: claude_settings::HookScope, tool_use: bool) -> Result<()> {
+    let binary = current_binary()?;
+
+    let cwd = std::env::current_dir()?;
+    let path = claude_settings::settings_path(scope, &cwd)?;
+
+    let mut settings = claude_settings::load(&path)?;
+    let specs = claude_settings::hook_specs(&binary, tool_use);
+    let changes = claude_settings::install(&mut settings, &specs)?;
+    claude_settings::save(&path, &settings)?;
+
+    println!("Updated {}:", path.display());
+    for change in changes {
+        println!("  {}", change);
+    }
+    Ok(())
+}
+
+fn cmd_hooks_status() -> Result<()> {
+    use claude_settings::{HookScope, HookState};
+
+    let binary = current_binary()?;
+    let cwd = std::env::current_dir()?;
+    let specs = claude_settings::hook_specs(&binary, false);
+
+    for scope in [HookScope::User, HookScope::Project] {
+        let path = claude_settings::settings_path(scope, &cwd)?;
+        println!("{}:", path.display());
+
+        if !path.exists() {
+            println!("  (not found)\n");
+            continue;
+        }
+
+        let settings = claude_settings::load(&path)?;
+        for (event, state) in claude_settings::check(&settings, &specs) {
+            match state {
+                HookState::Ok => println!("  {:<18} ok", event),
+                HookState::Missing => println!("  {:<18} missing", event),
+                HookState::Mismatch(command) => {
+                    println!("  {:<18} points elsewhere: {}", event, command)
+                }
+            }
+        }
+        if claude_settings::has_tool_use_hook(&settings) {
+            println!("  {:<18} installed (optional)", "PostToolUse");
+        }
+        println!();
+    }
+
+    Ok(())
+}
+
+fn cmd_hooks_uninstall(scope: claude_settings::HookScope) -> Result<()> {
+    let cwd = std::env::current_dir()?;
+    let path = claude_settings::settings_path(scope, &cwd)?;
+
+    if !path.exists() {
+        println!("No settings file at {}", path.display());
+        return Ok(());
+    }
+
+    let mut settings = claude_settings::load(&path)?;
+    let changes = claude_settings::remove_hooks(&mut settings)?;
+
+    if changes.is_empty() {
+        println!("No tracking hooks found in {}", path.display());
+        return Ok(());
+    }
+
+    claude_settings::save(&path, &settings)?;
+
+    println!("Updated {}:", path.display());
+    for change in changes {
+        println!("  {}", change);
+    }
+    Ok(())
+}
+
+/// Absolute path of the running binary, as hooks should invoke it
+fn current_binary() -> Result<String> {
+    let binary = std::env::current_exe().context("Failed to locate the running binary")?;
+    Ok(binary.to_str().context("Binary path is not valid UTF-8")?.to_string())
+}
@@ -0,0 +1,178 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::Path;
+use tracing::{debug, info};
+
+use crate::db::Database;
+use crate::git;
+use crate::models::Project;
+use crate::storage::Storage;
+
+/// Counts from merging another database
+#[derive(Debug, Default)]
+pub struct MergeStats {
+    /// Projects that were not tracked here yet
+    pub projects_added: usize,
+    /// Projects matched to one tracked here (by id, path or remote)
+    pub projects_matched: usize,
+    pub sessions: usize,
+    /// Sessions already present here
+    pub sessions_skipped: usize,
+}
+
+/// Merge the projects and sessions of another tracker database into `db`
+///
+/// A project is matched by its globally unique id, then by path (or alias),
+/// then by remote, so the same repository checked out at different paths on
+/// two machines ends up as one project; its other paths become aliases.
+/// Merging is idempotent: running it again only adds what is new, and
+/// atomic: a merge that fails part way changes nothing.
+pub fn merge(db: &Database, source_path: &Path) -> Result<MergeStats> {
+    // Opening brings the other database up to the current schema
+    let source = Database::open(source_path)?;
+    let mut projects = Vec::new();
+    for project in source.list_projects(true)? {
+        let aliases = source.get_project_aliases(project.id)?;
+        let rates = source.get_project_rates(project.id)?;
+        projects.push((project, aliases, rates));
+    }
+    drop(source);
+
+    db.with_attached(source_path, || {
+        db.atomically(|| {
+            let mut stats = MergeStats::default();
+            let mut project_ids = HashMap::new();
+            let tracked = db.list_projects(true)?;
+
+            for (project, aliases, source_rates) in projects {
+                let target = match find_project(db, &tracked, &project)? {
+                    Some(target) => {
+                        debug!("{} matches tracked project {}", project.path, target.path);
+                        if target.deleted_at.is_some() {
+                            // Sessions merged into the trash would stay out of sight
+                            info!("Restoring {} from the trash: {} still tracks it", target.path, source_path.display());
+                            db.undelete_project(target.id)?;
+                        }
+                        stats.projects_matched += 1;
+                        target
+                    }
+                    None => {
+                        stats.projects_added += 1;
+                        db.import_project(&project)?
+                    }
+                };
+
+                for path in std::iter::once(project.path.clone()).chain(aliases) {
+                    if db.get_project_by_path(&path)?.is_none() {
+                        db.add_project_alias(target.id, &path)?;
+                    }
+                }
+
+                // Rates set here win over those of the other database
+                let rates = db.get_project_rates(target.id)?;
+                for rate in source_rates {
+                    if !rates.iter().any(|r| r.effective_from == rate.effective_from) {
+                        db.set_project_rate(target.id, rate.effective_from, rate.hourly_rate, rate.currency.as_deref())?;
+                    }
+                }
+
+                project_ids.insert(project.id, target.id);
+            }
+
+            let (sessions, skipped) = db
+                .merge_sessions(&project_ids)
+                .with_context(|| format!("Failed to merge sessions from {}", source_path.display()))?;
+            stats.sessions = sessions;
+            stats.sessions_skipped = skipped;
+
+            Ok(stats)
+        })
+    })
+}
+
+/// The tracked project another database's project corresponds to, if any
+pub fn find_project(db: &Database, tracked: &[Project], project: &Project) -> Result<Option<Project>> {
+    if !project.uuid.is_empty() {
+        if let Some(found) = db.get_project_by_uuid(&project.uuid)? {
+            return Ok(Some(found));
+        }
+    }
+    if let Some(found) = db.get_project_by_path(&project.path)? {
+        return Ok(Some(found));
+    }
+
+    let Some(ref remote) = project.git_remote else {
+        return Ok(None);
+    };
+    let key = git::normalize_remote_url(remote);
+    Ok(tracked
+        .iter()
+        .find(|p| p.git_remote.as_deref().is_some_and(|r| git::normalize_remote_url(r) == key))
+        .cloned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::HeartbeatContext;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_merge() {
+        let dir = tempdir().unwrap();
+        let desktop = Database::open(&dir.path().join("desktop.db")).unwrap();
+        let laptop_path = dir.path().join("laptop.db");
+        let laptop = Database::open(&laptop_path).unwrap();
+
+        let remote = "git@github.com:acme/api.git";
+        let home = desktop.get_or_create_project("/home/me/api", Some(remote), None, None).unwrap();
+        desktop.create_session(home.id, "main", None, None, None, None).unwrap();
+
+        // The same repository at another path, plus a project only the laptop knows
+        let mac = laptop
+            .get_or_create_project("/Users/me/api", Some("https://github.com/acme/api"), None, None)
+            .unwrap();
+        let session = laptop.create_session(mac.id, "feature/ABC-1", Some("ABC-1"), None, None, None).unwrap();
+        laptop.record_heartbeat(session.id, &HeartbeatContext::default()).unwrap();
+        laptop.record_commits(session.id, &[("abc".to_string(), "Add login".to_string(), None)]).unwrap();
+        laptop.add_note(session.id, "Paired on the login form").unwrap();
+        let docs = laptop.get_or_create_project("/Users/me/docs", None, None, None).unwrap();
+        laptop.create_session(docs.id, "main", None, None, None, None).unwrap();
+        drop(laptop);
+
+        let stats = merge(&desktop, &laptop_path).unwrap();
+        assert_eq!((stats.projects_added, stats.projects_matched), (1, 1));
+        assert_eq!((stats.sessions, stats.sessions_skipped), (2, 0));
+
+        assert_eq!(desktop.list_projects(false).unwrap().len(), 2);
+        assert_eq!(desktop.get_project_by_path("/Users/me/api").unwrap().unwrap().id, home.id);
+        let (merged, _) = desktop.search_sessions("login", 10).unwrap().remove(0);
+        assert_eq!((merged.project_id, merged.uuid), (home.id, session.uuid));
+        assert_eq!(desktop.get_heartbeats(merged.id).unwrap().len(), 1);
+        assert_eq!(desktop.get_commits(merged.id).unwrap().len(), 1);
+        assert_eq!(desktop.get_notes(merged.id).unwrap().len(), 1);
+
+        let again = merge(&desktop, &laptop_path).unwrap();
+        assert_eq!((again.projects_added, again.sessions, again.sessions_skipped), (0, 0, 2));
+    }
+
+    #[test]
+    fn test_merge_restores_trashed_project() {
+        let dir = tempdir().unwrap();
+        let desktop = Database::open(&dir.path().join("desktop.db")).unwrap();
+        let trashed = desktop.get_or_create_project("/home/me/api", None, None, None).unwrap();
+        desktop.delete_project(trashed.id).unwrap();
+
+        let laptop_path = dir.path().join("laptop.db");
+        let laptop = Database::open(&laptop_path).unwrap();
+        let project = laptop.get_or_create_project("/home/me/api", None, None, None).unwrap();
+        laptop.create_session(project.id, "main", None, None, None, None).unwrap();
+        drop(laptop);
+
+        let stats = merge(&desktop, &laptop_path).unwrap();
+        assert_eq!((stats.projects_added, stats.projects_matched, stats.sessions), (0, 1, 1));
+        let projects = desktop.list_projects(false).unwrap();
+        assert_eq!(projects.len(), 1);
+        assert_eq!(projects[0].id, trashed.id);
+    }
+}
@@ -1,21 +1,153 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 /// Project information stored in database
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Project {
     pub id: i64,
+    /// Globally unique id, stable across machines and database merges
+    #[serde(default)]
+    pub uuid: String,
     pub path: String,
     pub git_remote: Option<String>,
+    /// URLs of all the checkout's remotes, `git_remote` first
+    pub remote_urls: Vec<String>,
+    /// `git_remote` split into host, owner and repository name
+    pub remote_host: Option<String>,
+    pub remote_owner: Option<String>,
+    pub remote_repo: Option<String>,
     pub display_name: Option<String>,
     pub work_item_pattern: Option<String>,
     pub created_at: DateTime<Utc>,
+    pub billable: bool,
+    pub hourly_rate: Option<f64>,
+    /// Client the project is invoiced to, grouping projects in reports
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client: Option<String>,
+    /// Archived projects are left out of listings, reports and status by default
+    #[serde(default)]
+    pub archived: bool,
+    /// When the project was moved to the trash
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+impl Project {
+    /// Name to show: the display name, else `owner/repo` from the remote, else the path
+    pub fn name(&self) -> String {
+        match (&self.display_name, self.repository()) {
+            (Some(name), _) => name.clone(),
+            (None, Some(repository)) => repository.slug(),
+            (None, None) => self.path.clone(),
+        }
+    }
+
+    /// Hosted repository the project's remote points at
+    pub fn repository(&self) -> Option<RemoteRepo> {
+        Some(RemoteRepo {
+            host: self.remote_host.clone()?,
+            owner: self.remote_owner.clone()?,
+            repo: self.remote_repo.clone()?,
+        })
+    }
+
+    /// Work item patterns to try in order (see [`decode_patterns`])
+    pub fn work_item_patterns(&self) -> Vec<String> {
+        decode_patterns(self.work_item_pattern.as_deref())
+    }
+}
+
+/// A repository on a code host, parsed from a remote URL
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RemoteRepo {
+    pub host: String,
+    /// User or organization; GitLab subgroups are kept (`group/sub`)
+    pub owner: String,
+    pub repo: String,
+}
+
+impl RemoteRepo {
+    /// `owner/repo`
+    pub fn slug(&self) -> String {
+        format!("{}/{}", self.owner, self.repo)
+    }
+
+    /// Web page of the repository
+    pub fn web_url(&self) -> String {
+        format!("https://{}/{}", self.host, self.slug())
+    }
+
+    /// Web page of an issue, for work items that are issue numbers (`123` or `#123`)
+    pub fn issue_url(&self, work_item: &str) -> Option<String> {
+        let number = issue_number(work_item)?;
+        if self.host.contains("github") {
+            Some(format!("{}/issues/{}", self.web_url(), number))
+        } else if self.host.contains("gitlab") {
+            Some(format!("{}/-/issues/{}", self.web_url(), number))
+        } else {
+            None
+        }
+    }
+}
+
+/// Issue number of a work item like `123` or `#123`
+pub fn issue_number(work_item: &str) -> Option<u64> {
+    let number = work_item.strip_prefix('#').unwrap_or(work_item);
+    if number.is_empty() || !number.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    number.parse().ok()
+}
+
+/// Decode the stored work item pattern column
+///
+/// The column holds either a single regex or a JSON array of regexes.
+pub fn decode_patterns(value: Option<&str>) -> Vec<String> {
+    match value {
+        None => Vec::new(),
+        Some(v) => serde_json::from_str(v).unwrap_or_else(|_| vec![v.to_string()]),
+    }
+}
+
+/// Encode work item patterns for storage; a single pattern is stored as-is
+pub fn encode_patterns(patterns: &[String]) -> Option<String> {
+    match patterns {
+        [] => None,
+        [single] => Some(single.clone()),
+        many => serde_json::to_string(many).ok(),
+    }
+}
+
+/// An hourly rate applying to a project from a given day on
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectRate {
+    pub project_id: i64,
+    pub effective_from: NaiveDate,
+    pub hourly_rate: f64,
+    pub currency: Option<String>,
+}
+
+/// Lifetime totals for a project
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectStats {
+    pub project_id: i64,
+    pub first_session: Option<DateTime<Utc>>,
+    pub last_session: Option<DateTime<Utc>>,
+    pub session_count: i64,
+    pub total_seconds: i64,
+    /// Work items (or branches) with the most time, descending
+    pub top_work_items: Vec<(String, i64)>,
 }
 
 /// A tracking session
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Session {
     pub id: i64,
+    /// Globally unique id, stable across machines and database merges
+    #[serde(default)]
+    pub uuid: String,
     pub project_id: i64,
     pub branch: String,
     pub work_item: Option<String>,
@@ -25,6 +157,63 @@ pub struct Session {
     pub ended_at: Option<DateTime<Utc>>,
     pub active_seconds: Option<i64>,
     pub status: SessionStatus,
+    /// Session id Claude Code passed to the hooks, if any
+    pub claude_session_id: Option<String>,
+    /// Claude model most recently reported for the session
+    pub model: Option<String>,
+    /// Machine the session was recorded on
+    pub machine: Option<String>,
+    /// Change between the start and end commit, measured at stop
+    pub diff_stats: Option<DiffStats>,
+    /// Pull request opened from the session's branch, if looked up
+    pub pull_request: Option<PullRequest>,
+    /// Work left uncommitted when a session without commits stopped
+    pub uncommitted: Option<DiffStats>,
+    /// When the session was moved to the trash
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime<Utc>>,
+    /// Time tracker the session was imported from, for time recorded outside Claude Code
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub imported_from: Option<String>,
+    /// Latest heartbeat of a running session, up to which `active_seconds` is
+    /// kept current; local bookkeeping, so not shared
+    #[serde(skip)]
+    pub last_heartbeat_at: Option<DateTime<Utc>>,
+}
+
+/// A pull (or merge) request on the code host
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct PullRequest {
+    pub number: i64,
+    pub url: String,
+    pub title: String,
+}
+
+/// Files and lines changed between two commits
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct DiffStats {
+    pub files_changed: i64,
+    pub insertions: i64,
+    pub deletions: i64,
+}
+
+impl DiffStats {
+    /// Sum of what several commits changed
+    pub fn total(measured: Vec<(String, DiffStats)>) -> Self {
+        let mut total = DiffStats::default();
+        for (_, stats) in measured {
+            total += stats;
+        }
+        total
+    }
+}
+
+impl std::ops::AddAssign for DiffStats {
+    fn add_assign(&mut self, other: Self) {
+        self.files_changed += other.files_changed;
+        self.insertions += other.insertions;
+        self.deletions += other.deletions;
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -44,7 +233,7 @@ impl SessionStatus {
         }
     }
 
-    pub fn from_str(s: &str) -> Option<Self> {
+    pub fn parse(s: &str) -> Option<Self> {
         match s {
             "active" => Some(SessionStatus::Active),
             "completed" => Some(SessionStatus::Completed),
@@ -60,6 +249,97 @@ pub struct Heartbeat {
     pub id: i64,
     pub session_id: i64,
     pub timestamp: DateTime<Utc>,
+    /// File touched by the tool call that produced this heartbeat, if any
+    pub file_path: Option<String>,
+    /// Branch checked out when the heartbeat was recorded
+    pub branch: Option<String>,
+    /// Hook event that produced the heartbeat (prompt-submit, tool-use, ...)
+    pub event_type: Option<String>,
+    /// Tool that ran, for tool-use heartbeats
+    pub tool: Option<String>,
+    /// Working directory Claude reported with the hook
+    pub cwd: Option<String>,
+    /// Claude model reported with the hook
+    pub model: Option<String>,
+    /// "main", or the name of the subagent that produced the heartbeat
+    pub agent: Option<String>,
+}
+
+impl Heartbeat {
+    /// The context recorded with this heartbeat
+    pub fn context(&self) -> HeartbeatContext {
+        HeartbeatContext {
+            file_path: self.file_path.clone(),
+            branch: self.branch.clone(),
+            event_type: self.event_type.clone(),
+            tool: self.tool.clone(),
+            cwd: self.cwd.clone(),
+            model: self.model.clone(),
+            agent: self.agent.clone(),
+        }
+    }
+}
+
+/// A start, heartbeat or stop noted in the database's event feed
+#[derive(Debug, Clone)]
+pub struct FeedEntry {
+    pub id: i64,
+    /// "start", "heartbeat" or "stop"
+    pub kind: String,
+    /// The session, or for heartbeats the heartbeat
+    pub row_id: i64,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Optional context captured alongside a new heartbeat
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HeartbeatContext {
+    pub file_path: Option<String>,
+    pub branch: Option<String>,
+    pub event_type: Option<String>,
+    pub tool: Option<String>,
+    pub cwd: Option<String>,
+    pub model: Option<String>,
+    pub agent: Option<String>,
+}
+
+/// Share of a session's active time spent on one branch
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionBranch {
+    pub session_id: i64,
+    pub branch: String,
+    pub work_item: Option<String>,
+    pub active_seconds: i64,
+}
+
+/// What is recorded along with a finished session, read in bulk for reports
+#[derive(Debug, Clone, Default)]
+pub struct SessionDetails {
+    /// Prompts submitted, including coalesced ones
+    pub prompts: i64,
+    pub activity: Vec<(String, i64)>,
+    pub branches: Vec<SessionBranch>,
+    pub commits: Vec<Commit>,
+}
+
+/// Time a window was focused, imported from ActivityWatch's window watcher
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WindowEvent {
+    /// Bucket the event came from, e.g. `aw-watcher-window_laptop`
+    pub bucket: String,
+    pub timestamp: DateTime<Utc>,
+    pub duration_seconds: f64,
+    pub app: Option<String>,
+    pub title: Option<String>,
+}
+
+/// A free-text note attached to a session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Note {
+    pub id: i64,
+    pub session_id: i64,
+    pub timestamp: DateTime<Utc>,
+    pub text: String,
 }
 
 /// A commit associated with a session
@@ -70,35 +350,116 @@ pub struct Commit {
     pub hash: String,
     pub message: Option<String>,
     pub committed_at: Option<DateTime<Utc>>,
+    /// Path of the submodule the commit was made in, `None` for the project itself
+    pub submodule: Option<String>,
+    /// Files and lines the commit changed, from the commit cache
+    pub diff_stats: Option<DiffStats>,
 }
 
-/// Report data structures
-#[derive(Debug, Clone, Serialize, Deserialize)]
+// Report data structures
+
+/// One project's time in the reported month, broken down by work item
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ProjectReport {
     pub name: String,
     pub path: String,
+    /// Client the project is invoiced to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client: Option<String>,
+    /// Web page of the project's hosted repository
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repository_url: Option<String>,
     pub total_seconds: i64,
+    /// What the time comes to at the project's hourly rates, for billable projects with one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub amount: Option<Amount>,
+    /// Prompts submitted across the project's sessions
+    #[serde(default)]
+    pub prompts: i64,
     pub work_items: Vec<WorkItemReport>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub modules: Vec<ModuleReport>,
+    /// Time per sub-project, for monorepos configured with `[subprojects]`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub subprojects: Vec<ModuleReport>,
+    /// Time per activity kind (prompting, tool, waiting, other)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub activity: Vec<ModuleReport>,
+    /// Commits made inside git submodules, per submodule path
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub submodules: Vec<SubmoduleReport>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SubmoduleReport {
+    pub path: String,
+    pub commits: Vec<CommitSummary>,
+}
+
+/// Money charged for reported time
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Amount {
+    pub value: f64,
+    /// Currency code of the rates, when they were set with one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub currency: Option<String>,
+}
+
+/// Time spent per top-level directory, sub-project, activity kind or model
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ModuleReport {
+    pub name: String,
+    pub total_seconds: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct WorkItemReport {
     pub id: String,
     pub branch: Option<String>,
     pub total_seconds: i64,
     pub completed_date: Option<String>,
     pub commits: Vec<CommitSummary>,
+    /// Change made by the sessions the work item's commits came from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diff_stats: Option<DiffStats>,
+    /// Pull request of the work item's branch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pull_request: Option<PullRequest>,
+    /// Work in progress left uncommitted by the item's latest session without commits
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uncommitted: Option<DiffStats>,
+    /// Issue on the code host, for work items that are issue numbers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub issue_url: Option<String>,
+    /// Title of that issue, with `settings.fetch_issue_titles`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Time per day (YYYY-MM-DD), counted on the day each session started
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub days: BTreeMap<String, i64>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct CommitSummary {
     pub hash: String,
     pub message: String,
+    /// Files and lines the commit changed, when they were measured
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diff_stats: Option<DiffStats>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct MonthlyReport {
     pub period: String,
     pub total_seconds: i64,
+    /// Time per Claude model, when the hooks reported one
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub models: Vec<ModuleReport>,
+    /// Time per client, when projects are assigned to clients
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub clients: Vec<ModuleReport>,
     pub projects: Vec<ProjectReport>,
+    /// Projects are ordered by client and shown under client headings
+    #[serde(skip)]
+    pub group_by_client: bool,
 }
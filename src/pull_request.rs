@@ -0,0 +1,245 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::io::{self, Read};
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+use crate::git;
+use crate::models::{PullRequest, RemoteRepo};
+
+/// How long `gh` or `glab` may take before a lookup is given up
+///
+/// Pull requests are looked up inside the Stop hook and issue titles while a
+/// report is generated; neither should hang on a slow network.
+const CLI_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Code hosts whose CLI can look up the pull request for a branch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Host {
+    GitHub,
+    GitLab,
+}
+
+#[derive(Deserialize)]
+struct GitHubPullRequest {
+    number: i64,
+    url: String,
+    title: String,
+}
+
+#[derive(Deserialize)]
+struct Issue {
+    title: String,
+}
+
+#[derive(Deserialize)]
+struct GitLabMergeRequest {
+    iid: i64,
+    web_url: String,
+    title: String,
+}
+
+/// Find the pull request opened from a branch, using the `gh` or `glab` CLI
+///
+/// Returns `None` when the host isn't supported, its CLI isn't installed or
+/// authenticated, or the branch has no pull request.
+pub fn find_for_branch(project_path: &Path, remote_url: &str, branch: &str) -> Result<Option<PullRequest>> {
+    let Some(host) = host_of(remote_url) else {
+        return Ok(None);
+    };
+
+    let (program, args) = match host {
+        Host::GitHub => ("gh", ["pr", "view", branch, "--json", "number,url,title"]),
+        Host::GitLab => ("glab", ["mr", "view", branch, "--output", "json"]),
+    };
+
+    let output = match output_within(Command::new(program).args(args).current_dir(project_path), CLI_TIMEOUT) {
+        Ok(output) => output,
+        Err(e) => {
+            debug!("Skipping pull request lookup, {} failed: {}", program, e);
+            return Ok(None);
+        }
+    };
+    if !output.status.success() {
+        debug!(
+            "No pull request found for {}: {}",
+            branch,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+        return Ok(None);
+    }
+
+    parse(host, &String::from_utf8_lossy(&output.stdout)).map(Some)
+}
+
+/// Title of an issue on GitHub or GitLab, using the `gh` or `glab` CLI
+///
+/// Returns `None` when the host isn't supported, its CLI isn't installed or
+/// authenticated, or the issue doesn't exist.
+pub fn find_issue_title(repository: &RemoteRepo, number: u64) -> Option<String> {
+    let host = host_named(&repository.host)?;
+    let (program, endpoint) = match host {
+        Host::GitHub => ("gh", format!("repos/{}/{}/issues/{}", repository.owner, repository.repo, number)),
+        Host::GitLab => ("glab", format!("projects/{}/issues/{}", project_id(repository), number)),
+    };
+    let output = match output_within(
+        Command::new(program).args(["api", "--hostname", &repository.host, &endpoint]),
+        CLI_TIMEOUT,
+    ) {
+        Ok(output) => output,
+        Err(e) => {
+            debug!("Skipping issue lookup, {} failed: {}", program, e);
+            return None;
+        }
+    };
+    if !output.status.success() {
+        debug!(
+            "No issue #{} in {}: {}",
+            number,
+            repository.slug(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+        return None;
+    }
+
+    let issue: Issue = serde_json::from_slice(&output.stdout).ok()?;
+    Some(issue.title)
+}
+
+/// Run a command and collect its output, killing it when it runs past `timeout`
+fn output_within(command: &mut Command, timeout: Duration) -> io::Result<Output> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    // Drain the pipes while waiting, so a chatty command can't fill them and stall
+    let stdout = child.stdout.take().map(read_to_end);
+    let stderr = child.stderr.take().map(read_to_end);
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("timed out after {}s", timeout.as_secs_f32()),
+            ));
+        }
+        thread::sleep(Duration::from_millis(20));
+    };
+
+    let collect = |reader: Option<JoinHandle<Vec<u8>>>| reader.and_then(|r| r.join().ok()).unwrap_or_default();
+    Ok(Output {
+        status,
+        stdout: collect(stdout),
+        stderr: collect(stderr),
+    })
+}
+
+fn read_to_end(mut pipe: impl Read + Send + 'static) -> JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buffer = Vec::new();
+        let _ = pipe.read_to_end(&mut buffer);
+        buffer
+    })
+}
+
+/// Whether the repository is on a GitLab instance
+pub fn is_gitlab(repository: &RemoteRepo) -> bool {
+    host_named(&repository.host) == Some(Host::GitLab)
+}
+
+/// GitLab API id of a project: its full path, URL-encoded
+pub fn project_id(repository: &RemoteRepo) -> String {
+    repository.slug().replace('/', "%2F")
+}
+
+fn host_of(remote_url: &str) -> Option<Host> {
+    host_named(&git::parse_remote_url(remote_url)?.host)
+}
+
+fn host_named(host: &str) -> Option<Host> {
+    if host.contains("github") {
+        Some(Host::GitHub)
+    } else if host.contains("gitlab") {
+        Some(Host::GitLab)
+    } else {
+        None
+    }
+}
+
+fn parse(host: Host, output: &str) -> Result<PullRequest> {
+    let pull_request = match host {
+        Host::GitHub => {
+            let pr: GitHubPullRequest =
+                serde_json::from_str(output).context("Failed to parse gh output")?;
+            PullRequest {
+                number: pr.number,
+                url: pr.url,
+                title: pr.title,
+            }
+        }
+        Host::GitLab => {
+            let mr: GitLabMergeRequest =
+                serde_json::from_str(output).context("Failed to parse glab output")?;
+            PullRequest {
+                number: mr.iid,
+                url: mr.web_url,
+                title: mr.title,
+            }
+        }
+    };
+
+    Ok(pull_request)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_within() {
+        let output = output_within(Command::new("echo").arg("hello"), CLI_TIMEOUT).unwrap();
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "hello");
+
+        let started = Instant::now();
+        let error = output_within(Command::new("sleep").arg("10"), Duration::from_millis(100)).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_host_of() {
+        assert_eq!(host_of("git@github.com:acme/api.git"), Some(Host::GitHub));
+        assert_eq!(host_of("https://gitlab.example.com/group/api"), Some(Host::GitLab));
+        assert_eq!(host_of("https://bitbucket.org/acme/github-tools"), None);
+
+        let repository = git::parse_remote_url("git@gitlab.com:acme/backend/api.git").unwrap();
+        assert!(is_gitlab(&repository));
+        assert_eq!(project_id(&repository), "acme%2Fbackend%2Fapi");
+    }
+
+    #[test]
+    fn test_parse() {
+        let gh = r#"{"number":42,"title":"Add login","url":"https://github.com/acme/api/pull/42"}"#;
+        let pr = parse(Host::GitHub, gh).unwrap();
+        assert_eq!(pr.number, 42);
+        assert_eq!(pr.url, "https://github.com/acme/api/pull/42");
+
+        let glab = r#"{"id":901,"iid":7,"title":"Fix billing","web_url":"https://gitlab.com/acme/api/-/merge_requests/7","state":"opened"}"#;
+        let mr = parse(Host::GitLab, glab).unwrap();
+        assert_eq!(mr.number, 7);
+        assert_eq!(mr.title, "Fix billing");
+
+        assert!(parse(Host::GitHub, "not json").is_err());
+    }
+}
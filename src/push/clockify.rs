@@ -0,0 +1,175 @@
+use anyhow::{Context, Result};
+use chrono::{Duration, SecondsFormat};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tracing::info;
+
+use super::{PushStats, SessionEntry};
+use crate::config::ClockifySettings;
+use crate::db::Database;
+use crate::http::Request;
+use crate::secrets;
+use crate::tracker::format_duration;
+
+/// Name sessions are recorded under in the sync state
+pub const TARGET: &str = "clockify";
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TimeEntryBody<'a> {
+    start: String,
+    end: String,
+    description: &'a str,
+    project_id: &'a str,
+}
+
+fn body<'a>(entry: &'a SessionEntry, project_id: &'a str) -> TimeEntryBody<'a> {
+    TimeEntryBody {
+        start: entry.start.to_rfc3339_opts(SecondsFormat::Secs, true),
+        end: (entry.start + Duration::seconds(entry.seconds)).to_rfc3339_opts(SecondsFormat::Secs, true),
+        description: &entry.description,
+        project_id,
+    }
+}
+
+#[derive(Serialize)]
+struct NewProject<'a> {
+    name: &'a str,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct User {
+    default_workspace: String,
+}
+
+#[derive(Deserialize)]
+struct ClockifyProject {
+    id: String,
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct Created {
+    id: String,
+}
+
+/// A Clockify workspace and the key to write to it
+pub struct Client {
+    base_url: String,
+    api_key: String,
+    workspace_id: String,
+}
+
+impl Client {
+    pub fn from_settings(settings: &ClockifySettings) -> Result<Self> {
+        let api_key = settings
+            .api_key
+            .clone()
+            .or_else(|| secrets::lookup("clockify"))
+            .context("Set clockify.api_key in the config or store the key in the keychain (account `clockify`)")?;
+        let base_url = settings.base_url.trim_end_matches('/').to_string();
+        let workspace_id = match settings.workspace_id {
+            Some(ref id) => id.clone(),
+            None => {
+                let user: User = Request::get(format!("{}/user", base_url))
+                    .header("X-Api-Key", &api_key)
+                    .send()
+                    .context("Failed to look up the Clockify workspace")?;
+                user.default_workspace
+            }
+        };
+        Ok(Self {
+            base_url,
+            api_key,
+            workspace_id,
+        })
+    }
+
+    fn request(&self, request: Request) -> Request {
+        request.header("X-Api-Key", &self.api_key)
+    }
+
+    fn workspace_url(&self, path: &str) -> String {
+        format!("{}/workspaces/{}/{}", self.base_url, self.workspace_id, path)
+    }
+
+    /// Project ids by lowercase name
+    fn projects(&self) -> Result<HashMap<String, String>> {
+        let projects: Vec<ClockifyProject> = self
+            .request(Request::get(self.workspace_url("projects?archived=false&page-size=5000")))
+            .send()
+            .context("Failed to read the Clockify projects")?;
+        Ok(projects.into_iter().map(|p| (p.name.to_lowercase(), p.id)).collect())
+    }
+
+    fn create_project(&self, name: &str) -> Result<String> {
+        let created: Created = self
+            .request(Request::post(self.workspace_url("projects"), &NewProject { name })?)
+            .send()
+            .with_context(|| format!("Failed to create the Clockify project {}", name))?;
+        info!("Created Clockify project {}", name);
+        Ok(created.id)
+    }
+}
+
+/// Create a time entry per session and remember it in the sync state
+///
+/// Entries go to the Clockify project with the same name (ignoring case),
+/// which is created when missing. Each session is recorded right after its
+/// entry is created, so an interrupted push doesn't create duplicates.
+pub fn push(client: &Client, db: &Database, entries: &[SessionEntry]) -> Result<PushStats> {
+    let mut stats = PushStats::default();
+    let mut projects = client.projects()?;
+    for entry in entries {
+        let key = entry.project.to_lowercase();
+        let project_id = match projects.get(&key) {
+            Some(id) => id.clone(),
+            None => {
+                let id = client.create_project(&entry.project)?;
+                projects.insert(key, id.clone());
+                id
+            }
+        };
+
+        let created: Created = client
+            .request(Request::post(client.workspace_url("time-entries"), &body(entry, &project_id))?)
+            .send()
+            .with_context(|| format!("Failed to create the Clockify entry for {}", entry.description))?;
+        db.mark_session_synced(TARGET, &entry.session_uuid, &created.id)?;
+        info!(
+            "Added {} on {} to {}",
+            format_duration(entry.seconds),
+            entry.description,
+            entry.project
+        );
+        stats.created += 1;
+    }
+
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_time_entry_body() {
+        let entry = SessionEntry {
+            session_uuid: "0b6f".to_string(),
+            project: "api".to_string(),
+            description: "ABC-1".to_string(),
+            start: "2025-01-15T09:00:00Z".parse().unwrap(),
+            seconds: 5400,
+        };
+        assert_eq!(
+            serde_json::to_value(body(&entry, "64a1f0")).unwrap(),
+            serde_json::json!({
+                "start": "2025-01-15T09:00:00Z",
+                "end": "2025-01-15T10:30:00Z",
+                "description": "ABC-1",
+                "projectId": "64a1f0",
+            })
+        );
+    }
+}
@@ -0,0 +1,339 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::collections::BTreeSet;
+use std::process::Command;
+use tracing::{debug, info};
+
+use super::{project_daily_entries, PushStats, MARKER};
+use crate::config::WorkCalendar;
+use crate::git;
+use crate::models::{self, MonthlyReport, RemoteRepo};
+use crate::pull_request;
+use crate::tracker::format_duration;
+
+/// GitLab only keeps whole minutes of spent time
+const MIN_SECONDS: i64 = 60;
+
+const ISSUE_QUERY: &str = "query($path: ID!, $iid: String!, $after: String) { \
+    project(fullPath: $path) { issue(iid: $iid) { id timelogs(first: 100, after: $after) { \
+    nodes { id timeSpent summary } pageInfo { hasNextPage endCursor } } } } }";
+
+const CREATE_TIMELOG: &str = "mutation($issue: IssuableID!, $time: String!, $spentAt: Time!, $summary: String!) { \
+    timelogCreate(input: {issuableId: $issue, timeSpent: $time, spentAt: $spentAt, summary: $summary}) { errors } }";
+
+const DELETE_TIMELOG: &str = "mutation($id: TimelogID!) { timelogDelete(input: {id: $id}) { errors } }";
+
+/// Time spent on a GitLab issue on one day
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimeLog {
+    pub repository: RemoteRepo,
+    pub issue: u64,
+    pub date: NaiveDate,
+    pub spent_at: DateTime<Utc>,
+    /// Whole minutes
+    pub seconds: i64,
+}
+
+impl TimeLog {
+    fn marker(&self) -> String {
+        format!("{}#{}:{}", MARKER, self.issue, self.date)
+    }
+
+    fn summary(&self) -> String {
+        format!("Claude Code time [{}]", self.marker())
+    }
+}
+
+#[derive(Deserialize)]
+struct Response<T> {
+    data: Option<T>,
+    #[serde(default)]
+    errors: Vec<ResponseError>,
+}
+
+#[derive(Deserialize)]
+struct ResponseError {
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct ProjectData {
+    project: Option<ProjectNode>,
+}
+
+#[derive(Deserialize)]
+struct ProjectNode {
+    issue: Option<IssueNode>,
+}
+
+#[derive(Deserialize)]
+struct IssueNode {
+    id: String,
+    timelogs: Nodes<Timelog>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Nodes<T> {
+    nodes: Vec<T>,
+    #[serde(default)]
+    page_info: Option<PageInfo>,
+}
+
+impl<T> Nodes<T> {
+    /// Where the next page starts, when there is one
+    fn next_cursor(&self) -> Option<String> {
+        self.page_info
+            .as_ref()
+            .filter(|info| info.has_next_page)
+            .and_then(|info| info.end_cursor.clone())
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PageInfo {
+    has_next_page: bool,
+    end_cursor: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Timelog {
+    id: String,
+    time_spent: i64,
+    #[serde(default)]
+    summary: Option<String>,
+}
+
+/// Time logs for the issue work items (`123` or `#123`) of projects hosted on GitLab
+///
+/// Returns the time logs and the work items left out, as `item (project)`.
+/// Each log is spent at midday of its (working) day, like the Jira worklogs.
+pub fn plan(report: &MonthlyReport, calendar: WorkCalendar) -> (Vec<TimeLog>, BTreeSet<String>) {
+    let mut logs = Vec::new();
+    let mut skipped = BTreeSet::new();
+    for project in &report.projects {
+        let repository = project
+            .repository_url
+            .as_deref()
+            .and_then(git::parse_remote_url)
+            .filter(pull_request::is_gitlab);
+        for entry in project_daily_entries(project) {
+            let (Some(repository), Some(issue)) = (&repository, models::issue_number(&entry.work_item)) else {
+                skipped.insert(format!("{} ({})", entry.work_item, project.name));
+                continue;
+            };
+            let seconds = entry.seconds / 60 * 60;
+            if seconds < MIN_SECONDS {
+                continue;
+            }
+            logs.push(TimeLog {
+                repository: repository.clone(),
+                issue,
+                date: entry.date,
+                spent_at: calendar.day_start(entry.date) + Duration::hours(12),
+                seconds,
+            });
+        }
+    }
+    // One issue after the other, so its time logs are read once
+    logs.sort_by(|a, b| {
+        (&a.repository.host, a.repository.slug(), a.issue, a.date)
+            .cmp(&(&b.repository.host, b.repository.slug(), b.issue, b.date))
+    });
+    (logs, skipped)
+}
+
+/// Add the spent time to the issues, replacing what was pushed before
+///
+/// Requests go through the `glab` CLI, so it has to be authenticated with each
+/// GitLab host. Time logs from earlier runs are recognized by the marker in
+/// their summary; when the time changed they are deleted and logged again.
+pub fn push(logs: &[TimeLog]) -> Result<PushStats> {
+    let mut stats = PushStats::default();
+    let mut current: Option<(&RemoteRepo, u64, IssueNode)> = None;
+
+    for log in logs {
+        if !current
+            .as_ref()
+            .is_some_and(|(repository, issue, _)| *repository == &log.repository && *issue == log.issue)
+        {
+            let issue = read_issue(log)?;
+            current = Some((&log.repository, log.issue, issue));
+        }
+        let Some((_, _, ref issue)) = current else {
+            continue;
+        };
+
+        let marker = log.marker();
+        let previous: Vec<&Timelog> = issue
+            .timelogs
+            .nodes
+            .iter()
+            .filter(|t| t.summary.as_deref().is_some_and(|s| s.contains(&marker)))
+            .collect();
+        if !previous.is_empty() && previous.iter().map(|t| t.time_spent).sum::<i64>() == log.seconds {
+            stats.unchanged += 1;
+            continue;
+        }
+
+        for timelog in &previous {
+            mutate(&log.repository.host, "timelogDelete", DELETE_TIMELOG, &[("id", &timelog.id)])
+                .with_context(|| format!("Failed to remove the time logged on {}", issue_name(log)))?;
+        }
+        let time = format!("{}m", log.seconds / 60);
+        let spent_at = log.spent_at.to_rfc3339();
+        let summary = log.summary();
+        mutate(
+            &log.repository.host,
+            "timelogCreate",
+            CREATE_TIMELOG,
+            &[("issue", &issue.id), ("time", &time), ("spentAt", &spent_at), ("summary", &summary)],
+        )
+        .with_context(|| format!("Failed to log time on {}", issue_name(log)))?;
+
+        if previous.is_empty() {
+            info!("Logged {} on {} for {}", format_duration(log.seconds), issue_name(log), log.date);
+            stats.created += 1;
+        } else {
+            info!("Updated {} on {} to {}", issue_name(log), log.date, format_duration(log.seconds));
+            stats.updated += 1;
+        }
+    }
+
+    Ok(stats)
+}
+
+/// `group/project#123`
+pub fn issue_name(log: &TimeLog) -> String {
+    format!("{}#{}", log.repository.slug(), log.issue)
+}
+
+/// An issue with all its time logs, read a page at a time
+fn read_issue(log: &TimeLog) -> Result<IssueNode> {
+    let iid = log.issue.to_string();
+    let slug = log.repository.slug();
+    let read_page = |after: Option<&str>| -> Result<IssueNode> {
+        let mut variables = vec![("path", slug.as_str()), ("iid", iid.as_str())];
+        variables.extend(after.map(|cursor| ("after", cursor)));
+        let data: ProjectData = graphql(&log.repository.host, ISSUE_QUERY, &variables)
+            .with_context(|| format!("Failed to read {}", issue_name(log)))?;
+        data.project
+            .and_then(|p| p.issue)
+            .with_context(|| format!("Issue {} not found", issue_name(log)))
+    };
+
+    let mut issue = read_page(None)?;
+    let mut cursor = issue.timelogs.next_cursor();
+    while let Some(after) = cursor {
+        let page = read_page(Some(&after))?;
+        cursor = page.timelogs.next_cursor();
+        issue.timelogs.nodes.extend(page.timelogs.nodes);
+    }
+    Ok(issue)
+}
+
+/// Run a mutation, failing on the errors it reports
+fn mutate(host: &str, name: &str, query: &str, variables: &[(&str, &str)]) -> Result<()> {
+    let data: serde_json::Value = graphql(host, query, variables)?;
+    let errors = data[name]["errors"].as_array().cloned().unwrap_or_default();
+    if !errors.is_empty() {
+        let messages: Vec<String> = errors.iter().map(|e| e.as_str().unwrap_or_default().to_string()).collect();
+        bail!("{}", messages.join("; "));
+    }
+    Ok(())
+}
+
+/// Send a GraphQL request to a GitLab host with `glab api`
+fn graphql<T: DeserializeOwned>(host: &str, query: &str, variables: &[(&str, &str)]) -> Result<T> {
+    debug!("GraphQL request to {}", host);
+    let mut command = Command::new("glab");
+    command
+        .args(["api", "graphql", "--hostname", host, "-f"])
+        .arg(format!("query={}", query));
+    for (name, value) in variables {
+        command.arg("-f").arg(format!("{}={}", name, value));
+    }
+    let output = command.output().context("Failed to run glab (is it installed?)")?;
+    if !output.status.success() {
+        bail!("glab api failed: {}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    parse_response(&String::from_utf8_lossy(&output.stdout))
+}
+
+fn parse_response<T: DeserializeOwned>(output: &str) -> Result<T> {
+    let response: Response<T> = serde_json::from_str(output).context("Unexpected response from GitLab")?;
+    if !response.errors.is_empty() {
+        let messages: Vec<&str> = response.errors.iter().map(|e| e.message.as_str()).collect();
+        bail!("{}", messages.join("; "));
+    }
+    response.data.context("Empty response from GitLab")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Timezone;
+    use crate::models::ProjectReport;
+    use crate::push::project;
+
+    #[test]
+    fn test_plan() {
+        let report = MonthlyReport {
+            period: "2025-01".to_string(),
+            total_seconds: 0,
+            models: Vec::new(),
+            clients: Vec::new(),
+            group_by_client: false,
+            projects: vec![
+                ProjectReport {
+                    repository_url: Some("https://gitlab.com/acme/api".to_string()),
+                    ..project(
+                        "api",
+                        &[("#12", "2025-01-15", 5430), ("7", "2025-01-15", 30), ("main", "2025-01-15", 600)],
+                    )
+                },
+                ProjectReport {
+                    repository_url: Some("https://github.com/acme/web".to_string()),
+                    ..project("web", &[("#3", "2025-01-15", 600)])
+                },
+            ],
+        };
+        let calendar = WorkCalendar {
+            timezone: Timezone::parse("Asia/Taipei").unwrap(),
+            day_start_hour: 0,
+        };
+
+        let (logs, skipped) = plan(&report, calendar);
+        assert_eq!(logs.len(), 1);
+        assert_eq!(issue_name(&logs[0]), "acme/api#12");
+        assert_eq!(logs[0].seconds, 5400);
+        assert_eq!(logs[0].spent_at, "2025-01-15T04:00:00Z".parse::<DateTime<Utc>>().unwrap());
+        assert_eq!(logs[0].summary(), "Claude Code time [claude-time-tracker:#12:2025-01-15]");
+        assert_eq!(skipped, BTreeSet::from(["#3 (web)".to_string(), "main (api)".to_string()]));
+    }
+
+    #[test]
+    fn test_parse_response() {
+        let issue: ProjectData = parse_response(
+            r#"{"data":{"project":{"issue":{"id":"gid://gitlab/Issue/901","timelogs":{"nodes":[
+                {"id":"gid://gitlab/Timelog/5","timeSpent":3600,"summary":"Claude Code time [claude-time-tracker:#12:2025-01-15]"}
+            ]}}}}}"#,
+        )
+        .unwrap();
+        let issue = issue.project.unwrap().issue.unwrap();
+        assert_eq!(issue.id, "gid://gitlab/Issue/901");
+        assert_eq!(issue.timelogs.nodes[0].time_spent, 3600);
+        assert_eq!(issue.timelogs.next_cursor(), None);
+
+        let page: Nodes<Timelog> =
+            serde_json::from_str(r#"{"nodes":[],"pageInfo":{"hasNextPage":true,"endCursor":"eyJpZCI6IjUifQ"}}"#).unwrap();
+        assert_eq!(page.next_cursor().as_deref(), Some("eyJpZCI6IjUifQ"));
+
+        let error = parse_response::<ProjectData>(r#"{"errors":[{"message":"Field 'timelogs' doesn't exist"}]}"#);
+        assert!(matches!(error, Err(e) if e.to_string().contains("timelogs")));
+    }
+}
@@ -0,0 +1,242 @@
+use anyhow::{Context, Result};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use tracing::info;
+
+use super::{project_daily_entries, PushStats, MARKER};
+use crate::config::{HarvestSettings, HarvestTask};
+use crate::http::Request;
+use crate::models::MonthlyReport;
+use crate::secrets;
+use crate::tracker::format_duration;
+
+const BASE_URL: &str = "https://api.harvestapp.com/v2";
+
+/// Harvest keeps hours with two decimals
+const MIN_SECONDS: i64 = 36;
+
+/// Time to log on a Harvest task on one day
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HarvestEntry {
+    /// Tracker projects whose time this is
+    pub projects: BTreeSet<String>,
+    pub task: HarvestTask,
+    pub date: NaiveDate,
+    pub seconds: i64,
+    pub work_items: BTreeSet<String>,
+}
+
+impl HarvestEntry {
+    fn marker(&self) -> String {
+        format!("{}{}:{}:{}", MARKER, self.task.project_id, self.task.task_id, self.date)
+    }
+
+    pub fn hours(&self) -> f64 {
+        (self.seconds as f64 / 36.0).round() / 100.0
+    }
+
+    fn notes(&self) -> String {
+        let items: Vec<&str> = self.work_items.iter().map(|s| s.as_str()).collect();
+        format!("Claude Code time: {} [{}]", items.join(", "), self.marker())
+    }
+}
+
+#[derive(Serialize)]
+struct NewEntry {
+    project_id: i64,
+    task_id: i64,
+    spent_date: String,
+    hours: f64,
+    notes: String,
+}
+
+#[derive(Serialize)]
+struct EntryUpdate {
+    hours: f64,
+    notes: String,
+}
+
+#[derive(Deserialize)]
+struct Me {
+    id: i64,
+}
+
+#[derive(Deserialize)]
+struct EntryPage {
+    time_entries: Vec<ExistingEntry>,
+    next_page: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct ExistingEntry {
+    id: i64,
+    #[serde(default)]
+    notes: Option<String>,
+    hours: f64,
+}
+
+/// A Harvest account and the token to write to it
+pub struct Client {
+    account_id: String,
+    token: String,
+}
+
+impl Client {
+    pub fn from_settings(settings: &HarvestSettings) -> Result<Self> {
+        let account_id = settings
+            .account_id
+            .clone()
+            .context("Set harvest.account_id in the config to push time entries")?;
+        let token = settings
+            .access_token
+            .clone()
+            .or_else(|| secrets::lookup("harvest"))
+            .context("Set harvest.access_token in the config or store the token in the keychain (account `harvest`)")?;
+        Ok(Self { account_id, token })
+    }
+
+    fn request(&self, request: Request) -> Request {
+        request
+            .bearer_auth(&self.token)
+            .header("Harvest-Account-Id", &self.account_id)
+            .header("User-Agent", "claude-time-tracker")
+    }
+}
+
+/// Time per Harvest task and day, from the projects mapped to a task
+///
+/// `tasks` is keyed by project path. Returns the entries and the names of the
+/// projects without a task.
+pub fn plan(report: &MonthlyReport, tasks: &HashMap<String, HarvestTask>) -> (Vec<HarvestEntry>, BTreeSet<String>) {
+    let mut entries: BTreeMap<(NaiveDate, HarvestTask), HarvestEntry> = BTreeMap::new();
+    let mut unmapped = BTreeSet::new();
+    for project in &report.projects {
+        let Some(&task) = tasks.get(&project.path) else {
+            unmapped.insert(project.name.clone());
+            continue;
+        };
+        for day in project_daily_entries(project) {
+            let entry = entries.entry((day.date, task)).or_insert_with(|| HarvestEntry {
+                projects: BTreeSet::new(),
+                task,
+                date: day.date,
+                seconds: 0,
+                work_items: BTreeSet::new(),
+            });
+            entry.projects.insert(project.name.clone());
+            entry.seconds += day.seconds;
+            entry.work_items.insert(day.work_item);
+        }
+    }
+    let entries = entries.into_values().filter(|e| e.seconds >= MIN_SECONDS).collect();
+    (entries, unmapped)
+}
+
+/// Create the time entries, updating the ones pushed before
+///
+/// Like `push jira`, earlier entries are recognized by the marker in their
+/// notes, so pushing a month again only corrects the hours that changed.
+pub fn push(client: &Client, entries: &[HarvestEntry]) -> Result<PushStats> {
+    let mut stats = PushStats::default();
+    let (Some(from), Some(to)) = (
+        entries.iter().map(|e| e.date).min(),
+        entries.iter().map(|e| e.date).max(),
+    ) else {
+        return Ok(stats);
+    };
+
+    let me: Me = client
+        .request(Request::get(format!("{}/users/me", BASE_URL)))
+        .send()
+        .context("Failed to look up the Harvest user")?;
+    let mut existing = Vec::new();
+    let mut page = Some(1);
+    while let Some(number) = page {
+        let url = format!(
+            "{}/time_entries?user_id={}&from={}&to={}&per_page=2000&page={}",
+            BASE_URL, me.id, from, to, number
+        );
+        let result: EntryPage = client
+            .request(Request::get(url))
+            .send()
+            .context("Failed to read the Harvest time entries")?;
+        existing.extend(result.time_entries);
+        page = result.next_page;
+    }
+
+    for entry in entries {
+        let marker = entry.marker();
+        let projects: Vec<&str> = entry.projects.iter().map(|s| s.as_str()).collect();
+        let projects = projects.join(", ");
+        match existing.iter().find(|e| e.notes.as_deref().is_some_and(|n| n.contains(&marker))) {
+            Some(previous) if (previous.hours - entry.hours()).abs() < 0.005 => stats.unchanged += 1,
+            Some(previous) => {
+                let update = EntryUpdate {
+                    hours: entry.hours(),
+                    notes: entry.notes(),
+                };
+                client
+                    .request(Request::patch(format!("{}/time_entries/{}", BASE_URL, previous.id), &update)?)
+                    .send::<serde_json::Value>()
+                    .with_context(|| format!("Failed to update the Harvest entry for {} on {}", projects, entry.date))?;
+                info!("Updated {} on {} to {}", projects, entry.date, format_duration(entry.seconds));
+                stats.updated += 1;
+            }
+            None => {
+                let new_entry = NewEntry {
+                    project_id: entry.task.project_id,
+                    task_id: entry.task.task_id,
+                    spent_date: entry.date.to_string(),
+                    hours: entry.hours(),
+                    notes: entry.notes(),
+                };
+                client
+                    .request(Request::post(format!("{}/time_entries", BASE_URL), &new_entry)?)
+                    .send::<serde_json::Value>()
+                    .with_context(|| format!("Failed to log time for {} in Harvest", projects))?;
+                info!("Logged {} for {} on {}", format_duration(entry.seconds), projects, entry.date);
+                stats.created += 1;
+            }
+        }
+    }
+
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::push::project;
+
+    #[test]
+    fn test_plan() {
+        let report = MonthlyReport {
+            period: "2025-01".to_string(),
+            total_seconds: 0,
+            models: Vec::new(),
+            clients: Vec::new(),
+            group_by_client: false,
+            projects: vec![
+                project("api", &[("ABC-1", "2025-01-15", 3600), ("ABC-2", "2025-01-15", 1800)]),
+                project("web", &[("ABC-3", "2025-01-15", 900), ("ABC-3", "2025-01-16", 20)]),
+                project("scratch", &[("main", "2025-01-15", 600)]),
+            ],
+        };
+        let task = HarvestTask {
+            project_id: 14307913,
+            task_id: 8083365,
+        };
+        let tasks = HashMap::from([("/src/api".to_string(), task), ("/src/web".to_string(), task)]);
+
+        let (entries, unmapped) = plan(&report, &tasks);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].seconds, 6300);
+        assert_eq!(entries[0].hours(), 1.75);
+        assert_eq!(
+            entries[0].notes(),
+            "Claude Code time: ABC-1, ABC-2, ABC-3 [claude-time-tracker:14307913:8083365:2025-01-15]"
+        );
+        assert_eq!(unmapped, BTreeSet::from(["scratch".to_string()]));
+    }
+}
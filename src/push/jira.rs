@@ -0,0 +1,275 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::sync::OnceLock;
+use tracing::info;
+
+use super::{DailyEntry, PushStats, MARKER};
+use crate::config::{JiraSettings, WorkCalendar};
+use crate::http::Request;
+use crate::secrets;
+use crate::tracker::format_duration;
+
+/// Jira rejects worklogs shorter than a minute
+const MIN_SECONDS: i64 = 60;
+
+/// A worklog to create on an issue
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Worklog {
+    pub issue: String,
+    pub date: NaiveDate,
+    pub started: DateTime<Utc>,
+    /// Whole minutes, as Jira shows them
+    pub seconds: i64,
+}
+
+impl Worklog {
+    pub fn marker(&self) -> String {
+        format!("{}{}:{}", MARKER, self.issue, self.date)
+    }
+
+    /// Whether an existing worklog's comment carries this worklog's marker,
+    /// including the date-only `claude-time-tracker:<date>]` form written
+    /// before the issue key was part of it
+    pub fn is_marked_in(&self, comment: &str) -> bool {
+        comment.contains(&self.marker()) || comment.contains(&format!("{}{}]", MARKER, self.date))
+    }
+
+    /// Worklog comment, carrying the marker
+    pub fn description(&self) -> String {
+        format!("Claude Code time [{}]", self.marker())
+    }
+
+    fn body(&self) -> WorklogBody {
+        WorklogBody {
+            comment: self.description(),
+            started: self.started.format("%Y-%m-%dT%H:%M:%S%.3f%z").to_string(),
+            time_spent_seconds: self.seconds,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct WorklogBody {
+    comment: String,
+    started: String,
+    time_spent_seconds: i64,
+}
+
+#[derive(Deserialize)]
+struct WorklogPage {
+    worklogs: Vec<ExistingWorklog>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExistingWorklog {
+    id: String,
+    #[serde(default)]
+    comment: Option<String>,
+    time_spent_seconds: i64,
+}
+
+#[derive(Deserialize)]
+struct Issue {
+    id: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Myself {
+    account_id: String,
+}
+
+/// A Jira site and the credentials to log work on it
+pub struct Client {
+    base_url: String,
+    email: Option<String>,
+    token: String,
+}
+
+impl Client {
+    pub fn from_settings(settings: &JiraSettings) -> Result<Self> {
+        let base_url = settings
+            .base_url
+            .as_deref()
+            .context("Set jira.base_url in the config to push worklogs")?
+            .trim_end_matches('/')
+            .to_string();
+        let token = settings
+            .api_token
+            .clone()
+            .or_else(|| secrets::lookup("jira"))
+            .context("Set jira.api_token in the config or store the token in the keychain (account `jira`)")?;
+        Ok(Self {
+            base_url,
+            email: settings.email.clone(),
+            token,
+        })
+    }
+
+    fn authorize(&self, request: Request) -> Request {
+        match self.email {
+            Some(ref email) => request.basic_auth(email, &self.token),
+            None => request.bearer_auth(&self.token),
+        }
+    }
+
+    fn worklog_url(&self, issue: &str) -> String {
+        format!("{}/rest/api/2/issue/{}/worklog", self.base_url, issue)
+    }
+
+    /// Numeric id of an issue, e.g. `10042` for `ABC-1`
+    pub fn issue_id(&self, key: &str) -> Result<i64> {
+        let url = format!("{}/rest/api/2/issue/{}?fields=summary", self.base_url, key);
+        let issue: Issue = self
+            .authorize(Request::get(url))
+            .send()
+            .with_context(|| format!("Failed to look up Jira issue {}", key))?;
+        issue.id.parse().with_context(|| format!("Unexpected id for {}: {}", key, issue.id))
+    }
+
+    /// Account id of the user the token belongs to (Jira Cloud)
+    pub fn account_id(&self) -> Result<String> {
+        let myself: Myself = self
+            .authorize(Request::get(format!("{}/rest/api/2/myself", self.base_url)))
+            .send()
+            .context("Failed to look up the Jira account")?;
+        Ok(myself.account_id)
+    }
+}
+
+/// Worklogs for the entries whose work item is a Jira issue key
+///
+/// Returns the worklogs and the work items left out. Each worklog starts at
+/// midday of its (working) day, so it lands on that day in any time zone.
+pub fn plan(entries: &[DailyEntry], calendar: WorkCalendar) -> (Vec<Worklog>, BTreeSet<String>) {
+    let mut worklogs = Vec::new();
+    let mut skipped = BTreeSet::new();
+    for entry in entries {
+        if !is_issue_key(&entry.work_item) {
+            skipped.insert(entry.work_item.clone());
+            continue;
+        }
+        let seconds = entry.seconds / 60 * 60;
+        if seconds < MIN_SECONDS {
+            continue;
+        }
+        worklogs.push(Worklog {
+            issue: entry.work_item.clone(),
+            date: entry.date,
+            started: calendar.day_start(entry.date) + Duration::hours(12),
+            seconds,
+        });
+    }
+    // One issue after the other, so its worklogs are read once
+    worklogs.sort_by(|a, b| (&a.issue, a.date).cmp(&(&b.issue, b.date)));
+    (worklogs, skipped)
+}
+
+/// Create the worklogs, updating the ones pushed before
+///
+/// Worklogs from earlier runs are recognized by the marker in their comment,
+/// so pushing a month again only corrects the time that changed.
+pub fn push(client: &Client, worklogs: &[Worklog]) -> Result<PushStats> {
+    let mut stats = PushStats::default();
+    let mut existing: Option<(String, Vec<ExistingWorklog>)> = None;
+
+    for worklog in worklogs {
+        if existing.as_ref().map(|(issue, _)| issue) != Some(&worklog.issue) {
+            let page: WorklogPage = client
+                .authorize(Request::get(format!("{}?maxResults=5000", client.worklog_url(&worklog.issue))))
+                .send()
+                .with_context(|| format!("Failed to read the worklogs of {}", worklog.issue))?;
+            existing = Some((worklog.issue.clone(), page.worklogs));
+        }
+        let logged = existing.as_ref().map(|(_, logs)| logs.as_slice()).unwrap_or_default();
+
+        match logged.iter().find(|w| w.comment.as_deref().is_some_and(|c| worklog.is_marked_in(c))) {
+            Some(previous) if previous.time_spent_seconds == worklog.seconds => stats.unchanged += 1,
+            Some(previous) => {
+                let url = format!("{}/{}", client.worklog_url(&worklog.issue), previous.id);
+                client
+                    .authorize(Request::put(url, &worklog.body())?)
+                    .send::<serde_json::Value>()
+                    .with_context(|| format!("Failed to update the worklog on {}", worklog.issue))?;
+                info!(
+                    "Updated {} on {} to {}",
+                    worklog.issue,
+                    worklog.date,
+                    format_duration(worklog.seconds)
+                );
+                stats.updated += 1;
+            }
+            None => {
+                let url = format!("{}?adjustEstimate=auto", client.worklog_url(&worklog.issue));
+                client
+                    .authorize(Request::post(url, &worklog.body())?)
+                    .send::<serde_json::Value>()
+                    .with_context(|| format!("Failed to log work on {}", worklog.issue))?;
+                info!(
+                    "Logged {} on {} for {}",
+                    format_duration(worklog.seconds),
+                    worklog.issue,
+                    worklog.date
+                );
+                stats.created += 1;
+            }
+        }
+    }
+
+    Ok(stats)
+}
+
+fn is_issue_key(work_item: &str) -> bool {
+    static ISSUE_KEY: OnceLock<Regex> = OnceLock::new();
+    ISSUE_KEY
+        .get_or_init(|| Regex::new(r"^[A-Z][A-Z0-9_]+-\d+$").expect("valid issue key regex"))
+        .is_match(work_item)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Timezone;
+
+    #[test]
+    fn test_plan() {
+        let date = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
+        let entry = |work_item: &str, seconds| DailyEntry {
+            work_item: work_item.to_string(),
+            date,
+            seconds,
+        };
+        let calendar = WorkCalendar {
+            timezone: Timezone::parse("Asia/Taipei").unwrap(),
+            day_start_hour: 0,
+        };
+
+        let (worklogs, skipped) = plan(
+            &[entry("ABC-1", 5430), entry("ABC-2", 59), entry("main", 600), entry("#12", 600)],
+            calendar,
+        );
+        assert_eq!(
+            worklogs,
+            vec![Worklog {
+                issue: "ABC-1".to_string(),
+                date,
+                started: "2025-01-15T04:00:00Z".parse().unwrap(),
+                seconds: 5400,
+            }]
+        );
+        assert_eq!(skipped, BTreeSet::from(["#12".to_string(), "main".to_string()]));
+
+        let body = worklogs[0].body();
+        assert_eq!(body.started, "2025-01-15T04:00:00.000+0000");
+        assert!(body.comment.contains("claude-time-tracker:ABC-1:2025-01-15"));
+        assert!(worklogs[0].is_marked_in(&body.comment));
+        assert!(worklogs[0].is_marked_in("Claude Code time [claude-time-tracker:2025-01-15]"));
+        assert!(!worklogs[0].is_marked_in("Claude Code time [claude-time-tracker:ABC-2:2025-01-15]"));
+        assert!(!worklogs[0].is_marked_in("Claude Code time [claude-time-tracker:2025-01-16]"));
+    }
+}
@@ -0,0 +1,151 @@
+pub mod clockify;
+pub mod gitlab;
+pub mod harvest;
+pub mod jira;
+pub mod tempo;
+pub mod toggl;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use std::collections::BTreeMap;
+
+use crate::models::{MonthlyReport, ProjectReport, Session};
+
+/// Marks time entries created by the tracker, followed by the work item and day they cover
+const MARKER: &str = "claude-time-tracker:";
+
+/// Time spent on one work item on one day
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DailyEntry {
+    pub work_item: String,
+    pub date: NaiveDate,
+    pub seconds: i64,
+}
+
+/// A completed session to mirror as a time entry in another tool
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionEntry {
+    pub session_uuid: String,
+    /// Tracker project name, matched against the project names there
+    pub project: String,
+    pub description: String,
+    pub start: DateTime<Utc>,
+    pub seconds: i64,
+}
+
+impl SessionEntry {
+    /// Entry for a completed session, `None` when it has no active time
+    pub fn for_session(session: &Session, project: &str) -> Option<Self> {
+        let seconds = session.active_seconds.filter(|&s| s > 0)?;
+        Some(Self {
+            session_uuid: session.uuid.clone(),
+            project: project.to_string(),
+            description: session.work_item.clone().unwrap_or_else(|| session.branch.clone()),
+            start: session.started_at,
+            seconds,
+        })
+    }
+}
+
+/// Outcome of pushing entries to another tool
+#[derive(Debug, Default)]
+pub struct PushStats {
+    pub created: usize,
+    pub updated: usize,
+    /// Entries already there with the same time
+    pub unchanged: usize,
+}
+
+/// Split a report into time per work item and day
+///
+/// The same work item in several projects is added up.
+pub fn daily_entries(report: &MonthlyReport) -> Vec<DailyEntry> {
+    entries_of(&report.projects)
+}
+
+/// Time per work item and day in one project
+pub fn project_daily_entries(project: &ProjectReport) -> Vec<DailyEntry> {
+    entries_of(std::slice::from_ref(project))
+}
+
+fn entries_of(projects: &[ProjectReport]) -> Vec<DailyEntry> {
+    let mut totals: BTreeMap<(NaiveDate, &str), i64> = BTreeMap::new();
+    for item in projects.iter().flat_map(|p| &p.work_items) {
+        for (day, seconds) in &item.days {
+            if let Ok(date) = NaiveDate::parse_from_str(day, "%Y-%m-%d") {
+                *totals.entry((date, item.id.as_str())).or_insert(0) += seconds;
+            }
+        }
+    }
+
+    totals
+        .into_iter()
+        .map(|((date, work_item), seconds)| DailyEntry {
+            work_item: work_item.to_string(),
+            date,
+            seconds,
+        })
+        .collect()
+}
+
+/// A project's report with work items of (id, day, seconds), for the push tests
+#[cfg(test)]
+fn project(name: &str, items: &[(&str, &str, i64)]) -> ProjectReport {
+    ProjectReport {
+        name: name.to_string(),
+        path: format!("/src/{}", name),
+        client: None,
+        repository_url: None,
+        total_seconds: items.iter().map(|(_, _, s)| s).sum(),
+        amount: None,
+        prompts: 0,
+        work_items: items
+            .iter()
+            .map(|(id, day, seconds)| crate::models::WorkItemReport {
+                id: id.to_string(),
+                branch: None,
+                total_seconds: *seconds,
+                completed_date: None,
+                commits: Vec::new(),
+                diff_stats: None,
+                pull_request: None,
+                uncommitted: None,
+                issue_url: None,
+                title: None,
+                days: BTreeMap::from([(day.to_string(), *seconds)]),
+            })
+            .collect(),
+        modules: Vec::new(),
+        subprojects: Vec::new(),
+        activity: Vec::new(),
+        submodules: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_daily_entries() {
+        let report = MonthlyReport {
+            period: "2025-01".to_string(),
+            total_seconds: 0,
+            models: Vec::new(),
+            clients: Vec::new(),
+            group_by_client: false,
+            projects: vec![
+                project("api", &[("ABC-1", "2025-01-16", 600), ("ABC-2", "2025-01-15", 300)]),
+                project("api", &[("ABC-1", "2025-01-16", 1200)]),
+            ],
+        };
+
+        let date = |day| NaiveDate::from_ymd_opt(2025, 1, day).unwrap();
+        assert_eq!(
+            daily_entries(&report),
+            vec![
+                DailyEntry { work_item: "ABC-2".to_string(), date: date(15), seconds: 300 },
+                DailyEntry { work_item: "ABC-1".to_string(), date: date(16), seconds: 1800 },
+            ]
+        );
+    }
+}
@@ -0,0 +1,182 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tracing::info;
+
+use super::jira::{self, Worklog};
+use super::PushStats;
+use crate::config::TempoSettings;
+use crate::http::Request;
+use crate::secrets;
+use crate::tracker::format_duration;
+
+/// Start time of every worklog, midday like the Jira worklogs
+const START_TIME: &str = "12:00:00";
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct WorklogBody<'a> {
+    author_account_id: &'a str,
+    issue_id: i64,
+    start_date: String,
+    start_time: &'a str,
+    time_spent_seconds: i64,
+    description: String,
+}
+
+#[derive(Deserialize)]
+struct WorklogPage {
+    results: Vec<ExistingWorklog>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExistingWorklog {
+    tempo_worklog_id: i64,
+    #[serde(default)]
+    description: Option<String>,
+    time_spent_seconds: i64,
+}
+
+/// A Tempo account and the user to log work as
+pub struct Client {
+    base_url: String,
+    token: String,
+    account_id: String,
+}
+
+impl Client {
+    pub fn from_settings(settings: &TempoSettings, jira: &jira::Client) -> Result<Self> {
+        let token = settings
+            .api_token
+            .clone()
+            .or_else(|| secrets::lookup("tempo"))
+            .context("Set tempo.api_token in the config or store the token in the keychain (account `tempo`)")?;
+        let account_id = match settings.account_id {
+            Some(ref account_id) => account_id.clone(),
+            None => jira.account_id()?,
+        };
+        Ok(Self {
+            base_url: settings.base_url.trim_end_matches('/').to_string(),
+            token,
+            account_id,
+        })
+    }
+
+    fn request(&self, request: Request) -> Request {
+        request.bearer_auth(&self.token)
+    }
+}
+
+/// Create the worklogs in Tempo, updating the ones pushed before
+///
+/// Like `push jira`, earlier worklogs are recognized by the marker in their
+/// description, so pushing a month again only corrects the time that changed.
+pub fn push(client: &Client, jira: &jira::Client, worklogs: &[Worklog]) -> Result<PushStats> {
+    let mut stats = PushStats::default();
+    let (Some(from), Some(to)) = (
+        worklogs.iter().map(|w| w.date).min(),
+        worklogs.iter().map(|w| w.date).max(),
+    ) else {
+        return Ok(stats);
+    };
+
+    let url = format!(
+        "{}/worklogs/user/{}?from={}&to={}&limit=5000",
+        client.base_url, client.account_id, from, to
+    );
+    let existing: WorklogPage = client
+        .request(Request::get(url))
+        .send()
+        .context("Failed to read the Tempo worklogs")?;
+
+    let mut issue_ids: HashMap<&str, i64> = HashMap::new();
+    for worklog in worklogs {
+        let marker = worklog.marker();
+        let previous = existing
+            .results
+            .iter()
+            .find(|w| w.description.as_deref().is_some_and(|d| d.contains(&marker)));
+        if previous.is_some_and(|p| p.time_spent_seconds == worklog.seconds) {
+            stats.unchanged += 1;
+            continue;
+        }
+
+        let issue_id = match issue_ids.get(worklog.issue.as_str()) {
+            Some(&id) => id,
+            None => {
+                let id = jira.issue_id(&worklog.issue)?;
+                issue_ids.insert(&worklog.issue, id);
+                id
+            }
+        };
+        let body = WorklogBody {
+            author_account_id: &client.account_id,
+            issue_id,
+            start_date: worklog.date.to_string(),
+            start_time: START_TIME,
+            time_spent_seconds: worklog.seconds,
+            description: worklog.description(),
+        };
+
+        match previous {
+            Some(previous) => {
+                let url = format!("{}/worklogs/{}", client.base_url, previous.tempo_worklog_id);
+                client
+                    .request(Request::put(url, &body)?)
+                    .send::<serde_json::Value>()
+                    .with_context(|| format!("Failed to update the Tempo worklog on {}", worklog.issue))?;
+                info!(
+                    "Updated {} on {} to {}",
+                    worklog.issue,
+                    worklog.date,
+                    format_duration(worklog.seconds)
+                );
+                stats.updated += 1;
+            }
+            None => {
+                client
+                    .request(Request::post(format!("{}/worklogs", client.base_url), &body)?)
+                    .send::<serde_json::Value>()
+                    .with_context(|| format!("Failed to log work on {} in Tempo", worklog.issue))?;
+                info!(
+                    "Logged {} on {} for {}",
+                    format_duration(worklog.seconds),
+                    worklog.issue,
+                    worklog.date
+                );
+                stats.created += 1;
+            }
+        }
+    }
+
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_worklog_body() {
+        let body = WorklogBody {
+            author_account_id: "5b10a2844c20165700ede21g",
+            issue_id: 10042,
+            start_date: "2025-01-15".to_string(),
+            start_time: START_TIME,
+            time_spent_seconds: 5400,
+            description: "Claude Code time [claude-time-tracker:ABC-1:2025-01-15]".to_string(),
+        };
+        assert_eq!(
+            serde_json::to_value(&body).unwrap(),
+            serde_json::json!({
+                "authorAccountId": "5b10a2844c20165700ede21g",
+                "issueId": 10042,
+                "startDate": "2025-01-15",
+                "startTime": "12:00:00",
+                "timeSpentSeconds": 5400,
+                "description": "Claude Code time [claude-time-tracker:ABC-1:2025-01-15]",
+            })
+        );
+    }
+}
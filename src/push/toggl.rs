@@ -0,0 +1,168 @@
+use anyhow::{Context, Result};
+use chrono::{Duration, SecondsFormat};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tracing::{info, warn};
+
+use super::{PushStats, SessionEntry};
+use crate::config::TogglSettings;
+use crate::db::Database;
+use crate::http::Request;
+use crate::secrets;
+use crate::tracker::format_duration;
+
+/// Name sessions are recorded under in the sync state
+pub const TARGET: &str = "toggl";
+
+/// Tag put on every time entry
+const TAG: &str = "claude";
+
+fn body(entry: &SessionEntry, workspace_id: i64, project_id: Option<i64>) -> TimeEntryBody {
+    TimeEntryBody {
+        created_with: "claude-time-tracker",
+        description: entry.description.clone(),
+        start: entry.start.to_rfc3339_opts(SecondsFormat::Secs, true),
+        stop: (entry.start + Duration::seconds(entry.seconds)).to_rfc3339_opts(SecondsFormat::Secs, true),
+        duration: entry.seconds,
+        workspace_id,
+        project_id,
+        tags: vec![TAG],
+    }
+}
+
+#[derive(Serialize)]
+struct TimeEntryBody {
+    created_with: &'static str,
+    description: String,
+    start: String,
+    stop: String,
+    duration: i64,
+    workspace_id: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    project_id: Option<i64>,
+    tags: Vec<&'static str>,
+}
+
+#[derive(Deserialize)]
+struct Me {
+    default_workspace_id: i64,
+}
+
+#[derive(Deserialize)]
+struct TogglProject {
+    id: i64,
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct Created {
+    id: i64,
+}
+
+/// A Toggl Track workspace and the token to write to it
+pub struct Client {
+    base_url: String,
+    token: String,
+    workspace_id: i64,
+}
+
+impl Client {
+    pub fn from_settings(settings: &TogglSettings) -> Result<Self> {
+        let token = settings
+            .api_token
+            .clone()
+            .or_else(|| secrets::lookup("toggl"))
+            .context("Set toggl.api_token in the config or store the token in the keychain (account `toggl`)")?;
+        let base_url = settings.base_url.trim_end_matches('/').to_string();
+        let workspace_id = match settings.workspace_id {
+            Some(id) => id,
+            None => {
+                let me: Me = Request::get(format!("{}/me", base_url))
+                    .basic_auth(&token, "api_token")
+                    .send()
+                    .context("Failed to look up the Toggl workspace")?;
+                me.default_workspace_id
+            }
+        };
+        Ok(Self {
+            base_url,
+            token,
+            workspace_id,
+        })
+    }
+
+    fn request(&self, request: Request) -> Request {
+        request.basic_auth(&self.token, "api_token")
+    }
+
+    /// Project ids by lowercase name
+    fn projects(&self) -> Result<HashMap<String, i64>> {
+        let url = format!("{}/workspaces/{}/projects?active=true", self.base_url, self.workspace_id);
+        let projects: Vec<TogglProject> = self
+            .request(Request::get(url))
+            .send()
+            .context("Failed to read the Toggl projects")?;
+        Ok(projects.into_iter().map(|p| (p.name.to_lowercase(), p.id)).collect())
+    }
+}
+
+/// Create a time entry per session and remember it in the sync state
+///
+/// Entries go to the Toggl project with the same name (ignoring case), or to
+/// no project when there is none. Each session is recorded right after its
+/// entry is created, so an interrupted push doesn't create duplicates.
+pub fn push(client: &Client, db: &Database, entries: &[SessionEntry]) -> Result<PushStats> {
+    let mut stats = PushStats::default();
+    let projects = client.projects()?;
+    for entry in entries {
+        let project_id = projects.get(&entry.project.to_lowercase()).copied();
+        if project_id.is_none() {
+            warn!("No Toggl project named {}, adding the entry without one", entry.project);
+        }
+
+        let url = format!("{}/workspaces/{}/time_entries", client.base_url, client.workspace_id);
+        let created: Created = client
+            .request(Request::post(url, &body(entry, client.workspace_id, project_id))?)
+            .send()
+            .with_context(|| format!("Failed to create the Toggl entry for {}", entry.description))?;
+        db.mark_session_synced(TARGET, &entry.session_uuid, &created.id.to_string())?;
+        info!(
+            "Added {} on {} to {}",
+            format_duration(entry.seconds),
+            entry.description,
+            entry.project
+        );
+        stats.created += 1;
+    }
+
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_time_entry_body() {
+        let entry = SessionEntry {
+            session_uuid: "0b6f".to_string(),
+            project: "api".to_string(),
+            description: "ABC-1".to_string(),
+            start: "2025-01-15T09:00:00Z".parse().unwrap(),
+            seconds: 5400,
+        };
+        assert_eq!(
+            serde_json::to_value(body(&entry, 42, None)).unwrap(),
+            serde_json::json!({
+                "created_with": "claude-time-tracker",
+                "description": "ABC-1",
+                "start": "2025-01-15T09:00:00Z",
+                "stop": "2025-01-15T10:30:00Z",
+                "duration": 5400,
+                "workspace_id": 42,
+                "tags": ["claude"],
+            })
+        );
+        assert_eq!(serde_json::to_value(body(&entry, 42, Some(7))).unwrap()["project_id"], 7);
+    }
+}
@@ -55,13 +55,6 @@ pub fn generate<W: Write>(report: &MonthlyReport, writer: W, include_commits: bo
     Ok(())
 }
 
-/// Generate CSV report as string
-pub fn generate_string(report: &MonthlyReport, include_commits: bool) -> Result<String> {
-    let mut buffer = Vec::new();
-    generate(report, &mut buffer, include_commits)?;
-    Ok(String::from_utf8(buffer)?)
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -72,10 +65,16 @@ mod tests {
         let report = MonthlyReport {
             period: "2025-01".to_string(),
             total_seconds: 7200,
+            models: Vec::new(),
+            clients: Vec::new(),
+            group_by_client: false,
             projects: vec![ProjectReport {
                 name: "Test Project".to_string(),
                 path: "/test/path".to_string(),
+                repository_url: None,
                 total_seconds: 7200,
+                amount: None,
+                prompts: 0,
                 work_items: vec![WorkItemReport {
                     id: "ABC-123".to_string(),
                     branch: Some("feature/ABC-123-test".to_string()),
@@ -84,12 +83,26 @@ mod tests {
                     commits: vec![CommitSummary {
                         hash: "abc123".to_string(),
                         message: "Test commit".to_string(),
+                        diff_stats: None,
                     }],
+                    diff_stats: None,
+                    pull_request: None,
+                    uncommitted: None,
+                    issue_url: None,
+                    title: None,
+                    days: Default::default(),
                 }],
+                modules: Vec::new(),
+                subprojects: Vec::new(),
+                activity: Vec::new(),
+                submodules: Vec::new(),
+                client: None,
             }],
         };
 
-        let csv = generate_string(&report, true).unwrap();
+        let mut buffer = Vec::new();
+        generate(&report, &mut buffer, true).unwrap();
+        let csv = String::from_utf8(buffer).unwrap();
         assert!(csv.contains("project,work_item,completed_date,hours,minutes,total_seconds,commits"));
         assert!(csv.contains("Test Project"));
         assert!(csv.contains("ABC-123"));
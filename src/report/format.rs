@@ -0,0 +1,193 @@
+//! Report formats, looked up by the names given to `--format`
+
+use anyhow::Result;
+use std::io::Write;
+
+use super::{csv, json, markdown, tsv};
+use crate::models::MonthlyReport;
+
+/// What a report includes beyond its totals
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RenderOptions {
+    pub include_commits: bool,
+    pub include_diff_stats: bool,
+}
+
+/// A way of writing a monthly report; formats are shared between the threads
+/// that write several formats at once
+pub trait ReportFormat: Send + Sync {
+    /// Name given to `--format`
+    fn name(&self) -> &'static str;
+    /// Other names it is known by
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+    /// Extension of the files it writes
+    fn extension(&self) -> &'static str;
+    /// Media type of the content, for the API
+    fn content_type(&self) -> &'static str;
+    fn generate(&self, report: &MonthlyReport, options: RenderOptions, writer: &mut dyn Write) -> Result<()>;
+
+    /// JSON Schema of what it writes, for formats that have one
+    fn schema(&self) -> Result<Option<String>> {
+        Ok(None)
+    }
+
+    fn render(&self, report: &MonthlyReport, options: RenderOptions) -> Result<String> {
+        let mut buffer = Vec::new();
+        self.generate(report, options, &mut buffer)?;
+        Ok(String::from_utf8(buffer)?)
+    }
+}
+
+/// The formats reports can be written in; the default one holds the built-in formats
+pub struct FormatRegistry {
+    formats: Vec<Box<dyn ReportFormat>>,
+}
+
+impl FormatRegistry {
+    /// Add a format, replacing one registered under the same name
+    pub fn register(&mut self, format: impl ReportFormat + 'static) {
+        self.formats.retain(|f| f.name() != format.name());
+        self.formats.push(Box::new(format));
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn ReportFormat> {
+        self.formats
+            .iter()
+            .find(|f| f.name() == name || f.aliases().contains(&name))
+            .map(|f| f.as_ref())
+    }
+
+    /// Names of all formats, in the order they were registered
+    pub fn names(&self) -> Vec<&'static str> {
+        self.formats.iter().map(|f| f.name()).collect()
+    }
+}
+
+impl Default for FormatRegistry {
+    fn default() -> Self {
+        let mut registry = Self { formats: Vec::new() };
+        registry.register(Markdown);
+        registry.register(Csv);
+        registry.register(Tsv);
+        registry.register(Json);
+        registry
+    }
+}
+
+struct Markdown;
+
+impl ReportFormat for Markdown {
+    fn name(&self) -> &'static str {
+        "md"
+    }
+    fn aliases(&self) -> &'static [&'static str] {
+        &["markdown"]
+    }
+    fn extension(&self) -> &'static str {
+        "md"
+    }
+    fn content_type(&self) -> &'static str {
+        "text/markdown; charset=utf-8"
+    }
+    fn generate(&self, report: &MonthlyReport, options: RenderOptions, writer: &mut dyn Write) -> Result<()> {
+        let content = markdown::generate(report, options.include_commits, options.include_diff_stats);
+        writer.write_all(content.as_bytes())?;
+        Ok(())
+    }
+}
+
+struct Csv;
+
+impl ReportFormat for Csv {
+    fn name(&self) -> &'static str {
+        "csv"
+    }
+    fn extension(&self) -> &'static str {
+        "csv"
+    }
+    fn content_type(&self) -> &'static str {
+        "text/csv; charset=utf-8"
+    }
+    fn generate(&self, report: &MonthlyReport, options: RenderOptions, writer: &mut dyn Write) -> Result<()> {
+        csv::generate(report, writer, options.include_commits)
+    }
+}
+
+struct Tsv;
+
+impl ReportFormat for Tsv {
+    fn name(&self) -> &'static str {
+        "tsv"
+    }
+    fn extension(&self) -> &'static str {
+        "tsv"
+    }
+    fn content_type(&self) -> &'static str {
+        "text/tab-separated-values; charset=utf-8"
+    }
+    fn generate(&self, report: &MonthlyReport, options: RenderOptions, writer: &mut dyn Write) -> Result<()> {
+        writer.write_all(tsv::generate_string(report, options.include_commits)?.as_bytes())?;
+        Ok(())
+    }
+}
+
+struct Json;
+
+impl ReportFormat for Json {
+    fn name(&self) -> &'static str {
+        "json"
+    }
+    fn extension(&self) -> &'static str {
+        "json"
+    }
+    fn content_type(&self) -> &'static str {
+        "application/json"
+    }
+    fn generate(&self, report: &MonthlyReport, _options: RenderOptions, writer: &mut dyn Write) -> Result<()> {
+        writer.write_all(json::generate(report)?.as_bytes())?;
+        Ok(())
+    }
+    fn schema(&self) -> Result<Option<String>> {
+        json::schema().map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry() {
+        let mut registry = FormatRegistry::default();
+        assert_eq!(registry.names(), ["md", "csv", "tsv", "json"]);
+        assert_eq!(registry.get("markdown").unwrap().extension(), "md");
+        assert!(registry.get("xlsx").is_none());
+
+        struct Total;
+        impl ReportFormat for Total {
+            fn name(&self) -> &'static str {
+                "total"
+            }
+            fn extension(&self) -> &'static str {
+                "txt"
+            }
+            fn content_type(&self) -> &'static str {
+                "text/plain; charset=utf-8"
+            }
+            fn generate(&self, report: &MonthlyReport, _: RenderOptions, writer: &mut dyn Write) -> Result<()> {
+                write!(writer, "{}", report.total_seconds)?;
+                Ok(())
+            }
+        }
+        registry.register(Total);
+
+        let report: MonthlyReport = serde_json::from_value(serde_json::json!({
+            "period": "2025-01", "total_seconds": 7200, "projects": [],
+        }))
+        .unwrap();
+        let format = registry.get("total").unwrap();
+        assert_eq!(format.render(&report, RenderOptions::default()).unwrap(), "7200");
+    }
+}
@@ -1,13 +1,48 @@
+//! JSON report
+//!
+//! Reports carry a `schema_version`. Within a version, fields are only ever
+//! added: none is removed, renamed or given another type, and only fields the
+//! schema marks optional are left out. Changes that break this bump the version.
+
 use anyhow::Result;
+use schemars::JsonSchema;
+use serde::Serialize;
 
 use crate::models::MonthlyReport;
 
+/// Version of the JSON report schema
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// A report as written in JSON, with the version of its schema
+#[derive(Serialize, JsonSchema)]
+#[schemars(title = "Claude time tracker monthly report")]
+pub struct Versioned<'a> {
+    /// Bumped only by changes that can break readers
+    schema_version: u32,
+    #[serde(flatten)]
+    report: &'a MonthlyReport,
+}
+
+impl<'a> Versioned<'a> {
+    pub fn new(report: &'a MonthlyReport) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            report,
+        }
+    }
+}
+
 /// Generate JSON report
 pub fn generate(report: &MonthlyReport) -> Result<String> {
-    let json = serde_json::to_string_pretty(report)?;
+    let json = serde_json::to_string_pretty(&Versioned::new(report))?;
     Ok(json)
 }
 
+/// JSON Schema of the report
+pub fn schema() -> Result<String> {
+    Ok(serde_json::to_string_pretty(&schemars::schema_for!(Versioned<'static>))?)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -18,10 +53,16 @@ mod tests {
         let report = MonthlyReport {
             period: "2025-01".to_string(),
             total_seconds: 7200,
+            models: Vec::new(),
+            clients: Vec::new(),
+            group_by_client: false,
             projects: vec![ProjectReport {
                 name: "Test Project".to_string(),
                 path: "/test/path".to_string(),
+                repository_url: None,
                 total_seconds: 7200,
+                amount: None,
+                prompts: 0,
                 work_items: vec![WorkItemReport {
                     id: "ABC-123".to_string(),
                     branch: Some("feature/ABC-123-test".to_string()),
@@ -30,14 +71,38 @@ mod tests {
                     commits: vec![CommitSummary {
                         hash: "abc123".to_string(),
                         message: "Test commit".to_string(),
+                        diff_stats: None,
                     }],
+                    diff_stats: None,
+                    pull_request: None,
+                    uncommitted: None,
+                    issue_url: None,
+                    title: None,
+                    days: Default::default(),
                 }],
+                modules: Vec::new(),
+                subprojects: Vec::new(),
+                activity: Vec::new(),
+                submodules: Vec::new(),
+                client: None,
             }],
         };
 
         let json = generate(&report).unwrap();
+        assert!(json.contains("\"schema_version\": 1"));
         assert!(json.contains("\"period\": \"2025-01\""));
         assert!(json.contains("\"total_seconds\": 7200"));
         assert!(json.contains("\"name\": \"Test Project\""));
     }
+
+    #[test]
+    fn test_schema_is_stable() {
+        // When this fails, bump SCHEMA_VERSION if readers could break, then
+        // regenerate the file with `report --format json --schema`
+        assert_eq!(
+            schema().unwrap(),
+            include_str!("../../docs/report-schema.json").trim_end(),
+            "the JSON report schema changed"
+        );
+    }
 }
@@ -1,8 +1,8 @@
-use crate::models::MonthlyReport;
+use crate::models::{DiffStats, MonthlyReport};
 use crate::tracker::format_duration;
 
 /// Generate markdown report
-pub fn generate(report: &MonthlyReport, include_commits: bool) -> String {
+pub fn generate(report: &MonthlyReport, include_commits: bool, include_diff_stats: bool) -> String {
     let mut output = String::new();
 
     // Header
@@ -16,32 +16,123 @@ pub fn generate(report: &MonthlyReport, include_commits: bool) -> String {
         format_duration(report.total_seconds)
     ));
 
+    if !report.models.is_empty() {
+        output.push_str("| 模型 | 時間 |\n");
+        output.push_str("|------|------|\n");
+        for model in &report.models {
+            output.push_str(&format!(
+                "| {} | {} |\n",
+                model.name,
+                format_duration(model.total_seconds)
+            ));
+        }
+        output.push('\n');
+    }
+
+    // Grouped reports show client subtotals in the client headings instead
+    if !report.clients.is_empty() && !report.group_by_client {
+        output.push_str("| 客戶 | 時間 |\n");
+        output.push_str("|------|------|\n");
+        for client in &report.clients {
+            output.push_str(&format!(
+                "| {} | {} |\n",
+                client.name,
+                format_duration(client.total_seconds)
+            ));
+        }
+        output.push('\n');
+    }
+
     output.push_str("---\n\n");
 
     // Projects
-    for project in &report.projects {
-        output.push_str(&format!("## {}\n\n", project.name));
+    let mut current_client = None;
+    for (index, project) in report.projects.iter().enumerate() {
+        if report.group_by_client {
+            if index == 0 || current_client != Some(&project.client) {
+                current_client = Some(&project.client);
+                let subtotal: i64 = report
+                    .projects
+                    .iter()
+                    .filter(|p| p.client == project.client)
+                    .map(|p| p.total_seconds)
+                    .sum();
+                output.push_str(&format!(
+                    "## {}\n\n**客戶小計：** {}\n\n",
+                    project.client.as_deref().unwrap_or("未指定客戶"),
+                    format_duration(subtotal)
+                ));
+            }
+            output.push_str(&format!("### {}\n\n", project.name));
+        } else {
+            output.push_str(&format!("## {}\n\n", project.name));
+            if let Some(ref client) = project.client {
+                output.push_str(&format!("**客戶：** {}\n\n", client));
+            }
+        }
+        if let Some(ref url) = project.repository_url {
+            output.push_str(&format!("**儲存庫：** {}\n\n", url));
+        }
         output.push_str(&format!(
             "**小計：** {}\n\n",
             format_duration(project.total_seconds)
         ));
+        if let Some(ref amount) = project.amount {
+            output.push_str(&format!(
+                "**金額：** {:.2}{}\n\n",
+                amount.value,
+                amount.currency.as_ref().map(|c| format!(" {}", c)).unwrap_or_default()
+            ));
+        }
+        if project.prompts > 0 {
+            output.push_str(&format!(
+                "**提問數：** {}（平均每則 {}）\n\n",
+                project.prompts,
+                format_duration(project.total_seconds / project.prompts)
+            ));
+        }
 
         // Work items table
+        let mut header = String::from("| 工作項 | 完成日期 | 時間 |");
+        let mut separator = String::from("|--------|----------|------|");
+        if include_diff_stats {
+            header.push_str(" 變更 |");
+            separator.push_str("------|");
+        }
         if include_commits {
-            output.push_str("| 工作項 | 完成日期 | 時間 | Commits |\n");
-            output.push_str("|--------|----------|------|----------|\n");
-        } else {
-            output.push_str("| 工作項 | 完成日期 | 時間 |\n");
-            output.push_str("|--------|----------|------|\n");
+            header.push_str(" 提交 |");
+            separator.push_str("------|");
         }
+        output.push_str(&format!("{}\n{}\n", header, separator));
 
         for item in &project.work_items {
             let time_str = format_duration(item.total_seconds);
             let date_str = item.completed_date.as_deref().unwrap_or("-");
 
+            let mut item_str = match item.issue_url {
+                Some(ref url) => format!("[{}]({})", item.id, url),
+                None => item.id.clone(),
+            };
+            // The issue's title, else the pull request's
+            let title = item.title.as_ref().or(item.pull_request.as_ref().map(|pr| &pr.title));
+            if let Some(title) = title.filter(|t| !t.is_empty()) {
+                item_str.push_str(&format!(" {}", title.replace('|', "\\|")));
+            }
+            if let Some(ref pr) = item.pull_request {
+                item_str.push_str(&format!(" ([#{}]({}))", pr.number, pr.url));
+            }
+
+            let mut row = format!("| {} | {} | {} |", item_str, date_str, time_str);
+            if include_diff_stats {
+                let diff_str = item.diff_stats.as_ref().map(format_diff_stats);
+                row.push_str(&format!(" {} |", diff_str.as_deref().unwrap_or("-")));
+            }
             if include_commits {
                 let commits_str = if item.commits.is_empty() {
-                    "-".to_string()
+                    match item.uncommitted {
+                        Some(ref stats) => format!("未提交：{}", format_diff_stats(stats)),
+                        None => "-".to_string(),
+                    }
                 } else {
                     item.commits
                         .iter()
@@ -50,9 +141,63 @@ pub fn generate(report: &MonthlyReport, include_commits: bool) -> String {
                         .join("、")
                 };
 
-                output.push_str(&format!("| {} | {} | {} | {} |\n", item.id, date_str, time_str, commits_str));
-            } else {
-                output.push_str(&format!("| {} | {} | {} |\n", item.id, date_str, time_str));
+                row.push_str(&format!(" {} |", commits_str));
+            }
+            output.push_str(&row);
+            output.push('\n');
+        }
+
+        if !project.subprojects.is_empty() {
+            output.push_str("\n**子專案分布：**\n\n");
+            output.push_str("| 子專案 | 時間 |\n");
+            output.push_str("|--------|------|\n");
+            for subproject in &project.subprojects {
+                output.push_str(&format!(
+                    "| {} | {} |\n",
+                    subproject.name,
+                    format_duration(subproject.total_seconds)
+                ));
+            }
+        }
+
+        if !project.activity.is_empty() {
+            output.push_str("\n**活動分布：**\n\n");
+            output.push_str("| 活動 | 時間 |\n");
+            output.push_str("|------|------|\n");
+            for activity in &project.activity {
+                output.push_str(&format!(
+                    "| {} | {} |\n",
+                    activity_label(&activity.name),
+                    format_duration(activity.total_seconds)
+                ));
+            }
+        }
+
+        if include_commits && !project.submodules.is_empty() {
+            output.push_str("\n**子模組提交：**\n\n");
+            output.push_str("| 子模組 | 提交 |\n");
+            output.push_str("|--------|------|\n");
+            for submodule in &project.submodules {
+                let commits_str = submodule
+                    .commits
+                    .iter()
+                    .map(|c| c.message.clone())
+                    .collect::<Vec<_>>()
+                    .join("、");
+                output.push_str(&format!("| {} | {} |\n", submodule.path, commits_str));
+            }
+        }
+
+        if !project.modules.is_empty() {
+            output.push_str("\n**模組分布：**\n\n");
+            output.push_str("| 模組 | 時間 |\n");
+            output.push_str("|------|------|\n");
+            for module in &project.modules {
+                output.push_str(&format!(
+                    "| {} | {} |\n",
+                    module.name,
+                    format_duration(module.total_seconds)
+                ));
             }
         }
 
@@ -62,6 +207,20 @@ pub fn generate(report: &MonthlyReport, include_commits: bool) -> String {
     output
 }
 
+fn format_diff_stats(stats: &DiffStats) -> String {
+    format!("+{} / -{}（{} 檔）", stats.insertions, stats.deletions, stats.files_changed)
+}
+
+fn activity_label(kind: &str) -> &str {
+    match kind {
+        "prompting" => "回應提問",
+        "tool" => "工具執行",
+        "waiting" => "等待使用者",
+        "other" => "其他",
+        _ => kind,
+    }
+}
+
 fn format_period(period: &str) -> String {
     // Parse "2025-01" into "2025 年 1 月"
     let parts: Vec<&str> = period.split('-').collect();
@@ -76,17 +235,23 @@ fn format_period(period: &str) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::models::{CommitSummary, ProjectReport, WorkItemReport};
+    use crate::models::{CommitSummary, ProjectReport, PullRequest, WorkItemReport};
 
     #[test]
     fn test_generate_markdown() {
-        let report = MonthlyReport {
+        let mut report = MonthlyReport {
             period: "2025-01".to_string(),
             total_seconds: 7200,
+            models: Vec::new(),
+            clients: Vec::new(),
+            group_by_client: false,
             projects: vec![ProjectReport {
                 name: "Test Project".to_string(),
                 path: "/test/path".to_string(),
+                repository_url: None,
                 total_seconds: 7200,
+                amount: None,
+                prompts: 0,
                 work_items: vec![WorkItemReport {
                     id: "ABC-123".to_string(),
                     branch: Some("feature/ABC-123-test".to_string()),
@@ -95,15 +260,74 @@ mod tests {
                     commits: vec![CommitSummary {
                         hash: "abc123".to_string(),
                         message: "Test commit".to_string(),
+                        diff_stats: None,
                     }],
+                    diff_stats: None,
+                    pull_request: None,
+                    uncommitted: None,
+                    issue_url: None,
+                    title: None,
+                    days: Default::default(),
                 }],
+                modules: Vec::new(),
+                subprojects: Vec::new(),
+                activity: Vec::new(),
+                submodules: Vec::new(),
+                client: None,
             }],
         };
 
-        let md = generate(&report, true);
+        let md = generate(&report, true, false);
         assert!(md.contains("Claude Code 工作時間報告"));
         assert!(md.contains("2025 年 1 月"));
         assert!(md.contains("Test Project"));
         assert!(md.contains("ABC-123"));
+        assert!(md.contains("| 工作項 | 完成日期 | 時間 | 提交 |"));
+
+        report.projects[0].work_items[0].diff_stats = Some(DiffStats {
+            files_changed: 4,
+            insertions: 120,
+            deletions: 30,
+        });
+        let md = generate(&report, false, true);
+        assert!(md.contains("| 工作項 | 完成日期 | 時間 | 變更 |"));
+        assert!(md.contains("| +120 / -30（4 檔） |"));
+
+        report.projects[0].work_items[0].pull_request = Some(PullRequest {
+            number: 42,
+            url: "https://github.com/acme/api/pull/42".to_string(),
+            title: "Add login".to_string(),
+        });
+        let md = generate(&report, false, false);
+        assert!(md.contains("| ABC-123 Add login ([#42](https://github.com/acme/api/pull/42)) |"));
+
+        report.projects[0].repository_url = Some("https://github.com/acme/api".to_string());
+        report.projects[0].work_items[0].issue_url = Some("https://github.com/acme/api/issues/7".to_string());
+        let md = generate(&report, false, false);
+        assert!(md.contains("**儲存庫：** https://github.com/acme/api"));
+        assert!(md.contains("| [ABC-123](https://github.com/acme/api/issues/7) Add login ([#42]("));
+
+        report.projects[0].work_items[0].title = Some("Login | signup".to_string());
+        let md = generate(&report, false, false);
+        assert!(md.contains("/issues/7) Login \\| signup ([#42]("));
+
+        let item = &mut report.projects[0].work_items[0];
+        item.commits.clear();
+        item.uncommitted = Some(DiffStats {
+            files_changed: 2,
+            insertions: 15,
+            deletions: 3,
+        });
+        let md = generate(&report, true, false);
+        assert!(md.contains("| 未提交：+15 / -3（2 檔） |"));
+
+        let mut other = report.projects[0].clone();
+        other.name = "Other Project".to_string();
+        other.client = Some("Acme".to_string());
+        report.projects.insert(0, other);
+        report.group_by_client = true;
+        let md = generate(&report, false, false);
+        assert!(md.contains("## Acme\n\n**客戶小計：** 2h 0m\n\n### Other Project"));
+        assert!(md.contains("## 未指定客戶\n\n**客戶小計：** 2h 0m\n\n### Test Project"));
     }
 }
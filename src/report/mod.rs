@@ -1,75 +1,409 @@
 pub mod csv;
+pub mod format;
 pub mod json;
 pub mod markdown;
+pub mod redact;
 pub mod tsv;
 
 use anyhow::{Context, Result};
-use chrono::{DateTime, Datelike, NaiveDate, TimeZone, Utc};
-use std::collections::HashMap;
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use clap::ValueEnum;
+use rayon::prelude::*;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
 
+use crate::config::{self, Attribution, EffectiveConfig, GlobalProjectSettings, WorkCalendar};
 use crate::db::Database;
-use crate::models::{CommitSummary, MonthlyReport, ProjectReport, WorkItemReport};
+use crate::models::{
+    self, Amount, CommitSummary, DiffStats, Heartbeat, ModuleReport, MonthlyReport, Project, ProjectRate,
+    ProjectReport, PullRequest, RemoteRepo, Session, SessionDetails, SessionStatus, SubmoduleReport,
+    WorkItemReport,
+};
+use crate::pull_request;
+use crate::storage::Storage;
+use crate::tracker;
+
+/// Model label for sessions recorded without model information
+const UNKNOWN_MODEL: &str = "unknown";
+
+/// How long an issue title that couldn't be looked up stays unasked for
+const MISSING_ISSUE_TTL_HOURS: i64 = 24;
+
+/// How session time is counted in reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TimeMetric {
+    /// Active time of each session, even when sessions ran concurrently
+    Active,
+    /// Overlapping sessions share their overlap, so totals never exceed wall-clock time
+    WallClock,
+}
+
+/// Options controlling report generation
+#[derive(Debug, Clone)]
+pub struct ReportOptions {
+    /// Only include projects whose name or path contains this string
+    pub project_filter: Option<String>,
+    /// Only include sessions recorded on this machine
+    pub machine: Option<String>,
+    /// Also report archived projects
+    pub include_archived: bool,
+    /// Count running sessions with their time up to now
+    pub include_active: bool,
+    pub max_commits_per_item: usize,
+    /// Break project time down by top-level directory
+    pub include_modules: bool,
+    pub idle_timeout_minutes: u32,
+    pub idle_grace_minutes: u32,
+    pub metric: TimeMetric,
+    /// Day (and month) the time of sessions spanning midnight counts towards
+    pub attribution: Attribution,
+    /// Working days (time zone and day start) the month and completion dates follow
+    pub calendar: WorkCalendar,
+    /// Order projects by client and show them under client headings
+    pub group_by_client: bool,
+    /// Look up (and cache) the titles of issue-number work items
+    pub fetch_issue_titles: bool,
+    /// Names and clients from the global config, keyed by project path or alias
+    pub projects: BTreeMap<PathBuf, GlobalProjectSettings>,
+}
+
+impl ReportOptions {
+    /// Options for a report of all projects' active time, as configured
+    pub fn from_config(config: &EffectiveConfig) -> Self {
+        Self {
+            project_filter: None,
+            machine: None,
+            include_archived: false,
+            include_active: false,
+            max_commits_per_item: config.max_commits_per_item,
+            include_modules: false,
+            group_by_client: false,
+            fetch_issue_titles: config.fetch_issue_titles,
+            idle_timeout_minutes: config.idle_timeout_minutes,
+            idle_grace_minutes: config.idle_grace_minutes,
+            metric: TimeMetric::Active,
+            attribution: config.attribution,
+            calendar: config.calendar,
+            projects: config.projects.clone(),
+        }
+    }
+}
+
+/// What a project's report is built from, read up front so that projects can
+/// be aggregated in parallel; heartbeats, the bulk of it, are left to the workers
+struct ProjectInput {
+    project: Project,
+    name: String,
+    client: Option<String>,
+    /// Dated hourly rates, oldest first
+    rates: Vec<ProjectRate>,
+    sessions: Vec<SessionInput>,
+    subproject_paths: Vec<(String, String)>,
+}
+
+/// A session with what was recorded along with it
+struct SessionInput {
+    session: Session,
+    details: SessionDetails,
+    /// Days of the month its time counts towards, with the share of it on each
+    days: Vec<(String, f64)>,
+}
+
+/// A project's report, with what is added up across projects; projects without
+/// any time still count towards the models seen
+struct ProjectTotals {
+    report: Option<ProjectReport>,
+    repository: Option<RemoteRepo>,
+    models: HashMap<String, i64>,
+}
 
 /// Generate report data for a given month
 pub fn generate_report(
     db: &Database,
     year: i32,
     month: u32,
-    project_filter: Option<&str>,
-    max_commits_per_item: usize,
+    options: &ReportOptions,
 ) -> Result<MonthlyReport> {
-    // Calculate date range for the month
-    let start = Utc
-        .with_ymd_and_hms(year, month, 1, 0, 0, 0)
-        .single()
-        .context("Invalid start date")?;
-
-    let end = if month == 12 {
-        Utc.with_ymd_and_hms(year + 1, 1, 1, 0, 0, 0)
-    } else {
-        Utc.with_ymd_and_hms(year, month + 1, 1, 0, 0, 0)
-    }
-    .single()
-    .context("Invalid end date")?;
+    let (start, end) = month_range(year, month, options.calendar)?;
 
     // Get all projects
-    let projects = db.list_projects()?;
+    let projects = db.list_projects(options.include_archived)?;
 
-    let mut project_reports = Vec::new();
-    let mut total_seconds: i64 = 0;
+    // Everything recorded for the month is read up front rather than per session
+    let mut details = db.get_session_details_in_range(start, end)?;
+    let mut sessions = db.get_sessions_overlapping(start, end)?;
 
+    // Running sessions count up to now, as `status` shows them
+    let now = Utc::now();
+    if options.include_active {
+        for mut session in db.get_all_active_sessions()? {
+            let (timeout, grace) = (options.idle_timeout_minutes, options.idle_grace_minutes);
+            session.active_seconds = Some(tracker::running_active_seconds(db, &session, timeout, grace)?);
+            details.entry(session.id).or_default().prompts = db.count_prompts(session.id)?;
+            sessions.push(session);
+        }
+    }
+
+    let mut month_sessions = Vec::new();
+    let mut session_days = HashMap::new();
+    for session in sessions {
+        let ended = match session.status {
+            SessionStatus::Active => now,
+            _ => session.ended_at.unwrap_or(session.started_at),
+        };
+        let days = attributed_days(session.started_at, ended, (start, end), options.attribution, options.calendar);
+        if !days.is_empty() {
+            session_days.insert(session.id, days);
+            month_sessions.push(session);
+        }
+    }
+
+    // Overlap is shared across all sessions of the month, whatever the filters;
+    // only each session's active spans are kept, not its heartbeats
+    let wall_clock = match options.metric {
+        TimeMetric::Active => None,
+        TimeMetric::WallClock => {
+            let (timeout, grace) = (options.idle_timeout_minutes, options.idle_grace_minutes);
+            let mut sessions = Vec::new();
+            for session in &month_sessions {
+                let heartbeats = db.get_heartbeats(session.id)?;
+                let spans = if heartbeats.is_empty() {
+                    // Added by hand or imported: active from start to end
+                    let ended = session.ended_at.unwrap_or(now);
+                    vec![(session.started_at, (ended - session.started_at).num_seconds())]
+                } else {
+                    tracker::active_spans(&heartbeats, timeout, grace)
+                };
+                sessions.push((session.id, spans));
+            }
+            Some(tracker::wall_clock_seconds(&sessions))
+        }
+    };
+
+    let mut sessions_by_project: HashMap<i64, Vec<Session>> = HashMap::new();
+    for session in month_sessions {
+        sessions_by_project.entry(session.project_id).or_default().push(session);
+    }
+
+    let mut inputs = Vec::new();
     for project in projects {
-        // Apply project filter if specified
-        if let Some(filter) = project_filter {
-            let name = project.display_name.as_deref().unwrap_or(&project.path);
-            if !name.to_lowercase().contains(&filter.to_lowercase())
-                && !project.path.to_lowercase().contains(&filter.to_lowercase())
-            {
+        let (name, client) = project_labels(db, &project, &options.projects)?;
+
+        // Apply project filter if specified (client names match too)
+        if let Some(ref filter) = options.project_filter {
+            let filter = filter.to_lowercase();
+            let matches = |value: &str| value.to_lowercase().contains(&filter);
+            let client_matches = client.as_deref().is_some_and(matches);
+            if !matches(&name) && !matches(&project.path) && !client_matches {
                 continue;
             }
         }
 
-        let sessions = db.get_sessions_in_range(start, end, Some(project.id))?;
+        let mut sessions = sessions_by_project.remove(&project.id).unwrap_or_default();
+        if let Some(ref machine) = options.machine {
+            sessions.retain(|s| s.machine.as_deref() == Some(machine.as_str()));
+        }
 
         if sessions.is_empty() {
             continue;
         }
 
-        // Group sessions by work item
-        // (total_seconds, commits, branch, completed_date)
-        let mut work_items: HashMap<String, (i64, Vec<CommitSummary>, Option<String>, Option<DateTime<Utc>>)> = HashMap::new();
+        // Sub-projects come from the config file in the (monorepo) project itself
+        let subproject_paths: Vec<(String, String)> = config::load_project_config(Path::new(&project.path))
+            .map(|c| c.subprojects.into_iter().collect())
+            .unwrap_or_default();
+
+        let mut project_sessions = Vec::with_capacity(sessions.len());
+        for session in sessions {
+            project_sessions.push(SessionInput {
+                details: details.remove(&session.id).unwrap_or_default(),
+                days: session_days.remove(&session.id).unwrap_or_default(),
+                session,
+            });
+        }
+
+        inputs.push(ProjectInput {
+            rates: db.get_project_rates(project.id)?,
+            project,
+            name,
+            client,
+            sessions: project_sessions,
+            subproject_paths,
+        });
+    }
 
-        for session in &sessions {
-            let work_item_id = session
-                .work_item
-                .clone()
-                .unwrap_or_else(|| session.branch.clone());
+    // Projects are aggregated in parallel. Workers that need heartbeats read
+    // them a session at a time, over a connection of their own opened on first use.
+    let path = db.path()?;
+    let totals = inputs
+        .into_par_iter()
+        .map_init(
+            || None,
+            |reader: &mut Option<Database>, input| {
+                let mut heartbeats = |session_id| {
+                    let reader = match reader.take() {
+                        Some(open) => reader.insert(open),
+                        None => reader.insert(Database::open(&path)?),
+                    };
+                    reader.get_heartbeats(session_id)
+                };
+                project_report(input, &mut heartbeats, wall_clock.as_ref(), options)
+            },
+        )
+        .collect::<Result<Vec<ProjectTotals>>>()?;
+
+    let mut project_reports = Vec::new();
+    let mut total_seconds: i64 = 0;
+    let mut models: HashMap<String, i64> = HashMap::new();
+    let mut clients: HashMap<String, i64> = HashMap::new();
+
+    for ProjectTotals { report, repository, models: project_models } in totals {
+        for (model, seconds) in project_models {
+            *models.entry(model).or_insert(0) += seconds;
+        }
+        let Some(mut report) = report else {
+            continue;
+        };
+
+        total_seconds += report.total_seconds;
+        if let Some(ref client) = report.client {
+            *clients.entry(client.clone()).or_insert(0) += report.total_seconds;
+        }
+
+        // Titles are cached in the database, so they are looked up one project at a time
+        if let Some(ref repository) = repository {
+            if options.fetch_issue_titles {
+                for item in &mut report.work_items {
+                    item.title = issue_title(db, repository, &item.id)?;
+                }
+            }
+        }
+        project_reports.push(report);
+    }
+
+    // Sort projects by total time descending
+    project_reports.sort_by(|a, b| b.total_seconds.cmp(&a.total_seconds));
+
+    let clients = breakdown_reports(clients);
+    if options.group_by_client {
+        // Clients in the order of their totals, projects without one last
+        let rank = |project: &ProjectReport| {
+            clients
+                .iter()
+                .position(|c| project.client.as_ref() == Some(&c.name))
+                .unwrap_or(clients.len())
+        };
+        project_reports.sort_by_key(rank);
+    }
+
+    let period = format!("{}-{:02}", year, month);
+
+    // Only break down by model once the hooks have reported any
+    if models.keys().all(|model| model == UNKNOWN_MODEL) {
+        models.clear();
+    }
+
+    Ok(MonthlyReport {
+        period,
+        total_seconds,
+        models: breakdown_reports(models),
+        clients,
+        projects: project_reports,
+        group_by_client: options.group_by_client,
+    })
+}
+
+/// Add up a project's sessions into its report
+///
+/// `heartbeats` reads a session's heartbeats, which are only needed for the
+/// module and sub-project breakdowns.
+fn project_report(
+    input: ProjectInput,
+    heartbeats: &mut impl FnMut(i64) -> Result<Vec<Heartbeat>>,
+    wall_clock: Option<&HashMap<i64, i64>>,
+    options: &ReportOptions,
+) -> Result<ProjectTotals> {
+    let ProjectInput {
+        project,
+        name,
+        client,
+        rates,
+        sessions,
+        subproject_paths,
+    } = input;
+    let mut models: HashMap<String, i64> = HashMap::new();
+
+    // Group sessions by work item
+    // (total_seconds, commits, branch, completed_date)
+    let mut work_items: HashMap<String, (i64, Vec<CommitSummary>, Option<String>, Option<DateTime<Utc>>)> = HashMap::new();
+    let mut diffs: HashMap<String, DiffStats> = HashMap::new();
+    let mut pull_requests: HashMap<String, PullRequest> = HashMap::new();
+    let mut uncommitted: HashMap<String, DiffStats> = HashMap::new();
+    let mut days: HashMap<String, BTreeMap<String, i64>> = HashMap::new();
+    let mut modules: HashMap<String, i64> = HashMap::new();
+    let mut subprojects: HashMap<String, i64> = HashMap::new();
+    let mut activity: HashMap<String, i64> = HashMap::new();
+    let mut submodules: BTreeMap<String, Vec<CommitSummary>> = BTreeMap::new();
+    let mut prompts = 0;
+
+    for SessionInput {
+        session,
+        details,
+        days: session_days,
+    } in sessions
+    {
+        // Sessions split across months only count the part that falls in this one
+        let month_share: f64 = session_days.iter().map(|(_, share)| share).sum();
+        prompts += (details.prompts as f64 * month_share).round() as i64;
+
+        // Scale everything attributed to this session to its wall-clock share
+        let recorded = session.active_seconds.unwrap_or(0);
+        let factor = match wall_clock.and_then(|w| w.get(&session.id)) {
+            Some(&share) if recorded > 0 => share as f64 / recorded as f64,
+            _ => 1.0,
+        } * month_share;
+        let adjust = |seconds: i64| (seconds as f64 * factor).round() as i64;
+
+        let model = session.model.clone().unwrap_or_else(|| UNKNOWN_MODEL.to_string());
+        *models.entry(model).or_insert(0) += adjust(recorded);
+
+        for (kind, seconds) in details.activity {
+            *activity.entry(kind).or_insert(0) += adjust(seconds);
+        }
+
+        // Sessions that switched branches are split across their branches;
+        // commits go to the branch the session spent the most time on.
+        let mut allocations: Vec<(String, Option<String>, i64)> = details
+            .branches
+            .into_iter()
+            .map(|b| (b.branch, b.work_item, adjust(b.active_seconds)))
+            .collect();
+        if allocations.is_empty() {
+            allocations.push((
+                session.branch.clone(),
+                session.work_item.clone(),
+                adjust(recorded),
+            ));
+        }
+        let primary = allocations
+            .iter()
+            .enumerate()
+            .max_by_key(|(i, (_, _, seconds))| (*seconds, std::cmp::Reverse(*i)))
+            .map(|(i, _)| i)
+            .unwrap_or(0);
+
+        for (index, (branch, work_item, seconds)) in allocations.into_iter().enumerate() {
+            let work_item_id = work_item.unwrap_or_else(|| branch.clone());
 
             let entry = work_items
-                .entry(work_item_id)
-                .or_insert_with(|| (0, Vec::new(), Some(session.branch.clone()), None));
+                .entry(work_item_id.clone())
+                .or_insert_with(|| (0, Vec::new(), Some(branch), None));
 
-            entry.0 += session.active_seconds.unwrap_or(0);
+            entry.0 += seconds;
+            let item_days = days.entry(work_item_id.clone()).or_default();
+            for (day, day_seconds) in spread(seconds, &session_days) {
+                *item_days.entry(day).or_insert(0) += day_seconds;
+            }
 
             // Track the latest ended_at as completed date
             if let Some(ended) = session.ended_at {
@@ -79,62 +413,282 @@ pub fn generate_report(
             }
 
             // Get commits for this session
-            if let Ok(commits) = db.get_commits(session.id) {
-                for commit in commits {
-                    if entry.1.len() < max_commits_per_item {
-                        entry.1.push(CommitSummary {
-                            hash: commit.hash[..8.min(commit.hash.len())].to_string(),
-                            message: commit.message.unwrap_or_default(),
-                        });
-                    }
+            if index != primary {
+                continue;
+            }
+            // Sessions come oldest first, so the latest snapshot wins
+            if let Some(stats) = session.uncommitted {
+                uncommitted.insert(work_item_id.clone(), stats);
+            }
+            if let Some(ref pull_request) = session.pull_request {
+                pull_requests.insert(work_item_id.clone(), pull_request.clone());
+            }
+            if let Some(stats) = session.diff_stats {
+                *diffs.entry(work_item_id).or_default() += stats;
+            }
+            for commit in &details.commits {
+                let list = match commit.submodule {
+                    Some(ref path) => submodules.entry(path.clone()).or_default(),
+                    None => &mut entry.1,
+                };
+                if list.len() < options.max_commits_per_item {
+                    list.push(CommitSummary {
+                        hash: commit.hash[..8.min(commit.hash.len())].to_string(),
+                        message: commit.message.clone().unwrap_or_default(),
+                        diff_stats: commit.diff_stats,
+                    });
                 }
             }
         }
 
-        let project_total: i64 = work_items.values().map(|(s, _, _, _)| s).sum();
-
-        if project_total == 0 {
-            continue;
+        if options.include_modules || !subproject_paths.is_empty() {
+            let heartbeats = heartbeats(session.id)?;
+            if options.include_modules {
+                let breakdown = tracker::module_breakdown(
+                    &heartbeats,
+                    Path::new(&project.path),
+                    options.idle_timeout_minutes,
+                    options.idle_grace_minutes,
+                );
+                for (module, seconds) in breakdown {
+                    *modules.entry(module).or_insert(0) += adjust(seconds);
+                }
+            }
+            if !subproject_paths.is_empty() {
+                let breakdown = tracker::subproject_breakdown(
+                    &heartbeats,
+                    Path::new(&project.path),
+                    &subproject_paths,
+                    options.idle_timeout_minutes,
+                    options.idle_grace_minutes,
+                );
+                for (name, seconds) in breakdown {
+                    *subprojects.entry(name).or_insert(0) += adjust(seconds);
+                }
+            }
         }
+    }
 
-        total_seconds += project_total;
-
-        let mut work_item_reports: Vec<WorkItemReport> = work_items
-            .into_iter()
-            .map(|(id, (seconds, commits, branch, completed))| WorkItemReport {
-                id,
-                branch,
-                total_seconds: seconds,
-                completed_date: completed.map(|dt| dt.format("%Y-%m-%d").to_string()),
-                commits,
-            })
-            .collect();
+    let project_total: i64 = work_items.values().map(|(s, _, _, _)| s).sum();
 
-        // Sort by time descending
-        work_item_reports.sort_by(|a, b| b.total_seconds.cmp(&a.total_seconds));
+    if project_total == 0 {
+        return Ok(ProjectTotals {
+            report: None,
+            repository: None,
+            models,
+        });
+    }
 
-        project_reports.push(ProjectReport {
-            name: project
-                .display_name
-                .unwrap_or_else(|| project.path.clone()),
-            path: project.path,
-            total_seconds: project_total,
-            work_items: work_item_reports,
+    let repository = project.repository();
+    let mut work_item_reports: Vec<WorkItemReport> = Vec::new();
+    for (id, (seconds, commits, branch, completed)) in work_items {
+        work_item_reports.push(WorkItemReport {
+            branch,
+            total_seconds: seconds,
+            completed_date: completed.map(|dt| options.calendar.day_of(dt).format("%Y-%m-%d").to_string()),
+            commits,
+            diff_stats: diffs.get(&id).copied(),
+            pull_request: pull_requests.remove(&id),
+            uncommitted: uncommitted.get(&id).copied(),
+            issue_url: repository.as_ref().and_then(|r| r.issue_url(&id)),
+            days: days.remove(&id).unwrap_or_default(),
+            title: None,
+            id,
         });
     }
 
-    // Sort projects by total time descending
-    project_reports.sort_by(|a, b| b.total_seconds.cmp(&a.total_seconds));
+    // Sort by time descending
+    work_item_reports.sort_by(|a, b| b.total_seconds.cmp(&a.total_seconds));
 
-    let period = format!("{}-{:02}", year, month);
+    let module_reports = breakdown_reports(modules);
+    let subproject_reports = breakdown_reports(subprojects);
+    let activity_reports = breakdown_reports(activity);
 
-    Ok(MonthlyReport {
-        period,
-        total_seconds,
-        projects: project_reports,
+    let report = ProjectReport {
+        amount: billed_amount(&project, &rates, &work_item_reports),
+        name,
+        client,
+        repository_url: repository.as_ref().map(|r| r.web_url()),
+        path: project.path,
+        total_seconds: project_total,
+        prompts,
+        work_items: work_item_reports,
+        modules: module_reports,
+        subprojects: subproject_reports,
+        activity: activity_reports,
+        submodules: submodules
+            .into_iter()
+            .map(|(path, commits)| SubmoduleReport { path, commits })
+            .collect(),
+    };
+    Ok(ProjectTotals {
+        report: Some(report),
+        repository,
+        models,
     })
 }
 
+
+/// What a billable project's time comes to at its hourly rates
+///
+/// Each day's time is charged at the dated rate in effect that day, else at
+/// the project's flat rate. There is no amount when some of the time has no
+/// rate, nor when the rates used are in different currencies.
+fn billed_amount(project: &Project, rates: &[ProjectRate], work_items: &[WorkItemReport]) -> Option<Amount> {
+    if !project.billable {
+        return None;
+    }
+    let mut amount: Option<Amount> = None;
+    for (day, seconds) in work_items.iter().flat_map(|item| &item.days) {
+        let (rate, currency) = match rates.iter().rev().find(|r| r.effective_from.to_string() <= *day) {
+            Some(rate) => (rate.hourly_rate, rate.currency.clone()),
+            None => (project.hourly_rate?, None),
+        };
+        let value = *seconds as f64 / 3600.0 * rate;
+        match amount {
+            Some(ref mut amount) if amount.currency == currency => amount.value += value,
+            Some(_) => return None,
+            None => amount = Some(Amount { value, currency }),
+        }
+    }
+    amount.map(|amount| Amount {
+        value: (amount.value * 100.0).round() / 100.0,
+        ..amount
+    })
+}
+
+/// Title of the issue a work item like `#123` refers to, from the cache or the code host
+fn issue_title(db: &Database, repository: &RemoteRepo, work_item: &str) -> Result<Option<String>> {
+    let Some(number) = models::issue_number(work_item) else {
+        return Ok(None);
+    };
+    let key = format!("{}/{}", repository.host, repository.slug());
+    if let Some(title) = db.get_issue_title(&key, number, chrono::Duration::hours(MISSING_ISSUE_TTL_HOURS))? {
+        return Ok(title);
+    }
+    let title = pull_request::find_issue_title(repository, number);
+    db.set_issue_title(&key, number, title.as_deref())?;
+    Ok(title)
+}
+
+/// Global config entry for a project, found by its path or one of its aliases
+pub fn configured_project(
+    db: &Database,
+    project: &Project,
+    projects: &BTreeMap<PathBuf, GlobalProjectSettings>,
+) -> Result<GlobalProjectSettings> {
+    if projects.is_empty() {
+        return Ok(GlobalProjectSettings::default());
+    }
+    let paths = std::iter::once(project.path.clone()).chain(db.get_project_aliases(project.id)?);
+    for path in paths {
+        if let Some(settings) = projects.get(Path::new(&path)) {
+            return Ok(settings.clone());
+        }
+    }
+    Ok(GlobalProjectSettings::default())
+}
+
+/// Name and client a project is reported under
+///
+/// As when tracking, values set with `projects set` win; the global
+/// `[projects]` entry only fills in what they leave out.
+pub fn project_labels(
+    db: &Database,
+    project: &Project,
+    projects: &BTreeMap<PathBuf, GlobalProjectSettings>,
+) -> Result<(String, Option<String>)> {
+    let settings = configured_project(db, project, projects)?;
+    let name = match (&project.display_name, settings.name) {
+        (Some(name), _) => name.clone(),
+        (None, Some(name)) => name,
+        (None, None) => project.name(),
+    };
+    Ok((name, project.client.clone().or(settings.client)))
+}
+
+/// Days of a month (`start` to `end`) the time of a session that ran from
+/// `started` to `ended` counts towards, with the share of its time on each;
+/// none when it counts towards another month
+fn attributed_days(
+    started: DateTime<Utc>,
+    ended: DateTime<Utc>,
+    (start, end): (DateTime<Utc>, DateTime<Utc>),
+    attribution: Attribution,
+    calendar: WorkCalendar,
+) -> Vec<(String, f64)> {
+    let ended = ended.max(started);
+    let whole_on = |instant: DateTime<Utc>| {
+        let within = instant >= start && instant < end;
+        within
+            .then(|| (calendar.day_of(instant).format("%Y-%m-%d").to_string(), 1.0))
+            .into_iter()
+            .collect()
+    };
+    let span = (ended - started).num_milliseconds();
+    match attribution {
+        Attribution::Start => whole_on(started),
+        Attribution::End => whole_on(ended),
+        Attribution::Split if span == 0 => whole_on(started),
+        Attribution::Split => {
+            let mut days = Vec::new();
+            let mut from = started.max(start);
+            let until = ended.min(end);
+            while from < until {
+                let day = calendar.day_of(from);
+                let next = day
+                    .succ_opt()
+                    .map(|next| calendar.day_start(next))
+                    .filter(|&next| next > from)
+                    .map_or(until, |next| next.min(until));
+                let share = (next - from).num_milliseconds() as f64 / span as f64;
+                days.push((day.format("%Y-%m-%d").to_string(), share));
+                from = next;
+            }
+            days
+        }
+    }
+}
+
+/// Divide seconds between days in proportion to their shares, so that they add up
+fn spread(seconds: i64, days: &[(String, f64)]) -> Vec<(String, i64)> {
+    let total: f64 = days.iter().map(|(_, share)| share).sum();
+    let mut left = seconds;
+    let mut spread = Vec::with_capacity(days.len());
+    for (index, (day, share)) in days.iter().enumerate() {
+        let day_seconds = if index + 1 == days.len() {
+            left
+        } else {
+            (seconds as f64 * share / total).round() as i64
+        };
+        left -= day_seconds;
+        spread.push((day.clone(), day_seconds));
+    }
+    spread
+}
+
+/// Turn per-name totals into report rows, largest first
+fn breakdown_reports(totals: HashMap<String, i64>) -> Vec<ModuleReport> {
+    let mut reports: Vec<ModuleReport> = totals
+        .into_iter()
+        .map(|(name, total_seconds)| ModuleReport { name, total_seconds })
+        .collect();
+    reports.sort_by(|a, b| b.total_seconds.cmp(&a.total_seconds).then(a.name.cmp(&b.name)));
+    reports
+}
+
+/// First instant of a month and of the month after it, in the working calendar
+pub fn month_range(year: i32, month: u32, calendar: WorkCalendar) -> Result<(DateTime<Utc>, DateTime<Utc>)> {
+    let first_day = NaiveDate::from_ymd_opt(year, month, 1).context("Invalid start date")?;
+    let next_first_day = if month == 12 {
+        NaiveDate::from_ymd_opt(year + 1, 1, 1)
+    } else {
+        NaiveDate::from_ymd_opt(year, month + 1, 1)
+    }
+    .context("Invalid end date")?;
+    Ok((calendar.day_start(first_day), calendar.day_start(next_first_day)))
+}
+
 /// Parse month string (YYYY-MM) into year and month
 pub fn parse_month(month_str: &str) -> Result<(i32, u32)> {
     let date = NaiveDate::parse_from_str(&format!("{}-01", month_str), "%Y-%m-%d")
@@ -143,8 +697,180 @@ pub fn parse_month(month_str: &str) -> Result<(i32, u32)> {
     Ok((date.year(), date.month()))
 }
 
-/// Get current year and month
-pub fn current_month() -> (i32, u32) {
-    let now = Utc::now();
-    (now.year(), now.month())
+/// Get current year and month of the working calendar
+pub fn current_month(calendar: WorkCalendar) -> (i32, u32) {
+    let today = calendar.today();
+    (today.year(), today.month())
+}
+
+/// Get year and month of the previous month of the working calendar
+pub fn previous_month(calendar: WorkCalendar) -> (i32, u32) {
+    let (year, month) = current_month(calendar);
+    if month == 1 {
+        (year - 1, 12)
+    } else {
+        (year, month - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Timezone;
+
+    #[test]
+    fn test_attributed_days() {
+        let calendar = WorkCalendar {
+            timezone: Timezone::parse("UTC").unwrap(),
+            day_start_hour: 0,
+        };
+        let at = |s: &str| DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc);
+        // Jan 31 23:00 to Feb 1 02:00
+        let (started, ended) = (at("2025-01-31T23:00:00Z"), at("2025-02-01T02:00:00Z"));
+        let january = month_range(2025, 1, calendar).unwrap();
+        let february = month_range(2025, 2, calendar).unwrap();
+        let days = |range, attribution| attributed_days(started, ended, range, attribution, calendar);
+
+        assert_eq!(days(january, Attribution::Start), [("2025-01-31".to_string(), 1.0)]);
+        assert!(days(february, Attribution::Start).is_empty());
+        assert!(days(january, Attribution::End).is_empty());
+        assert_eq!(days(february, Attribution::End), [("2025-02-01".to_string(), 1.0)]);
+
+        let split = days(january, Attribution::Split);
+        assert_eq!(split.len(), 1);
+        assert!((split[0].1 - 1.0 / 3.0).abs() < 1e-9);
+        let split = days(february, Attribution::Split);
+        assert_eq!(split[0].0, "2025-02-01");
+        assert!((split[0].1 - 2.0 / 3.0).abs() < 1e-9);
+
+        assert_eq!(spread(7200, &split), [("2025-02-01".to_string(), 7200)]);
+        let both = [("2025-01-31".to_string(), 1.0 / 3.0), ("2025-02-01".to_string(), 2.0 / 3.0)];
+        assert_eq!(spread(10801, &both), [("2025-01-31".to_string(), 3600), ("2025-02-01".to_string(), 7201)]);
+    }
+
+    #[test]
+    fn test_include_active() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::open(&dir.path().join("test.db")).unwrap();
+        let project = db.get_or_create_project("/src/api", None, None, None).unwrap();
+        let session = db.create_session(project.id, "main", Some("ABC-1"), None, None, None).unwrap();
+        db.set_running_active_time(session.id, 1800, Utc::now()).unwrap();
+
+        let config = EffectiveConfig::from_global(config::GlobalConfig::default(), None).unwrap();
+        let mut options = ReportOptions::from_config(&config);
+        let (year, month) = current_month(options.calendar);
+        assert!(generate_report(&db, year, month, &options).unwrap().projects.is_empty());
+
+        // The running session counts with its time so far
+        options.include_active = true;
+        let report = generate_report(&db, year, month, &options).unwrap();
+        assert_eq!(report.projects.len(), 1);
+        assert!((1800..1860).contains(&report.total_seconds), "{}", report.total_seconds);
+        assert_eq!(report.projects[0].work_items[0].id, "ABC-1");
+
+        // But only towards the month it runs in
+        let (year, month) = previous_month(options.calendar);
+        assert!(generate_report(&db, year, month, &options).unwrap().projects.is_empty());
+    }
+
+    #[test]
+    fn test_wall_clock_without_heartbeats() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::open(&dir.path().join("test.db")).unwrap();
+        let project = db.get_or_create_project("/src/api", None, None, None).unwrap();
+
+        let config = EffectiveConfig::from_global(config::GlobalConfig::default(), None).unwrap();
+        let mut options = ReportOptions::from_config(&config);
+        options.metric = TimeMetric::WallClock;
+        let (year, month) = current_month(options.calendar);
+        let (start, _) = month_range(year, month, options.calendar).unwrap();
+
+        // Two imported hours, overlapping by half an hour
+        let first = start + chrono::Duration::hours(1);
+        db.import_session(project.id, "toggl", "main", Some("ABC-1"), first, 3600).unwrap();
+        let second = first + chrono::Duration::minutes(30);
+        db.import_session(project.id, "toggl", "main", Some("ABC-2"), second, 3600).unwrap();
+
+        let report = generate_report(&db, year, month, &options).unwrap();
+        assert_eq!(report.total_seconds, 5400);
+        let items = &report.projects[0].work_items;
+        assert!(items.iter().all(|item| item.total_seconds == 2700));
+    }
+
+    #[test]
+    fn test_billed_amount() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::open(&dir.path().join("test.db")).unwrap();
+        let mut project = db.get_or_create_project("/src/api", None, None, None).unwrap();
+
+        let config = EffectiveConfig::from_global(config::GlobalConfig::default(), None).unwrap();
+        let options = ReportOptions::from_config(&config);
+        let (year, month) = current_month(options.calendar);
+        let (start, _) = month_range(year, month, options.calendar).unwrap();
+
+        // An hour on each of the first two days of the month, the rate going up on the second
+        for day in 0..2 {
+            let started = start + chrono::Duration::days(day) + chrono::Duration::hours(1);
+            db.import_session(project.id, "toggl", "main", Some("ABC-1"), started, 3600).unwrap();
+        }
+        let amount = |db: &Database| generate_report(db, year, month, &options).unwrap().projects[0].amount.clone();
+        assert_eq!(amount(&db), None);
+
+        project.hourly_rate = Some(50.0);
+        db.update_project(&project).unwrap();
+        assert_eq!(amount(&db).map(|a| a.value), Some(100.0));
+
+        let second = NaiveDate::from_ymd_opt(year, month, 2).unwrap();
+        db.set_project_rate(project.id, second, 80.0, Some("EUR")).unwrap();
+        // The flat rate comes without a currency
+        assert_eq!(amount(&db), None);
+        db.set_project_rate(project.id, second.pred_opt().unwrap(), 60.0, Some("EUR")).unwrap();
+        let euros = Amount {
+            value: 140.0,
+            currency: Some("EUR".to_string()),
+        };
+        assert_eq!(amount(&db), Some(euros));
+
+        project.billable = false;
+        db.update_project(&project).unwrap();
+        assert_eq!(amount(&db), None);
+    }
+
+    #[test]
+    fn test_project_labels() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::open(&dir.path().join("test.db")).unwrap();
+        let mut named = db.get_or_create_project("/src/shop", None, Some("Shop"), None).unwrap();
+        named.client = Some("Acme".to_string());
+        db.update_project(&named).unwrap();
+        let unnamed = db.get_or_create_project("/src/blog", None, None, None).unwrap();
+        for project in [&named, &unnamed] {
+            let session = db.create_session(project.id, "main", Some("ABC-1"), None, None, None).unwrap();
+            db.complete_session(session.id, None, 600, Utc::now(), SessionStatus::Completed).unwrap();
+        }
+
+        let config = EffectiveConfig::from_global(config::GlobalConfig::default(), None).unwrap();
+        let mut options = ReportOptions::from_config(&config);
+        for path in ["/src/shop", "/src/blog"] {
+            let settings = GlobalProjectSettings {
+                name: Some(format!("Configured {}", path)),
+                client: Some("Configured client".to_string()),
+                ..Default::default()
+            };
+            options.projects.insert(PathBuf::from(path), settings);
+        }
+        let (year, month) = current_month(options.calendar);
+        let report = generate_report(&db, year, month, &options).unwrap();
+        let labels = |path: &str| {
+            let project = report.projects.iter().find(|p| p.path == path).unwrap();
+            (project.name.clone(), project.client.clone())
+        };
+
+        // `projects set` wins; the config fills in what it left out
+        assert_eq!(labels("/src/shop"), ("Shop".to_string(), Some("Acme".to_string())));
+        assert_eq!(
+            labels("/src/blog"),
+            ("Configured /src/blog".to_string(), Some("Configured client".to_string()))
+        );
+    }
 }
@@ -0,0 +1,138 @@
+use clap::ValueEnum;
+use std::path::Path;
+
+use crate::models::{MonthlyReport, WorkItemReport};
+
+/// Label used for work items that were only identified by a branch name
+const UNLABELLED_WORK_ITEM: &str = "other";
+
+/// What to strip from a report before sharing it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum RedactProfile {
+    /// External sharing: keep display names and durations only
+    Client,
+}
+
+/// Remove sensitive details from a report according to the profile
+pub fn apply(report: &mut MonthlyReport, profile: RedactProfile) {
+    match profile {
+        RedactProfile::Client => redact_client(report),
+    }
+}
+
+fn redact_client(report: &mut MonthlyReport) {
+    // One client should not learn who else is billed
+    report.clients.clear();
+    for project in &mut report.projects {
+        project.client = None;
+        // Projects without a display name are named after their path
+        if project.name == project.path {
+            project.name = Path::new(&project.path)
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+        }
+        project.path = String::new();
+        project.repository_url = None;
+        project.modules.clear();
+        project.activity.clear();
+        project.submodules.clear();
+
+        let mut items: Vec<WorkItemReport> = Vec::new();
+        for mut item in project.work_items.drain(..) {
+            if item.branch.as_deref() == Some(item.id.as_str()) {
+                item.id = UNLABELLED_WORK_ITEM.to_string();
+            }
+            item.branch = None;
+            item.commits.clear();
+            item.diff_stats = None;
+            item.pull_request = None;
+            item.uncommitted = None;
+            item.issue_url = None;
+            item.title = None;
+
+            match items.iter_mut().find(|existing| existing.id == item.id) {
+                Some(existing) => {
+                    existing.total_seconds += item.total_seconds;
+                    if item.completed_date > existing.completed_date {
+                        existing.completed_date = item.completed_date;
+                    }
+                }
+                None => items.push(item),
+            }
+        }
+
+        items.sort_by(|a, b| b.total_seconds.cmp(&a.total_seconds).then(a.id.cmp(&b.id)));
+        project.work_items = items;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CommitSummary, ModuleReport, ProjectReport};
+
+    fn item(id: &str, branch: &str, seconds: i64) -> WorkItemReport {
+        WorkItemReport {
+            id: id.to_string(),
+            branch: Some(branch.to_string()),
+            total_seconds: seconds,
+            completed_date: Some("2025-01-15".to_string()),
+            commits: vec![CommitSummary {
+                hash: "abc123".to_string(),
+                message: "Secret commit".to_string(),
+                diff_stats: None,
+            }],
+            diff_stats: None,
+            pull_request: None,
+            uncommitted: None,
+            issue_url: None,
+            title: None,
+            days: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_redact_client() {
+        let mut report = MonthlyReport {
+            period: "2025-01".to_string(),
+            total_seconds: 10800,
+            models: Vec::new(),
+            clients: Vec::new(),
+            group_by_client: false,
+            projects: vec![ProjectReport {
+                name: "/home/me/work/acme-api".to_string(),
+                path: "/home/me/work/acme-api".to_string(),
+                repository_url: None,
+                total_seconds: 10800,
+                amount: None,
+                prompts: 0,
+                work_items: vec![
+                    item("ABC-123", "feature/ABC-123-secret", 3600),
+                    item("main", "main", 3600),
+                    item("hotfix/leak", "hotfix/leak", 3600),
+                ],
+                modules: vec![ModuleReport {
+                    name: "src".to_string(),
+                    total_seconds: 3600,
+                }],
+                subprojects: Vec::new(),
+                activity: Vec::new(),
+                submodules: Vec::new(),
+                client: None,
+            }],
+        };
+
+        apply(&mut report, RedactProfile::Client);
+
+        let project = &report.projects[0];
+        assert_eq!(project.name, "acme-api");
+        assert!(project.path.is_empty());
+        assert!(project.modules.is_empty());
+        assert_eq!(project.work_items.len(), 2);
+        assert_eq!(project.work_items[0].id, "other");
+        assert_eq!(project.work_items[0].total_seconds, 7200);
+        assert_eq!(project.work_items[1].id, "ABC-123");
+        assert!(project.work_items.iter().all(|i| i.branch.is_none() && i.commits.is_empty()));
+    }
+}
@@ -75,10 +75,16 @@ mod tests {
         let report = MonthlyReport {
             period: "2025-01".to_string(),
             total_seconds: 7200,
+            models: Vec::new(),
+            clients: Vec::new(),
+            group_by_client: false,
             projects: vec![ProjectReport {
                 name: "Test Project".to_string(),
                 path: "/test/path".to_string(),
+                repository_url: None,
                 total_seconds: 7200,
+                amount: None,
+                prompts: 0,
                 work_items: vec![WorkItemReport {
                     id: "ABC-123".to_string(),
                     branch: Some("feature/ABC-123-test".to_string()),
@@ -87,8 +93,20 @@ mod tests {
                     commits: vec![CommitSummary {
                         hash: "abc123".to_string(),
                         message: "Test commit".to_string(),
+                        diff_stats: None,
                     }],
+                    diff_stats: None,
+                    pull_request: None,
+                    uncommitted: None,
+                    issue_url: None,
+                    title: None,
+                    days: Default::default(),
                 }],
+                modules: Vec::new(),
+                subprojects: Vec::new(),
+                activity: Vec::new(),
+                submodules: Vec::new(),
+                client: None,
             }],
         };
 
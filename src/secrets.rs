@@ -0,0 +1,33 @@
+use std::process::Command;
+use tracing::debug;
+
+/// Keychain service the tracker's secrets are stored under
+const SERVICE: &str = "claude-time-tracker";
+
+/// Look up a secret in the system keychain, e.g. the `jira` API token
+///
+/// Store it with `security add-generic-password -s claude-time-tracker -a jira -w`
+/// on macOS, or `secret-tool store --label=jira service claude-time-tracker account jira`
+/// elsewhere.
+pub fn lookup(account: &str) -> Option<String> {
+    let (program, args) = if cfg!(target_os = "macos") {
+        ("security", vec!["find-generic-password", "-s", SERVICE, "-a", account, "-w"])
+    } else {
+        ("secret-tool", vec!["lookup", "service", SERVICE, "account", account])
+    };
+
+    let output = match Command::new(program).args(&args).output() {
+        Ok(output) => output,
+        Err(e) => {
+            debug!("Skipping keychain lookup, {} is not available: {}", program, e);
+            return None;
+        }
+    };
+    if !output.status.success() {
+        debug!("No {} secret in the keychain", account);
+        return None;
+    }
+
+    let secret = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (!secret.is_empty()).then_some(secret)
+}
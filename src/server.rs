@@ -0,0 +1,733 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Datelike, Days, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use tracing::{debug, info, warn};
+
+use crate::config::EffectiveConfig;
+use crate::db::Database;
+use crate::events::{self, Event};
+use crate::report;
+use crate::report::format::{FormatRegistry, RenderOptions};
+use crate::storage::Storage;
+use crate::tracker;
+
+/// Source recorded on sessions created through the API
+const MANUAL_SOURCE: &str = "manual";
+
+/// Web UI served at `/`, reading everything through the API
+const DASHBOARD: &str = include_str!("dashboard.html");
+
+/// Request bodies are small JSON documents
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+
+/// Limit on the request line and headers together
+const MAX_HEAD_BYTES: u64 = 16 * 1024;
+
+/// Connections served at once; event streams each hold one open
+const MAX_CONNECTIONS: usize = 64;
+
+/// How long a client may take to send its request, or to take a response
+const IO_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Server-sent events of sessions starting and stopping and of new heartbeats
+const EVENTS_PATH: &str = "/api/events";
+
+/// How often the event stream looks for new activity
+const EVENT_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// Quiet polls after which a comment is sent, so closed connections are noticed
+const KEEPALIVE_POLLS: u32 = 15;
+
+/// A parsed HTTP request
+#[derive(Debug, Default)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub query: HashMap<String, String>,
+    /// Keyed by lowercase name
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub headers: Vec<(&'static str, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    fn json(status: u16, value: &impl Serialize) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            headers: Vec::new(),
+            body: serde_json::to_vec(value).unwrap_or_default(),
+        }
+    }
+
+    fn error(status: u16, message: &str) -> Self {
+        Self::json(status, &json!({ "error": message }))
+    }
+
+    fn text(content_type: &'static str, body: String) -> Self {
+        Self {
+            status: 200,
+            content_type,
+            headers: Vec::new(),
+            body: body.into_bytes(),
+        }
+    }
+}
+
+/// Body of `POST /api/sessions`
+#[derive(Debug, Deserialize)]
+struct ManualEntry {
+    /// Path of a tracked project
+    project: String,
+    start: DateTime<Utc>,
+    end: Option<DateTime<Utc>>,
+    /// Used when there is no end
+    minutes: Option<i64>,
+    work_item: Option<String>,
+}
+
+/// Serve the API on localhost until the process is stopped
+///
+/// Every connection gets its own thread, database connection and freshly
+/// loaded config, like each hook invocation does. Past `MAX_CONNECTIONS` open
+/// connections, new ones are turned away until some close.
+pub fn serve(port: u16, token: Option<String>) -> Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port)).with_context(|| format!("Failed to listen on port {}", port))?;
+    info!("Serving the API on http://127.0.0.1:{}", port);
+    if token.is_none() {
+        warn!("No API token set: any local process can read and add sessions");
+    }
+
+    let open = Arc::new(AtomicUsize::new(0));
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                warn!("Failed to accept a connection: {}", e);
+                continue;
+            }
+        };
+        if let Err(e) = stream
+            .set_read_timeout(Some(IO_TIMEOUT))
+            .and_then(|_| stream.set_write_timeout(Some(IO_TIMEOUT)))
+        {
+            warn!("Failed to set connection timeouts: {}", e);
+            continue;
+        }
+        if open.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+            open.fetch_sub(1, Ordering::SeqCst);
+            debug!("Turning a connection away: {} already open", MAX_CONNECTIONS);
+            let _ = write_response(&mut stream, &Response::error(503, "Too many connections"));
+            continue;
+        }
+
+        let token = token.clone();
+        let open = Arc::clone(&open);
+        thread::spawn(move || {
+            if let Err(e) = handle_connection(stream, token.as_deref()) {
+                debug!("Connection failed: {:#}", e);
+            }
+            open.fetch_sub(1, Ordering::SeqCst);
+        });
+    }
+    Ok(())
+}
+
+fn handle_connection(mut stream: TcpStream, token: Option<&str>) -> Result<()> {
+    let response = match read_request(&mut BufReader::new(&stream)) {
+        Ok(request) => {
+            let opened = EffectiveConfig::load(None)
+                .and_then(|config| Ok((Database::open(&config.database_path)?, config)));
+            let response = match opened {
+                Ok((db, _))
+                    if request.method == "GET"
+                        && request.path == EVENTS_PATH
+                        && forbidden(&request).is_none()
+                        && authorized(token, &request) =>
+                {
+                    debug!("GET {} -> event stream", request.path);
+                    return stream_events(&mut stream, &db);
+                }
+                Ok((db, config)) => handle(&db, &config, token, &request),
+                Err(e) => Response::error(500, &format!("{:#}", e)),
+            };
+            debug!("{} {} -> {}", request.method, request.path, response.status);
+            response
+        }
+        Err(e) => Response::error(400, &format!("{:#}", e)),
+    };
+    write_response(&mut stream, &response)
+}
+
+/// Read a request line, headers and a `Content-Length` body
+///
+/// Requests with more than `MAX_HEAD_BYTES` of headers or `MAX_BODY_BYTES` of
+/// body are refused before they are read in full.
+pub fn read_request(reader: &mut impl BufRead) -> Result<Request> {
+    let mut head = reader.take(MAX_HEAD_BYTES);
+    let mut line = String::new();
+    read_head_line(&mut head, &mut line)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        bail!("Malformed request line");
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let mut request = Request {
+        method: method.to_string(),
+        path: percent_decode(path),
+        query: query
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+                (percent_decode(key), percent_decode(value))
+            })
+            .collect(),
+        ..Request::default()
+    };
+
+    loop {
+        line.clear();
+        if read_head_line(&mut head, &mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            request.headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+        }
+    }
+
+    let length: usize = match request.headers.get("content-length") {
+        Some(length) => length.parse().context("Invalid Content-Length")?,
+        None => 0,
+    };
+    if length > MAX_BODY_BYTES {
+        bail!("Request body too large");
+    }
+    request.body = vec![0; length];
+    head.into_inner().read_exact(&mut request.body)?;
+    Ok(request)
+}
+
+/// Read a line of the request head, failing once the head is over its limit
+fn read_head_line<R: BufRead>(head: &mut std::io::Take<R>, line: &mut String) -> Result<usize> {
+    let read = head.read_line(line)?;
+    if head.limit() == 0 && !line.ends_with('\n') {
+        bail!("Request headers too large");
+    }
+    Ok(read)
+}
+
+fn write_response(stream: &mut impl Write, response: &Response) -> Result<()> {
+    let mut head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+        response.status,
+        reason(response.status),
+        response.content_type,
+        response.body.len()
+    );
+    for (name, value) in &response.headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str("\r\n");
+    stream.write_all(head.as_bytes())?;
+    stream.write_all(&response.body)?;
+    stream.flush()?;
+    Ok(())
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    }
+}
+
+pub fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let hex = |byte: u8| (byte as char).to_digit(16).map(|d| d as u8);
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match (bytes[i], bytes.get(i + 1).copied().and_then(hex), bytes.get(i + 2).copied().and_then(hex)) {
+            (b'%', Some(high), Some(low)) => {
+                decoded.push(high * 16 + low);
+                i += 2;
+            }
+            (b'+', _, _) => decoded.push(b' '),
+            (byte, _, _) => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Answer a request, checking the bearer token first when one is set
+///
+/// The dashboard page holds no data, so it loads without the token and asks
+/// for it (`/?token=…`) when calling the API.
+pub fn handle(db: &Database, config: &EffectiveConfig, token: Option<&str>, request: &Request) -> Response {
+    if let Some(response) = forbidden(request) {
+        return response;
+    }
+    if request.method == "GET" && request.path == "/" {
+        return Response::text("text/html; charset=utf-8", DASHBOARD.to_string());
+    }
+    if !authorized(token, request) {
+        let mut response = Response::error(401, "Missing or wrong bearer token");
+        response.headers.push(("WWW-Authenticate", "Bearer".to_string()));
+        return response;
+    }
+
+    match route(db, config, request) {
+        Ok(response) => response,
+        Err(e) => Response::error(400, &format!("{:#}", e)),
+    }
+}
+
+/// Turn away requests a web page could have made on the user's behalf
+///
+/// The API listens on localhost, but any page open in a browser can send it
+/// requests: through a hostname of its own that resolves to 127.0.0.1 (caught
+/// by the `Host` check), or with forms posting across origins (which can't set
+/// a JSON content type, and say where they come from in `Origin`).
+fn forbidden(request: &Request) -> Option<Response> {
+    let host = request.headers.get("host").map(|host| host_name(host));
+    if !host.is_some_and(is_local_host) {
+        return Some(Response::error(403, "Only localhost may be used as the Host"));
+    }
+
+    let origin = request.headers.get("origin").map(|origin| {
+        let authority = origin.split_once("://").map_or(origin.as_str(), |(_, rest)| rest);
+        is_local_host(host_name(authority))
+    });
+    if origin == Some(false) {
+        return Some(Response::error(403, "Cross-origin requests are not allowed"));
+    }
+
+    let json = request
+        .headers
+        .get("content-type")
+        .is_some_and(|content_type| content_type.to_lowercase().starts_with("application/json"));
+    if !matches!(request.method.as_str(), "GET" | "HEAD") && !json && origin.is_none() {
+        return Some(Response::error(403, "Send a JSON body (Content-Type: application/json)"));
+    }
+    None
+}
+
+/// Host of a `host[:port]` authority, brackets and all for IPv6
+fn host_name(authority: &str) -> &str {
+    match authority.strip_prefix('[') {
+        Some(rest) => rest.split_once(']').map_or(authority, |(host, _)| host),
+        None => authority.split(':').next().unwrap_or(authority),
+    }
+}
+
+fn is_local_host(host: &str) -> bool {
+    matches!(host.to_lowercase().as_str(), "localhost" | "127.0.0.1" | "::1")
+}
+
+/// Whether the request carries the token, if one is required
+///
+/// Browsers cannot add headers to an `EventSource`, so the event stream also
+/// takes the token as `?token=`.
+fn authorized(token: Option<&str>, request: &Request) -> bool {
+    let Some(token) = token else {
+        return true;
+    };
+    let header = request.headers.get("authorization").and_then(|h| h.strip_prefix("Bearer "));
+    let query = request.query.get("token").filter(|_| request.path == EVENTS_PATH);
+    [header, query.map(|t| t.as_str())]
+        .into_iter()
+        .flatten()
+        .any(|given| constant_time_eq(given.as_bytes(), token.as_bytes()))
+}
+
+/// Compare secrets in time that depends only on their length, not on where they differ
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Send events as they happen until the client goes away
+fn stream_events(stream: &mut impl Write, db: &Database) -> Result<()> {
+    stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\n\r\n")?;
+    stream.flush()?;
+
+    let mut feed = EventFeed::new(db)?;
+    let mut quiet = 0;
+    loop {
+        thread::sleep(EVENT_POLL_INTERVAL);
+        let events = feed.poll(db)?;
+        if events.is_empty() {
+            quiet += 1;
+            if quiet < KEEPALIVE_POLLS {
+                continue;
+            }
+            stream.write_all(b": keepalive\n\n")?;
+        }
+        for event in &events {
+            stream.write_all(sse_message(event)?.as_bytes())?;
+        }
+        stream.flush()?;
+        quiet = 0;
+    }
+}
+
+/// An event in `text/event-stream` form, named after its type
+fn sse_message(event: &Event) -> Result<String> {
+    let data = serde_json::to_value(event)?;
+    let name = data["event"].as_str().unwrap_or("message").to_string();
+    Ok(format!("event: {}\ndata: {}\n\n", name, data))
+}
+
+/// Follows the database for new events
+///
+/// Hooks write from their own processes (or the daemon), so triggers note
+/// every start, heartbeat and stop in the database's event feed, which is read
+/// on from where the last poll left off.
+struct EventFeed {
+    last: i64,
+}
+
+impl EventFeed {
+    fn new(db: &Database) -> Result<Self> {
+        Ok(Self {
+            last: db.last_feed_id()?,
+        })
+    }
+
+    /// Events since the last poll, in the order they happened
+    fn poll(&mut self, db: &Database) -> Result<Vec<Event>> {
+        let mut events = Vec::new();
+        for entry in db.get_feed_after(self.last)? {
+            self.last = entry.id;
+            let row_id = entry.row_id;
+            let event = match entry.kind.as_str() {
+                "start" => db.get_session_by_id(row_id).and_then(|session| {
+                    Ok(Event::Start {
+                        project: Box::new(db.get_project_by_id(session.project_id)?),
+                        session,
+                    })
+                }),
+                "heartbeat" => db.get_heartbeat(row_id).map(|heartbeat| Event::Heartbeat {
+                    session_id: heartbeat.session_id,
+                    timestamp: entry.timestamp,
+                    context: heartbeat.context(),
+                }),
+                "stop" => Event::stop(db, row_id),
+                _ => continue,
+            };
+            match event {
+                Ok(event) => events.push(event),
+                // Deleted or compacted since
+                Err(e) => debug!("No {} event for {}: {:#}", entry.kind, row_id, e),
+            }
+        }
+        Ok(events)
+    }
+}
+
+fn route(db: &Database, config: &EffectiveConfig, request: &Request) -> Result<Response> {
+    let segments: Vec<&str> = request.path.trim_matches('/').split('/').collect();
+    let query = |key: &str| request.query.get(key).map(|s| s.as_str());
+
+    Ok(match (request.method.as_str(), segments.as_slice()) {
+        ("GET", ["api", "projects"]) => {
+            let projects: Vec<_> = db
+                .list_projects(query("include_archived") == Some("true"))?
+                .into_iter()
+                .map(|p| json!({ "name": p.name(), "project": p }))
+                .collect();
+            Response::json(200, &projects)
+        }
+        ("GET", ["api", "sessions"]) => {
+            let sessions = match query("month") {
+                Some(month) => {
+                    let (year, month) = report::parse_month(month)?;
+                    let (start, end) = report::month_range(year, month, config.calendar)?;
+                    let project = query("project").map(|id| id.parse::<i64>()).transpose().context("Invalid project id")?;
+                    db.get_sessions_in_range(start, end, project)?
+                }
+                None => {
+                    let limit = query("limit").map(|l| l.parse()).transpose().context("Invalid limit")?;
+                    db.get_recent_sessions(limit.unwrap_or(20))?
+                }
+            };
+            Response::json(200, &sessions)
+        }
+        ("GET", ["api", "sessions", id]) => match id.parse().ok().and_then(|id| db.get_session_by_id(id).ok()) {
+            Some(session) => Response::json(200, &session),
+            None => Response::error(404, "No such session"),
+        },
+        ("POST", ["api", "sessions"]) => create_session(db, config, request)?,
+        ("GET", ["api", "status"]) => {
+            let mut active = Vec::new();
+            let (timeout, grace) = (config.idle_timeout_minutes, config.idle_grace_minutes);
+            for session in db.get_all_active_sessions()? {
+                let project = db.get_project_by_id(session.project_id)?;
+                active.push(json!({
+                    "project": project.name(),
+                    "project_path": project.path,
+                    "elapsed_seconds": tracker::running_active_seconds(db, &session, timeout, grace)?,
+                    "prompts": db.count_prompts(session.id)?,
+                    "session": session,
+                }));
+            }
+            Response::json(200, &active)
+        }
+        ("GET", ["api", "totals"]) => {
+            let calendar = config.calendar;
+            let today = calendar.today();
+            let monday = today - Days::new(today.weekday().num_days_from_monday() as u64);
+            Response::json(
+                200,
+                &json!({
+                    "today": project_totals(db, config, calendar.day_start(today))?,
+                    "week": project_totals(db, config, calendar.day_start(monday))?,
+                }),
+            )
+        }
+        ("GET", ["api", "report"]) => {
+            let (year, month) = match query("month") {
+                Some(month) => report::parse_month(month)?,
+                None => report::current_month(config.calendar),
+            };
+            let options = report::ReportOptions {
+                project_filter: query("project").map(String::from),
+                ..report::ReportOptions::from_config(config)
+            };
+            let report_data = report::generate_report(db, year, month, &options)?;
+            match query("format").unwrap_or("json") {
+                "json" => Response::json(200, &report::json::Versioned::new(&report_data)),
+                name => match FormatRegistry::default().get(name) {
+                    Some(format) => {
+                        let options = RenderOptions {
+                            include_commits: config.include_commits,
+                            include_diff_stats: true,
+                        };
+                        Response::text(format.content_type(), format.render(&report_data, options)?)
+                    }
+                    None => Response::error(400, &format!("Unknown format: {}", name)),
+                },
+            }
+        }
+        (_, ["api", "projects" | "sessions" | "status" | "totals" | "report", ..]) => Response::error(405, "Method not allowed"),
+        _ => Response::error(404, "Not found"),
+    })
+}
+
+/// Time per project since an instant, most first: finished sessions plus the running ones
+fn project_totals(db: &Database, config: &EffectiveConfig, since: DateTime<Utc>) -> Result<Vec<serde_json::Value>> {
+    let mut seconds: HashMap<i64, i64> = HashMap::new();
+    for session in db.get_sessions_in_range(since, Utc::now(), None)? {
+        *seconds.entry(session.project_id).or_default() += session.active_seconds.unwrap_or(0);
+    }
+    let (timeout, grace) = (config.idle_timeout_minutes, config.idle_grace_minutes);
+    for session in db.get_all_active_sessions()? {
+        if session.started_at >= since {
+            *seconds.entry(session.project_id).or_default() += tracker::running_active_seconds(db, &session, timeout, grace)?;
+        }
+    }
+
+    let mut totals = Vec::new();
+    for (project_id, seconds) in seconds.into_iter().filter(|(_, s)| *s > 0) {
+        totals.push((db.get_project_by_id(project_id)?.name(), seconds));
+    }
+    totals.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    Ok(totals
+        .into_iter()
+        .map(|(project, seconds)| json!({ "project": project, "seconds": seconds }))
+        .collect())
+}
+
+/// Record time spent outside Claude Code on a tracked project
+fn create_session(db: &Database, config: &EffectiveConfig, request: &Request) -> Result<Response> {
+    let entry: ManualEntry = serde_json::from_slice(&request.body).context("Invalid session JSON")?;
+    let seconds = match (entry.end, entry.minutes) {
+        (Some(end), _) => (end - entry.start).num_seconds(),
+        (None, Some(minutes)) => Duration::try_minutes(minutes).context("Too many minutes")?.num_seconds(),
+        (None, None) => bail!("Give an end or minutes"),
+    };
+    if seconds <= 0 {
+        bail!("The session must end after it starts");
+    }
+    let Some(project) = db.get_project_by_path(&entry.project)? else {
+        return Ok(Response::error(404, &format!("Project not tracked: {}", entry.project)));
+    };
+
+    let work_item = entry.work_item.as_deref().filter(|w| !w.is_empty());
+    Ok(match db.import_session(project.id, MANUAL_SOURCE, MANUAL_SOURCE, work_item, entry.start, seconds)? {
+        Some(session) => {
+            events::log(config, || Event::session(db, session.id));
+            Response::json(201, &session)
+        }
+        None => Response::error(409, "A manual session already starts at that time"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::GlobalConfig;
+    use tempfile::tempdir;
+
+    fn request(method: &str, path: &str, body: &str) -> Request {
+        let raw = format!(
+            "{} {} HTTP/1.1\r\nHost: localhost:7878\r\nAuthorization: Bearer secret\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            method,
+            path,
+            body.len(),
+            body
+        );
+        read_request(&mut raw.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_read_request() {
+        let request = request("GET", "/api/report?month=2025-01&project=acme%20api", "");
+        assert_eq!(request.path, "/api/report");
+        assert_eq!(request.query["project"], "acme api");
+        assert_eq!(request.headers["authorization"], "Bearer secret");
+        assert!(read_request(&mut "\r\n".as_bytes()).is_err());
+
+        let padding = "a".repeat(MAX_HEAD_BYTES as usize);
+        let oversized = format!("GET / HTTP/1.1\r\nHost: localhost\r\nX-Padding: {}\r\n\r\n", padding);
+        assert!(read_request(&mut oversized.as_bytes()).is_err());
+        let oversized = format!("POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n", MAX_BODY_BYTES + 1);
+        assert!(read_request(&mut oversized.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_event_feed() {
+        let dir = tempdir().unwrap();
+        let db = Database::open(&dir.path().join("test.db")).unwrap();
+        let project = db.get_or_create_project("/src/api", None, None, None).unwrap();
+        let mut feed = EventFeed::new(&db).unwrap();
+        assert!(feed.poll(&db).unwrap().is_empty());
+
+        let session = db.create_session(project.id, "main", None, None, None, None).unwrap();
+        db.record_heartbeat(session.id, &Default::default()).unwrap();
+        let events = feed.poll(&db).unwrap();
+        assert!(matches!(&events[..], [Event::Start { .. }, Event::Heartbeat { session_id, .. }] if *session_id == session.id));
+        assert_eq!(sse_message(&events[0]).unwrap().lines().next(), Some("event: start"));
+
+        // Folded into the previous heartbeat, but still an event
+        db.record_heartbeat_coalesced(session.id, Utc::now(), &Default::default(), 30).unwrap();
+        let events = feed.poll(&db).unwrap();
+        assert!(matches!(&events[..], [Event::Heartbeat { .. }]));
+
+        db.complete_session(session.id, None, 60, Utc::now(), crate::models::SessionStatus::Completed)
+            .unwrap();
+        let events = feed.poll(&db).unwrap();
+        assert!(matches!(&events[..], [Event::Stop { session, .. }] if session.active_seconds == Some(60)));
+        assert!(feed.poll(&db).unwrap().is_empty());
+
+        // Started and stopped between two polls
+        let brief = db.create_session(project.id, "main", None, None, None, None).unwrap();
+        db.complete_session(brief.id, None, 5, Utc::now(), crate::models::SessionStatus::Completed).unwrap();
+        let events = feed.poll(&db).unwrap();
+        assert!(matches!(&events[..], [Event::Start { .. }, Event::Stop { session, .. }] if session.id == brief.id));
+
+        let mut request = request("GET", "/api/events?token=query", "");
+        assert!(authorized(Some("secret"), &request));
+        request.headers.clear();
+        assert!(authorized(Some("query"), &request));
+        assert!(!authorized(Some("quer"), &request));
+        assert!(!authorized(Some("queries"), &request));
+        request.path = "/api/status".to_string();
+        assert!(!authorized(Some("query"), &request));
+    }
+
+    #[test]
+    fn test_forbidden() {
+        let mut get = request("GET", "/api/projects", "");
+        assert!(forbidden(&get).is_none());
+        for host in ["127.0.0.1:7878", "[::1]:7878", "LOCALHOST"] {
+            get.headers.insert("host".to_string(), host.to_string());
+            assert!(forbidden(&get).is_none(), "{}", host);
+        }
+
+        // A rebound DNS name reaches the server with its own Host
+        get.headers.insert("host".to_string(), "attacker.example:7878".to_string());
+        assert_eq!(forbidden(&get).map(|r| r.status), Some(403));
+        get.headers.remove("host");
+        assert_eq!(forbidden(&get).map(|r| r.status), Some(403));
+
+        // Forms can post across origins, but not as JSON
+        let mut post = request("POST", "/api/sessions", "{}");
+        assert!(forbidden(&post).is_none());
+        post.headers.insert("content-type".to_string(), "text/plain".to_string());
+        assert_eq!(forbidden(&post).map(|r| r.status), Some(403));
+        post.headers.insert("origin".to_string(), "http://localhost:7878".to_string());
+        assert!(forbidden(&post).is_none());
+        post.headers.insert("origin".to_string(), "https://attacker.example".to_string());
+        assert_eq!(forbidden(&post).map(|r| r.status), Some(403));
+    }
+
+    #[test]
+    fn test_routes() {
+        let dir = tempdir().unwrap();
+        let db = Database::open(&dir.path().join("test.db")).unwrap();
+        let config = EffectiveConfig::from_global(GlobalConfig::default(), None).unwrap();
+        db.get_or_create_project("/src/api", None, None, None).unwrap();
+
+        let unauthorized = handle(&db, &config, Some("other"), &request("GET", "/api/projects", ""));
+        assert_eq!(unauthorized.status, 401);
+
+        let projects = handle(&db, &config, Some("secret"), &request("GET", "/api/projects", ""));
+        let projects: serde_json::Value = serde_json::from_slice(&projects.body).unwrap();
+        assert_eq!(projects[0]["project"]["path"], "/src/api");
+
+        let body = r#"{"project": "/src/api", "start": "2025-01-15T09:00:00Z", "minutes": 90, "work_item": "ABC-1"}"#;
+        let created = handle(&db, &config, None, &request("POST", "/api/sessions", body));
+        assert_eq!(created.status, 201);
+        let session: serde_json::Value = serde_json::from_slice(&created.body).unwrap();
+        assert_eq!(session["active_seconds"], 5400);
+        assert_eq!(session["imported_from"], "manual");
+        assert_eq!(handle(&db, &config, None, &request("POST", "/api/sessions", body)).status, 409);
+
+        let sessions = handle(&db, &config, None, &request("GET", "/api/sessions?month=2025-01", ""));
+        let sessions: serde_json::Value = serde_json::from_slice(&sessions.body).unwrap();
+        assert_eq!(sessions[0]["work_item"], "ABC-1");
+
+        let bad = r#"{"project": "/src/api", "start": "2025-01-15T09:00:00Z"}"#;
+        assert_eq!(handle(&db, &config, None, &request("POST", "/api/sessions", bad)).status, 400);
+        let huge = r#"{"project": "/src/api", "start": "2025-01-15T09:00:00Z", "minutes": 9223372036854775807}"#;
+        assert_eq!(handle(&db, &config, None, &request("POST", "/api/sessions", huge)).status, 400);
+        assert_eq!(handle(&db, &config, None, &request("DELETE", "/api/projects", "")).status, 405);
+        assert_eq!(handle(&db, &config, None, &request("GET", "/nope", "")).status, 404);
+
+        // The dashboard page loads without the token, its data doesn't
+        let page = handle(&db, &config, Some("other"), &request("GET", "/", ""));
+        assert_eq!((page.status, page.content_type), (200, "text/html; charset=utf-8"));
+        assert_eq!(handle(&db, &config, Some("other"), &request("GET", "/api/totals", "")).status, 401);
+
+        let now = Utc::now().to_rfc3339();
+        let body = format!(r#"{{"project": "/src/api", "start": "{}", "minutes": 30}}"#, now);
+        handle(&db, &config, None, &request("POST", "/api/sessions", &body));
+        let totals = handle(&db, &config, None, &request("GET", "/api/totals", ""));
+        let totals: serde_json::Value = serde_json::from_slice(&totals.body).unwrap();
+        assert_eq!(totals["week"][0]["seconds"], 1800);
+    }
+}
@@ -0,0 +1,206 @@
+//! Running the daemon as a systemd user service (Linux) or launchd agent (macOS)
+
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use tracing::{debug, warn};
+
+const SYSTEMD_UNIT: &str = "claude-time-tracker.service";
+const LAUNCHD_LABEL: &str = "com.claude-time-tracker.daemon";
+
+/// What `daemon install` sets up
+pub struct ServiceSpec {
+    /// Binary followed by its arguments
+    pub command: Vec<String>,
+    /// PATH for the service, so git, jj and curl resolve as in the installing shell
+    pub path_env: Option<String>,
+    /// launchd has no journal; the daemon's output goes here
+    pub log_file: PathBuf,
+}
+
+/// Write the unit file or plist and start the service; returns the file written
+pub fn install(spec: &ServiceSpec) -> Result<PathBuf> {
+    let file = service_file()?;
+    if let Some(parent) = file.parent() {
+        fs::create_dir_all(parent).with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+
+    if cfg!(target_os = "macos") {
+        // Reloading picks up a changed plist; it fails harmlessly when nothing is loaded
+        if file.exists() {
+            let _ = run("launchctl", &["unload", &file.to_string_lossy()]);
+        }
+        fs::write(&file, launchd_plist(spec)).with_context(|| format!("Failed to write {}", file.display()))?;
+        run("launchctl", &["load", "-w", &file.to_string_lossy()])?;
+    } else {
+        fs::write(&file, systemd_unit(spec)).with_context(|| format!("Failed to write {}", file.display()))?;
+        run("systemctl", &["--user", "daemon-reload"])?;
+        run("systemctl", &["--user", "enable", SYSTEMD_UNIT])?;
+        run("systemctl", &["--user", "restart", SYSTEMD_UNIT])?;
+    }
+    Ok(file)
+}
+
+/// Stop the service and remove its file; returns the file removed, if there was one
+pub fn uninstall() -> Result<Option<PathBuf>> {
+    let file = service_file()?;
+    if !file.exists() {
+        return Ok(None);
+    }
+
+    if cfg!(target_os = "macos") {
+        // A service that is not loaded still has its file removed
+        if let Err(e) = run("launchctl", &["unload", "-w", &file.to_string_lossy()]) {
+            warn!("{:#}", e);
+        }
+        fs::remove_file(&file)?;
+    } else {
+        if let Err(e) = run("systemctl", &["--user", "disable", "--now", SYSTEMD_UNIT]) {
+            warn!("{:#}", e);
+        }
+        fs::remove_file(&file)?;
+        if let Err(e) = run("systemctl", &["--user", "daemon-reload"]) {
+            warn!("{:#}", e);
+        }
+    }
+    Ok(Some(file))
+}
+
+/// Command that shows whether the service runs
+pub fn status_hint() -> String {
+    if cfg!(target_os = "macos") {
+        format!("launchctl list {}", LAUNCHD_LABEL)
+    } else {
+        format!("systemctl --user status {}", SYSTEMD_UNIT)
+    }
+}
+
+fn service_file() -> Result<PathBuf> {
+    if cfg!(target_os = "macos") {
+        let home = dirs::home_dir().context("Could not find home directory")?;
+        Ok(home.join("Library/LaunchAgents").join(format!("{}.plist", LAUNCHD_LABEL)))
+    } else {
+        let config = dirs::config_dir().context("Could not find config directory")?;
+        Ok(config.join("systemd/user").join(SYSTEMD_UNIT))
+    }
+}
+
+fn run(program: &str, args: &[&str]) -> Result<()> {
+    debug!("Running {} {}", program, args.join(" "));
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .with_context(|| format!("Failed to run {}", program))?;
+    if !output.status.success() {
+        bail!(
+            "{} {} failed: {}",
+            program,
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+fn systemd_unit(spec: &ServiceSpec) -> String {
+    let exec: Vec<String> = spec.command.iter().map(|arg| systemd_quote(arg)).collect();
+    let mut unit = String::from("[Unit]\nDescription=Claude Time Tracker daemon\n\n[Service]\n");
+    unit.push_str(&format!("ExecStart={}\n", exec.join(" ")));
+    if let Some(ref path) = spec.path_env {
+        unit.push_str(&format!("Environment={}\n", systemd_quote(&format!("PATH={}", path))));
+    }
+    unit.push_str("Restart=on-failure\nRestartSec=10\n\n[Install]\nWantedBy=default.target\n");
+    unit
+}
+
+/// Quote a word for a unit file, escaping `%` specifiers as well
+fn systemd_quote(word: &str) -> String {
+    format!(
+        "\"{}\"",
+        word.replace('\\', "\\\\").replace('"', "\\\"").replace('%', "%%")
+    )
+}
+
+fn launchd_plist(spec: &ServiceSpec) -> String {
+    let string = |value: &str| format!("<string>{}</string>", xml_escape(value));
+    let arguments: String = spec
+        .command
+        .iter()
+        .map(|arg| format!("        {}\n", string(arg)))
+        .collect();
+    let environment = match spec.path_env {
+        Some(ref path) => format!(
+            "    <key>EnvironmentVariables</key>\n    <dict>\n        <key>PATH</key>\n        {}\n    </dict>\n",
+            string(path)
+        ),
+        None => String::new(),
+    };
+    let log = string(&spec.log_file.to_string_lossy());
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    {label}
+    <key>ProgramArguments</key>
+    <array>
+{arguments}    </array>
+{environment}    <key>RunAtLoad</key>
+    <true/>
+    <key>KeepAlive</key>
+    <true/>
+    <key>StandardOutPath</key>
+    {log}
+    <key>StandardErrorPath</key>
+    {log}
+</dict>
+</plist>
+"#,
+        label = string(LAUNCHD_LABEL),
+    )
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec() -> ServiceSpec {
+        ServiceSpec {
+            command: vec![
+                "/opt/ctt bin/claude-time-tracker".to_string(),
+                "--db".to_string(),
+                "/data/100%.db".to_string(),
+                "daemon".to_string(),
+            ],
+            path_env: Some("/usr/bin:/opt/homebrew/bin".to_string()),
+            log_file: PathBuf::from("/data/daemon.log"),
+        }
+    }
+
+    #[test]
+    fn test_service_files() {
+        let unit = systemd_unit(&spec());
+        assert!(unit.contains(
+            "ExecStart=\"/opt/ctt bin/claude-time-tracker\" \"--db\" \"/data/100%%.db\" \"daemon\"\n"
+        ));
+        assert!(unit.contains("Environment=\"PATH=/usr/bin:/opt/homebrew/bin\"\n"));
+        assert!(unit.contains("WantedBy=default.target"));
+
+        let plist = launchd_plist(&spec());
+        assert!(plist.contains("        <string>/opt/ctt bin/claude-time-tracker</string>\n        <string>--db</string>\n"));
+        assert!(plist.contains("<key>PATH</key>\n        <string>/usr/bin:/opt/homebrew/bin</string>"));
+        assert!(plist.contains("<key>StandardErrorPath</key>\n    <string>/data/daemon.log</string>"));
+        assert!(plist.contains("<string>com.claude-time-tracker.daemon</string>"));
+    }
+}
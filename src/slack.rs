@@ -0,0 +1,166 @@
+use anyhow::{Context, Result};
+use chrono::{Days, NaiveDate};
+use serde::Serialize;
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::config::{EffectiveConfig, SlackSettings};
+use crate::db::Database;
+use crate::http::Request;
+use crate::models::Session;
+use crate::report;
+use crate::secrets;
+use crate::storage::Storage;
+use crate::tracker::format_duration;
+
+/// Stop hooks wait for the post, so don't let a slow Slack hold them up
+const TIMEOUT_SECONDS: u64 = 10;
+
+#[derive(Serialize)]
+struct Message<'a> {
+    text: &'a str,
+}
+
+/// Time tracked on a project during a day
+#[derive(Debug, Default)]
+struct ProjectDay {
+    seconds: i64,
+    work_items: BTreeSet<String>,
+    commits: usize,
+}
+
+/// Incoming webhook URL, from the config or the keychain (account `slack`)
+pub fn webhook_url(settings: &SlackSettings) -> Option<String> {
+    settings.webhook_url.clone().or_else(|| secrets::lookup("slack"))
+}
+
+/// Post a message to an incoming webhook
+pub fn post(webhook_url: &str, text: &str) -> Result<()> {
+    Request::post(webhook_url, &Message { text })?
+        .timeout(TIMEOUT_SECONDS)
+        .send_text()
+        .context("Failed to post to Slack")?;
+    Ok(())
+}
+
+/// One line about a stopped session, e.g. `Stopped: acme-api, ABC-123, 2h 15m, 4 commits`
+pub fn session_summary(session: &Session, project: &str, commits: usize) -> String {
+    let mut parts = vec![
+        project.to_string(),
+        session.work_item.clone().unwrap_or_else(|| session.branch.clone()),
+        format_duration(session.active_seconds.unwrap_or(0)),
+    ];
+    if commits > 0 {
+        parts.push(commit_count(commits));
+    }
+    format!("Stopped: {}", parts.join(", "))
+}
+
+/// Time per project on a working day, `None` when nothing was tracked
+pub fn daily_summary(db: &Database, config: &EffectiveConfig, day: NaiveDate) -> Result<Option<String>> {
+    let start = config.calendar.day_start(day);
+    let end = config.calendar.day_start(day + Days::new(1));
+
+    let mut names = BTreeMap::new();
+    let mut projects: BTreeMap<String, ProjectDay> = BTreeMap::new();
+    for session in db.get_sessions_in_range(start, end, None)? {
+        if let Entry::Vacant(slot) = names.entry(session.project_id) {
+            let project = db.get_project_by_id(session.project_id)?;
+            slot.insert(report::project_labels(db, &project, &config.projects)?.0);
+        }
+        let project = projects.entry(names[&session.project_id].clone()).or_default();
+        project.seconds += session.active_seconds.unwrap_or(0);
+        project.work_items.extend(session.work_item.clone());
+        project.commits += db.get_commits(session.id)?.len();
+    }
+
+    Ok(format_day(day, &projects))
+}
+
+fn format_day(day: NaiveDate, projects: &BTreeMap<String, ProjectDay>) -> Option<String> {
+    let total: i64 = projects.values().map(|p| p.seconds).sum();
+    if total == 0 {
+        return None;
+    }
+
+    let mut text = format!("*Claude Code on {}: {}*", day, format_duration(total));
+    let mut rows: Vec<_> = projects.iter().filter(|(_, p)| p.seconds > 0).collect();
+    rows.sort_by_key(|(_, p)| std::cmp::Reverse(p.seconds));
+    for (name, project) in rows {
+        text.push_str(&format!("\n• {}: {}", name, format_duration(project.seconds)));
+        if !project.work_items.is_empty() {
+            let items: Vec<&str> = project.work_items.iter().map(|s| s.as_str()).collect();
+            text.push_str(&format!(" ({})", items.join(", ")));
+        }
+        if project.commits > 0 {
+            text.push_str(&format!(", {}", commit_count(project.commits)));
+        }
+    }
+    Some(text)
+}
+
+fn commit_count(commits: usize) -> String {
+    format!("{} commit{}", commits, if commits == 1 { "" } else { "s" })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::SessionStatus;
+    use chrono::Utc;
+
+    #[test]
+    fn test_session_summary() {
+        let session = Session {
+            id: 1,
+            uuid: String::new(),
+            project_id: 1,
+            branch: "feature/ABC-123-login".to_string(),
+            work_item: Some("ABC-123".to_string()),
+            start_commit: None,
+            end_commit: None,
+            started_at: Utc::now(),
+            ended_at: Some(Utc::now()),
+            active_seconds: Some(8100),
+            status: SessionStatus::Completed,
+            claude_session_id: None,
+            model: None,
+            machine: None,
+            diff_stats: None,
+            pull_request: None,
+            uncommitted: None,
+            deleted_at: None,
+            imported_from: None,
+            last_heartbeat_at: None,
+        };
+        assert_eq!(session_summary(&session, "acme-api", 4), "Stopped: acme-api, ABC-123, 2h 15m, 4 commits");
+        assert_eq!(session_summary(&session, "acme-api", 0), "Stopped: acme-api, ABC-123, 2h 15m");
+    }
+
+    #[test]
+    fn test_format_day() {
+        let day = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
+        let projects = BTreeMap::from([
+            (
+                "acme-api".to_string(),
+                ProjectDay {
+                    seconds: 3600,
+                    work_items: BTreeSet::from(["ABC-1".to_string(), "ABC-2".to_string()]),
+                    commits: 1,
+                },
+            ),
+            (
+                "web".to_string(),
+                ProjectDay {
+                    seconds: 5400,
+                    ..ProjectDay::default()
+                },
+            ),
+        ]);
+        assert_eq!(
+            format_day(day, &projects).unwrap(),
+            "*Claude Code on 2025-01-15: 2h 30m*\n• web: 1h 30m\n• acme-api: 1h 0m (ABC-1, ABC-2), 1 commit"
+        );
+        assert_eq!(format_day(day, &BTreeMap::new()), None);
+    }
+}
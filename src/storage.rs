@@ -0,0 +1,247 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+
+use crate::models::{
+    Commit, DiffStats, Heartbeat, HeartbeatContext, Project, PullRequest, RemoteRepo, Session, SessionBranch,
+    SessionStatus,
+};
+use crate::vcs::CommitEntry;
+
+/// What tracking needs from a database: projects, sessions, heartbeats and commits
+///
+/// The tracker, and so every hook, only goes through this trait, so another
+/// database can back it. [`Database`](crate::db::Database) is the SQLite
+/// implementation; reports, sync and maintenance still use it directly.
+pub trait Storage {
+    /// Run `f` in a transaction, committing if it succeeds and rolling back if it fails
+    ///
+    /// Calls nest: inside another `atomically` a savepoint is used, so methods that
+    /// are atomic on their own can be combined into one larger atomic write. The
+    /// outermost level takes the write lock up front, so a read followed by writes
+    /// waits for other writers instead of failing halfway.
+    fn atomically<T>(&self, f: impl FnOnce() -> Result<T>) -> Result<T>;
+
+    /// Get or create a project by path
+    fn get_or_create_project(
+        &self,
+        path: &str,
+        git_remote: Option<&str>,
+        display_name: Option<&str>,
+        work_item_pattern: Option<&str>,
+    ) -> Result<Project>;
+
+    /// Store the URLs of all of a project's remotes
+    fn set_project_remotes(&self, project_id: i64, remote_urls: &[String]) -> Result<()>;
+
+    /// Store the hosted repository a project's remote points at
+    fn set_project_repository(&self, project_id: i64, repository: &RemoteRepo) -> Result<()>;
+
+    /// Get project by ID
+    fn get_project_by_id(&self, id: i64) -> Result<Project>;
+
+    /// Get project by path, or by a path recorded as one of its aliases
+    ///
+    /// Aliases let renamed directories and other worktrees or clones share one history.
+    fn get_project_by_path(&self, path: &str) -> Result<Option<Project>>;
+
+    /// List all projects, leaving out archived ones unless asked for
+    fn list_projects(&self, include_archived: bool) -> Result<Vec<Project>>;
+
+    /// Save editable project metadata
+    fn update_project(&self, project: &Project) -> Result<()>;
+
+    /// Record another path (e.g. a worktree) under which a project is found
+    fn add_project_alias(&self, project_id: i64, path: &str) -> Result<()>;
+
+    /// Create a new session
+    ///
+    /// `claude_session_id` is the id Claude Code passes to hooks, which lets
+    /// several windows on the same project track separate sessions.
+    fn create_session(
+        &self,
+        project_id: i64,
+        branch: &str,
+        work_item: Option<&str>,
+        start_commit: Option<&str>,
+        claude_session_id: Option<&str>,
+        machine: Option<&str>,
+    ) -> Result<Session>;
+
+    /// Get session by ID
+    fn get_session_by_id(&self, id: i64) -> Result<Session>;
+
+    /// Get the active session for a project
+    ///
+    /// With a Claude session id, only that window's session matches, falling
+    /// back to a session started by hooks that didn't pass an id. Without one,
+    /// the most recently started active session is returned.
+    fn get_active_session(
+        &self,
+        project_id: i64,
+        claude_session_id: Option<&str>,
+    ) -> Result<Option<Session>>;
+
+    /// Get the most recent session of a Claude Code session in a project, whatever its status
+    fn get_latest_session(&self, project_id: i64, claude_session_id: &str) -> Result<Option<Session>>;
+
+    /// Get all active sessions (for cleanup)
+    fn get_all_active_sessions(&self) -> Result<Vec<Session>>;
+
+    /// Set (or clear) the work item a session is attributed to
+    fn set_session_work_item(&self, session_id: i64, work_item: Option<&str>) -> Result<()>;
+
+    /// End an active session, returning false when it had already ended
+    ///
+    /// The status check and the update are one statement, so of several
+    /// processes ending the same session only one succeeds.
+    fn complete_session(
+        &self,
+        session_id: i64,
+        end_commit: Option<&str>,
+        active_seconds: i64,
+        ended_at: DateTime<Utc>,
+        status: SessionStatus,
+    ) -> Result<bool>;
+
+    /// Get the latest session on a branch completed at or after `since`
+    fn get_resumable_session(
+        &self,
+        project_id: i64,
+        branch: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Option<Session>>;
+
+    /// Make a completed session active again
+    ///
+    /// Its commits, branch split and activity breakdown are dropped; they are
+    /// collected again when the session stops.
+    fn reopen_session(&self, session_id: i64, claude_session_id: Option<&str>) -> Result<()>;
+
+    /// Get the most recently started sessions, newest first
+    fn get_recent_sessions(&self, limit: usize) -> Result<Vec<Session>>;
+
+    /// Record the change a session made between its start and end commit
+    fn set_session_diff_stats(&self, session_id: i64, stats: &DiffStats) -> Result<()>;
+
+    /// Record the work a session left uncommitted
+    fn set_session_uncommitted(&self, session_id: i64, stats: &DiffStats) -> Result<()>;
+
+    /// Record the pull request a session's branch belongs to
+    fn set_session_pull_request(&self, session_id: i64, pull_request: &PullRequest) -> Result<()>;
+
+    /// Record the Claude model a session is running on
+    fn set_session_model(&self, session_id: i64, model: &str) -> Result<()>;
+
+    /// Replace the per-branch time split of a session
+    fn record_session_branches(
+        &self,
+        session_id: i64,
+        allocations: &[(String, Option<String>, i64)],
+    ) -> Result<()>;
+
+    /// Get the per-branch time split of a session (empty if it never switched branch)
+    fn get_session_branches(&self, session_id: i64) -> Result<Vec<SessionBranch>>;
+
+    /// Replace the per-activity-kind time split of a session
+    fn record_session_activity(&self, session_id: i64, breakdown: &[(String, i64)]) -> Result<()>;
+
+    /// Get the per-activity-kind time split of a session
+    fn get_session_activity(&self, session_id: i64) -> Result<Vec<(String, i64)>>;
+
+    /// Record a heartbeat with whatever context the hook could provide
+    fn record_heartbeat(&self, session_id: i64, context: &HeartbeatContext) -> Result<Heartbeat>;
+
+    /// Record buffered heartbeats in one transaction
+    ///
+    /// Meant for callers that collect many heartbeats before writing them
+    /// (imports, long-running processes); a single hook uses `record_heartbeat`.
+    fn record_heartbeats(
+        &self,
+        session_id: i64,
+        heartbeats: &[(DateTime<Utc>, HeartbeatContext)],
+    ) -> Result<Vec<Heartbeat>>;
+
+    /// Record a heartbeat at `timestamp` unless an identical one was recorded within `window_seconds` before it
+    ///
+    /// A coalesced heartbeat only bumps the `event_count` of the earlier one, so
+    /// event counts stay accurate without a row per event. Returns the new
+    /// heartbeat, `None` when it was coalesced.
+    fn record_heartbeat_coalesced(
+        &self,
+        session_id: i64,
+        timestamp: DateTime<Utc>,
+        context: &HeartbeatContext,
+        window_seconds: u32,
+    ) -> Result<Option<Heartbeat>>;
+
+    /// Store a running session's active time up to its latest heartbeat
+    fn set_running_active_time(&self, session_id: i64, active_seconds: i64, last_heartbeat_at: DateTime<Utc>) -> Result<()>;
+
+    /// Add the gap between a running session's stored latest heartbeat and
+    /// `timestamp` to its active time, credited as `tracker::credited_seconds` does
+    ///
+    /// Reads and writes in one statement, and changes nothing when a heartbeat
+    /// at or after `timestamp` was counted already, so concurrent hooks never
+    /// count a gap twice.
+    fn advance_running_active_time(
+        &self,
+        session_id: i64,
+        timestamp: DateTime<Utc>,
+        idle_timeout_minutes: u32,
+        idle_grace_minutes: u32,
+    ) -> Result<()>;
+
+    /// Get heartbeats for a session
+    ///
+    /// Compacted intervals are expanded back into heartbeats (with id 0) at a
+    /// fixed step, so time calculations see them as before.
+    fn get_heartbeats(&self, session_id: i64) -> Result<Vec<Heartbeat>>;
+
+    /// Get last heartbeat for a session
+    fn get_last_heartbeat(&self, session_id: i64) -> Result<Option<Heartbeat>>;
+
+    /// Completed sessions that ended before `ended_before` and still have heartbeat rows
+    fn get_compactable_sessions(&self, ended_before: DateTime<Utc>) -> Result<Vec<i64>>;
+
+    /// Fold a session's heartbeat rows into interval rows
+    ///
+    /// Consecutive heartbeats with the same context and at most `max_gap_seconds`
+    /// apart become one interval. Returns the number of heartbeat rows removed
+    /// and of intervals written.
+    fn compact_heartbeats(&self, session_id: i64, max_gap_seconds: i64) -> Result<(usize, usize)>;
+
+    /// Record commits for a session
+    fn record_commits(&self, session_id: i64, commits: &[(String, String, Option<DateTime<Utc>>)]) -> Result<()>;
+
+    /// Record commits made inside a submodule (path relative to the project) during a session
+    fn record_submodule_commits(
+        &self,
+        session_id: i64,
+        submodule: &str,
+        commits: &[(String, String, Option<DateTime<Utc>>)],
+    ) -> Result<()>;
+
+    /// Remember commits read from a project (`submodule` empty) or one of its submodules
+    fn cache_commits(&self, project_id: i64, submodule: &str, commits: &[CommitEntry]) -> Result<()>;
+
+    /// Files and lines a cached commit changed, once measured
+    fn get_commit_diff_stats(&self, project_id: i64, submodule: &str, hash: &str) -> Result<Option<DiffStats>>;
+
+    /// Store what a cached commit changed
+    fn set_commit_diff_stats(&self, project_id: i64, submodule: &str, hash: &str, stats: &DiffStats) -> Result<()>;
+
+    /// Newest commit cached for a project's submodule
+    fn latest_cached_commit(&self, project_id: i64, submodule: &str) -> Result<Option<String>>;
+
+    /// Cached commits of a project's submodule made between two instants, oldest first
+    fn get_cached_commits(
+        &self,
+        project_id: i64,
+        submodule: &str,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<CommitEntry>>;
+
+    /// Get commits for a session, including those made in submodules
+    fn get_commits(&self, session_id: i64) -> Result<Vec<Commit>>;
+}
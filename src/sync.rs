@@ -0,0 +1,335 @@
+//! Sharing sessions between machines through a git repository
+//!
+//! Each machine writes its finished sessions to `<machine>.jsonl` in the
+//! repository and reads the other machines' files back. A machine only ever
+//! writes its own file, so pulls never conflict. Sessions travel with their
+//! commits, branch split and activity, but without heartbeats.
+//!
+//! The same records make up the bundles of `export --since` and `import
+//! --bundle`, for carrying sessions between machines by hand.
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+use crate::db::Database;
+use crate::git;
+use crate::merge;
+use crate::models::{Commit, Project, Session, SessionBranch};
+use crate::storage::Storage;
+
+/// One session with what travels along with it, as written to a machine's file
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SyncRecord {
+    pub project: Project,
+    pub session: Session,
+    pub commits: Vec<Commit>,
+    pub branches: Vec<SessionBranch>,
+    pub activity: Vec<(String, i64)>,
+}
+
+/// What became of a record from another machine
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Merged {
+    Added,
+    Updated,
+    Kept,
+}
+
+/// What a sync did
+#[derive(Debug, Default)]
+pub struct SyncStats {
+    /// Sessions of other machines new here
+    pub added: usize,
+    /// Sessions of other machines changed since the last sync
+    pub updated: usize,
+    /// Sessions written to this machine's file
+    pub exported: usize,
+    pub committed: bool,
+    /// Whether the repository has a remote that was pulled from and pushed to
+    pub remote: bool,
+}
+
+/// Pull, merge the other machines' sessions, write this machine's and push
+pub fn sync(db: &Database, repo: &Path, machine: &str) -> Result<SyncStats> {
+    if !repo.join(".git").exists() {
+        bail!("{} is not a git repository (clone or create one there first)", repo.display());
+    }
+
+    let remotes = git::run_git(repo, &["remote"])?;
+    let remote = remotes.lines().next().map(str::to_string);
+    let mut stats = SyncStats {
+        remote: remote.is_some(),
+        ..SyncStats::default()
+    };
+    if let Some(ref remote) = remote {
+        git::run_git(repo, &["fetch", remote]).context("Failed to fetch")?;
+        // A clone of a still empty repository has nothing to pull yet
+        if git::run_git(repo, &["rev-parse", "--verify", "--quiet", "@{u}"]).is_ok() {
+            git::run_git(repo, &["pull", "--rebase"]).context("Failed to pull")?;
+        }
+    }
+
+    // Sessions from before machines were recorded belong to the first machine to sync them
+    let claimed = db.claim_sessions(machine)?;
+    if claimed > 0 {
+        debug!("Claimed {} sessions without a machine for {}", claimed, machine);
+    }
+
+    let file = machine_file(repo, machine);
+    for other in fs::read_dir(repo)? {
+        let path = other?.path();
+        if path.extension().is_some_and(|e| e == "jsonl") && path != file {
+            let (added, updated) = import(db, &path, machine)?;
+            stats.added += added;
+            stats.updated += updated;
+        }
+    }
+
+    stats.exported = export(db, machine, &file)?;
+    let name = file.file_name().and_then(|n| n.to_str()).context("Invalid machine name")?;
+    git::run_git(repo, &["add", name])?;
+    if !git::run_git(repo, &["status", "--porcelain", "--", name])?.trim().is_empty() {
+        git::run_git(repo, &["commit", "-m", &format!("Sync {}", machine), "--", name])?;
+        stats.committed = true;
+    }
+    if let Some(remote) = remote {
+        git::run_git(repo, &["push", "-u", &remote, "HEAD"]).context("Failed to push")?;
+    }
+
+    Ok(stats)
+}
+
+/// File a machine writes its sessions to
+fn machine_file(repo: &Path, machine: &str) -> PathBuf {
+    repo.join(format!("{}.jsonl", machine.replace(['/', '\\'], "_")))
+}
+
+/// Write this machine's finished sessions, one per line; returns how many
+fn export(db: &Database, machine: &str, file: &Path) -> Result<usize> {
+    let sessions = db.get_sessions_for_sync(Some(machine), None)?;
+    let content = records(db, &sessions)?;
+    fs::write(file, content).with_context(|| format!("Failed to write {}", file.display()))?;
+    Ok(sessions.len())
+}
+
+/// Finished sessions that ended or changed since `since` (all without it) as a
+/// bundle; returns the bundle and how many sessions it holds
+pub fn export_bundle(db: &Database, since: Option<DateTime<Utc>>) -> Result<(String, usize)> {
+    let sessions = db.get_sessions_for_sync(None, since)?;
+    Ok((records(db, &sessions)?, sessions.len()))
+}
+
+/// Sessions as records, one per line
+fn records(db: &Database, sessions: &[Session]) -> Result<String> {
+    let mut content = String::new();
+    for session in sessions {
+        content.push_str(&serde_json::to_string(&record(db, session)?)?);
+        content.push('\n');
+    }
+    Ok(content)
+}
+
+/// A session with what travels along with it
+pub fn record(db: &Database, session: &Session) -> Result<SyncRecord> {
+    Ok(SyncRecord {
+        project: db.get_project_by_id(session.project_id)?,
+        commits: db.get_commits(session.id)?,
+        branches: db.get_session_branches(session.id)?,
+        activity: db.get_session_activity(session.id)?,
+        session: session.clone(),
+    })
+}
+
+/// Merge a record from another machine
+///
+/// Its project is matched as `db merge` matches projects (and added if there is
+/// none), its session by uuid. When the session is known already, `replace`
+/// decides between the local session and the incoming one, which by then
+/// carries the local ids.
+pub fn merge_record(
+    db: &Database,
+    tracked: &mut Vec<Project>,
+    record: SyncRecord,
+    replace: impl FnOnce(&Session, &Session) -> Result<bool>,
+) -> Result<Merged> {
+    let project = match merge::find_project(db, tracked, &record.project)? {
+        Some(project) => project,
+        None => {
+            let project = db.import_project(&record.project)?;
+            tracked.push(project.clone());
+            project
+        }
+    };
+
+    let mut session = record.session;
+    session.project_id = project.id;
+    let merged = match db.get_session_by_uuid(&session.uuid)? {
+        Some(local) => {
+            session.id = local.id;
+            if !replace(&local, &session)? {
+                return Ok(Merged::Kept);
+            }
+            debug!("Updating session {}", session.uuid);
+            Merged::Updated
+        }
+        None => {
+            session.id = db.next_session_id()?;
+            Merged::Added
+        }
+    };
+    db.restore_session(&session, &record.commits, &record.branches, &record.activity)?;
+    Ok(merged)
+}
+
+/// Merge another machine's file; returns (sessions added, sessions updated)
+///
+/// Sessions recorded on this machine are never overwritten; others take the
+/// other machine's version whenever it differs. Sessions the file has without
+/// a machine are taken to be the file's machine's.
+fn import(db: &Database, file: &Path, machine: &str) -> Result<(usize, usize)> {
+    let content = fs::read_to_string(file).with_context(|| format!("Failed to read {}", file.display()))?;
+    let owner = file.file_stem().and_then(|s| s.to_str());
+    merge_records(db, &content, &file.display().to_string(), owner, |local, incoming| {
+        Ok(local.machine.as_deref() != Some(machine) && serde_json::to_value(local)? != serde_json::to_value(incoming)?)
+    })
+}
+
+/// Merge a bundle; returns (sessions added, sessions updated)
+///
+/// A session known here takes the bundle's version when it differs, unless
+/// the local one ended later. Merging a bundle again changes nothing.
+pub fn import_bundle(db: &Database, content: &str, source: &str) -> Result<(usize, usize)> {
+    merge_records(db, content, source, None, |local, incoming| {
+        Ok(incoming.ended_at >= local.ended_at && serde_json::to_value(local)? != serde_json::to_value(incoming)?)
+    })
+}
+
+/// Merge records, one per line, in one transaction; lines that are no record are skipped
+///
+/// Sessions without a machine get `owner`, when given.
+fn merge_records(
+    db: &Database,
+    content: &str,
+    source: &str,
+    owner: Option<&str>,
+    replace: impl Fn(&Session, &Session) -> Result<bool>,
+) -> Result<(usize, usize)> {
+    db.atomically(|| {
+        let mut tracked = db.list_projects(true)?;
+        let (mut added, mut updated) = (0, 0);
+        for (index, line) in content.lines().enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+            let mut record: SyncRecord = match serde_json::from_str(line) {
+                Ok(record) => record,
+                Err(e) => {
+                    warn!("Skipping line {} of {}: {}", index + 1, source, e);
+                    continue;
+                }
+            };
+
+            if record.session.machine.is_none() {
+                record.session.machine = owner.map(String::from);
+            }
+            match merge_record(db, &mut tracked, record, &replace)? {
+                Merged::Added => added += 1,
+                Merged::Updated => updated += 1,
+                Merged::Kept => {}
+            }
+        }
+        Ok((added, updated))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::SessionStatus;
+    use chrono::Duration;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_export_and_import() {
+        let dir = tempdir().unwrap();
+        let desktop = Database::open(&dir.path().join("desktop.db")).unwrap();
+        let laptop = Database::open(&dir.path().join("laptop.db")).unwrap();
+        let file = dir.path().join("laptop.jsonl");
+
+        let remote = "git@github.com:acme/api.git";
+        let home = desktop.get_or_create_project("/home/me/api", Some(remote), None, None).unwrap();
+        let mac = laptop.get_or_create_project("/Users/me/api", Some(remote), None, None).unwrap();
+        let session = laptop
+            .create_session(mac.id, "feature/ABC-1", Some("ABC-1"), None, None, Some("laptop"))
+            .unwrap();
+        laptop.record_commits(session.id, &[("abc".to_string(), "Add login".to_string(), None)]).unwrap();
+        laptop.complete_session(session.id, None, 1800, Utc::now(), SessionStatus::Completed).unwrap();
+        // Still running, so not shared yet
+        laptop.create_session(mac.id, "main", None, None, None, Some("laptop")).unwrap();
+
+        assert_eq!(export(&laptop, "laptop", &file).unwrap(), 1);
+        assert_eq!(import(&desktop, &file, "desktop").unwrap(), (1, 0));
+        let synced = desktop.get_session_by_uuid(&session.uuid).unwrap().unwrap();
+        assert_eq!((synced.project_id, synced.active_seconds), (home.id, Some(1800)));
+        assert_eq!(desktop.get_commits(synced.id).unwrap().len(), 1);
+
+        laptop.set_session_work_item(session.id, Some("ABC-2")).unwrap();
+        export(&laptop, "laptop", &file).unwrap();
+        assert_eq!(import(&desktop, &file, "desktop").unwrap(), (0, 1));
+        assert_eq!(import(&desktop, &file, "desktop").unwrap(), (0, 0));
+        let synced = desktop.get_session_by_uuid(&session.uuid).unwrap().unwrap();
+        assert_eq!(synced.work_item.as_deref(), Some("ABC-2"));
+
+        // A machine's own sessions are never overwritten from the repository
+        assert_eq!(import(&laptop, &file, "laptop").unwrap(), (0, 0));
+    }
+
+    #[test]
+    fn test_sessions_without_a_machine() {
+        let dir = tempdir().unwrap();
+        let desktop = Database::open(&dir.path().join("desktop.db")).unwrap();
+        let laptop = Database::open(&dir.path().join("laptop.db")).unwrap();
+        let file = dir.path().join("laptop.jsonl");
+
+        let project = laptop.get_or_create_project("/Users/me/api", None, None, None).unwrap();
+        let session = laptop.create_session(project.id, "main", None, None, None, None).unwrap();
+        laptop.complete_session(session.id, None, 600, Utc::now(), SessionStatus::Completed).unwrap();
+
+        // Written by a machine that has not claimed them yet
+        let (old, _) = export_bundle(&laptop, None).unwrap();
+        fs::write(&file, old).unwrap();
+        assert_eq!(import(&desktop, &file, "desktop").unwrap(), (1, 0));
+        let copied = desktop.get_session_by_uuid(&session.uuid).unwrap().unwrap();
+        assert_eq!(copied.machine.as_deref(), Some("laptop"));
+        // So the other machine does not export them as its own
+        assert_eq!(export(&desktop, "desktop", &dir.path().join("desktop.jsonl")).unwrap(), 0);
+
+        assert_eq!(laptop.claim_sessions("laptop").unwrap(), 1);
+        assert_eq!(laptop.claim_sessions("laptop").unwrap(), 0);
+        assert_eq!(export(&laptop, "laptop", &file).unwrap(), 1);
+        assert_eq!(import(&desktop, &file, "desktop").unwrap(), (0, 0));
+    }
+
+    #[test]
+    fn test_bundles() {
+        let dir = tempdir().unwrap();
+        let desktop = Database::open(&dir.path().join("desktop.db")).unwrap();
+        let laptop = Database::open(&dir.path().join("laptop.db")).unwrap();
+
+        let project = laptop.get_or_create_project("/Users/me/api", None, None, None).unwrap();
+        let session = laptop.create_session(project.id, "main", None, None, None, Some("laptop")).unwrap();
+        let ended = Utc::now() - Duration::days(10);
+        laptop.complete_session(session.id, None, 600, ended, SessionStatus::Completed).unwrap();
+
+        // Ended long ago, but changed just now
+        let (bundle, count) = export_bundle(&laptop, Some(Utc::now() - Duration::days(1))).unwrap();
+        assert_eq!(count, 1);
+        assert_eq!(export_bundle(&laptop, Some(Utc::now() + Duration::hours(1))).unwrap().1, 0);
+
+        assert_eq!(import_bundle(&desktop, &bundle, "bundle").unwrap(), (1, 0));
+        assert_eq!(import_bundle(&desktop, &bundle, "bundle").unwrap(), (0, 0));
+        let copied = desktop.get_session_by_uuid(&session.uuid).unwrap().unwrap();
+        assert_eq!(copied.active_seconds, Some(600));
+    }
+}
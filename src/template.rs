@@ -0,0 +1,102 @@
+use anyhow::{bail, Result};
+
+/// Piece of a parsed template
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Placeholder(&'static str),
+}
+
+/// A one-line template such as `{project} {elapsed}`
+///
+/// `{name}` is replaced by a value, `{{` and `}}` stand for literal braces.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+impl Template {
+    /// Parse a template whose placeholders must be among `names`
+    pub fn parse(text: &str, names: &[&'static str]) -> Result<Self> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => bail!("Unclosed {{{} in template", name),
+                        }
+                    }
+                    let Some(&known) = names.iter().find(|&&n| n == name) else {
+                        bail!("Unknown placeholder {{{}}} (available: {})", name, names.join(", "));
+                    };
+                    if !literal.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Placeholder(known));
+                }
+                '}' => bail!("Unmatched }} in template (write }}}} for a literal brace)"),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Text(literal));
+        }
+        Ok(Self { parts })
+    }
+
+    /// Whether the template shows a value, so values that are costly to get can be skipped
+    pub fn uses(&self, name: &str) -> bool {
+        self.parts.iter().any(|p| matches!(p, Part::Placeholder(n) if *n == name))
+    }
+
+    pub fn render(&self, mut value: impl FnMut(&str) -> String) -> String {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                Part::Text(text) => text.clone(),
+                Part::Placeholder(name) => value(name),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NAMES: &[&str] = &["project", "elapsed"];
+
+    #[test]
+    fn test_render() {
+        let template = Template::parse("{{{project}}} {elapsed}!", NAMES).unwrap();
+        assert!(template.uses("elapsed"));
+        assert!(!template.uses("branch"));
+        let line = template.render(|name| match name {
+            "project" => "api".to_string(),
+            _ => "1h 5m".to_string(),
+        });
+        assert_eq!(line, "{api} 1h 5m!");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let unknown = Template::parse("{branch}", NAMES);
+        assert!(matches!(unknown, Err(e) if e.to_string().contains("available: project, elapsed")));
+        assert!(Template::parse("a } b", NAMES).is_err());
+        assert!(Template::parse("{project", NAMES).is_err());
+    }
+}
@@ -1,74 +1,249 @@
 use anyhow::{Context, Result};
-use chrono::{Duration, Utc};
+use chrono::{DateTime, Duration, SubsecRound, Utc};
 use regex::Regex;
+use std::collections::HashMap;
 use std::path::Path;
+use tracing::{debug, info, warn};
 
-use crate::config::EffectiveConfig;
-use crate::db::Database;
+use crate::config::{EffectiveConfig, ProjectIdentity, WebhookEvent};
+use crate::storage::Storage;
+use crate::events::{self, Event};
 use crate::git;
-use crate::models::SessionStatus;
+use crate::hook::HookPayload;
+use crate::models::{self, Heartbeat, HeartbeatContext, Project, PullRequest, Session, SessionStatus};
+use crate::pull_request;
+use crate::slack;
+use crate::vcs::{self, CommitEntry};
+use crate::webhook;
+
+/// Issue keys looked for in commit messages when no pattern is configured
+const DEFAULT_COMMIT_WORK_ITEM_PATTERN: &str = r"\b([A-Z][A-Z0-9]+-\d+)\b";
+
+/// File at the project root whose first line names the work item, whatever the branch
+const WORK_ITEM_FILE: &str = ".claude-work-item";
 
 /// Start a new tracking session
-pub fn start_session(db: &Database, project_path: &Path, config: &EffectiveConfig) -> Result<()> {
+///
+/// Each Claude Code window gets its own session, identified by the hook
+/// payload's session id. `work_item` overrides the one derived from the branch.
+pub fn start_session(
+    db: &impl Storage,
+    project_path: &Path,
+    config: &EffectiveConfig,
+    payload: &HookPayload,
+    work_item: Option<&str>,
+) -> Result<()> {
     let path_str = project_path
         .to_str()
         .context("Invalid project path")?;
 
     // Get git information
-    let git_info = git::get_git_info(project_path).ok();
+    let git_info = git_info_or_warn(project_path, config);
 
     // Check for abandoned sessions and close them
     close_abandoned_sessions(db, config)?;
 
     // Get or create project
-    let project = db.get_or_create_project(
-        path_str,
-        git_info.as_ref().and_then(|g| g.remote_url.as_deref()),
-        config.project_name.as_deref(),
-        config.work_item_pattern.as_deref(),
-    )?;
+    let project = resolve_project(db, path_str, git_info.as_ref(), config)?;
+    if project.deleted_at.is_some() {
+        info!("{} is in the trash; restore it to track it again", project.path);
+        return Ok(());
+    }
 
-    // Check if there's already an active session for this project
-    if let Some(existing) = db.get_active_session(project.id)? {
-        eprintln!(
+    // Check if this window already has an active session for the project
+    let claude_session_id = payload.session_id.as_deref();
+    if let Some(existing) = db.get_active_session(project.id, claude_session_id)? {
+        info!(
             "Session already active for project (started at {})",
             existing.started_at
         );
         return Ok(());
     }
 
+    let resumed = resume_session(db, &project, git_info.as_ref(), config, claude_session_id, work_item)?;
+    if let Some(session) = resumed {
+        record_model(db, &session, payload)?;
+        info!(
+            "Resumed tracking: {} (branch: {}, session {})",
+            config.project_name.as_deref().unwrap_or(path_str),
+            session.branch,
+            session.id
+        );
+        return Ok(());
+    }
+
+    let session = open_session(db, &project, git_info.as_ref(), config, claude_session_id, work_item)?;
+    record_model(db, &session, payload)?;
+
+    info!(
+        "Started tracking: {} (branch: {}, work_item: {})",
+        config.project_name.as_deref().unwrap_or(path_str),
+        session.branch,
+        session.work_item.as_deref().unwrap_or(&session.branch)
+    );
+
+    Ok(())
+}
+
+/// Find the project a checkout belongs to, creating it if needed
+///
+/// With `project_identity = "remote"`, a new checkout of a repository already
+/// tracked elsewhere (another worktree or clone) joins that project as an alias.
+fn resolve_project(
+    db: &impl Storage,
+    path: &str,
+    git_info: Option<&git::GitInfo>,
+    config: &EffectiveConfig,
+) -> Result<Project> {
+    let remote = git_info.and_then(|g| g.remote_url.as_deref());
+    let remote_urls = git_info.map(|g| g.remote_urls.as_slice()).unwrap_or_default();
+
+    let mut existing = db.get_project_by_path(path)?;
+    if existing.is_none() && config.project_identity == ProjectIdentity::Remote && !remote_urls.is_empty() {
+        // Any shared remote counts, so a fork and its upstream checkout still match
+        let keys: Vec<String> = remote_urls.iter().map(|url| git::normalize_remote_url(url)).collect();
+        existing = db.list_projects(true)?.into_iter().find(|p| {
+            p.git_remote
+                .iter()
+                .chain(&p.remote_urls)
+                .any(|url| keys.contains(&git::normalize_remote_url(url)))
+        });
+        if let Some(ref project) = existing {
+            info!("{} is a checkout of {}; tracking it as the same project", path, project.path);
+            db.add_project_alias(project.id, path)?;
+        }
+    }
+
+    let canonical = existing.as_ref().map(|p| p.path.as_str()).unwrap_or(path);
+    let mut project = db.get_or_create_project(
+        canonical,
+        remote,
+        config.project_name.as_deref(),
+        models::encode_patterns(&config.work_item_patterns).as_deref(),
+    )?;
+    // Configured clients only fill in a missing one; `projects set client` wins
+    if project.client.is_none() && config.client.is_some() {
+        project.client = config.client.clone();
+        db.update_project(&project)?;
+    }
+
+    let repository = remote.and_then(git::parse_remote_url);
+    let remotes_changed = !remote_urls.is_empty() && project.remote_urls != remote_urls;
+    let repository_changed = repository.is_some() && project.repository() != repository;
+    if remotes_changed {
+        db.set_project_remotes(project.id, remote_urls)?;
+    }
+    if let Some(repository) = repository.filter(|_| repository_changed) {
+        db.set_project_repository(project.id, &repository)?;
+    }
+    if remotes_changed || repository_changed {
+        return db.get_project_by_id(project.id);
+    }
+    Ok(project)
+}
+
+/// Remember the model reported by the hook, if it changed
+fn record_model(db: &impl Storage, session: &Session, payload: &HookPayload) -> Result<()> {
+    match payload.model() {
+        Some(model) if session.model.as_deref() != Some(model) => db.set_session_model(session.id, model),
+        _ => Ok(()),
+    }
+}
+
+/// Reopen the session stopped on this branch within `settings.resume_window_minutes`
+///
+/// Restarting Claude Code would otherwise split one block of work into many
+/// short sessions. A session is only resumed if it keeps the same work item.
+fn resume_session(
+    db: &impl Storage,
+    project: &Project,
+    git_info: Option<&git::GitInfo>,
+    config: &EffectiveConfig,
+    claude_session_id: Option<&str>,
+    work_item: Option<&str>,
+) -> Result<Option<Session>> {
+    if config.resume_window_minutes == 0 {
+        return Ok(None);
+    }
+    let Some(branch) = git_info.map(|g| g.branch.as_str()) else {
+        return Ok(None);
+    };
+
+    let since = Utc::now() - Duration::minutes(config.resume_window_minutes as i64);
+    let Some(session) = db.get_resumable_session(project.id, branch, since)? else {
+        return Ok(None);
+    };
+    if work_item.is_some() && work_item != session.work_item.as_deref() {
+        return Ok(None);
+    }
+
+    db.reopen_session(session.id, claude_session_id)?;
+    let context = HeartbeatContext {
+        branch: Some(branch.to_string()),
+        event_type: Some("session-start".to_string()),
+        ..Default::default()
+    };
+    let heartbeat = db.record_heartbeat(session.id, &context)?;
+    log_start(db, config, project, session.id, heartbeat.timestamp, context);
+    webhook::fire(db, config, WebhookEvent::Start, session.id);
+
+    Ok(Some(session))
+}
+
+/// Create a session on the current branch and record its first heartbeat
+fn open_session(
+    db: &impl Storage,
+    project: &Project,
+    git_info: Option<&git::GitInfo>,
+    config: &EffectiveConfig,
+    claude_session_id: Option<&str>,
+    work_item_override: Option<&str>,
+) -> Result<Session> {
     // Extract work item from branch name
     let branch = git_info
-        .as_ref()
         .map(|g| g.branch.clone())
         .unwrap_or_else(|| "unknown".to_string());
 
-    let work_item = extract_work_item(&branch, config.work_item_pattern.as_deref());
+    let work_item = match work_item_override {
+        Some(work_item) => Some(work_item.to_string()),
+        None => resolve_work_item(Path::new(&project.path), &branch, &work_item_patterns(config, project)),
+    };
 
-    // Create new session
     let session = db.create_session(
         project.id,
         &branch,
         work_item.as_deref(),
-        git_info.as_ref().and_then(|g| g.head_commit.as_deref()),
+        git_info.and_then(|g| g.head_commit.as_deref()),
+        claude_session_id,
+        Some(&config.machine),
     )?;
 
     // Record initial heartbeat
-    db.record_heartbeat(session.id)?;
-
-    eprintln!(
-        "Started tracking: {} (branch: {}, work_item: {})",
-        config.project_name.as_deref().unwrap_or(path_str),
-        branch,
-        work_item.as_deref().unwrap_or(&branch)
-    );
+    let context = HeartbeatContext {
+        branch: Some(branch),
+        event_type: Some("session-start".to_string()),
+        ..Default::default()
+    };
+    let heartbeat = db.record_heartbeat(session.id, &context)?;
+    log_start(db, config, project, session.id, heartbeat.timestamp, context);
+    webhook::fire(db, config, WebhookEvent::Start, session.id);
 
-    Ok(())
+    Ok(session)
 }
 
 /// Record a heartbeat for the current session
 /// If no active session exists, silently succeeds (session will be created on next start)
-pub fn record_heartbeat(db: &Database, project_path: &Path) -> Result<()> {
+///
+/// `timestamp` and `branch` are taken when the hook fired, which can be a while
+/// before a buffered heartbeat is written.
+pub fn record_heartbeat(
+    db: &impl Storage,
+    project_path: &Path,
+    config: &EffectiveConfig,
+    payload: &HookPayload,
+    timestamp: DateTime<Utc>,
+    branch: Option<String>,
+) -> Result<()> {
     let path_str = project_path
         .to_str()
         .context("Invalid project path")?;
@@ -76,80 +251,332 @@ pub fn record_heartbeat(db: &Database, project_path: &Path) -> Result<()> {
     // If project doesn't exist, just return Ok (no session to track)
     let project = match db.get_project_by_path(path_str)? {
         Some(p) => p,
-        None => return Ok(()),
+        None => {
+            debug!("Heartbeat ignored: {} is not a tracked project", path_str);
+            return Ok(());
+        }
     };
 
     // If no active session, just return Ok (session might have been stopped)
-    let session = match db.get_active_session(project.id)? {
+    let session = match db.get_active_session(project.id, payload.session_id.as_deref())? {
         Some(s) => s,
-        None => return Ok(()),
+        None => {
+            debug!("Heartbeat ignored: no active session for {}", path_str);
+            return Ok(());
+        }
+    };
+
+    // Forgotten sessions are closed at the cap and tracking continues in a new one
+    let session = if session_expired(&session, config) {
+        let last_heartbeat = db.get_last_heartbeat(session.id)?;
+        let ended_at = last_activity(&session, last_heartbeat.as_ref(), config);
+        let active_seconds = finish_session(db, &session, &project, project_path, config, ended_at)?.unwrap_or(0);
+        info!(
+            "Session {} reached the {}h limit (active time: {}); starting a new session",
+            session.id,
+            config.max_session_hours,
+            format_duration(active_seconds)
+        );
+        let git_info = git_info_or_warn(project_path, config);
+        open_session(
+            db,
+            &project,
+            git_info.as_ref(),
+            config,
+            session.claude_session_id.as_deref(),
+            None,
+        )?
+    } else {
+        session
     };
 
-    db.record_heartbeat(session.id)?;
+    let context = HeartbeatContext {
+        file_path: payload.file_path().map(String::from),
+        branch,
+        event_type: payload.event_type().map(String::from),
+        tool: payload.tool_name.clone(),
+        cwd: payload.cwd.clone(),
+        model: payload.model().map(String::from),
+        agent: payload.agent(),
+    };
+    record_model(db, &session, payload)?;
+    match db.record_heartbeat_coalesced(session.id, timestamp, &context, config.heartbeat_coalesce_seconds)? {
+        Some(heartbeat) => {
+            debug!("Recorded heartbeat for session {}", session.id);
+            advance_active_time(db, &session, heartbeat.timestamp, config)?;
+        }
+        None => debug!("Coalesced heartbeat into previous one for session {}", session.id),
+    }
+    // The log keeps every heartbeat; coalescing only applies to the database
+    events::log(config, || {
+        Ok(Event::Heartbeat {
+            session_id: session.id,
+            timestamp,
+            context,
+        })
+    });
 
     Ok(())
 }
 
 /// Stop the current tracking session
-pub fn stop_session(db: &Database, project_path: &Path, config: &EffectiveConfig) -> Result<()> {
+pub fn stop_session(
+    db: &impl Storage,
+    project_path: &Path,
+    config: &EffectiveConfig,
+    payload: &HookPayload,
+) -> Result<()> {
     let path_str = project_path
         .to_str()
         .context("Invalid project path")?;
 
-    let project = db
-        .get_project_by_path(path_str)?
-        .context("Project not found")?;
+    let project = db.get_project_by_path(path_str)?.context("Project not found")?;
 
-    let session = match db.get_active_session(project.id)? {
+    // Stop hooks can be delivered more than once; a window whose session is already
+    // closed must not stop another window's session
+    if let Some(ref claude_session_id) = payload.session_id {
+        if let Some(latest) = db.get_latest_session(project.id, claude_session_id)? {
+            if latest.status != SessionStatus::Active {
+                info!("Session {} already stopped", latest.id);
+                return Ok(());
+            }
+        }
+    }
+
+    let session = match db.get_active_session(project.id, payload.session_id.as_deref())? {
         Some(s) => s,
         None => {
-            eprintln!("No active session to stop");
+            info!("No active session to stop");
             return Ok(());
         }
     };
 
+    let Some(active_seconds) = finish_session(db, &session, &project, project_path, config, Utc::now())? else {
+        info!("Session {} already stopped", session.id);
+        return Ok(());
+    };
+
+    let duration = format_duration(active_seconds);
+    info!(
+        "Stopped tracking: {} (active time: {})",
+        config.project_name.as_deref().unwrap_or(path_str),
+        duration
+    );
+
+    if config.slack.notify_on_stop {
+        let name = config.project_name.clone().unwrap_or_else(|| project.name());
+        if let Err(e) = post_stop_summary(db, session.id, &name, config) {
+            warn!("Failed to post the session to Slack: {:#}", e);
+        }
+    }
+
+    Ok(())
+}
+
+/// Post the `Stopped: …` line of a finished session to Slack
+fn post_stop_summary(db: &impl Storage, session_id: i64, project_name: &str, config: &EffectiveConfig) -> Result<()> {
+    let url = slack::webhook_url(&config.slack).context("No Slack webhook configured")?;
+    let session = db.get_session_by_id(session_id)?;
+    let commits = db.get_commits(session_id)?.len();
+    slack::post(&url, &slack::session_summary(&session, project_name, commits))
+}
+
+/// Complete a session: tally its active time, collect its commits and close it
+///
+/// The repository (and code host) are read first; everything learned is then
+/// written in one transaction, so a stop that dies halfway leaves the session
+/// active rather than half-completed. Returns the session's active time in seconds,
+/// or `None` when another process ended it first.
+fn finish_session(
+    db: &impl Storage,
+    session: &Session,
+    project: &Project,
+    project_path: &Path,
+    config: &EffectiveConfig,
+    ended_at: DateTime<Utc>,
+) -> Result<Option<i64>> {
     // Get current git state
-    let git_info = git::get_git_info(project_path).ok();
+    let repo = vcs::open(project_path);
+    let git_info = git_info_or_warn(project_path, config);
     let end_commit = git_info.as_ref().and_then(|g| g.head_commit.clone());
 
     // Calculate active time from heartbeats
     let heartbeats = db.get_heartbeats(session.id)?;
-    let active_seconds = calculate_active_time(&heartbeats, config.idle_timeout_minutes);
+    let active_seconds = calculate_active_time(&heartbeats, config.idle_timeout_minutes, config.idle_grace_minutes);
+    let branches = branch_split(session, project, project_path, &heartbeats, config);
+    let activity = activity_breakdown(&heartbeats, config.idle_timeout_minutes, config.idle_grace_minutes);
+
+    // Measure the change made during this session
+    let diff_stats = match (session.start_commit.as_deref(), end_commit.as_deref()) {
+        (Some(start), Some(end)) => repo
+            .diff_stats(start, end, &config.commit_filter)
+            .map_err(|e| warn!("Failed to compute diff stats for session {}: {:#}", session.id, e))
+            .ok(),
+        _ => None,
+    };
 
     // Collect commits made during this session
-    if let Some(ref start) = session.start_commit {
-        if let Ok(commits) = git::get_commits_between(
-            project_path,
-            Some(start),
-            end_commit.as_deref(),
-        ) {
-            if !commits.is_empty() {
-                db.record_commits(session.id, &commits)?;
+    let mut commits = Vec::new();
+    if session.start_commit.is_some() {
+        match cached_commits(db, session, project, "", repo.as_ref(), config, ended_at) {
+            Ok(found) => {
+                debug!("Found {} commits for session {}", found.len(), session.id);
+                commits = found;
             }
+            Err(e) => warn!("Failed to collect commits for session {}: {:#}", session.id, e),
         }
     }
+    let messages: Vec<String> = commits.iter().map(|(_, message, _)| message.clone()).collect();
 
-    // Complete the session
-    db.complete_session(
-        session.id,
-        end_commit.as_deref(),
-        active_seconds,
-        SessionStatus::Completed,
-    )?;
+    let submodule_commits = if config.track_submodules {
+        submodule_commits(db, session, project, project_path, config, ended_at)?
+    } else {
+        Vec::new()
+    };
 
-    let duration = format_duration(active_seconds);
-    eprintln!(
-        "Stopped tracking: {} (active time: {})",
-        config.project_name.as_deref().unwrap_or(path_str),
-        duration
-    );
+    // Without commits, keep a trace of the work left in the working tree
+    let uncommitted = if commits.is_empty() && session.start_commit.is_some() {
+        match repo.uncommitted_changes() {
+            Ok(stats) if stats.files_changed > 0 => Some(stats),
+            Ok(_) => None,
+            Err(e) => {
+                warn!("Failed to inspect uncommitted work for session {}: {:#}", session.id, e);
+                None
+            }
+        }
+    } else {
+        None
+    };
 
-    Ok(())
+    let pull_request = if config.link_pull_requests {
+        find_pull_request(session, project_path, git_info.as_ref())
+    } else {
+        None
+    };
+
+    // Fall back to issue keys in commit messages when the branch had none
+    let work_item = if session.work_item.is_none() {
+        let pattern = config
+            .commit_work_item_pattern
+            .as_deref()
+            .unwrap_or(DEFAULT_COMMIT_WORK_ITEM_PATTERN);
+        work_item_from_commits(&messages, pattern)
+    } else {
+        None
+    };
+
+    let completed = db.atomically(|| {
+        // Claim the session first: a stop that loses the race writes nothing
+        if !db.complete_session(
+            session.id,
+            end_commit.as_deref(),
+            active_seconds,
+            ended_at,
+            SessionStatus::Completed,
+        )? {
+            return Ok(false);
+        }
+        if let Some(ref allocations) = branches {
+            db.record_session_branches(session.id, allocations)?;
+        }
+        db.record_session_activity(session.id, &activity)?;
+        if let Some(ref stats) = diff_stats {
+            db.set_session_diff_stats(session.id, stats)?;
+        }
+        if !commits.is_empty() {
+            db.record_commits(session.id, &commits)?;
+        }
+        for (submodule, commits) in &submodule_commits {
+            db.record_submodule_commits(session.id, submodule, commits)?;
+        }
+        if let Some(ref stats) = uncommitted {
+            db.set_session_uncommitted(session.id, stats)?;
+        }
+        if let Some(ref pull_request) = pull_request {
+            db.set_session_pull_request(session.id, pull_request)?;
+        }
+        if let Some(ref work_item) = work_item {
+            debug!("Session {} work item {} taken from commit messages", session.id, work_item);
+            db.set_session_work_item(session.id, Some(work_item))?;
+        }
+        Ok(true)
+    })?;
+    if !completed {
+        return Ok(None);
+    }
+    events::log(config, || Event::stop(db, session.id));
+    webhook::fire(db, config, WebhookEvent::Stop, session.id);
+
+    Ok(Some(active_seconds))
+}
+
+/// The pull request opened from the session's branch, if there is one
+///
+/// Lookup failures only warn: a stop hook must not fail because the code host is unreachable.
+fn find_pull_request(session: &Session, project_path: &Path, git_info: Option<&git::GitInfo>) -> Option<PullRequest> {
+    let remote = git_info.and_then(|g| g.remote_url.as_deref())?;
+
+    match pull_request::find_for_branch(project_path, remote, &session.branch) {
+        Ok(Some(pull_request)) => {
+            debug!("Session {} belongs to pull request #{}", session.id, pull_request.number);
+            Some(pull_request)
+        }
+        Ok(None) => None,
+        Err(e) => {
+            warn!("Failed to look up pull request for session {}: {:#}", session.id, e);
+            None
+        }
+    }
+}
+
+/// When a session's activity ended: its last heartbeat plus the idle grace period
+fn last_activity(
+    session: &Session,
+    last_heartbeat: Option<&Heartbeat>,
+    config: &EffectiveConfig,
+) -> DateTime<Utc> {
+    match last_heartbeat {
+        Some(heartbeat) => {
+            let end = heartbeat.timestamp + Duration::minutes(config.idle_grace_minutes as i64);
+            end.min(Utc::now())
+        }
+        None => session.started_at,
+    }
+}
+
+/// Whether a session has run past `settings.max_session_hours`
+fn session_expired(session: &Session, config: &EffectiveConfig) -> bool {
+    config.max_session_hours > 0
+        && Utc::now() - session.started_at > Duration::hours(config.max_session_hours as i64)
+}
+
+/// Fold the heartbeats of sessions that ended over `older_than_days` ago into intervals
+///
+/// Heartbeats are only merged across gaps the idle timeout counts in full, so
+/// reports come out the same as long as the idle timeout isn't lowered later.
+/// Returns (sessions compacted, heartbeat rows removed, intervals written).
+pub fn compact_sessions(db: &impl Storage, config: &EffectiveConfig, older_than_days: u32) -> Result<(usize, usize, usize)> {
+    let cutoff = Utc::now() - Duration::days(older_than_days as i64);
+    let max_gap = config.idle_timeout_minutes as i64 * 60;
+
+    let sessions = db.get_compactable_sessions(cutoff)?;
+    let (mut removed, mut written) = (0, 0);
+    for session_id in &sessions {
+        let (rows, intervals) = db.compact_heartbeats(*session_id, max_gap)?;
+        debug!("Session {}: {} heartbeats compacted into {} intervals", session_id, rows, intervals);
+        removed += rows;
+        written += intervals;
+    }
+
+    Ok((sessions.len(), removed, written))
 }
 
 /// Close any abandoned sessions (from previous runs that didn't properly stop)
-fn close_abandoned_sessions(db: &Database, config: &EffectiveConfig) -> Result<()> {
+///
+/// Returns the number of sessions closed.
+pub fn close_abandoned_sessions(db: &impl Storage, config: &EffectiveConfig) -> Result<usize> {
     let active_sessions = db.get_all_active_sessions()?;
+    let mut closed = 0;
 
     for session in active_sessions {
         let heartbeats = db.get_heartbeats(session.id)?;
@@ -160,61 +587,704 @@ fn close_abandoned_sessions(db: &Database, config: &EffectiveConfig) -> Result<(
 
             if Utc::now() > cutoff {
                 // Session is abandoned - close it
-                let active_seconds = calculate_active_time(&heartbeats, config.idle_timeout_minutes);
+                let Some(active_seconds) = abandon(db, &session, &heartbeats, config)? else {
+                    continue;
+                };
 
-                db.complete_session(session.id, None, active_seconds, SessionStatus::Abandoned)?;
-
-                eprintln!(
+                info!(
                     "Closed abandoned session {} (was active for {})",
                     session.id,
                     format_duration(active_seconds)
                 );
+                closed += 1;
             }
         }
     }
 
-    Ok(())
+    Ok(closed)
+}
+
+/// Close an active session by hand, marking it abandoned
+///
+/// Returns the session's active time in seconds.
+pub fn abandon_session(db: &impl Storage, session_id: i64, config: &EffectiveConfig) -> Result<i64> {
+    let session = db.get_session_by_id(session_id)?;
+    if session.status != SessionStatus::Active {
+        anyhow::bail!("Session {} is not active ({})", session.id, session.status.as_str());
+    }
+
+    let heartbeats = db.get_heartbeats(session.id)?;
+    abandon(db, &session, &heartbeats, config)?.with_context(|| format!("Session {} is not active", session.id))
+}
+
+/// Mark a session abandoned, ending it at its last activity rather than now
+///
+/// Returns `None` when another process ended the session first.
+fn abandon(
+    db: &impl Storage,
+    session: &Session,
+    heartbeats: &[Heartbeat],
+    config: &EffectiveConfig,
+) -> Result<Option<i64>> {
+    let active_seconds = calculate_active_time(heartbeats, config.idle_timeout_minutes, config.idle_grace_minutes);
+    let project = db.get_project_by_id(session.project_id)?;
+    let branches = branch_split(session, &project, Path::new(&project.path), heartbeats, config);
+    let activity = activity_breakdown(heartbeats, config.idle_timeout_minutes, config.idle_grace_minutes);
+
+    let ended_at = last_activity(session, heartbeats.last(), config);
+    let completed = db.atomically(|| {
+        if !db.complete_session(session.id, None, active_seconds, ended_at, SessionStatus::Abandoned)? {
+            return Ok(false);
+        }
+        if let Some(ref allocations) = branches {
+            db.record_session_branches(session.id, allocations)?;
+        }
+        db.record_session_activity(session.id, &activity)?;
+        Ok(true)
+    })?;
+    if !completed {
+        return Ok(None);
+    }
+    events::log(config, || Event::stop(db, session.id));
+    webhook::fire(db, config, WebhookEvent::Abandon, session.id);
+
+    Ok(Some(active_seconds))
+}
+
+/// Log a session (re)opening together with its first heartbeat
+fn log_start(
+    db: &impl Storage,
+    config: &EffectiveConfig,
+    project: &Project,
+    session_id: i64,
+    timestamp: DateTime<Utc>,
+    context: HeartbeatContext,
+) {
+    events::log(config, || {
+        Ok(Event::Start {
+            project: Box::new(project.clone()),
+            session: db.get_session_by_id(session_id)?,
+        })
+    });
+    events::log(config, || {
+        Ok(Event::Heartbeat {
+            session_id,
+            timestamp,
+            context,
+        })
+    });
+}
+
+/// How a session's time divides across branches, if it switched branch
+fn branch_split(
+    session: &Session,
+    project: &Project,
+    project_path: &Path,
+    heartbeats: &[Heartbeat],
+    config: &EffectiveConfig,
+) -> Option<Vec<(String, Option<String>, i64)>> {
+    // Heartbeats recorded without a branch take it from the checkouts in the HEAD reflog
+    let checkouts = vcs::open(project_path)
+        .branch_checkouts(session.started_at, Utc::now())
+        .unwrap_or_else(|e| {
+            debug!("Could not read branch checkouts for session {}: {:#}", session.id, e);
+            Vec::new()
+        });
+    let heartbeats = with_checkout_branches(heartbeats, &checkouts);
+
+    let breakdown = branch_breakdown(
+        &heartbeats,
+        &session.branch,
+        config.idle_timeout_minutes,
+        config.idle_grace_minutes,
+    );
+    if breakdown.len() < 2 {
+        return None;
+    }
+
+    // A work item the branch doesn't yield was set by hand and covers the whole session
+    let patterns = work_item_patterns(config, project);
+    if session.work_item.is_some()
+        && session.work_item != resolve_work_item(project_path, &session.branch, &patterns)
+    {
+        return None;
+    }
+
+    let allocations: Vec<(String, Option<String>, i64)> = breakdown
+        .into_iter()
+        .map(|(branch, seconds)| {
+            let work_item = resolve_work_item(project_path, &branch, &patterns);
+            (branch, work_item, seconds)
+        })
+        .collect();
+
+    debug!(
+        "Session {} switched branches; splitting time across {} branches",
+        session.id,
+        allocations.len()
+    );
+    Some(allocations)
+}
+
+/// Commits made inside the project's submodules while the session ran
+///
+/// Unreadable submodules are skipped.
+fn submodule_commits(
+    db: &impl Storage,
+    session: &Session,
+    project: &Project,
+    project_path: &Path,
+    config: &EffectiveConfig,
+    ended_at: DateTime<Utc>,
+) -> Result<Vec<(String, Vec<CommitEntry>)>> {
+    let mut found = Vec::new();
+    for submodule in git::submodule_paths(project_path) {
+        let repo = vcs::open(&project_path.join(&submodule));
+        let commits = match cached_commits(db, session, project, &submodule, repo.as_ref(), config, ended_at) {
+            Ok(commits) => commits,
+            Err(e) => {
+                debug!("Skipping submodule {}: {:#}", submodule, e);
+                continue;
+            }
+        };
+        if commits.is_empty() {
+            continue;
+        }
+
+        debug!("Found {} commits in submodule {} for session {}", commits.len(), submodule, session.id);
+        found.push((submodule, commits));
+    }
+    Ok(found)
+}
+
+/// Commits of the project (`submodule` empty) or a submodule made while the session ran
+///
+/// Commits read before are cached per project, so the walk only reads commits
+/// newer than the newest cached one (for the project itself, the session's
+/// start commit at the latest), and sessions running at the same time share
+/// what was found. Commits are then
+/// picked from the cache by commit time. Those picked for the first time are
+/// measured, all in one pass, so reports can show what each changed.
+fn cached_commits(
+    db: &impl Storage,
+    session: &Session,
+    project: &Project,
+    submodule: &str,
+    repo: &dyn vcs::Vcs,
+    config: &EffectiveConfig,
+    ended_at: DateTime<Utc>,
+) -> Result<Vec<CommitEntry>> {
+    let known = db.latest_cached_commit(project.id, submodule)?;
+    let start = session.start_commit.as_deref().filter(|_| submodule.is_empty());
+    let stop_at: Vec<&str> = known.as_deref().into_iter().chain(start).collect();
+    let walked = repo.commits_between(&stop_at, None, &config.commit_filter)?;
+    db.cache_commits(project.id, submodule, &walked)?;
+
+    // Commit times have whole seconds, so a commit made right after the start counts
+    let since = session.started_at.trunc_subsecs(0);
+    let commits = db.get_cached_commits(project.id, submodule, since, ended_at)?;
+
+    let mut unmeasured = Vec::new();
+    for (hash, _, _) in &commits {
+        if db.get_commit_diff_stats(project.id, submodule, hash)?.is_none() {
+            unmeasured.push(hash.clone());
+        }
+    }
+    if !unmeasured.is_empty() {
+        for (hash, stats) in repo.commit_diff_stats(&unmeasured)? {
+            db.set_commit_diff_stats(project.id, submodule, &hash, &stats)?;
+        }
+    }
+    Ok(commits)
+}
+
+/// Copy heartbeats, giving those without a branch the branch checked out at the time
+fn with_checkout_branches(heartbeats: &[Heartbeat], checkouts: &[(DateTime<Utc>, String)]) -> Vec<Heartbeat> {
+    heartbeats
+        .iter()
+        .map(|heartbeat| {
+            let mut heartbeat = heartbeat.clone();
+            if heartbeat.branch.is_none() {
+                heartbeat.branch = checkouts
+                    .iter()
+                    .rev()
+                    .find(|(time, _)| *time <= heartbeat.timestamp)
+                    .map(|(_, branch)| branch.clone());
+            }
+            heartbeat
+        })
+        .collect()
+}
+
+/// Work item patterns for a project, in priority order
+///
+/// Project config takes precedence over the patterns stored via `projects set-pattern`.
+fn work_item_patterns(config: &EffectiveConfig, project: &Project) -> Vec<String> {
+    if config.work_item_patterns.is_empty() {
+        project.work_item_patterns()
+    } else {
+        config.work_item_patterns.clone()
+    }
+}
+
+/// Read git state, logging (rather than failing) when it is unavailable
+fn git_info_or_warn(project_path: &Path, config: &EffectiveConfig) -> Option<git::GitInfo> {
+    match vcs::open(project_path).info(config.remote_name.as_deref()) {
+        Ok(info) => Some(info),
+        Err(e) => {
+            warn!("Git info unavailable for {}: {:#}", project_path.display(), e);
+            None
+        }
+    }
 }
 
 /// Calculate active time from heartbeats
 ///
 /// Active time is calculated by summing intervals between consecutive heartbeats,
 /// but only counting intervals shorter than the idle timeout.
-fn calculate_active_time(heartbeats: &[crate::models::Heartbeat], idle_timeout_minutes: u32) -> i64 {
+fn calculate_active_time(
+    heartbeats: &[Heartbeat],
+    idle_timeout_minutes: u32,
+    idle_grace_minutes: u32,
+) -> i64 {
+    heartbeats
+        .windows(2)
+        .map(|window| {
+            let interval = (window[1].timestamp - window[0].timestamp).num_seconds();
+            credited_seconds(interval, idle_timeout_minutes, idle_grace_minutes)
+        })
+        .sum()
+}
+
+/// Bring a running session's stored active time up to a new heartbeat
+///
+/// Sessions without a running total yet (just started, resumed, or from an
+/// older version) get one from all their heartbeats, once.
+fn advance_active_time(db: &impl Storage, session: &Session, timestamp: DateTime<Utc>, config: &EffectiveConfig) -> Result<()> {
+    let (timeout, grace) = (config.idle_timeout_minutes, config.idle_grace_minutes);
+    match session.last_heartbeat_at {
+        // Counted from the latest heartbeat as stored, not as read, in case another hook got ahead
+        Some(_) => db.advance_running_active_time(session.id, timestamp, timeout, grace),
+        None => {
+            let active_seconds = calculate_active_time(&db.get_heartbeats(session.id)?, timeout, grace);
+            db.set_running_active_time(session.id, active_seconds, timestamp)
+        }
+    }
+}
+
+/// Active time of a running session so far, including the time since its last heartbeat
+///
+/// Reads the running total kept on the session, and only falls back to its
+/// heartbeats when there is none yet.
+pub fn running_active_seconds(
+    db: &impl Storage,
+    session: &Session,
+    idle_timeout_minutes: u32,
+    idle_grace_minutes: u32,
+) -> Result<i64> {
+    match session.last_heartbeat_at {
+        Some(last) => {
+            let since_last = (Utc::now() - last).num_seconds();
+            Ok(session.active_seconds.unwrap_or(0) + credited_seconds(since_last, idle_timeout_minutes, idle_grace_minutes))
+        }
+        None => Ok(calculate_active_time_with_current(
+            &db.get_heartbeats(session.id)?,
+            idle_timeout_minutes,
+            idle_grace_minutes,
+        )),
+    }
+}
+
+/// Active time of a running session since an instant, such as the start of today
+///
+/// Works from the session's heartbeats, counting only the parts of its
+/// active spans (and of the time since its last heartbeat) after `since`.
+pub fn running_active_seconds_since(
+    db: &impl Storage,
+    session: &Session,
+    since: DateTime<Utc>,
+    idle_timeout_minutes: u32,
+    idle_grace_minutes: u32,
+) -> Result<i64> {
+    let heartbeats = db.get_heartbeats(session.id)?;
+    let mut spans = active_spans(&heartbeats, idle_timeout_minutes, idle_grace_minutes);
+    if let Some(last) = heartbeats.last() {
+        let since_last = (Utc::now() - last.timestamp).num_seconds();
+        spans.push((last.timestamp, credited_seconds(since_last, idle_timeout_minutes, idle_grace_minutes)));
+    }
+    Ok(spans
+        .into_iter()
+        .map(|(start, seconds)| (start + Duration::seconds(seconds) - start.max(since)).num_seconds().max(0))
+        .sum())
+}
+
+/// Calculate active time including time since last heartbeat (for status display)
+pub fn calculate_active_time_with_current(
+    heartbeats: &[Heartbeat],
+    idle_timeout_minutes: u32,
+    idle_grace_minutes: u32,
+) -> i64 {
     if heartbeats.is_empty() {
         return 0;
     }
 
-    let timeout_seconds = (idle_timeout_minutes as i64) * 60;
     let mut total_seconds: i64 = 0;
 
     for window in heartbeats.windows(2) {
         let interval = (window[1].timestamp - window[0].timestamp).num_seconds();
+        total_seconds += credited_seconds(interval, idle_timeout_minutes, idle_grace_minutes);
+    }
+
+    // Add time from last heartbeat to now (if within timeout, or the grace period)
+    if let Some(last) = heartbeats.last() {
+        let since_last = (Utc::now() - last.timestamp).num_seconds();
+        total_seconds += credited_seconds(since_last, idle_timeout_minutes, idle_grace_minutes);
+    }
+
+    total_seconds
+}
+
+/// A period of activity, as (start, seconds)
+pub type Span = (DateTime<Utc>, i64);
+
+/// Periods of activity in a session
+///
+/// Heartbeat gaps within the idle timeout join into one span; a longer gap
+/// ends the span after its credited grace time.
+pub fn active_spans(
+    heartbeats: &[Heartbeat],
+    idle_timeout_minutes: u32,
+    idle_grace_minutes: u32,
+) -> Vec<Span> {
+    let mut spans: Vec<Span> = Vec::new();
+    let mut open = false;
+    for window in heartbeats.windows(2) {
+        let interval = (window[1].timestamp - window[0].timestamp).num_seconds();
+        let credited = credited_seconds(interval, idle_timeout_minutes, idle_grace_minutes);
+        if credited == 0 {
+            open = false;
+            continue;
+        }
+        match spans.last_mut() {
+            Some(span) if open => span.1 += credited,
+            _ => spans.push((window[0].timestamp, credited)),
+        }
+        open = credited == interval;
+    }
+    spans
+}
 
-        if interval <= timeout_seconds {
-            total_seconds += interval;
+/// Overlap-aware active time per session, from each session's [`active_spans`]
+///
+/// Active spans of all sessions are merged on one timeline; wherever several
+/// sessions were active at once, the overlap is shared equally between them.
+/// The results therefore add up to wall-clock time spent, never more.
+pub fn wall_clock_seconds(sessions: &[(i64, Vec<Span>)]) -> HashMap<i64, i64> {
+    // (time, +1 for a span start / -1 for a span end, session id)
+    let mut events: Vec<(DateTime<Utc>, i32, i64)> = Vec::new();
+    for (session_id, spans) in sessions {
+        for &(start, seconds) in spans {
+            events.push((start, 1, *session_id));
+            events.push((start + Duration::seconds(seconds), -1, *session_id));
         }
-        // If interval > timeout, we assume user was away, don't count it
     }
+    events.sort();
 
-    total_seconds
+    let mut shares: HashMap<i64, f64> = sessions.iter().map(|(id, _)| (*id, 0.0)).collect();
+    let mut active: HashMap<i64, i32> = HashMap::new();
+    let mut previous: Option<DateTime<Utc>> = None;
+
+    for (time, delta, session_id) in events {
+        if let Some(previous) = previous {
+            let running: Vec<i64> = active.iter().filter(|(_, n)| **n > 0).map(|(id, _)| *id).collect();
+            let segment = (time - previous).num_milliseconds() as f64 / 1000.0;
+            if !running.is_empty() && segment > 0.0 {
+                let share = segment / running.len() as f64;
+                for id in running {
+                    *shares.entry(id).or_insert(0.0) += share;
+                }
+            }
+        }
+        *active.entry(session_id).or_insert(0) += delta;
+        previous = Some(time);
+    }
+
+    shares
+        .into_iter()
+        .map(|(id, seconds)| (id, seconds.round() as i64))
+        .collect()
+}
+
+/// Seconds of a gap between two heartbeats that count as active time
+///
+/// Gaps within the idle timeout count in full. Longer gaps mean the user
+/// was away, but work usually continued briefly after the last heartbeat,
+/// so up to `idle_grace_minutes` of them is still credited.
+pub fn credited_seconds(interval: i64, idle_timeout_minutes: u32, idle_grace_minutes: u32) -> i64 {
+    if interval <= (idle_timeout_minutes as i64) * 60 {
+        interval
+    } else {
+        interval.min((idle_grace_minutes as i64) * 60)
+    }
+}
+
+/// Split active time by the top-level directory of the files Claude touched
+///
+/// Each counted interval is attributed to the module touched by the heartbeat
+/// that closes it, or to the most recently touched module when that heartbeat
+/// carries no file (e.g. a prompt submission). Time before the first file touch
+/// is not attributed to any module.
+pub fn module_breakdown(
+    heartbeats: &[Heartbeat],
+    project_path: &Path,
+    idle_timeout_minutes: u32,
+    idle_grace_minutes: u32,
+) -> Vec<(String, i64)> {
+    attribute_intervals(heartbeats, idle_timeout_minutes, idle_grace_minutes, None, |hb| {
+        hb.file_path.as_deref().and_then(|f| module_of(f, project_path))
+    })
+}
+
+/// Split active time by the branch checked out at each heartbeat
+///
+/// Heartbeats recorded without a branch keep the previous one, starting from
+/// the branch the session was started on.
+pub fn branch_breakdown(
+    heartbeats: &[Heartbeat],
+    start_branch: &str,
+    idle_timeout_minutes: u32,
+    idle_grace_minutes: u32,
+) -> Vec<(String, i64)> {
+    attribute_intervals(
+        heartbeats,
+        idle_timeout_minutes,
+        idle_grace_minutes,
+        Some(start_branch.to_string()),
+        |hb| hb.branch.clone(),
+    )
+}
+
+/// Split active time across the sub-projects of a monorepo
+///
+/// `subprojects` maps paths relative to the project root to names; a file (or,
+/// failing that, Claude's working directory) belongs to the longest matching
+/// path. Activity outside every sub-project is grouped as "(shared)".
+pub fn subproject_breakdown(
+    heartbeats: &[Heartbeat],
+    project_path: &Path,
+    subprojects: &[(String, String)],
+    idle_timeout_minutes: u32,
+    idle_grace_minutes: u32,
+) -> Vec<(String, i64)> {
+    attribute_intervals(heartbeats, idle_timeout_minutes, idle_grace_minutes, None, |hb| {
+        let path = hb.file_path.as_deref().or(hb.cwd.as_deref())?;
+        let relative = Path::new(path).strip_prefix(project_path).ok()?;
+        let name = subprojects
+            .iter()
+            .filter(|(subpath, _)| relative.starts_with(subpath.trim_matches('/')))
+            .max_by_key(|(subpath, _)| subpath.trim_matches('/').len())
+            .map(|(_, name)| name.clone());
+        Some(name.unwrap_or_else(|| "(shared)".to_string()))
+    })
+}
+
+/// Activity kind of a heartbeat, from the hook event that recorded it
+///
+/// - `prompting`: Claude working on a submitted prompt
+/// - `tool`: Claude running tools
+/// - `waiting`: Claude waiting on the user (permission or input)
+/// - `other`: session start and heartbeats recorded before event types existed
+pub fn activity_kind(event_type: Option<&str>) -> &'static str {
+    match event_type {
+        Some("prompt-submit") => "prompting",
+        Some("tool-use") => "tool",
+        Some("notification") => "waiting",
+        _ => "other",
+    }
+}
+
+/// Split active time by what was happening in the session
+///
+/// Unlike the other breakdowns, an interval belongs to the heartbeat that opens
+/// it: the time after a prompt submission is spent on that prompt, the time
+/// after a notification is spent waiting for the user.
+pub fn activity_breakdown(
+    heartbeats: &[Heartbeat],
+    idle_timeout_minutes: u32,
+    idle_grace_minutes: u32,
+) -> Vec<(String, i64)> {
+    let mut totals: Vec<(String, i64)> = Vec::new();
+
+    for window in heartbeats.windows(2) {
+        let interval = (window[1].timestamp - window[0].timestamp).num_seconds();
+        let interval = credited_seconds(interval, idle_timeout_minutes, idle_grace_minutes);
+        if interval == 0 {
+            continue;
+        }
+
+        let kind = activity_kind(window[0].event_type.as_deref());
+        match totals.iter_mut().find(|(name, _)| name == kind) {
+            Some(entry) => entry.1 += interval,
+            None => totals.push((kind.to_string(), interval)),
+        }
+    }
+
+    totals
+}
+
+/// Sum counted intervals per key, in order of first appearance
+///
+/// The key of an interval comes from the heartbeat closing it; heartbeats
+/// without a key inherit the most recent one (or `initial`).
+fn attribute_intervals<F>(
+    heartbeats: &[Heartbeat],
+    idle_timeout_minutes: u32,
+    idle_grace_minutes: u32,
+    initial: Option<String>,
+    key_of: F,
+) -> Vec<(String, i64)>
+where
+    F: Fn(&Heartbeat) -> Option<String>,
+{
+    let mut totals: Vec<(String, i64)> = Vec::new();
+    let mut current = initial;
+
+    for window in heartbeats.windows(2) {
+        if let Some(key) = key_of(&window[1]) {
+            current = Some(key);
+        }
+
+        let interval = (window[1].timestamp - window[0].timestamp).num_seconds();
+        let interval = credited_seconds(interval, idle_timeout_minutes, idle_grace_minutes);
+        if interval == 0 {
+            continue;
+        }
+
+        if let Some(ref key) = current {
+            match totals.iter_mut().find(|(name, _)| name == key) {
+                Some(entry) => entry.1 += interval,
+                None => totals.push((key.clone(), interval)),
+            }
+        }
+    }
+
+    totals
 }
 
-/// Extract work item ID from branch name using regex pattern
-fn extract_work_item(branch: &str, pattern: Option<&str>) -> Option<String> {
-    let pattern = pattern?;
+/// Top-level directory of a file relative to the project root
+fn module_of(file_path: &str, project_path: &Path) -> Option<String> {
+    let relative = Path::new(file_path).strip_prefix(project_path).ok()?;
+    let mut components = relative.components();
+    let first = components.next()?.as_os_str().to_str()?.to_string();
+
+    // Files directly in the project root are grouped together
+    if components.next().is_none() {
+        return Some("(root)".to_string());
+    }
 
-    let re = match Regex::new(pattern) {
-        Ok(r) => r,
-        Err(_) => return None,
+    Some(first)
+}
+
+/// Work item for a branch, letting the repository override the branch name patterns
+///
+/// Overrides are tried in order: the first line of the branch's description
+/// (`git branch --edit-description`), then the first line of [`WORK_ITEM_FILE`].
+fn resolve_work_item(project_path: &Path, branch: &str, patterns: &[String]) -> Option<String> {
+    let first_line = |text: &str| {
+        text.lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(String::from)
     };
 
-    let caps = re.captures(branch)?;
+    vcs::open(project_path)
+        .branch_description(branch)
+        .and_then(|description| first_line(&description))
+        .or_else(|| {
+            let content = std::fs::read_to_string(project_path.join(WORK_ITEM_FILE)).ok()?;
+            first_line(&content)
+        })
+        .or_else(|| extract_work_item(branch, patterns))
+}
+
+/// Extract work item ID from branch name, trying each regex pattern in order
+fn extract_work_item(branch: &str, patterns: &[String]) -> Option<String> {
+    patterns.iter().find_map(|pattern| {
+        let re = Regex::new(pattern).ok()?;
+        let caps = re.captures(branch)?;
+
+        // Return first capture group, or entire match if no groups
+        caps.get(1)
+            .or_else(|| caps.get(0))
+            .map(|m| m.as_str().to_string())
+    })
+}
+
+/// Pick the work item mentioned most often in commit messages
+///
+/// Ties go to the key that appears first.
+fn work_item_from_commits(messages: &[String], pattern: &str) -> Option<String> {
+    let re = Regex::new(pattern).ok()?;
+    let mut counts: Vec<(String, usize)> = Vec::new();
+
+    for message in messages {
+        for caps in re.captures_iter(message) {
+            let Some(key) = caps.get(1).or_else(|| caps.get(0)) else {
+                continue;
+            };
+            match counts.iter_mut().find(|(k, _)| k == key.as_str()) {
+                Some(entry) => entry.1 += 1,
+                None => counts.push((key.as_str().to_string(), 1)),
+            }
+        }
+    }
+
+    // max_by_key returns the last maximum; iterate in reverse so ties go to the first key
+    counts
+        .into_iter()
+        .rev()
+        .max_by_key(|(_, count)| *count)
+        .map(|(key, _)| key)
+}
+
+/// Suggest a work item pattern matching the naming style of existing branches
+///
+/// Candidates are tried against all non-trunk branches; the one matching the
+/// most branches wins, provided it covers at least a third of them.
+pub fn suggest_work_item_pattern(branches: &[String]) -> Option<&'static str> {
+    const CANDIDATES: [&str; 3] = [
+        // Jira / Linear keys in upper case: feature/ABC-123-title
+        r"([A-Z][A-Z0-9]+-\d+)",
+        // Linear-generated branch names: user/abc-123-title
+        r"(?:^|/)([a-z][a-z0-9]+-\d+)",
+        // GitHub issue numbers: 123-fix-login, fix/123-login
+        r"(?:^|/)(\d+)-",
+    ];
+    const TRUNKS: [&str; 4] = ["main", "master", "develop", "trunk"];
+
+    let candidates: Vec<&String> = branches
+        .iter()
+        .filter(|b| !TRUNKS.contains(&b.as_str()))
+        .collect();
 
-    // Return first capture group, or entire match if no groups
-    caps.get(1)
-        .or_else(|| caps.get(0))
-        .map(|m| m.as_str().to_string())
+    if candidates.is_empty() {
+        return None;
+    }
+
+    let (pattern, matches) = CANDIDATES
+        .iter()
+        .map(|pattern| {
+            let re = Regex::new(pattern).expect("valid built-in pattern");
+            let count = candidates.iter().filter(|b| re.is_match(b)).count();
+            (*pattern, count)
+        })
+        .max_by_key(|(_, count)| *count)?;
+
+    if matches > 0 && matches * 3 >= candidates.len() {
+        Some(pattern)
+    } else {
+        None
+    }
 }
 
 /// Format duration in human-readable format
@@ -232,32 +1302,291 @@ pub fn format_duration(seconds: i64) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::db::Database;
+
+    #[test]
+    fn test_resolve_work_item() {
+        let dir = tempfile::tempdir().unwrap();
+        let patterns = vec![r"^feature/([A-Z]+-\d+)".to_string()];
+
+        assert_eq!(
+            resolve_work_item(dir.path(), "feature/ABC-1", &patterns),
+            Some("ABC-1".to_string())
+        );
+
+        std::fs::write(dir.path().join(WORK_ITEM_FILE), "# billed to\n\n  OPS-9  \n").unwrap();
+        assert_eq!(
+            resolve_work_item(dir.path(), "feature/ABC-1", &patterns),
+            Some("OPS-9".to_string())
+        );
+
+        gix::init(dir.path()).unwrap();
+        std::fs::write(
+            dir.path().join(".git/config"),
+            "[branch \"spike\"]\n\tdescription = DATA-42\n",
+        )
+        .unwrap();
+        assert_eq!(resolve_work_item(dir.path(), "spike", &patterns), Some("DATA-42".to_string()));
+        assert_eq!(resolve_work_item(dir.path(), "other", &patterns), Some("OPS-9".to_string()));
+    }
+
+    #[test]
+    fn test_resolve_project_keeps_client() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::open(&dir.path().join("test.db")).unwrap();
+        let mut config = EffectiveConfig::from_global(crate::config::GlobalConfig::default(), None).unwrap();
+        config.client = Some("Acme".to_string());
+
+        let mut project = resolve_project(&db, "/work/api", None, &config).unwrap();
+        assert_eq!(project.client.as_deref(), Some("Acme"));
+
+        // A client set by hand outlives the configured one
+        project.client = Some("Globex".to_string());
+        db.update_project(&project).unwrap();
+        let project = resolve_project(&db, "/work/api", None, &config).unwrap();
+        assert_eq!(project.client.as_deref(), Some("Globex"));
+    }
+
+    #[test]
+    fn test_stop_twice_finishes_once() {
+        use crate::config::{GlobalConfig, WebhookSettings};
+        use std::net::TcpListener;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        // Counts the webhook deliveries it answers
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let delivered = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&delivered);
+        std::thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let mut reader = std::io::BufReader::new(&stream);
+                if crate::server::read_request(&mut reader).is_ok() {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                }
+                let _ = std::io::Write::write_all(&mut stream, b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n");
+            }
+        });
+
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let git = |args: &[&str]| {
+            let status = std::process::Command::new("git").arg("-C").arg(&root).args(args).status().unwrap();
+            assert!(status.success(), "git {:?} failed", args);
+        };
+        git(&["init", "-q"]);
+        git(&["config", "user.name", "Me"]);
+        git(&["config", "user.email", "me@example.com"]);
+        git(&["commit", "-q", "--allow-empty", "-m", "Initial"]);
+
+        let db = Database::open(&root.join(".tracker.db")).unwrap();
+        let mut config = EffectiveConfig::from_global(GlobalConfig::default(), None).unwrap();
+        config.webhooks = vec![WebhookSettings {
+            url,
+            events: vec![WebhookEvent::Stop],
+            retries: 0,
+        }];
+        config.webhook_dispatch = webhook::Dispatch::Inline;
+        let payload = HookPayload {
+            session_id: Some("window-1".to_string()),
+            ..Default::default()
+        };
+
+        start_session(&db, &root, &config, &payload, None).unwrap();
+        git(&["commit", "-q", "--allow-empty", "-m", "ABC-1 Add login"]);
+        stop_session(&db, &root, &config, &payload).unwrap();
+        let stopped = db.get_recent_sessions(10).unwrap();
+
+        // The Stop hook delivered again changes nothing
+        stop_session(&db, &root, &config, &payload).unwrap();
+        let sessions = db.get_recent_sessions(10).unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].status, SessionStatus::Completed);
+        assert_eq!(sessions[0].ended_at, stopped[0].ended_at);
+        let commits = db.get_commits(sessions[0].id).unwrap();
+        assert_eq!(commits.len(), 1);
+        // Measured once into the commit cache
+        assert_eq!(commits[0].diff_stats, Some(models::DiffStats::default()));
+        assert_eq!(delivered.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_commits_are_shared_through_the_cache() {
+        use crate::config::GlobalConfig;
+
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let git = |args: &[&str]| {
+            let status = std::process::Command::new("git").arg("-C").arg(&root).args(args).status().unwrap();
+            assert!(status.success(), "git {:?} failed", args);
+        };
+        git(&["init", "-q"]);
+        git(&["config", "user.name", "Me"]);
+        git(&["config", "user.email", "me@example.com"]);
+        git(&["commit", "-q", "--allow-empty", "-m", "Initial"]);
+
+        let db = Database::open(&root.join(".tracker.db")).unwrap();
+        let config = EffectiveConfig::from_global(GlobalConfig::default(), None).unwrap();
+        let window = |id: &str| HookPayload {
+            session_id: Some(id.to_string()),
+            ..Default::default()
+        };
+
+        // Two windows open on the same commit
+        start_session(&db, &root, &config, &window("a"), None).unwrap();
+        start_session(&db, &root, &config, &window("b"), None).unwrap();
+        git(&["commit", "-q", "--allow-empty", "-m", "ABC-1 First"]);
+        stop_session(&db, &root, &config, &window("a")).unwrap();
+        git(&["commit", "-q", "--allow-empty", "-m", "ABC-1 Second"]);
+        stop_session(&db, &root, &config, &window("b")).unwrap();
+
+        let messages = |index: usize| -> Vec<String> {
+            let session = &db.get_recent_sessions(10).unwrap()[index];
+            let commits = db.get_commits(session.id).unwrap();
+            // Every commit was measured into the cache
+            assert!(commits.iter().all(|c| c.diff_stats.is_some()));
+            commits.into_iter().map(|c| c.message.unwrap_or_default().trim().to_string()).collect()
+        };
+        // The second stop only walked the new commit and took the first from the cache
+        let project = db.get_project_by_path(root.to_str().unwrap()).unwrap().unwrap();
+        assert_eq!(db.get_cached_commits(project.id, "", Utc::now() - Duration::hours(1), Utc::now()).unwrap().len(), 2);
+        let mut stopped = [messages(0), messages(1)];
+        stopped.sort();
+        assert_eq!(stopped, [vec!["ABC-1 First"], vec!["ABC-1 First", "ABC-1 Second"]]);
+    }
+
+    #[test]
+    fn test_concurrent_stops_finish_once() {
+        use crate::config::GlobalConfig;
+        use std::sync::{Arc, Barrier};
+
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let git = |args: &[&str]| {
+            let status = std::process::Command::new("git").arg("-C").arg(&root).args(args).status().unwrap();
+            assert!(status.success(), "git {:?} failed", args);
+        };
+        git(&["init", "-q"]);
+        git(&["config", "user.name", "Me"]);
+        git(&["config", "user.email", "me@example.com"]);
+        git(&["commit", "-q", "--allow-empty", "-m", "Initial"]);
+
+        let db_path = root.join(".tracker.db");
+        let db = Database::open(&db_path).unwrap();
+        let config = EffectiveConfig::from_global(GlobalConfig::default(), None).unwrap();
+        let payload = HookPayload {
+            session_id: Some("window-1".to_string()),
+            ..Default::default()
+        };
+        start_session(&db, &root, &config, &payload, None).unwrap();
+        git(&["commit", "-q", "--allow-empty", "-m", "ABC-1 Add login"]);
+        let project = db.get_project_by_path(root.to_str().unwrap()).unwrap().unwrap();
+        let stale = db.get_active_session(project.id, Some("window-1")).unwrap().unwrap();
+
+        // Two Stop hooks delivered at the same time
+        let barrier = Arc::new(Barrier::new(2));
+        let stops: Vec<_> = (0..2)
+            .map(|_| {
+                let (root, db_path, config, payload) = (root.clone(), db_path.clone(), config.clone(), payload.clone());
+                let barrier = Arc::clone(&barrier);
+                std::thread::spawn(move || {
+                    let db = Database::open(&db_path).unwrap();
+                    barrier.wait();
+                    stop_session(&db, &root, &config, &payload).unwrap();
+                })
+            })
+            .collect();
+        for stop in stops {
+            stop.join().unwrap();
+        }
+
+        let sessions = db.get_recent_sessions(10).unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].status, SessionStatus::Completed);
+        assert_eq!(db.get_commits(sessions[0].id).unwrap().len(), 1);
+
+        // A stop that read the session while it was still active loses the race
+        let finished = finish_session(&db, &stale, &project, &root, &config, Utc::now()).unwrap();
+        assert_eq!(finished, None);
+        assert_eq!(db.get_commits(stale.id).unwrap().len(), 1);
+        assert_eq!(db.get_recent_sessions(10).unwrap()[0].ended_at, sessions[0].ended_at);
+    }
 
     #[test]
     fn test_extract_work_item() {
         // Linear-style pattern
-        let pattern = r"^(?:feature|fix|chore)/([A-Z]+-\d+)";
+        let pattern = vec![r"^(?:feature|fix|chore)/([A-Z]+-\d+)".to_string()];
 
         assert_eq!(
-            extract_work_item("feature/ABC-123-some-description", Some(pattern)),
+            extract_work_item("feature/ABC-123-some-description", &pattern),
             Some("ABC-123".to_string())
         );
 
         assert_eq!(
-            extract_work_item("fix/XYZ-456-bug-fix", Some(pattern)),
+            extract_work_item("fix/XYZ-456-bug-fix", &pattern),
             Some("XYZ-456".to_string())
         );
 
         assert_eq!(
-            extract_work_item("main", Some(pattern)),
+            extract_work_item("main", &pattern),
             None
         );
 
         // No pattern - should return None
         assert_eq!(
-            extract_work_item("feature/ABC-123", None),
+            extract_work_item("feature/ABC-123", &[]),
             None
         );
+
+        // Patterns are tried in order until one matches
+        let patterns = vec![
+            r"^(?:feature|fix)/([A-Z]+-\d+)".to_string(),
+            r"^(\d+)-".to_string(),
+        ];
+        assert_eq!(extract_work_item("fix/ABC-7-crash", &patterns), Some("ABC-7".to_string()));
+        assert_eq!(extract_work_item("42-add-search", &patterns), Some("42".to_string()));
+        assert_eq!(extract_work_item("main", &patterns), None);
+    }
+
+    #[test]
+    fn test_work_item_from_commits() {
+        let messages = vec![
+            "ABC-12: add login form".to_string(),
+            "Fix typo".to_string(),
+            "XYZ-9 and ABC-12: wire up session".to_string(),
+            "XYZ-9 follow-up".to_string(),
+        ];
+
+        assert_eq!(
+            work_item_from_commits(&messages, DEFAULT_COMMIT_WORK_ITEM_PATTERN),
+            Some("ABC-12".to_string())
+        );
+        assert_eq!(work_item_from_commits(&messages[1..2], DEFAULT_COMMIT_WORK_ITEM_PATTERN), None);
+        assert_eq!(work_item_from_commits(&messages, r"#(\d+)"), None);
+    }
+
+    #[test]
+    fn test_suggest_work_item_pattern() {
+        let branches = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+
+        let jira = branches(&["main", "feature/ABC-12-login", "fix/ABC-13", "spike"]);
+        let pattern = [suggest_work_item_pattern(&jira).unwrap().to_string()];
+        assert_eq!(extract_work_item("fix/ABC-13", &pattern), Some("ABC-13".to_string()));
+
+        let linear = branches(&["alice/eng-42-search", "alice/eng-43-cache"]);
+        let pattern = [suggest_work_item_pattern(&linear).unwrap().to_string()];
+        assert_eq!(
+            extract_work_item("alice/eng-42-search", &pattern),
+            Some("eng-42".to_string())
+        );
+
+        let github = branches(&["123-fix-login", "fix/456-crash"]);
+        let pattern = [suggest_work_item_pattern(&github).unwrap().to_string()];
+        assert_eq!(extract_work_item("fix/456-crash", &pattern), Some("456".to_string()));
+
+        assert_eq!(suggest_work_item_pattern(&branches(&["main", "wip", "experiment"])), None);
+        assert_eq!(suggest_work_item_pattern(&branches(&["main"])), None);
     }
 
     #[test]
@@ -279,33 +1608,356 @@ mod tests {
                 id: 1,
                 session_id: 1,
                 timestamp: base,
+                file_path: None,
+                branch: None,
+                event_type: None,
+                tool: None,
+                cwd: None,
+                model: None,
+                agent: None,
             },
             crate::models::Heartbeat {
                 id: 2,
                 session_id: 1,
                 timestamp: base + Duration::minutes(5),
+                file_path: None,
+                branch: None,
+                event_type: None,
+                tool: None,
+                cwd: None,
+                model: None,
+                agent: None,
             },
             crate::models::Heartbeat {
                 id: 3,
                 session_id: 1,
                 timestamp: base + Duration::minutes(10),
+                file_path: None,
+                branch: None,
+                event_type: None,
+                tool: None,
+                cwd: None,
+                model: None,
+                agent: None,
             },
             // 20 minute gap (user was away)
             crate::models::Heartbeat {
                 id: 4,
                 session_id: 1,
                 timestamp: base + Duration::minutes(30),
+                file_path: None,
+                branch: None,
+                event_type: None,
+                tool: None,
+                cwd: None,
+                model: None,
+                agent: None,
             },
             crate::models::Heartbeat {
                 id: 5,
                 session_id: 1,
                 timestamp: base + Duration::minutes(35),
+                file_path: None,
+                branch: None,
+                event_type: None,
+                tool: None,
+                cwd: None,
+                model: None,
+                agent: None,
             },
         ];
 
         // With 10 minute timeout:
         // 5 min + 5 min (counted) + 20 min (not counted, > 10) + 5 min (counted) = 15 min = 900 seconds
-        let active = calculate_active_time(&heartbeats, 10);
+        let active = calculate_active_time(&heartbeats, 10, 0);
         assert_eq!(active, 900);
+
+        // A 3 minute grace credits the start of the 20 minute gap
+        let active = calculate_active_time(&heartbeats, 10, 3);
+        assert_eq!(active, 1080);
+
+        assert_eq!(
+            active_spans(&heartbeats, 10, 3),
+            vec![(base, 780), (base + Duration::minutes(30), 300)]
+        );
+        assert_eq!(active_spans(&heartbeats, 10, 0)[0], (base, 600));
+    }
+
+    #[test]
+    fn test_running_active_time() {
+        use crate::config::GlobalConfig;
+        use chrono::Duration;
+
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::open(&dir.path().join("tracker.db")).unwrap();
+        let config = EffectiveConfig::from_global(GlobalConfig::default(), None).unwrap();
+        let project = db.get_or_create_project("/repo", None, None, None).unwrap();
+        let session = db.create_session(project.id, "main", None, None, None, None).unwrap();
+
+        // Minutes after the start; the 40 minute gap is longer than the idle timeout
+        let base = Utc::now() - Duration::hours(2);
+        let minutes = [0, 2, 5, 45, 47];
+        let batch: Vec<_> = minutes
+            .iter()
+            .map(|&m| (base + Duration::minutes(m), HeartbeatContext::default()))
+            .collect();
+        // The first two arrive before the running total exists
+        db.record_heartbeats(session.id, &batch[..2]).unwrap();
+        for single in batch[2..].chunks(1) {
+            let heartbeat = db.record_heartbeats(session.id, single).unwrap().remove(0);
+            let session = db.get_session_by_id(session.id).unwrap();
+            advance_active_time(&db, &session, heartbeat.timestamp, &config).unwrap();
+        }
+        // Out of order heartbeats are already covered
+        let session = db.get_session_by_id(session.id).unwrap();
+        advance_active_time(&db, &session, base, &config).unwrap();
+        // As is the latest one, for a hook that read the session before it was counted
+        let mut stale = db.get_session_by_id(session.id).unwrap();
+        stale.last_heartbeat_at = Some(base + Duration::minutes(45));
+        advance_active_time(&db, &stale, base + Duration::minutes(47), &config).unwrap();
+
+        let session = db.get_session_by_id(session.id).unwrap();
+        let heartbeats = db.get_heartbeats(session.id).unwrap();
+        let (timeout, grace) = (config.idle_timeout_minutes, config.idle_grace_minutes);
+        assert_eq!(session.active_seconds, Some(calculate_active_time(&heartbeats, timeout, grace)));
+        assert_eq!(session.last_heartbeat_at.map(|t| t.timestamp()), Some((base + Duration::minutes(47)).timestamp()));
+        assert_eq!(
+            running_active_seconds(&db, &session, timeout, grace).unwrap(),
+            calculate_active_time_with_current(&heartbeats, timeout, grace)
+        );
+
+        // Only the time after a cutoff counts: one minute before the last
+        // heartbeat, then the grace after it, as it has been idle since
+        let since = base + Duration::minutes(46);
+        assert_eq!(
+            running_active_seconds_since(&db, &session, since, timeout, grace).unwrap(),
+            60 + i64::from(grace) * 60
+        );
+        assert_eq!(
+            running_active_seconds_since(&db, &session, base, timeout, grace).unwrap(),
+            calculate_active_time_with_current(&heartbeats, timeout, grace)
+        );
+    }
+
+    #[test]
+    fn test_heartbeat_keeps_its_time_and_branch() {
+        use crate::config::GlobalConfig;
+
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::open(&dir.path().join("tracker.db")).unwrap();
+        let config = EffectiveConfig::from_global(GlobalConfig::default(), None).unwrap();
+        let project = db.get_or_create_project("/repo", None, None, None).unwrap();
+        let session = db.create_session(project.id, "main", None, None, None, None).unwrap();
+
+        // Buffered by the daemon: received a while ago, on a branch since left
+        let received_at = Utc::now() - Duration::minutes(3);
+        let payload = HookPayload::default();
+        record_heartbeat(&db, Path::new("/repo"), &config, &payload, received_at, Some("feature".to_string())).unwrap();
+
+        let heartbeats = db.get_heartbeats(session.id).unwrap();
+        assert_eq!(heartbeats.len(), 1);
+        assert_eq!(heartbeats[0].timestamp.timestamp(), received_at.timestamp());
+        assert_eq!(heartbeats[0].branch.as_deref(), Some("feature"));
+    }
+
+    #[test]
+    fn test_wall_clock_seconds() {
+        let base = Utc::now();
+        let hbs = |minutes: &[i64]| -> Vec<Heartbeat> {
+            minutes
+                .iter()
+                .map(|m| Heartbeat {
+                    id: *m,
+                    session_id: 0,
+                    timestamp: base + Duration::minutes(*m),
+                    file_path: None,
+                    branch: None,
+                    event_type: None,
+                    tool: None,
+                    cwd: None,
+                    model: None,
+                    agent: None,
+                })
+                .collect()
+        };
+
+        // Session 1 is active 0-20, session 2 10-30 (with an idle gap 15-25 it doesn't get)
+        let sessions = vec![(1, hbs(&[0, 5, 10, 15, 20])), (2, hbs(&[10, 15, 25, 30]))];
+        let spans: Vec<_> = sessions.iter().map(|(id, hbs)| (*id, active_spans(hbs, 5, 0))).collect();
+        let split = wall_clock_seconds(&spans);
+
+        // 0-10 session 1 alone, 10-15 shared, 15-20 session 1 alone, 25-30 session 2 alone
+        assert_eq!(split[&1], 10 * 60 + 150 + 5 * 60);
+        assert_eq!(split[&2], 150 + 5 * 60);
+        assert_eq!(split[&1] + split[&2], 25 * 60);
+
+        // Without overlap the result matches plain active time
+        let alone = wall_clock_seconds(&spans[..1]);
+        assert_eq!(alone[&1], calculate_active_time(&sessions[0].1, 5, 0));
+    }
+
+    #[test]
+    fn test_activity_breakdown() {
+        let base = Utc::now();
+        let hb = |minute: i64, event_type: Option<&str>| Heartbeat {
+            id: minute,
+            session_id: 1,
+            timestamp: base + Duration::minutes(minute),
+            file_path: None,
+            branch: None,
+            event_type: event_type.map(str::to_string),
+            tool: None,
+            cwd: None,
+            model: None,
+            agent: None,
+        };
+
+        let heartbeats = vec![
+            hb(0, Some("session-start")),
+            hb(1, Some("prompt-submit")),
+            hb(3, Some("tool-use")),
+            hb(4, Some("tool-use")),
+            hb(6, Some("notification")),
+            hb(10, Some("prompt-submit")),
+            hb(12, Some("stop")),
+        ];
+
+        let breakdown = activity_breakdown(&heartbeats, 5, 0);
+        assert_eq!(
+            breakdown,
+            vec![
+                ("other".to_string(), 60),
+                ("prompting".to_string(), 240),
+                ("tool".to_string(), 180),
+                ("waiting".to_string(), 240),
+            ]
+        );
+        let total: i64 = breakdown.iter().map(|(_, s)| s).sum();
+        assert_eq!(total, calculate_active_time(&heartbeats, 5, 0));
+    }
+
+    #[test]
+    fn test_module_breakdown() {
+        let base = Utc::now();
+        let hb = |id: i64, minutes: i64, file: Option<&str>| Heartbeat {
+            id,
+            session_id: 1,
+            timestamp: base + Duration::minutes(minutes),
+            file_path: file.map(String::from),
+            branch: None,
+            event_type: None,
+            tool: None,
+            cwd: None,
+            model: None,
+            agent: None,
+        };
+
+        let heartbeats = vec![
+            hb(1, 0, None),
+            hb(2, 5, Some("/repo/src/main.rs")),
+            hb(3, 8, None),
+            hb(4, 10, Some("/repo/docs/DESIGN.md")),
+            hb(5, 12, Some("/repo/Cargo.toml")),
+            hb(6, 14, Some("/elsewhere/file.rs")),
+        ];
+
+        let modules = module_breakdown(&heartbeats, Path::new("/repo"), 10, 0);
+        assert_eq!(
+            modules,
+            vec![
+                ("src".to_string(), 8 * 60),
+                ("docs".to_string(), 2 * 60),
+                ("(root)".to_string(), 4 * 60),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_subproject_breakdown() {
+        let base = Utc::now();
+        let hb = |minutes: i64, file: Option<&str>, cwd: Option<&str>| Heartbeat {
+            id: minutes,
+            session_id: 1,
+            timestamp: base + Duration::minutes(minutes),
+            file_path: file.map(String::from),
+            branch: None,
+            event_type: None,
+            tool: None,
+            cwd: cwd.map(String::from),
+            model: None,
+            agent: None,
+        };
+        let subprojects = vec![
+            ("apps/web".to_string(), "Web".to_string()),
+            ("apps/web/admin".to_string(), "Admin".to_string()),
+            ("services/api/".to_string(), "API".to_string()),
+        ];
+
+        let heartbeats = vec![
+            hb(0, None, Some("/repo/services/api")),
+            hb(2, None, Some("/repo/services/api")),
+            hb(5, Some("/repo/apps/web/src/App.tsx"), None),
+            hb(9, Some("/repo/apps/web/admin/page.tsx"), None),
+            hb(10, Some("/repo/apps/webhooks/main.go"), None),
+        ];
+
+        let split = subproject_breakdown(&heartbeats, Path::new("/repo"), &subprojects, 10, 0);
+        assert_eq!(
+            split,
+            vec![
+                ("API".to_string(), 2 * 60),
+                ("Web".to_string(), 3 * 60),
+                ("Admin".to_string(), 4 * 60),
+                ("(shared)".to_string(), 60),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_branch_breakdown() {
+        let base = Utc::now();
+        let hb = |id: i64, minutes: i64, branch: Option<&str>| Heartbeat {
+            id,
+            session_id: 1,
+            timestamp: base + Duration::minutes(minutes),
+            file_path: None,
+            branch: branch.map(String::from),
+            event_type: None,
+            tool: None,
+            cwd: None,
+            model: None,
+            agent: None,
+        };
+
+        let heartbeats = vec![
+            hb(1, 0, None),
+            hb(2, 5, None),
+            hb(3, 9, Some("feature/ABC-2")),
+            hb(4, 12, None),
+            // idle gap is not attributed
+            hb(5, 40, Some("main")),
+            hb(6, 41, Some("main")),
+        ];
+
+        let branches = branch_breakdown(&heartbeats, "main", 10, 0);
+        assert_eq!(
+            branches,
+            vec![("main".to_string(), 6 * 60), ("feature/ABC-2".to_string(), 7 * 60)]
+        );
+
+        // A session that never switched has a single entry
+        let single = branch_breakdown(&heartbeats[..2], "main", 10, 0);
+        assert_eq!(single, vec![("main".to_string(), 5 * 60)]);
+
+        // Without per-heartbeat branches, the reflog's checkouts provide them
+        let plain: Vec<Heartbeat> = (0..5).map(|i| hb(i, i * 3, None)).collect();
+        let checkouts = vec![(base + Duration::minutes(4), "feature/ABC-3".to_string())];
+        let filled = with_checkout_branches(&plain, &checkouts);
+        assert_eq!(filled[1].branch, None);
+        assert_eq!(filled[2].branch.as_deref(), Some("feature/ABC-3"));
+        assert_eq!(
+            branch_breakdown(&filled, "main", 10, 0),
+            vec![("main".to_string(), 3 * 60), ("feature/ABC-3".to_string(), 9 * 60)]
+        );
     }
 }
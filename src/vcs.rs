@@ -0,0 +1,167 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use std::path::{Path, PathBuf};
+
+use crate::git::{self, CommitFilter, GitInfo};
+use crate::jj;
+use crate::models::DiffStats;
+
+/// A commit attributed to a session: hash, first line of the message and commit time
+pub type CommitEntry = (String, String, Option<DateTime<Utc>>);
+
+/// What the tracker needs from a version control system
+///
+/// "Branch" is whatever a backend uses to tell lines of work apart: the checked
+/// out branch for git, the current change id for jj.
+pub trait Vcs {
+    /// Current branch, head commit and remotes, preferring the remote called `remote_name`
+    fn info(&self, remote_name: Option<&str>) -> Result<GitInfo>;
+
+    /// Current branch only (called on every heartbeat, so it should be cheap)
+    fn current_branch(&self) -> Result<String>;
+
+    /// Commits up to `end_commit` (default: head) made after any of `stop_at`, oldest first
+    ///
+    /// Without stops only the latest commits are listed.
+    fn commits_between(
+        &self,
+        stop_at: &[&str],
+        end_commit: Option<&str>,
+        filter: &CommitFilter,
+    ) -> Result<Vec<CommitEntry>>;
+
+    /// Files and lines changed between two commits, by the commits `filter` keeps
+    fn diff_stats(&self, start_commit: &str, end_commit: &str, filter: &CommitFilter) -> Result<DiffStats>;
+
+    /// Files and lines changed by each commit, leaving out those that can't be read
+    fn commit_diff_stats(&self, commits: &[String]) -> Result<Vec<(String, DiffStats)>>;
+
+    /// Changes in the working tree that aren't committed yet
+    fn uncommitted_changes(&self) -> Result<DiffStats>;
+
+    /// Branches switched to between `since` and `until`, oldest first
+    fn branch_checkouts(&self, since: DateTime<Utc>, until: DateTime<Utc>) -> Result<Vec<(DateTime<Utc>, String)>>;
+
+    /// Branch names, most recently used first
+    fn list_branches(&self) -> Result<Vec<String>>;
+
+    /// Free-form description attached to a branch
+    fn branch_description(&self, branch: &str) -> Option<String>;
+}
+
+/// Root of the repository containing `path`, or `path` itself outside of one
+///
+/// When repositories are nested (a git checkout inside a jj repository, say),
+/// the innermost one wins.
+pub fn repo_root(path: &Path) -> PathBuf {
+    [jj::find_root(path), git::find_root(path)]
+        .into_iter()
+        .flatten()
+        .max_by_key(|root| root.components().count())
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+/// Open the repository at a path, using jj for jj repositories (including ones colocated with git)
+pub fn open(path: &Path) -> Box<dyn Vcs> {
+    if jj::is_jj_repo(path) {
+        Box::new(jj::Jujutsu::new(path))
+    } else {
+        Box::new(Git {
+            path: path.to_path_buf(),
+        })
+    }
+}
+
+/// A git repository, read with gix
+struct Git {
+    path: PathBuf,
+}
+
+impl Vcs for Git {
+    fn info(&self, remote_name: Option<&str>) -> Result<GitInfo> {
+        git::get_git_info(&self.path, remote_name)
+    }
+
+    fn current_branch(&self) -> Result<String> {
+        git::current_branch(&self.path)
+    }
+
+    fn commits_between(
+        &self,
+        stop_at: &[&str],
+        end_commit: Option<&str>,
+        filter: &CommitFilter,
+    ) -> Result<Vec<CommitEntry>> {
+        git::get_commits_between(&self.path, stop_at, end_commit, filter)
+    }
+
+    fn diff_stats(&self, start_commit: &str, end_commit: &str, filter: &CommitFilter) -> Result<DiffStats> {
+        git::diff_stats(&self.path, start_commit, end_commit, filter)
+    }
+
+    fn commit_diff_stats(&self, commits: &[String]) -> Result<Vec<(String, DiffStats)>> {
+        git::commit_diff_stats(&self.path, commits)
+    }
+
+    fn uncommitted_changes(&self) -> Result<DiffStats> {
+        git::uncommitted_changes(&self.path)
+    }
+
+    fn branch_checkouts(&self, since: DateTime<Utc>, until: DateTime<Utc>) -> Result<Vec<(DateTime<Utc>, String)>> {
+        git::branch_checkouts(&self.path, since, until)
+    }
+
+    fn list_branches(&self) -> Result<Vec<String>> {
+        git::list_branches(&self.path)
+    }
+
+    fn branch_description(&self, branch: &str) -> Option<String> {
+        git::branch_description(&self.path, branch)
+    }
+}
+
+/// Parse the summary line of `git diff --shortstat` or `jj diff --stat`
+///
+/// e.g. `3 files changed, 12 insertions(+), 4 deletions(-)`
+pub fn parse_diff_summary(output: &str) -> Option<DiffStats> {
+    let summary = output.lines().rev().find(|line| line.contains("changed"))?;
+
+    let mut stats = DiffStats::default();
+    for part in summary.split(',') {
+        let mut words = part.split_whitespace();
+        let count: i64 = words.next()?.parse().ok()?;
+        match words.next()? {
+            w if w.starts_with("file") => stats.files_changed = count,
+            w if w.starts_with("insertion") => stats.insertions = count,
+            w if w.starts_with("deletion") => stats.deletions = count,
+            _ => {}
+        }
+    }
+
+    Some(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_diff_summary() {
+        let output = "src/main.rs | 10 ++++++----\n\
+                      src/lib.rs  |  2 ++\n\
+                      2 files changed, 8 insertions(+), 4 deletions(-)\n";
+        assert_eq!(
+            parse_diff_summary(output),
+            Some(DiffStats {
+                files_changed: 2,
+                insertions: 8,
+                deletions: 4,
+            })
+        );
+        assert_eq!(
+            parse_diff_summary("1 file changed, 3 insertions(+)\n").map(|s| s.insertions),
+            Some(3)
+        );
+        assert_eq!(parse_diff_summary(""), None);
+    }
+}
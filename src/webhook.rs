@@ -0,0 +1,247 @@
+use anyhow::{Context, Result};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+use crate::config::{EffectiveConfig, WebhookEvent, WebhookSettings};
+use crate::http::Request;
+use crate::storage::Storage;
+
+/// Time each delivery attempt gets
+const TIMEOUT_SECONDS: u64 = 5;
+
+/// A payload and the webhooks it goes to, handed to `webhooks --deliver` on its stdin
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Delivery {
+    pub webhooks: Vec<WebhookSettings>,
+    pub payload: Value,
+}
+
+/// How deliveries are handed off
+#[derive(Debug, Clone)]
+pub enum Dispatch {
+    /// Piped to a detached process, so hooks return without waiting for slow
+    /// endpoints or retries; `args` make `program` read and post the delivery
+    Detached { program: PathBuf, args: Vec<String> },
+    /// Posted before returning
+    Inline,
+}
+
+impl Default for Dispatch {
+    /// A detached `webhooks --deliver` run of the running binary
+    fn default() -> Self {
+        Dispatch::Detached {
+            program: std::env::current_exe().unwrap_or_else(|_| PathBuf::from(env!("CARGO_PKG_NAME"))),
+            args: vec!["webhooks".to_string(), "--deliver".to_string()],
+        }
+    }
+}
+
+impl Dispatch {
+    fn dispatch(&self, delivery: Delivery) -> Result<()> {
+        match self {
+            Dispatch::Detached { program, args } => detach(program, args, &delivery),
+            Dispatch::Inline => {
+                deliver_all(&delivery);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Post a session to the webhooks subscribed to an event, as `config.webhook_dispatch` says
+///
+/// Failures only warn: tracking must not fail because an endpoint is down.
+pub fn fire(db: &impl Storage, config: &EffectiveConfig, event: WebhookEvent, session_id: i64) {
+    let webhooks: Vec<WebhookSettings> = config
+        .webhooks
+        .iter()
+        .filter(|w| w.events.contains(&event))
+        .cloned()
+        .collect();
+    if webhooks.is_empty() {
+        return;
+    }
+
+    let payload = match payload(db, event, session_id) {
+        Ok(payload) => payload,
+        Err(e) => {
+            warn!("Failed to build the webhook payload for session {}: {:#}", session_id, e);
+            return;
+        }
+    };
+    if let Err(e) = config.webhook_dispatch.dispatch(Delivery { webhooks, payload }) {
+        warn!("Failed to start delivering webhooks for session {}: {:#}", session_id, e);
+    }
+}
+
+/// Hand a delivery to a detached run of `program`
+fn detach(program: &Path, args: &[String], delivery: &Delivery) -> Result<()> {
+    use std::os::unix::process::CommandExt;
+    use std::process::{Command, Stdio};
+
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        // Its own process group, so it isn't killed along with the hook
+        .process_group(0)
+        .spawn()
+        .context("Failed to start the webhook delivery")?;
+    let stdin = child.stdin.take().context("Failed to open the delivery's stdin")?;
+    serde_json::to_writer(stdin, delivery).context("Failed to pass the delivery on")?;
+    // Reaped in the background, for long-running processes like the daemon
+    thread::spawn(move || child.wait());
+    Ok(())
+}
+
+/// Read a delivery from stdin and post it (`webhooks --deliver`)
+pub fn deliver_from(mut input: impl Read) -> Result<()> {
+    let mut content = String::new();
+    input.read_to_string(&mut content).context("Failed to read the delivery")?;
+    let delivery: Delivery = serde_json::from_str(&content).context("Invalid webhook delivery")?;
+    deliver_all(&delivery);
+    Ok(())
+}
+
+fn deliver_all(delivery: &Delivery) {
+    for webhook in &delivery.webhooks {
+        if let Err(e) = deliver(webhook, &delivery.payload) {
+            warn!("Webhook {} failed: {:#}", webhook.url, e);
+        }
+    }
+}
+
+/// JSON body for an event: the session and the project it belongs to
+fn payload(db: &impl Storage, event: WebhookEvent, session_id: i64) -> Result<Value> {
+    let session = db.get_session_by_id(session_id)?;
+    let project = db.get_project_by_id(session.project_id)?;
+    Ok(json!({
+        "event": event,
+        "timestamp": Utc::now(),
+        "project": {
+            "name": project.name(),
+            "path": project.path,
+            "client": project.client,
+        },
+        "session": session,
+    }))
+}
+
+/// Body sent by `webhooks --test`
+pub fn test_payload() -> Value {
+    json!({
+        "event": "test",
+        "timestamp": Utc::now(),
+        "message": "Test delivery from claude-time-tracker",
+    })
+}
+
+/// Post a payload, retrying with exponential backoff
+pub fn deliver(webhook: &WebhookSettings, payload: &Value) -> Result<()> {
+    let request = Request::post(&webhook.url, payload)?.timeout(TIMEOUT_SECONDS);
+    let mut attempt = 0;
+    loop {
+        match request.send_text() {
+            Ok(_) => return Ok(()),
+            Err(e) if attempt < webhook.retries => {
+                let wait = backoff(attempt);
+                debug!("Webhook {} failed ({:#}), retrying in {:?}", webhook.url, e, wait);
+                thread::sleep(wait);
+                attempt += 1;
+            }
+            Err(e) => return Err(e.context(format!("Gave up after {} attempts", attempt + 1))),
+        }
+    }
+}
+
+/// Wait before retry `attempt` (0-based): 1s, 2s, 4s, … up to a minute
+fn backoff(attempt: u32) -> Duration {
+    Duration::from_secs((1u64 << attempt.min(6)).min(60))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_backoff() {
+        assert_eq!(backoff(0), Duration::from_secs(1));
+        assert_eq!(backoff(2), Duration::from_secs(4));
+        assert_eq!(backoff(10), Duration::from_secs(60));
+    }
+
+    /// Marks the run of [`deliver_child`] started by [`test_detached_delivery`]
+    const CHILD_MARKER: &str = "ctt-delivery-child";
+
+    /// Stands in for `webhooks --deliver`, which the test binary can't be run as
+    #[test]
+    #[ignore]
+    fn deliver_child() {
+        if std::env::args().any(|arg| arg == CHILD_MARKER) {
+            deliver_from(std::io::stdin().lock()).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_detached_delivery() {
+        use crate::config::GlobalConfig;
+        use std::net::TcpListener;
+        use std::sync::mpsc;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let (received, bodies) = mpsc::channel();
+        thread::spawn(move || {
+            let mut stream = listener.incoming().next().unwrap().unwrap();
+            let request = crate::server::read_request(&mut std::io::BufReader::new(&stream)).unwrap();
+            std::io::Write::write_all(&mut stream, b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n").unwrap();
+            received.send(request.body).unwrap();
+        });
+
+        let dir = tempdir().unwrap();
+        let db = Database::open(&dir.path().join("test.db")).unwrap();
+        let project = db.get_or_create_project("/src/api", None, None, None).unwrap();
+        let session = db.create_session(project.id, "main", None, None, None, None).unwrap();
+        let mut config = EffectiveConfig::from_global(GlobalConfig::default(), None).unwrap();
+        config.webhooks = vec![WebhookSettings {
+            url,
+            events: vec![WebhookEvent::Start],
+            retries: 0,
+        }];
+        config.webhook_dispatch = Dispatch::Detached {
+            program: std::env::current_exe().unwrap(),
+            args: ["webhook::tests::deliver_child", "--exact", "--ignored", CHILD_MARKER]
+                .map(String::from)
+                .to_vec(),
+        };
+
+        fire(&db, &config, WebhookEvent::Start, session.id);
+        let body = bodies.recv_timeout(Duration::from_secs(30)).unwrap();
+        let payload: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(payload["event"], "start");
+        assert_eq!(payload["session"]["uuid"], session.uuid);
+    }
+
+    #[test]
+    fn test_payload() {
+        let dir = tempdir().unwrap();
+        let db = Database::open(&dir.path().join("test.db")).unwrap();
+        let project = db.get_or_create_project("/src/api", None, None, None).unwrap();
+        let session = db.create_session(project.id, "main", Some("ABC-1"), None, None, None).unwrap();
+
+        let payload = payload(&db, WebhookEvent::Start, session.id).unwrap();
+        assert_eq!(payload["event"], "start");
+        assert_eq!(payload["project"]["path"], "/src/api");
+        assert_eq!(payload["session"]["work_item"], "ABC-1");
+        assert_eq!(payload["session"]["uuid"], session.uuid);
+    }
+}